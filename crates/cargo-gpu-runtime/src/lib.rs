@@ -0,0 +1,390 @@
+//! Load shaders built by `cargo gpu`, and hot reload them while `cargo gpu build --watch` is
+//! running.
+//!
+//! `cargo gpu build` writes a `manifest.json` listing every shader entry point and the `.spv`
+//! module that it was compiled into. [`Shaders::load`] reads the manifest and the bytes of every
+//! module, decompressing them if they were built with `--compress`, and a [`ShaderWatcher`]
+//! loads them again whenever watch mode rebuilds them, calling back so that an engine can
+//! recreate its pipelines.
+//!
+//! ```no_run
+//! let mut watcher = cargo_gpu_runtime::ShaderWatcher::new("shaders/manifest.json", "shaders")?;
+//! watcher.on_change(|shaders| {
+//!     for entry_point in &shaders.entry_points {
+//!         println!("reloaded {}", entry_point.name);
+//!     }
+//! });
+//! loop {
+//!     // Once per frame, or however often is convenient.
+//!     watcher.poll()?;
+//!     # break;
+//! }
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use anyhow::Context as _;
+
+/// A shader entry point from the manifest.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+#[non_exhaustive]
+pub struct EntryPoint {
+    /// The entry point's name in its module, eg `main_fs`.
+    #[serde(rename = "entry_point")]
+    pub name: String,
+    /// The module containing the entry point, relative to the shader crate.
+    #[serde(rename = "source_path")]
+    pub module: std::path::PathBuf,
+    /// The entry point's shader stage, eg "vertex".
+    #[serde(default)]
+    pub stage: Option<String>,
+    /// The SHA-256 of the module file, if the manifest has it.
+    #[serde(default)]
+    pub sha256: Option<String>,
+    /// How the module file is compressed, if it was built with `--compress`.
+    #[serde(default)]
+    pub compression: Option<Compression>,
+    /// Where the module is in the pack file, if it was built with `--pack`.
+    #[serde(default)]
+    pub packed: Option<Packed>,
+}
+
+/// Where a module is in a pack file.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+#[non_exhaustive]
+pub struct Packed {
+    /// The pack file, relative to the shader crate.
+    pub path: std::path::PathBuf,
+    /// Where the module starts in the pack.
+    pub offset: u64,
+    /// The size of the module in the pack.
+    pub length: u64,
+}
+
+/// How a module file is compressed.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+#[non_exhaustive]
+pub struct Compression {
+    /// The compression scheme, eg "zstd".
+    pub scheme: String,
+    /// The size of the module before compression.
+    pub size: u64,
+    /// The size of the compressed module file.
+    pub compressed_size: u64,
+}
+
+/// A manifest written by `cargo gpu build`.
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum Manifest {
+    /// A manifest with a `schema_version`, whose entry points are in `entry_points`.
+    Versioned {
+        /// What the paths are relative to, eg "relative-to-manifest".
+        #[serde(default)]
+        paths: Option<String>,
+        /// Every entry point.
+        entry_points: Vec<EntryPoint>,
+    },
+    /// A manifest from before the schema was versioned, which is just the entry points.
+    Unversioned(Vec<EntryPoint>),
+}
+
+/// The entry points of a shader crate and the bytes of their compiled modules.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Shaders {
+    /// Every entry point in the manifest.
+    pub entry_points: Vec<EntryPoint>,
+    /// The bytes of every module, keyed by their path relative to the shader crate.
+    pub modules: std::collections::BTreeMap<std::path::PathBuf, Vec<u8>>,
+}
+
+impl Shaders {
+    /// Read the manifest at `manifest_path` and every module that it mentions. `shader_crate` is
+    /// the directory of the shader crate, which the module paths are relative to, unless the
+    /// manifest was built with `--manifest-paths relative-to-manifest` or `absolute`. Modules that
+    /// were packed with `--pack` are read from the pack file, which is only read once.
+    ///
+    /// # Errors
+    /// If the manifest or any of the modules can't be read, or a module can't be decompressed.
+    #[expect(
+        clippy::missing_inline_in_public_items,
+        reason = "Reading the files dwarfs any call overhead"
+    )]
+    pub fn load<M: AsRef<std::path::Path>, S: AsRef<std::path::Path>>(
+        manifest_path: M,
+        shader_crate: S,
+    ) -> anyhow::Result<Self> {
+        let manifest_file = manifest_path.as_ref();
+        let manifest: Manifest =
+            serde_json::from_slice(&std::fs::read(manifest_file).with_context(|| {
+                format!("could not read manifest '{}'", manifest_file.display())
+            })?)
+            .with_context(|| format!("could not parse manifest '{}'", manifest_file.display()))?;
+        let entry_points = match manifest {
+            Manifest::Versioned {
+                paths: Some(paths),
+                entry_points,
+            } if paths == "relative-to-manifest" => {
+                // Make the paths absolute, so that they no longer depend on `shader_crate`.
+                let manifest_dir = std::path::absolute(
+                    manifest_file
+                        .parent()
+                        .unwrap_or_else(|| std::path::Path::new("./")),
+                )?;
+                entry_points
+                    .into_iter()
+                    .map(|mut entry_point| {
+                        entry_point.module = manifest_dir.join(&entry_point.module);
+                        if let Some(packed) = &mut entry_point.packed {
+                            packed.path = manifest_dir.join(&packed.path);
+                        }
+                        entry_point
+                    })
+                    .collect()
+            }
+            Manifest::Versioned { entry_points, .. } | Manifest::Unversioned(entry_points) => {
+                entry_points
+            }
+        };
+
+        let mut modules = std::collections::BTreeMap::new();
+        let mut packs = std::collections::HashMap::new();
+        for entry_point in &entry_points {
+            if modules.contains_key(&entry_point.module) {
+                continue;
+            }
+            let module_path = shader_crate.as_ref().join(&entry_point.module);
+            let file_bytes = match &entry_point.packed {
+                Some(packed) => {
+                    let pack_path = shader_crate.as_ref().join(&packed.path);
+                    if !packs.contains_key(&pack_path) {
+                        let pack = std::fs::read(&pack_path).with_context(|| {
+                            format!("could not read pack '{}'", pack_path.display())
+                        })?;
+                        packs.insert(pack_path.clone(), pack);
+                    }
+                    let pack = packs.get(&pack_path).context("pack was just read")?;
+                    unpack(pack, packed).with_context(|| {
+                        format!(
+                            "module '{}' isn't in pack '{}'",
+                            module_path.display(),
+                            pack_path.display()
+                        )
+                    })?
+                }
+                None => std::fs::read(&module_path).with_context(|| {
+                    format!("could not read module '{}'", module_path.display())
+                })?,
+            };
+            let bytes = match &entry_point.compression {
+                Some(compression) => decompress(&file_bytes, compression).with_context(|| {
+                    format!("could not decompress module '{}'", module_path.display())
+                })?,
+                None => file_bytes,
+            };
+            modules.insert(entry_point.module.clone(), bytes);
+        }
+        Ok(Self {
+            entry_points,
+            modules,
+        })
+    }
+
+    /// Find an entry point by its name.
+    #[inline]
+    #[must_use]
+    pub fn entry_point(&self, name: &str) -> Option<&EntryPoint> {
+        self.entry_points
+            .iter()
+            .find(|entry_point| entry_point.name == name)
+    }
+
+    /// The bytes of the module containing the entry point called `name`.
+    #[inline]
+    #[must_use]
+    pub fn module_of(&self, name: &str) -> Option<&[u8]> {
+        let entry_point = self.entry_point(name)?;
+        self.modules.get(&entry_point.module).map(Vec::as_slice)
+    }
+}
+
+/// The bytes of a module in a pack.
+fn unpack(pack: &[u8], packed: &Packed) -> Option<Vec<u8>> {
+    let start = usize::try_from(packed.offset).ok()?;
+    let end = start.checked_add(usize::try_from(packed.length).ok()?)?;
+    pack.get(start..end).map(<[u8]>::to_vec)
+}
+
+/// Decompress a module file, so that [`Shaders`] always has plain SPIR-V.
+fn decompress(bytes: &[u8], compression: &Compression) -> anyhow::Result<Vec<u8>> {
+    use std::io::Read as _;
+
+    anyhow::ensure!(
+        compression.scheme == "zstd",
+        "unknown compression scheme `{}`",
+        compression.scheme
+    );
+    let mut decoder = ruzstd::decoding::StreamingDecoder::new(bytes)
+        .map_err(|error| anyhow::anyhow!("{error}"))?;
+    let mut decompressed = Vec::with_capacity(usize::try_from(compression.size)?);
+    decoder.read_to_end(&mut decompressed)?;
+    Ok(decompressed)
+}
+
+/// A callback for when shaders have been reloaded.
+type Callback = Box<dyn FnMut(&Shaders) + Send>;
+
+/// Reloads a shader crate's [`Shaders`] whenever `cargo gpu build --watch` rebuilds them.
+///
+/// Changes are found by checking the modification times of the manifest and modules in
+/// [`ShaderWatcher::poll`], which is cheap enough to call every frame.
+pub struct ShaderWatcher {
+    /// The manifest written by `cargo gpu build`.
+    manifest_path: std::path::PathBuf,
+    /// The directory of the shader crate.
+    shader_crate: std::path::PathBuf,
+    /// The most recently loaded shaders.
+    shaders: Shaders,
+    /// The modification times of the manifest and modules when they were last loaded.
+    modified: Vec<Option<std::time::SystemTime>>,
+    /// What to call when the shaders are reloaded.
+    callbacks: Vec<Callback>,
+}
+
+impl ShaderWatcher {
+    /// Load the shaders of the manifest at `manifest_path` and start watching them for changes.
+    ///
+    /// # Errors
+    /// If the shaders can't be loaded, see [`Shaders::load`].
+    #[expect(
+        clippy::missing_inline_in_public_items,
+        reason = "Reading the files dwarfs any call overhead"
+    )]
+    pub fn new<M: Into<std::path::PathBuf>, S: Into<std::path::PathBuf>>(
+        manifest_path: M,
+        shader_crate: S,
+    ) -> anyhow::Result<Self> {
+        let manifest = manifest_path.into();
+        let crate_dir = shader_crate.into();
+        let shaders = Shaders::load(&manifest, &crate_dir)?;
+        let mut watcher = Self {
+            manifest_path: manifest,
+            shader_crate: crate_dir,
+            shaders,
+            modified: Vec::new(),
+            callbacks: Vec::new(),
+        };
+        watcher.modified = watcher.modification_times();
+        Ok(watcher)
+    }
+
+    /// The most recently loaded shaders.
+    #[inline]
+    #[must_use]
+    pub const fn shaders(&self) -> &Shaders {
+        &self.shaders
+    }
+
+    /// Call `callback` with the new shaders every time that they're reloaded.
+    #[inline]
+    pub fn on_change<C: FnMut(&Shaders) + Send + 'static>(&mut self, callback: C) {
+        self.callbacks.push(Box::new(callback));
+    }
+
+    /// Reload the shaders if they've changed since they were last loaded, calling every
+    /// [`ShaderWatcher::on_change`] callback. Returns whether they were reloaded.
+    ///
+    /// A rebuild that's only partly written is skipped, and picked up again by a later poll.
+    ///
+    /// # Errors
+    /// Never at the moment, as a failed reload is retried by the next poll instead.
+    #[expect(
+        clippy::missing_inline_in_public_items,
+        reason = "Checking the modification times dwarfs any call overhead"
+    )]
+    pub fn poll(&mut self) -> anyhow::Result<bool> {
+        let modified = self.modification_times();
+        if modified == self.modified {
+            return Ok(false);
+        }
+        let shaders = match Shaders::load(&self.manifest_path, &self.shader_crate) {
+            Ok(shaders) => shaders,
+            Err(error) => {
+                log::debug!("couldn't reload shaders yet: {error:?}");
+                return Ok(false);
+            }
+        };
+        self.shaders = shaders;
+        // The new manifest may have new modules, so they're only known after loading it.
+        self.modified = self.modification_times();
+        for callback in &mut self.callbacks {
+            callback(&self.shaders);
+        }
+        Ok(true)
+    }
+
+    /// The modification times of the manifest and every module of the current shaders.
+    fn modification_times(&self) -> Vec<Option<std::time::SystemTime>> {
+        core::iter::once(self.manifest_path.clone())
+            .chain(
+                self.shaders
+                    .modules
+                    .keys()
+                    .map(|module| self.shader_crate.join(module)),
+            )
+            .map(|path| {
+                std::fs::metadata(path)
+                    .and_then(|metadata| metadata.modified())
+                    .ok()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_log::test]
+    fn reloading_rebuilt_shaders() {
+        let dir = std::env::temp_dir().join(format!("cargo-gpu-runtime-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let manifest_path = dir.join("manifest.json");
+        std::fs::write(
+            &manifest_path,
+            r#"[{"source_path":"shader.spv","entry_point":"main_fs","wgsl_entry_point":"main_fs"}]"#,
+        )
+        .unwrap();
+        std::fs::write(dir.join("shader.spv"), [1, 2, 3, 4]).unwrap();
+
+        let mut watcher = ShaderWatcher::new(&manifest_path, &dir).unwrap();
+        assert_eq!(
+            watcher.shaders().module_of("main_fs"),
+            Some([1, 2, 3, 4].as_slice())
+        );
+        let reloads = std::sync::Arc::new(core::sync::atomic::AtomicUsize::new(0));
+        let counter = std::sync::Arc::clone(&reloads);
+        watcher.on_change(move |_| {
+            counter.fetch_add(1, core::sync::atomic::Ordering::Relaxed);
+        });
+        assert!(!watcher.poll().unwrap());
+
+        std::fs::write(dir.join("shader.spv"), [5, 6, 7, 8]).unwrap();
+        // Make sure that the modification time changes, even on coarse grained filesystems.
+        let later = std::time::SystemTime::now() + core::time::Duration::from_secs(10);
+        std::fs::File::options()
+            .write(true)
+            .open(dir.join("shader.spv"))
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+
+        assert!(watcher.poll().unwrap());
+        assert_eq!(
+            watcher.shaders().module_of("main_fs"),
+            Some([5, 6, 7, 8].as_slice())
+        );
+        assert_eq!(reloads.load(core::sync::atomic::Ordering::Relaxed), 1);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,216 @@
+//! Vendored installs, for air-gapped machines that can't reach GitHub or crates.io.
+//!
+//! `cargo gpu package-install` installs as normal on a machine that's online, then bundles
+//! everything needed to install again without a network into a `.tar.gz`:
+//!
+//!   * the `rust-gpu` checkout, and the clone of `rust-gpu` that it's a worktree of
+//!   * the `spirv-builder-cli` sources, with their `Cargo.lock`
+//!   * every crate that `spirv-builder-cli` depends on, from `cargo vendor`
+//!
+//! `cargo gpu install --from-archive <ARCHIVE>` unpacks it into the cache dir on the other
+//! machine, and then installs in [`crate::offline`] mode. The archive's paths mirror the cache
+//! dir, so that the install finds them just as if they had been downloaded there.
+//!
+//! The Rust toolchain isn't included, it has to be installed separately, eg with `rustup`'s own
+//! offline installers.
+
+use anyhow::Context as _;
+
+/// Where `cargo vendor` puts the dependencies of `spirv-builder-cli`, in its install dir.
+const VENDOR_DIR: &str = "vendor";
+
+/// The files and directories of an install dir that go in the archive. The compiled artifacts
+/// are left out, as they only work on the machine that they were built for.
+const INSTALL_FILES: &[&str] = &["Cargo.toml", "Cargo.lock", "src", ".cargo", VENDOR_DIR];
+
+/// `cargo gpu package-install`.
+#[derive(clap::Parser, Debug)]
+pub struct PackageInstall {
+    /// The same arguments as `cargo gpu install`.
+    #[clap(flatten)]
+    pub install: crate::install::Install,
+
+    /// Where to write the archive.
+    #[clap(long, default_value = "rust-gpu-install.tar.gz")]
+    pub archive: std::path::PathBuf,
+}
+
+impl PackageInstall {
+    /// Entrypoint.
+    pub fn run(&self) -> anyhow::Result<()> {
+        let mut command = crate::config::Config::install_with_cargo_config(
+            &self.install.spirv_install.shader_crate,
+            &self.install,
+        )?;
+        let cli_path = command.install.run()?;
+        let install_dir = cli_path
+            .parent()
+            .context("install has no directory")?
+            .to_path_buf();
+        let metadata =
+            crate::install::PairMetadata::read(&install_dir).context("install has no metadata")?;
+
+        vendor(&install_dir, &metadata.channel)?;
+
+        let cache_dir = crate::cache_dir()?;
+        let file = std::fs::File::create(&self.archive)
+            .with_context(|| format!("could not create '{}'", self.archive.display()))?;
+        let mut archive = tar::Builder::new(flate2::write::GzEncoder::new(
+            file,
+            flate2::Compression::default(),
+        ));
+        crate::timings::time("package install", install_dir.display(), || {
+            for name in INSTALL_FILES {
+                let path = install_dir.join(name);
+                if path.exists() {
+                    append(&mut archive, &cache_dir, &path)?;
+                }
+            }
+            if let Some(checkout) = &metadata.checkout {
+                append(&mut archive, &cache_dir, checkout)?;
+                // The checkout is added again from the clone that it's a worktree of.
+                if let Some(clone) = crate::spirv_source::SpirvSource::shared_clone_of(checkout) {
+                    append(&mut archive, &cache_dir, &clone)?;
+                }
+            }
+            archive.into_inner()?.finish()?;
+            anyhow::Ok(())
+        })?;
+
+        crate::user_output!(
+            "Packaged `rust-gpu` {} for {} into {}\n",
+            metadata.version,
+            metadata.channel,
+            self.archive.display()
+        );
+        Ok(())
+    }
+}
+
+/// Copy the sources of every dependency of `spirv-builder-cli` into its install dir, and
+/// configure `cargo` to use them instead of crates.io or git.
+fn vendor(install_dir: &std::path::Path, channel: &str) -> anyhow::Result<()> {
+    crate::user_output!("Vendoring the dependencies of `spirv-builder-cli`\n");
+    let output = std::process::Command::new("cargo")
+        .current_dir(install_dir)
+        .arg(format!("+{channel}"))
+        .args(["vendor", "--versioned-dirs", VENDOR_DIR])
+        .args(crate::offline::cargo_args())
+        .stderr(std::process::Stdio::inherit())
+        .output()?;
+    anyhow::ensure!(
+        output.status.success(),
+        "could not vendor the dependencies of `spirv-builder-cli`"
+    );
+
+    // `cargo vendor` prints the config that's needed to use the vendored sources.
+    let config_dir = install_dir.join(".cargo");
+    std::fs::create_dir_all(&config_dir)?;
+    std::fs::write(config_dir.join("config.toml"), &output.stdout)?;
+    Ok(())
+}
+
+/// Add a file or directory to the archive, at its path relative to the cache dir.
+fn append<W: std::io::Write>(
+    archive: &mut tar::Builder<W>,
+    cache_dir: &std::path::Path,
+    path: &std::path::Path,
+) -> anyhow::Result<()> {
+    let name = path
+        .strip_prefix(cache_dir)
+        .with_context(|| format!("'{}' isn't in the cache dir", path.display()))?;
+    log::debug!("archiving {}", name.display());
+    if path.is_dir() {
+        archive.append_dir_all(name, path)?;
+    } else {
+        archive.append_path_with_name(path, name)?;
+    }
+    Ok(())
+}
+
+/// Unpack an archive made by `cargo gpu package-install` into the cache dir.
+///
+/// # Errors
+/// If the archive can't be read or unpacked.
+pub fn unpack(archive_path: &std::path::Path) -> anyhow::Result<()> {
+    let cache_dir = crate::cache_dir()?;
+    std::fs::create_dir_all(&cache_dir)?;
+    let file = std::fs::File::open(archive_path)
+        .with_context(|| format!("could not open '{}'", archive_path.display()))?;
+    crate::user_output!("Unpacking {}\n", archive_path.display());
+    // Entries that would escape the cache dir, eg with `..`, are skipped by `tar`.
+    tar::Archive::new(flate2::read::GzDecoder::new(file))
+        .unpack(&cache_dir)
+        .with_context(|| {
+            format!(
+                "could not unpack '{}' into '{}'",
+                archive_path.display(),
+                cache_dir.display()
+            )
+        })
+}
+
+/// Whether an install dir was unpacked from an archive, in which case its `Cargo.lock` has to be
+/// kept, as it's the one that matches the vendored sources.
+#[must_use]
+pub fn is_vendored(install_dir: &std::path::Path) -> bool {
+    install_dir.join(VENDOR_DIR).is_dir()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_log::test]
+    fn installing_with_the_parsed_args() {
+        use clap::Parser as _;
+
+        let shader_crate = crate::test::shader_crate_test_path();
+        let package = PackageInstall::parse_from([
+            "cargo-gpu",
+            "--shader-crate",
+            &shader_crate.display().to_string(),
+            "--spirv-builder-source",
+            "package-install",
+            "--archive",
+            "gpu.tar.gz",
+        ]);
+        let command =
+            crate::config::Config::install_with_cargo_config(&shader_crate, &package.install)
+                .unwrap();
+        assert_eq!(
+            command
+                .install
+                .spirv_install
+                .spirv_builder_source
+                .as_deref(),
+            Some("package-install")
+        );
+        assert_eq!(command.install.spirv_install.shader_crate, shader_crate);
+    }
+
+    #[test_log::test]
+    fn archiving_paths_relative_to_the_cache_dir() {
+        let cache_dir = crate::cache_dir().unwrap();
+        let install_dir = cache_dir.join("spirv-builder-cli").join("pair");
+        std::fs::create_dir_all(install_dir.join("src")).unwrap();
+        std::fs::write(install_dir.join("Cargo.toml"), "[package]").unwrap();
+        std::fs::write(install_dir.join("src").join("main.rs"), "fn main() {}").unwrap();
+
+        let mut archive = tar::Builder::new(Vec::new());
+        append(&mut archive, &cache_dir, &install_dir.join("Cargo.toml")).unwrap();
+        append(&mut archive, &cache_dir, &install_dir.join("src")).unwrap();
+        assert!(append(&mut archive, &install_dir, &cache_dir).is_err());
+        let bytes = archive.into_inner().unwrap();
+
+        let unpacked = cache_dir.join("unpacked");
+        tar::Archive::new(bytes.as_slice())
+            .unpack(&unpacked)
+            .unwrap();
+        assert!(unpacked
+            .join("spirv-builder-cli/pair/src/main.rs")
+            .is_file());
+        assert!(unpacked.join("spirv-builder-cli/pair/Cargo.toml").is_file());
+        crate::test::tests_teardown();
+    }
+}
@@ -0,0 +1,80 @@
+//! Atomic writes of outputs, so that anything watching the output dir, eg `--watch` clients,
+//! never reads a partly written module or manifest.
+//!
+//! Files are written next to where they're going and then renamed into place. Renaming also
+//! replaces links from `--output-link` rather than writing through them.
+
+use anyhow::Context as _;
+
+/// The temporary file that `path` is written to before it's moved into place.
+fn temporary_path(path: &std::path::Path) -> std::path::PathBuf {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(format!(".{}.partial", std::process::id()));
+    temporary.into()
+}
+
+/// Create the file at `path` with `create`, which is given a temporary path next to it to create
+/// the file at, and then move it into place.
+///
+/// # Errors
+/// If `create` fails or the file can't be moved into place.
+pub fn replace<F: FnOnce(&std::path::Path) -> anyhow::Result<()>>(
+    path: &std::path::Path,
+    create: F,
+) -> anyhow::Result<()> {
+    let temporary = temporary_path(path);
+    if let Err(error) = create(&temporary) {
+        if let Err(cleanup_error) = std::fs::remove_file(&temporary) {
+            log::debug!("couldn't remove '{}': {cleanup_error}", temporary.display());
+        }
+        return Err(error);
+    }
+    std::fs::rename(&temporary, path)
+        .with_context(|| format!("could not move '{}' into place", path.display()))
+}
+
+/// Atomically write `contents` to `path`.
+///
+/// # Errors
+/// If the file can't be written.
+pub fn write<C: AsRef<[u8]>>(path: &std::path::Path, contents: C) -> anyhow::Result<()> {
+    replace(path, |temporary| {
+        std::fs::write(temporary, contents)
+            .with_context(|| format!("could not write '{}'", path.display()))
+    })
+}
+
+/// Atomically copy `source` to `destination`.
+///
+/// # Errors
+/// If the file can't be copied.
+pub fn copy(source: &std::path::Path, destination: &std::path::Path) -> anyhow::Result<()> {
+    replace(destination, |temporary| {
+        std::fs::copy(source, temporary)
+            .with_context(|| format!("could not copy '{}'", source.display()))?;
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_log::test]
+    fn writing_atomically() {
+        let dir = crate::cache_dir().unwrap().join("atomic");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("manifest.json");
+
+        write(&path, "[]").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "[]");
+        replace(&path, |temporary| {
+            std::fs::write(temporary, "partial")?;
+            anyhow::bail!("failed halfway")
+        })
+        .unwrap_err();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "[]");
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        crate::test::tests_teardown();
+    }
+}
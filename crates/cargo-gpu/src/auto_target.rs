@@ -0,0 +1,233 @@
+//! `--shader-target auto`, pick the newest Vulkan shader target that the local GPU can run, by
+//! asking `vulkaninfo` what the GPU supports.
+//!
+//! It's meant for local development, so if there's no `vulkaninfo` or no GPU then the default
+//! target is used instead of failing the build.
+
+use spirv_builder_cli::AutoShaderTarget;
+
+/// The `--shader-target` that asks for the target to be picked.
+pub const AUTO: &str = "auto";
+
+/// The target to use when the GPU can't be probed, the same as `--shader-target`'s default.
+const FALLBACK: &str = "spirv-unknown-vulkan1.2";
+
+/// A GPU listed by `vulkaninfo --summary`.
+#[derive(Debug, PartialEq, Eq)]
+struct Device {
+    /// The GPU's name.
+    name: String,
+    /// The newest Vulkan version that it supports, as `(major, minor)`.
+    vulkan_version: (u32, u32),
+    /// Whether it's a software renderer, like llvmpipe, rather than an actual GPU.
+    is_cpu: bool,
+}
+
+/// Pick the newest `spirv-unknown-vulkan*` target in `spec_dir` that the local GPU supports.
+pub fn choose(spec_dir: &std::path::Path) -> anyhow::Result<AutoShaderTarget> {
+    let targets = vulkan_targets(spec_dir)?;
+    let maybe_device = match probe() {
+        Ok(devices) => best_device(devices),
+        Err(error) => {
+            log::warn!("couldn't probe the GPU for `--shader-target auto`: {error}");
+            None
+        }
+    };
+    let Some(device) = maybe_device else {
+        log::warn!("no GPU found for `--shader-target auto`, using `{FALLBACK}`");
+        return Ok(AutoShaderTarget {
+            target: FALLBACK.to_owned(),
+            device: None,
+            vulkan_version: None,
+        });
+    };
+
+    let (major, minor) = device.vulkan_version;
+    let target = newest_target(&targets, device.vulkan_version).ok_or_else(|| {
+        anyhow::anyhow!(
+            "this version of `rust-gpu` has no shader target for Vulkan {major}.{minor}, \
+                 which is the newest that '{}' supports",
+            device.name
+        )
+    })?;
+    Ok(AutoShaderTarget {
+        target,
+        device: Some(device.name),
+        vulkan_version: Some(format!("{major}.{minor}")),
+    })
+}
+
+/// The `spirv-unknown-vulkanX.Y` targets with a spec in `spec_dir`, oldest first.
+fn vulkan_targets(
+    spec_dir: &std::path::Path,
+) -> anyhow::Result<std::collections::BTreeMap<(u32, u32), String>> {
+    let mut targets = std::collections::BTreeMap::new();
+    for maybe_entry in std::fs::read_dir(spec_dir)? {
+        let path = maybe_entry?.path();
+        let Some(name) = path
+            .file_name()
+            .and_then(|file_name| file_name.to_str()?.strip_suffix(".json"))
+        else {
+            continue;
+        };
+        if let Some(version) = name
+            .strip_prefix("spirv-unknown-vulkan")
+            .and_then(parse_version)
+        {
+            targets.insert(version, name.to_owned());
+        }
+    }
+    Ok(targets)
+}
+
+/// The newest of `targets` for a Vulkan version no newer than `vulkan_version`.
+fn newest_target(
+    targets: &std::collections::BTreeMap<(u32, u32), String>,
+    vulkan_version: (u32, u32),
+) -> Option<String> {
+    targets
+        .range(..=vulkan_version)
+        .next_back()
+        .map(|(_, target)| target.clone())
+}
+
+/// Run `vulkaninfo --summary` and parse the GPUs that it lists.
+fn probe() -> anyhow::Result<Vec<Device>> {
+    let output = std::process::Command::new("vulkaninfo")
+        .arg("--summary")
+        .output()
+        .map_err(|error| anyhow::anyhow!("couldn't run `vulkaninfo`: {error}"))?;
+    anyhow::ensure!(
+        output.status.success(),
+        "`vulkaninfo --summary` failed: {}",
+        String::from_utf8_lossy(&output.stderr).trim()
+    );
+    Ok(parse_summary(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// The GPUs in the output of `vulkaninfo --summary`, which lists each one's `apiVersion`,
+/// `deviceType` and `deviceName`, in that order.
+fn parse_summary(summary: &str) -> Vec<Device> {
+    let mut devices: Vec<Device> = Vec::new();
+    for line in summary.lines() {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let field = value.trim();
+        match key.trim() {
+            "apiVersion" => {
+                // Older `vulkaninfo`s write the packed version first, eg `4206847 (1.3.255)`.
+                let version = field
+                    .split_once('(')
+                    .map_or(field, |(_, rest)| rest.trim_end_matches(')'));
+                if let Some(vulkan_version) = parse_version(version) {
+                    devices.push(Device {
+                        name: String::new(),
+                        vulkan_version,
+                        is_cpu: false,
+                    });
+                }
+            }
+            "deviceType" => {
+                if let Some(device) = devices.last_mut() {
+                    device.is_cpu = field.ends_with("CPU");
+                }
+            }
+            "deviceName" => {
+                if let Some(device) = devices.last_mut() {
+                    field.clone_into(&mut device.name);
+                }
+            }
+            _ => {}
+        }
+    }
+    devices
+}
+
+/// The device to pick a target for: the actual GPU with the newest Vulkan, or a software renderer
+/// if that's all there is.
+fn best_device(devices: Vec<Device>) -> Option<Device> {
+    devices
+        .into_iter()
+        .max_by_key(|device| (!device.is_cpu, device.vulkan_version))
+}
+
+/// Parse the major and minor of a version like `1.3` or `1.3.255`.
+fn parse_version(version: &str) -> Option<(u32, u32)> {
+    let mut parts = version.split('.');
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next()?.parse().ok()?;
+    Some((major, minor))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_log::test]
+    fn picking_a_device_from_vulkaninfo() {
+        let summary = "\
+Devices:
+========
+GPU0:
+\tapiVersion         = 1.3.255
+\tdriverVersion      = 535.104.5
+\tdeviceType         = PHYSICAL_DEVICE_TYPE_DISCRETE_GPU
+\tdeviceName         = NVIDIA GeForce RTX 3060
+GPU1:
+\tapiVersion         = 4206847 (1.4.0)
+\tdeviceType         = PHYSICAL_DEVICE_TYPE_CPU
+\tdeviceName         = llvmpipe (LLVM 15.0.7, 256 bits)
+";
+        let devices = parse_summary(summary);
+        assert_eq!(
+            devices,
+            [
+                Device {
+                    name: "NVIDIA GeForce RTX 3060".to_owned(),
+                    vulkan_version: (1, 3),
+                    is_cpu: false,
+                },
+                Device {
+                    name: "llvmpipe (LLVM 15.0.7, 256 bits)".to_owned(),
+                    vulkan_version: (1, 4),
+                    is_cpu: true,
+                },
+            ]
+        );
+        assert_eq!(
+            best_device(devices).map(|device| device.name),
+            Some("NVIDIA GeForce RTX 3060".to_owned())
+        );
+    }
+
+    #[test_log::test]
+    fn picking_the_newest_supported_vulkan_target() {
+        let spec_dir = crate::cache_dir().unwrap().join("auto");
+        std::fs::create_dir_all(&spec_dir).unwrap();
+        for target in [
+            "spirv-unknown-vulkan1.1",
+            "spirv-unknown-vulkan1.1spv1.4",
+            "spirv-unknown-vulkan1.2",
+            "spirv-unknown-vulkan1.4",
+            "spirv-unknown-spv1.5",
+        ] {
+            std::fs::write(spec_dir.join(format!("{target}.json")), "{}").unwrap();
+        }
+        let targets = vulkan_targets(&spec_dir).unwrap();
+        assert_eq!(
+            targets.values().collect::<Vec<_>>(),
+            [
+                "spirv-unknown-vulkan1.1",
+                "spirv-unknown-vulkan1.2",
+                "spirv-unknown-vulkan1.4"
+            ]
+        );
+        assert_eq!(
+            newest_target(&targets, (1, 3)).as_deref(),
+            Some("spirv-unknown-vulkan1.2")
+        );
+        assert_eq!(newest_target(&targets, (1, 0)), None);
+        crate::test::tests_teardown();
+    }
+}
@@ -1,30 +1,205 @@
 #![allow(clippy::shadow_reuse, reason = "let's not be silly")]
 #![allow(clippy::unwrap_used, reason = "this is basically a test")]
-//! `cargo gpu build`, analogous to `cargo build`
+//! `cargo gpu build`, analogous to `cargo build`.
 
 use anyhow::Context as _;
-use std::io::Write as _;
 
-use crate::{install::Install, target_spec_dir};
-use spirv_builder_cli::{args::BuildArgs, Linkage, ShaderModule};
+use crate::{
+    config::Config,
+    install::Install,
+    metadata::{Metadata, ShaderCrate},
+};
+use spirv_builder_cli::{
+    args::{BuildArgs, ManifestFormat, ManifestPaths},
+    AutoShaderTarget, Linkage, Manifest, ShaderModule,
+};
 
-/// `cargo build` subcommands
-#[derive(clap::Parser, Debug, serde::Deserialize, serde::Serialize)]
+/// `cargo build` subcommands.
+#[derive(clap::Parser, Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[expect(
+    clippy::struct_field_names,
+    reason = "`build_args` are the args of `spirv-builder-cli`'s build, not of this struct"
+)]
 pub struct Build {
-    /// CLI args for install the `rust-gpu` compiler and components
+    /// CLI args for install the `rust-gpu` compiler and components.
     #[clap(flatten)]
     pub install: Install,
 
-    /// CLI args for configuring the build of the shader
+    /// CLI args for configuring the build of the shader.
     #[clap(flatten)]
     pub build_args: BuildArgs,
+
+    /// Build every shader crate (any crate depending on `spirv-std`) in the workspace that
+    /// `--shader-crate` belongs to.
+    #[clap(long)]
+    #[serde(skip)]
+    pub workspace: bool,
+
+    /// Build the shader crate with this package name in the workspace that `--shader-crate`
+    /// belongs to, eg when `--shader-crate` is the workspace root.
+    #[clap(long, short, conflicts_with = "workspace")]
+    #[serde(skip)]
+    pub package: Option<String>,
 }
 
 impl Build {
-    /// Entrypoint
+    /// Build all the shader crates in the workspace containing `workspace_path`.
+    ///
+    /// Each crate gets its own merged config, just as if `cargo gpu build` had been run in it.
+    /// Crates whose config would write their manifest to the same place as a previous crate get
+    /// their output put in a subdirectory named after the crate instead.
+    pub fn run_workspace(
+        workspace_path: &std::path::PathBuf,
+        env_args: &[String],
+    ) -> anyhow::Result<Vec<(ShaderCrate, Vec<Linkage>)>> {
+        let shader_crates = Metadata::shader_crates(workspace_path)?;
+        anyhow::ensure!(
+            !shader_crates.is_empty(),
+            "no shader crates (crates depending on `spirv-std`) found in the workspace at '{}'",
+            workspace_path.display()
+        );
+
+        let total = shader_crates.len();
+        let mut manifest_paths = std::collections::HashSet::new();
+        let mut workspace_manifests = Vec::new();
+        let mut builds = Vec::new();
+        for (index, shader_crate) in shader_crates.iter().enumerate() {
+            let mut command =
+                Config::clap_command_with_cargo_config(&shader_crate.path, env_args.to_vec())?;
+            anyhow::ensure!(
+                !command.build_args.watch,
+                "`--watch` can't be used with `--workspace`"
+            );
+            command
+                .install
+                .spirv_install
+                .shader_crate
+                .clone_from(&shader_crate.path);
+
+            let manifest_path = std::path::absolute(
+                command
+                    .build_args
+                    .output_dir
+                    .join(&command.build_args.manifest_file),
+            )?;
+            // The workspace manifest goes in the shared output dir, not in the crate's own one.
+            workspace_manifests.push((
+                command.build_args.output_dir.clone(),
+                command.build_args.manifest_paths,
+            ));
+            if !manifest_paths.insert(manifest_path) {
+                command.build_args.output_dir =
+                    command.build_args.output_dir.join(&shader_crate.name);
+            }
+
+            // Only the first crate needs to rebuild the binary pair, any later crates using the
+            // same versions can then reuse it.
+            if index > 0 {
+                command.install.spirv_install.force_spirv_cli_rebuild = false;
+            }
+
+            log::debug!(
+                "building `{}` with arguments: {command:#?}",
+                shader_crate.name
+            );
+            builds.push(command);
+        }
+
+        let jobs = crate::jobs::count(builds.first().and_then(|build| build.build_args.jobs));
+        let started = core::sync::atomic::AtomicUsize::new(0);
+        let results = Self::run_all(builds, jobs, |build| {
+            let count = started.fetch_add(1, core::sync::atomic::Ordering::Relaxed) + 1;
+            crate::user_output!(
+                "Building shader crate `{}` ({count}/{total})...\n",
+                build.shader_crate_name()
+            );
+        });
+        let built = shader_crates
+            .into_iter()
+            .zip(results)
+            .map(|(shader_crate, linkage)| Ok((shader_crate, linkage?)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        for ((shader_crate, linkage), (output_dir, paths)) in built.iter().zip(workspace_manifests)
+        {
+            crate::workspace_manifest::update(
+                &output_dir,
+                &shader_crate.name,
+                &shader_crate.path,
+                linkage,
+                paths,
+            )?;
+        }
+        Ok(built)
+    }
+
+    /// Run `builds`, with at most `jobs` at once, calling `on_start` as each one starts. If the
+    /// first build rebuilds the binary pair, it's run before any of the others, so that none of
+    /// them use the pair while it's being rebuilt.
+    fn run_all(
+        mut builds: Vec<Self>,
+        jobs: usize,
+        on_start: impl Fn(&Self) + Sync,
+    ) -> Vec<anyhow::Result<Vec<Linkage>>> {
+        let mut results = Vec::new();
+        if builds
+            .first()
+            .is_some_and(|build| build.install.spirv_install.force_spirv_cli_rebuild)
+        {
+            let mut first = builds.remove(0);
+            on_start(&first);
+            let result = first.run();
+            let is_failure = result.is_err();
+            results.push(result);
+            if is_failure {
+                return results;
+            }
+        }
+        results.extend(crate::jobs::run(jobs, builds, |mut build| {
+            on_start(&build);
+            build.run()
+        }));
+        results
+    }
+
+    /// The shader crate to build: the one chosen with `--package`, or else `--shader-crate`.
+    pub fn selected_shader_crate(&self) -> anyhow::Result<std::path::PathBuf> {
+        let shader_crate = &self.install.spirv_install.shader_crate;
+        match &self.package {
+            Some(name) => Ok(Metadata::shader_crate_named(shader_crate, name)?.path),
+            None => Ok(shader_crate.clone()),
+        }
+    }
+
+    /// Entrypoint. Returns the linkage of every compiled entry point.
+    pub fn run(&mut self) -> anyhow::Result<Vec<Linkage>> {
+        crate::hooks::pre_build(&self.build_args, &self.install.spirv_install.shader_crate)?;
+        let linkage = self.compile()?;
+        crate::hooks::post_build(&self.build_args, &self.install.spirv_install.shader_crate)?;
+        Ok(linkage)
+    }
+
+    /// Compile the shader crate, or reuse what's already compiled if it's up to date.
     #[expect(clippy::too_many_lines, reason = "these lines are fine")]
-    pub fn run(&mut self) -> anyhow::Result<()> {
-        let spirv_builder_cli_path = self.install.run()?;
+    fn compile(&mut self) -> anyhow::Result<Vec<Linkage>> {
+        if self.build_args.build_script_mode {
+            crate::output::set_build_script_mode(true);
+        }
+        if !self.build_args.variants.is_empty() {
+            return self.run_each_variant();
+        }
+        if self.build_args.shader_target.len() > 1 {
+            return self.run_each_target();
+        }
+        crate::compatibility::check(&self.build_args)?;
+        crate::debug_info::prepare(&mut self.build_args)?;
+
+        let spirv_builder_cli_path = {
+            let _installing = crate::jobs::install_lock();
+            self.install.run()?
+        };
+        self.warn_about_unsupported_flags(&spirv_builder_cli_path);
+        let maybe_auto_shader_target = self.choose_auto_shader_target()?;
+        let maybe_reproducible = self.reproducible_install(&spirv_builder_cli_path)?;
 
         // Ensure the shader output dir exists
         log::debug!(
@@ -33,7 +208,7 @@ impl Build {
         );
         std::fs::create_dir_all(&self.build_args.output_dir)?;
         let canonicalized = self.build_args.output_dir.canonicalize()?;
-        log::debug!("canonicalized output dir: {canonicalized:?}");
+        log::debug!("canonicalized output dir: {}", canonicalized.display());
         self.build_args.output_dir = canonicalized;
 
         // Ensure the shader crate exists
@@ -46,12 +221,19 @@ impl Build {
             std::env::current_dir()?.display()
         );
 
-        if !self.build_args.watch {
-            self.build_args.shader_target = target_spec_dir()?
-                .join(format!("{}.json", self.build_args.shader_target))
-                .display()
-                .to_string();
-        }
+        self.declare_inputs()?;
+
+        let spec = self
+            .install
+            .target_spec_dir
+            .join(format!("{}.json", self.build_args.single_shader_target()));
+        anyhow::ensure!(
+            spec.is_file(),
+            "this version of `rust-gpu` can't compile for the shader target `{}`, see \
+             `cargo gpu show targets`",
+            self.build_args.single_shader_target()
+        );
+        self.build_args.shader_target = vec![spec.display().to_string()];
 
         let args_as_json = serde_json::json!({
             "install": self.install.spirv_install,
@@ -60,20 +242,76 @@ impl Build {
         let arg = serde_json::to_string_pretty(&args_as_json)?;
         log::info!("using spirv-builder-cli arg: {arg}");
 
-        if !self.build_args.watch {
-            crate::user_output!(
-                "Running `spirv-builder-cli` to compile shader at {}...\n",
-                self.install.spirv_install.shader_crate.display()
-            );
+        let fingerprinted_args: std::borrow::Cow<'_, str> =
+            match &self.install.spirv_install.codegen_backend_path {
+                // The user's own backend can be rebuilt without its path changing.
+                Some(_) => format!(
+                    "{arg}\n{}",
+                    crate::fingerprint::file_stamp(&self.install.spirv_install.dylib_path)?
+                )
+                .into(),
+                None => arg.as_str().into(),
+            };
+        let fingerprint = crate::fingerprint::fingerprint(
+            &self.install.spirv_install.shader_crate,
+            &self.build_args.output_dir,
+            &fingerprinted_args,
+        )?;
+        if !self.build_args.force_rebuild {
+            if let Some(linkage) = crate::fingerprint::cached_linkage(
+                &self.build_args.output_dir,
+                &fingerprint,
+                &self.install.spirv_install.shader_crate,
+                &self.expected_outputs(),
+            ) {
+                crate::user_output!(
+                    "Shaders at {} are up to date\n",
+                    self.install.spirv_install.shader_crate.display()
+                );
+                return Ok(linkage);
+            }
+            if let Some(linkage) = self.try_download_from_remote_cache(&fingerprint) {
+                return Ok(linkage);
+            }
         }
 
+        crate::user_output!(
+            "Running `spirv-builder-cli` to compile shader at {}...\n",
+            self.install.spirv_install.shader_crate.display()
+        );
+        crate::diff::keep_previous(
+            &self
+                .build_args
+                .output_dir
+                .join(&self.build_args.manifest_file),
+            &self.install.spirv_install.shader_crate,
+        )?;
+        crate::output::emit(&crate::output::Event::Compiling {
+            package: self.shader_crate_name(),
+            path: self.install.spirv_install.shader_crate.clone(),
+        });
+
         // Call spirv-builder-cli to compile the shaders.
-        let output = std::process::Command::new(spirv_builder_cli_path)
+        let mut spirv_builder_cli = std::process::Command::new(spirv_builder_cli_path);
+        spirv_builder_cli
             .arg(arg)
-            .stdout(std::process::Stdio::inherit())
-            .stderr(std::process::Stdio::inherit())
-            .output()?;
-        anyhow::ensure!(output.status.success(), "build failed");
+            .stdout(crate::output::child_stdout())
+            .stderr(crate::output::child_stderr());
+        self.configure_env(&mut spirv_builder_cli)?;
+        let compile_started = std::time::Instant::now();
+        let output = crate::timings::time("compile shaders", self.shader_crate_name(), || {
+            crate::log_file::output(
+                &mut spirv_builder_cli,
+                crate::output::is_build_script_mode(),
+            )
+        })?;
+        let compile_time = compile_started.elapsed();
+        if crate::output::is_build_script_mode() {
+            crate::output::forward_compiler_stderr(&output.stderr);
+        }
+        if !output.status.success() {
+            return Err(build_failure(&output.stderr));
+        }
 
         let spirv_manifest = self.build_args.output_dir.join("spirv-manifest.json");
         if spirv_manifest.is_file() {
@@ -86,62 +324,62 @@ impl Build {
             anyhow::bail!("missing raw manifest");
         }
 
-        let shaders: Vec<ShaderModule> =
+        let mut shaders: Vec<ShaderModule> =
             serde_json::from_reader(std::fs::File::open(&spirv_manifest)?)?;
+        self.write_depfile(&shaders)?;
+        if !self.build_args.entry_point.is_empty() {
+            for name in &self.build_args.entry_point {
+                if !shaders
+                    .iter()
+                    .any(|shader| is_entry_point_match(name, &shader.entry))
+                {
+                    log::warn!("`--entry-point {name}` didn't match any entry points");
+                }
+            }
+            shaders.retain(|shader| {
+                self.build_args
+                    .entry_point
+                    .iter()
+                    .any(|name| is_entry_point_match(name, &shader.entry))
+            });
+            anyhow::ensure!(
+                !shaders.is_empty(),
+                "none of the entry points matched `--entry-point`"
+            );
+        }
+        shaders.retain(|shader| {
+            let is_excluded = self
+                .build_args
+                .exclude_entry_points
+                .iter()
+                .any(|pattern| is_entry_point_match_glob(pattern, &shader.entry));
+            if is_excluded {
+                log::debug!("excluding entry point `{}`", shader.entry);
+            }
+            !is_excluded
+        });
+        let entry_point_renames = self.rename_entry_points(&mut shaders)?;
 
-        let mut linkage: Vec<Linkage> = shaders
-            .into_iter()
-            .map(
-                |ShaderModule {
-                     entry,
-                     path: filepath,
-                 }|
-                 -> anyhow::Result<Linkage> {
-                    use relative_path::PathExt as _;
-                    let path = self.build_args.output_dir.join(
-                        filepath
-                            .file_name()
-                            .context("Couldn't parse file name from shader module path")?,
-                    );
-                    log::debug!("copying {} to {}", filepath.display(), path.display());
-                    std::fs::copy(&filepath, &path)?;
-                    log::debug!(
-                        "linkage of {} relative to {}",
-                        path.display(),
-                        self.install.spirv_install.shader_crate.display()
-                    );
-                    let spv_path = path
-                        .relative_to(&self.install.spirv_install.shader_crate)
-                        .map_or(path, |path_relative_to_shader_crate| {
-                            path_relative_to_shader_crate.to_path("")
-                        });
-                    Ok(Linkage::new(entry, spv_path))
-                },
-            )
-            .collect::<anyhow::Result<Vec<Linkage>>>()?;
-
-        // Write the shader manifest json file
         let manifest_path = self
             .build_args
             .output_dir
             .join(&self.build_args.manifest_file);
+        let mut linkage = crate::pipeline::run(
+            shaders,
+            &crate::pipeline::Context {
+                build: self,
+                manifest_path: manifest_path.clone(),
+                compile_time,
+                entry_point_renames,
+            },
+        )?;
+        crate::capabilities::warn_about_unused(&self.build_args.capability, &linkage);
+
         // Sort the contents so the output is deterministic
         linkage.sort();
-        let json = serde_json::to_string_pretty(&linkage)?;
-        let mut file = std::fs::File::create(&manifest_path).with_context(|| {
-            format!(
-                "could not create shader manifest file '{}'",
-                manifest_path.display(),
-            )
+        crate::timings::time("write manifest", self.shader_crate_name(), || {
+            self.write_manifests(&linkage, &manifest_path, maybe_auto_shader_target.as_ref())
         })?;
-        file.write_all(json.as_bytes()).with_context(|| {
-            format!(
-                "could not write shader manifest file '{}'",
-                manifest_path.display(),
-            )
-        })?;
-
-        log::info!("wrote manifest to '{}'", manifest_path.display());
 
         if spirv_manifest.is_file() {
             log::debug!(
@@ -151,14 +389,870 @@ impl Build {
             std::fs::remove_file(spirv_manifest)?;
         }
 
+        if let Some(metadata) = &maybe_reproducible {
+            self.write_build_info(metadata, &fingerprint, &linkage)?;
+        }
+
+        crate::fingerprint::write(&self.build_args.output_dir, &fingerprint, &linkage)?;
+        self.upload_to_remote_cache(&fingerprint, &linkage);
+
+        Ok(linkage)
+    }
+
+    /// Give the shaders the new names from `--rename-entry-point`. Returns the exact old and new
+    /// name of each renamed entry point, for renaming them in their modules.
+    fn rename_entry_points(
+        &self,
+        shaders: &mut [ShaderModule],
+    ) -> anyhow::Result<Vec<(String, String)>> {
+        let mut renamed = Vec::new();
+        for (old, _) in &self.build_args.entry_point_renames {
+            if !shaders
+                .iter()
+                .any(|shader| is_entry_point_match(old, &shader.entry))
+            {
+                log::warn!("`--rename-entry-point {old}` didn't match any entry points");
+            }
+        }
+        for shader in shaders.iter_mut() {
+            let Some((_, new)) = self
+                .build_args
+                .entry_point_renames
+                .iter()
+                .find(|(old, _)| is_entry_point_match(old, &shader.entry))
+            else {
+                continue;
+            };
+            renamed.push((
+                core::mem::replace(&mut shader.entry, new.clone()),
+                new.clone(),
+            ));
+        }
+
+        let mut names = std::collections::HashSet::new();
+        for shader in shaders.iter() {
+            anyhow::ensure!(
+                names.insert((&shader.path, &shader.entry)),
+                "more than one entry point in '{}' would be called `{}` after renaming them",
+                shader.path.display(),
+                shader.entry
+            );
+        }
+        Ok(renamed)
+    }
+
+    /// Swap `--shader-target auto` for the target that suits the local GPU, which has to be done
+    /// after installing as it's picked from the targets that the `rust-gpu` can compile for.
+    /// Returns what was picked, for the manifest.
+    fn choose_auto_shader_target(&mut self) -> anyhow::Result<Option<AutoShaderTarget>> {
+        if self.build_args.single_shader_target() != crate::auto_target::AUTO {
+            return Ok(None);
+        }
+        let auto_shader_target = crate::auto_target::choose(&self.install.target_spec_dir)?;
+        crate::user_output!(
+            "Picked shader target `{}`{}\n",
+            auto_shader_target.target,
+            auto_shader_target
+                .device
+                .as_ref()
+                .map(|device| format!(" for {device}"))
+                .unwrap_or_default()
+        );
+        self.build_args.shader_target = vec![auto_shader_target.target.clone()];
+        crate::compatibility::check(&self.build_args)?;
+        Ok(Some(auto_shader_target))
+    }
+
+    /// Write the shader manifest in each of the requested formats.
+    fn write_manifests(
+        &self,
+        linkage: &[Linkage],
+        manifest_path: &std::path::Path,
+        auto_shader_target: Option<&AutoShaderTarget>,
+    ) -> anyhow::Result<()> {
+        let manifest = Manifest {
+            auto_shader_target: auto_shader_target.cloned(),
+            ..manifest_with_paths(
+                linkage,
+                self.build_args.manifest_paths,
+                &self.install.spirv_install.shader_crate,
+                manifest_path
+                    .parent()
+                    .unwrap_or(&self.build_args.output_dir),
+            )
+        };
+        for format in &self.build_args.manifest_format {
+            match format {
+                ManifestFormat::Json | ManifestFormat::Toml | ManifestFormat::Ron => {
+                    let path = manifest_format_path(manifest_path, *format);
+                    let contents = serialize_manifest(&manifest, *format)?;
+                    crate::atomic::write(&path, contents).with_context(|| {
+                        format!("could not write shader manifest file '{}'", path.display())
+                    })?;
+                    log::info!("wrote manifest to '{}'", path.display());
+                    crate::output::emit(&crate::output::Event::ManifestWritten { path });
+                }
+                ManifestFormat::Rust => {
+                    let rust_manifest_path = crate::codegen::write_rust_manifest(
+                        linkage,
+                        manifest_path,
+                        &self.install.spirv_install.shader_crate,
+                    )?;
+                    log::info!("wrote Rust manifest to '{}'", rust_manifest_path.display());
+                    crate::output::emit(&crate::output::Event::ManifestWritten {
+                        path: rust_manifest_path,
+                    });
+                }
+                ManifestFormat::Bevy => {
+                    let bevy_manifest_path = crate::codegen::write_bevy_manifest(
+                        linkage,
+                        manifest_path,
+                        &self.install.spirv_install.shader_crate,
+                    )?;
+                    log::info!("wrote Bevy manifest to '{}'", bevy_manifest_path.display());
+                    crate::output::emit(&crate::output::Event::ManifestWritten {
+                        path: bevy_manifest_path,
+                    });
+                }
+            }
+        }
+        if let Some(mode) = self.build_args.embed {
+            let embed_path = crate::codegen::write_embedded(
+                linkage,
+                mode,
+                manifest_path,
+                &self.install.spirv_install.shader_crate,
+            )?;
+            log::info!("wrote embedded modules to '{}'", embed_path.display());
+        }
+        Ok(())
+    }
+
+    /// Try to download a build with the same fingerprint from `--remote-cache`. A broken remote
+    /// cache isn't an error, because we can always fall back to compiling.
+    fn try_download_from_remote_cache(&self, fingerprint: &str) -> Option<Vec<Linkage>> {
+        let remote_cache = self.build_args.remote_cache.as_ref()?;
+        if crate::offline::is_enabled() {
+            log::info!("not using the remote cache, as we're offline");
+            return None;
+        }
+        if self.build_args.emit_depfile.is_some() {
+            log::info!("not using the remote cache, as the depfile needs a local compile");
+            return None;
+        }
+        let backend = crate::cache::backend(remote_cache);
+        match crate::cache::download(backend.as_ref(), fingerprint, &self.build_args.output_dir) {
+            Ok(Some(linkage)) => {
+                crate::user_output!("Downloaded shaders from remote cache {remote_cache}\n");
+                if let Err(error) =
+                    crate::fingerprint::write(&self.build_args.output_dir, fingerprint, &linkage)
+                {
+                    log::warn!("couldn't record fingerprint: {error:?}");
+                }
+                Some(linkage)
+            }
+            Ok(None) => None,
+            Err(error) => {
+                log::warn!("couldn't download from remote cache, compiling instead: {error:?}");
+                None
+            }
+        }
+    }
+
+    /// Upload a build to `--remote-cache`, if `--remote-cache-upload` is set. The build has
+    /// already succeeded, so failing to upload it is only a warning.
+    fn upload_to_remote_cache(&self, fingerprint: &str, linkage: &[Linkage]) {
+        let Some(remote_cache) = &self.build_args.remote_cache else {
+            return;
+        };
+        if !self.build_args.remote_cache_upload || crate::offline::is_enabled() {
+            return;
+        }
+
+        let mut files =
+            crate::fingerprint::linkage_outputs(linkage, &self.install.spirv_install.shader_crate);
+        files.extend(self.expected_outputs());
+        let backend = crate::cache::backend(remote_cache);
+        match crate::cache::upload(
+            backend.as_ref(),
+            fingerprint,
+            &self.build_args.output_dir,
+            &files,
+            linkage,
+        ) {
+            Ok(()) => {
+                crate::user_output!("Uploaded shaders to remote cache {remote_cache}\n");
+            }
+            Err(error) => log::warn!("couldn't upload to remote cache: {error:?}"),
+        }
+    }
+
+    /// Warn about any flags that the install's `spirv-builder` doesn't support, if it's known what
+    /// it supports.
+    fn warn_about_unsupported_flags(&self, spirv_builder_cli_path: &std::path::Path) {
+        if let Some(support) = spirv_builder_cli_path
+            .parent()
+            .and_then(crate::install::PairMetadata::read)
+            .and_then(|metadata| metadata.builder_support)
+        {
+            support.warn_about_unsupported_flags(&self.build_args);
+        }
+    }
+
+    /// With `--reproducible`, the metadata of the install at `spirv_builder_cli_path`, once it's
+    /// been checked that it can give a reproducible build.
+    fn reproducible_install(
+        &self,
+        spirv_builder_cli_path: &std::path::Path,
+    ) -> anyhow::Result<Option<crate::install::PairMetadata>> {
+        if !self.build_args.reproducible {
+            return Ok(None);
+        }
+        anyhow::ensure!(
+            !self.build_args.stats,
+            "`--stats` records compile times, so it can't be used with `--reproducible`"
+        );
+        anyhow::ensure!(
+            self.install.spirv_install.codegen_backend_path.is_none(),
+            "`--reproducible` needs an installed `rust-gpu`, not `--codegen-backend-path`"
+        );
+        let metadata = spirv_builder_cli_path
+            .parent()
+            .and_then(crate::install::PairMetadata::read)
+            .context("`--reproducible` needs the install's metadata")?;
+        crate::reproducible::ensure_pinned(&metadata)?;
+        Ok(Some(metadata))
+    }
+
+    /// Set up the environment of `spirv-builder-cli`, and so of the `cargo` that it runs.
+    fn configure_env(&self, spirv_builder_cli: &mut std::process::Command) -> anyhow::Result<()> {
+        crate::output::configure_child_color(spirv_builder_cli);
+        // First, so that the env vars that `cargo gpu` relies on can't be replaced.
+        spirv_builder_cli.envs(self.build_args.env.iter().map(|(key, value)| (key, value)));
+        if crate::offline::is_enabled() {
+            // `spirv-builder` runs `cargo` itself, which reads this just like `--offline`.
+            spirv_builder_cli.env("CARGO_NET_OFFLINE", "true");
+        }
+        if self.build_args.reproducible {
+            crate::reproducible::pin_env(spirv_builder_cli);
+        }
+        match self.install.toolchain_path() {
+            Ok(toolchain_path) => crate::spirv_cli::configure_toolchain_env(
+                spirv_builder_cli,
+                toolchain_path.as_deref(),
+            ),
+            Err(error) => log::warn!("{error:?}"),
+        }
+        if let Some(target_dir) = &self.build_args.target_dir {
+            // `spirv-builder` asks `cargo metadata` for the target dir, which reads this.
+            spirv_builder_cli.env(
+                "CARGO_TARGET_DIR",
+                std::path::absolute(target_dir).unwrap_or_else(|_| target_dir.clone()),
+            );
+        }
+        let cargo_flags = self.cargo_flags()?;
+        let extra_flags = [
+            ("RUSTGPU_RUSTFLAGS", self.build_args.rustflags.as_ref()),
+            (
+                "RUSTGPU_CODEGEN_ARGS",
+                self.build_args.codegen_args.as_ref(),
+            ),
+            // `spirv-builder` adds these to the `cargo build` that it runs.
+            ("RUSTGPU_CARGOFLAGS", cargo_flags.as_ref()),
+        ];
+        for (name, maybe_flags) in extra_flags {
+            if let Some(flags) = maybe_flags {
+                let inherited = if self.build_args.reproducible {
+                    None
+                } else {
+                    std::env::var(name).ok()
+                };
+                spirv_builder_cli.env(name, join_flags(inherited.as_deref(), flags));
+            }
+        }
+        Ok(())
+    }
+
+    /// The extra flags for the shader crate's `cargo build`, from `--cargo-flags` and `--`, as
+    /// `RUSTGPU_CARGOFLAGS` takes them.
+    fn cargo_flags(&self) -> anyhow::Result<Option<String>> {
+        let flags = self.build_args.all_cargo_flags();
+        if let Some(flag) = flags.iter().find(|flag| flag.contains(char::is_whitespace)) {
+            anyhow::bail!(
+                "`{flag}` can't be passed on to `cargo`, as `spirv-builder` splits its extra \
+                 `cargo` flags on whitespace"
+            );
+        }
+        Ok((!flags.is_empty()).then(|| flags.join(" ")))
+    }
+
+    /// Write `build-info.json` for a `--reproducible` build.
+    fn write_build_info(
+        &self,
+        metadata: &crate::install::PairMetadata,
+        fingerprint: &str,
+        linkage: &[Linkage],
+    ) -> anyhow::Result<()> {
+        let build_info_path = crate::reproducible::write_build_info(
+            self,
+            metadata,
+            fingerprint,
+            linkage,
+            &self.expected_outputs(),
+        )?;
+        log::info!("wrote build info to '{}'", build_info_path.display());
+        Ok(())
+    }
+
+    /// The files, other than the modules themselves, that a build writes, mostly to the output dir.
+    fn expected_outputs(&self) -> Vec<std::path::PathBuf> {
+        let manifest_path = self
+            .build_args
+            .output_dir
+            .join(&self.build_args.manifest_file);
+        let manifests = self
+            .build_args
+            .manifest_format
+            .iter()
+            .map(|format| manifest_format_path(&manifest_path, *format));
+        let generated = self
+            .build_args
+            .codegen
+            .iter()
+            .map(|target| crate::codegen::output_path(&manifest_path, *target));
+        let embedded = self
+            .build_args
+            .embed
+            .map(|_| crate::codegen::embed_path(&manifest_path));
+        let build_info = self.build_args.reproducible.then(|| {
+            self.build_args
+                .output_dir
+                .join(crate::reproducible::BUILD_INFO_FILE)
+        });
+        let pack = self
+            .build_args
+            .pack
+            .as_ref()
+            .map(|pack| self.build_args.output_dir.join(pack));
+        manifests
+            .chain(generated)
+            .chain(embedded)
+            .chain(build_info)
+            .chain(pack)
+            .chain(self.build_args.emit_depfile.clone())
+            .collect()
+    }
+
+    /// Build for each of the `--shader-target`s in turn, each into its own subdirectory of the
+    /// output dir. The JSON manifest in the output dir then combines every target's manifest,
+    /// keyed by target.
+    fn run_each_target(&self) -> anyhow::Result<Vec<Linkage>> {
+        anyhow::ensure!(
+            !self
+                .build_args
+                .shader_target
+                .iter()
+                .any(|target| target == crate::auto_target::AUTO),
+            "`--shader-target auto` can't be combined with other shader targets"
+        );
+
+        let mut subdirs = std::collections::HashSet::new();
+        let mut builds = Vec::new();
+        for (index, target) in self.build_args.shader_target.iter().enumerate() {
+            let subdir = target_subdir(target);
+            anyhow::ensure!(
+                subdirs.insert(subdir.clone()),
+                "more than one `--shader-target` would build into the `{subdir}` subdirectory"
+            );
+            let mut build = Self {
+                install: self.install.clone(),
+                build_args: self.build_args.clone(),
+                workspace: false,
+                package: None,
+            };
+            build.build_args.shader_target = vec![target.clone()];
+            // The hooks run once, around building every target.
+            build.build_args.pre_build = Vec::new();
+            build.build_args.post_build = Vec::new();
+            build.build_args.output_dir = self.build_args.output_dir.join(subdir);
+            // Every target uses the same binary pair, so only the first one needs to rebuild it.
+            if index > 0 {
+                build.install.spirv_install.force_spirv_cli_rebuild = false;
+            }
+            builds.push(build);
+        }
+        let results = Self::run_all(builds, crate::jobs::count(self.build_args.jobs), |build| {
+            crate::user_output!(
+                "Building for shader target `{}`...\n",
+                build.build_args.single_shader_target()
+            );
+        });
+        let mut linkage_by_target = std::collections::BTreeMap::new();
+        for (target, linkage) in self.build_args.shader_target.iter().zip(results) {
+            linkage_by_target.insert(target.clone(), linkage?);
+        }
+
+        if self
+            .build_args
+            .manifest_format
+            .contains(&ManifestFormat::Json)
+        {
+            let manifest_path = self
+                .build_args
+                .output_dir
+                .join(&self.build_args.manifest_file);
+            let json = serde_json::to_string_pretty(&linkage_by_target)?;
+            crate::atomic::write(&manifest_path, json).with_context(|| {
+                format!(
+                    "could not write combined shader manifest file '{}'",
+                    manifest_path.display(),
+                )
+            })?;
+            log::info!("wrote combined manifest to '{}'", manifest_path.display());
+            crate::output::emit(&crate::output::Event::ManifestWritten {
+                path: manifest_path,
+            });
+        }
+
+        Ok(linkage_by_target.into_values().flatten().collect())
+    }
+
+    /// Build each `--variant` in turn, with its Cargo features and into its own subdirectory of
+    /// the output dir. The manifest in the output dir then lists the entry points of every
+    /// variant, along with the variant's name and features.
+    fn run_each_variant(&self) -> anyhow::Result<Vec<Linkage>> {
+        let mut names = std::collections::HashSet::new();
+        let mut builds = Vec::new();
+        for (index, variant) in self.build_args.variants.iter().enumerate() {
+            anyhow::ensure!(
+                names.insert(&variant.name),
+                "the variant `{}` is given more than once",
+                variant.name
+            );
+            let mut build = Self {
+                install: self.install.clone(),
+                build_args: self.build_args.clone(),
+                workspace: false,
+                package: None,
+            };
+            build.build_args.variants = Vec::new();
+            // The hooks run once, around building every variant.
+            build.build_args.pre_build = Vec::new();
+            build.build_args.post_build = Vec::new();
+            build
+                .build_args
+                .features
+                .extend(variant.features.iter().cloned());
+            build.build_args.no_default_features |= variant.no_default_features;
+            build.build_args.output_dir = self.build_args.output_dir.join(&variant.name);
+            // Every variant uses the same binary pair, so only the first one needs to rebuild it.
+            if index > 0 {
+                build.install.spirv_install.force_spirv_cli_rebuild = false;
+            }
+            builds.push(build);
+        }
+        let features = builds
+            .iter()
+            .map(|build| build.build_args.features.clone())
+            .collect::<Vec<_>>();
+        let results = Self::run_all(builds, crate::jobs::count(self.build_args.jobs), |build| {
+            crate::user_output!(
+                "Building variant `{}`...\n",
+                build
+                    .build_args
+                    .output_dir
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+            );
+        });
+        let mut linkage = Vec::new();
+        for ((variant, variant_features), result) in
+            self.build_args.variants.iter().zip(features).zip(results)
+        {
+            linkage.extend(
+                result?.into_iter().map(|entry| {
+                    entry.with_variant(variant.name.clone(), variant_features.clone())
+                }),
+            );
+        }
+        linkage.sort();
+
+        let shader_crate = self.install.spirv_install.shader_crate.canonicalize()?;
+        let output_dir = self.build_args.output_dir.canonicalize()?;
+        let manifest_path = output_dir.join(&self.build_args.manifest_file);
+        let manifest = manifest_with_paths(
+            &linkage,
+            self.build_args.manifest_paths,
+            &shader_crate,
+            manifest_path.parent().unwrap_or(&output_dir),
+        );
+        for format in &self.build_args.manifest_format {
+            if *format == ManifestFormat::Rust {
+                log::warn!("the Rust manifest is only written for each variant, not for them all");
+                continue;
+            }
+            if *format == ManifestFormat::Bevy {
+                let path =
+                    crate::codegen::write_bevy_manifest(&linkage, &manifest_path, &shader_crate)?;
+                log::info!("wrote combined Bevy manifest to '{}'", path.display());
+                crate::output::emit(&crate::output::Event::ManifestWritten { path });
+                continue;
+            }
+            let path = manifest_format_path(&manifest_path, *format);
+            let contents = serialize_manifest(&manifest, *format)?;
+            crate::atomic::write(&path, contents).with_context(|| {
+                format!(
+                    "could not write combined shader manifest file '{}'",
+                    path.display()
+                )
+            })?;
+            log::info!("wrote combined manifest to '{}'", path.display());
+            crate::output::emit(&crate::output::Event::ManifestWritten { path });
+        }
+
+        Ok(linkage)
+    }
+
+    /// Add the shader crate's entry points to the workspace manifest in the output dir.
+    pub fn update_workspace_manifest(&self, linkage: &[Linkage]) -> anyhow::Result<()> {
+        crate::workspace_manifest::update(
+            &self.build_args.output_dir,
+            &self.shader_crate_name(),
+            &self.install.spirv_install.shader_crate,
+            linkage,
+            self.build_args.manifest_paths,
+        )?;
+        Ok(())
+    }
+
+    /// Rename a module copied to the output dir according to `--output-file-template`, returning
+    /// its new path.
+    #[expect(
+        clippy::literal_string_with_formatting_args,
+        reason = "The template's placeholders look like formatting args"
+    )]
+    pub fn rename_to_output_file_template(
+        &self,
+        path: &std::path::Path,
+        entry_points: &[String],
+    ) -> anyhow::Result<std::path::PathBuf> {
+        let name = path
+            .file_stem()
+            .context("module path has no file name")?
+            .to_string_lossy();
+        // Modules with more than one entry point are named after the crate instead.
+        let entry = match entry_points {
+            [entry] => entry.replace("::", "-"),
+            _ => name.to_string(),
+        };
+        let target = std::path::Path::new(self.build_args.single_shader_target())
+            .file_stem()
+            .map_or_else(
+                || self.build_args.single_shader_target().to_owned(),
+                |stem| stem.to_string_lossy().into_owned(),
+            );
+        let hash = if self.build_args.output_file_template.contains("{hash}") {
+            let bytes = std::fs::read(path)
+                .with_context(|| format!("could not read '{}'", path.display()))?;
+            crate::prebuilt::sha256_hex(&bytes)
+                .get(..16)
+                .unwrap_or_default()
+                .to_owned()
+        } else {
+            String::new()
+        };
+
+        let file_name = render_output_file_template(
+            &self.build_args.output_file_template,
+            &[
+                ("name", &name),
+                ("entry", &entry),
+                ("target", &target),
+                ("hash", &hash),
+            ],
+        )?;
+        let renamed = path.with_file_name(file_name);
+        if renamed != path {
+            log::debug!("renaming {} to {}", path.display(), renamed.display());
+            std::fs::rename(path, &renamed).with_context(|| {
+                format!(
+                    "could not rename '{}' to '{}'",
+                    path.display(),
+                    renamed.display()
+                )
+            })?;
+        }
+        Ok(renamed)
+    }
+
+    /// In build script mode, tell `cargo` about every file that the shaders are built from, with
+    /// `cargo:rerun-if-changed` directives.
+    fn declare_inputs(&self) -> anyhow::Result<()> {
+        if !self.build_args.build_script_mode {
+            return Ok(());
+        }
+        let inputs = crate::fingerprint::input_files(
+            &self.install.spirv_install.shader_crate,
+            &self.build_args.output_dir,
+        )?;
+        for input in &inputs {
+            crate::output::cargo_directive(&format!("rerun-if-changed={}", input.display()));
+        }
         Ok(())
     }
+
+    /// Write the `--emit-depfile`, from the dep-info of the freshly compiled `shaders`.
+    fn write_depfile(&self, shaders: &[ShaderModule]) -> anyhow::Result<()> {
+        let Some(depfile) = &self.build_args.emit_depfile else {
+            return Ok(());
+        };
+        let modules = shaders
+            .iter()
+            .map(|shader| shader.path.as_path())
+            .collect::<Vec<_>>();
+        let inputs = crate::depfile::inputs(
+            &modules,
+            crate::metadata::Metadata::local_inputs(&self.install.spirv_install.shader_crate)?,
+        )?;
+        let manifest_path = self
+            .build_args
+            .output_dir
+            .join(&self.build_args.manifest_file);
+        crate::depfile::write(depfile, &manifest_path, &inputs)
+    }
+
+    /// The package name of the shader crate, falling back to its directory name if its
+    /// `Cargo.toml` can't be read.
+    #[must_use]
+    pub fn shader_crate_name(&self) -> String {
+        let shader_crate = &self.install.spirv_install.shader_crate;
+        let maybe_name = std::fs::read_to_string(shader_crate.join("Cargo.toml"))
+            .ok()
+            .and_then(|contents| contents.parse::<toml::Table>().ok())
+            .and_then(|cargo_toml| {
+                cargo_toml
+                    .get("package")?
+                    .get("name")?
+                    .as_str()
+                    .map(ToOwned::to_owned)
+            });
+        maybe_name.unwrap_or_else(|| {
+            shader_crate
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        })
+    }
+
+    /// Paths in the manifest are relative to the shader crate, when possible.
+    #[must_use]
+    pub fn relative_to_shader_crate(&self, path: &std::path::Path) -> std::path::PathBuf {
+        use relative_path::PathExt as _;
+        path.relative_to(&self.install.spirv_install.shader_crate)
+            .map_or_else(
+                |_| path.to_path_buf(),
+                |path_relative_to_shader_crate| path_relative_to_shader_crate.to_path(""),
+            )
+    }
+}
+
+/// Where the manifest is written in `format`, next to the JSON manifest at `manifest_path`.
+fn manifest_format_path(
+    manifest_path: &std::path::Path,
+    format: ManifestFormat,
+) -> std::path::PathBuf {
+    match format {
+        ManifestFormat::Json => manifest_path.to_path_buf(),
+        ManifestFormat::Toml => manifest_path.with_extension("toml"),
+        ManifestFormat::Ron => manifest_path.with_extension("ron"),
+        ManifestFormat::Rust => manifest_path.with_extension("rs"),
+        ManifestFormat::Bevy => manifest_path.with_extension("bevy.ron"),
+    }
+}
+
+/// The manifest of `linkage`, whose paths are relative to `shader_crate`, with its paths changed
+/// to be relative to what `paths` asks for. `shader_crate` and `manifest_dir` must be absolute.
+#[must_use]
+pub fn manifest_with_paths(
+    linkage: &[Linkage],
+    paths: ManifestPaths,
+    shader_crate: &std::path::Path,
+    manifest_dir: &std::path::Path,
+) -> Manifest {
+    use relative_path::PathExt as _;
+
+    let entry_points = linkage
+        .iter()
+        .cloned()
+        .map(|entry| match paths {
+            ManifestPaths::RelativeToCrate => entry,
+            ManifestPaths::RelativeToManifest => entry.with_paths_mapped(|path| {
+                let absolute = shader_crate.join(path);
+                absolute.relative_to(manifest_dir).map_or_else(
+                    |_| absolute.display().to_string(),
+                    relative_path::RelativePathBuf::into_string,
+                )
+            }),
+            ManifestPaths::Absolute => {
+                entry.with_paths_mapped(|path| shader_crate.join(path).display().to_string())
+            }
+        })
+        .collect();
+    Manifest {
+        paths,
+        ..Manifest::new(entry_points)
+    }
+}
+
+/// The manifest as JSON, TOML or RON.
+fn serialize_manifest(manifest: &Manifest, format: ManifestFormat) -> anyhow::Result<String> {
+    Ok(match format {
+        ManifestFormat::Json => serde_json::to_string_pretty(manifest)?,
+        ManifestFormat::Toml => toml::to_string_pretty(manifest)?,
+        ManifestFormat::Ron => ron::ser::to_string_pretty(manifest, ron::ser::PrettyConfig::new())?,
+        ManifestFormat::Rust => anyhow::bail!("the Rust manifest is generated code"),
+        ManifestFormat::Bevy => anyhow::bail!("the Bevy manifest has its own layout"),
+    })
+}
+
+/// Fill in the `{placeholder}`s of an `--output-file-template`.
+fn render_output_file_template(template: &str, values: &[(&str, &str)]) -> anyhow::Result<String> {
+    let mut rendered = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(rest.get(..start).unwrap_or_default());
+        let after_brace = rest.get(start.saturating_add(1)..).unwrap_or_default();
+        let end = after_brace
+            .find('}')
+            .with_context(|| format!("unclosed `{{` in output file template `{template}`"))?;
+        let placeholder = after_brace.get(..end).unwrap_or_default();
+        let value = values
+            .iter()
+            .find(|(name, _)| *name == placeholder)
+            .map(|(_, value)| *value)
+            .with_context(|| {
+                format!(
+                    "unknown placeholder `{{{placeholder}}}` in output file template `{template}`, \
+                     expected one of {{name}}, {{entry}}, {{target}} or {{hash}}"
+                )
+            })?;
+        rendered.push_str(value);
+        rest = after_brace.get(end.saturating_add(1)..).unwrap_or_default();
+    }
+    rendered.push_str(rest);
+    anyhow::ensure!(
+        !rendered.is_empty() && !rendered.contains(['/', '\\']),
+        "output file template `{template}` must render to a file name, got `{rendered}`"
+    );
+    Ok(rendered)
+}
+
+/// Whether `name`, from `--entry-point`, selects the entry point `entry`. Entry points can be
+/// selected by their full path, eg `lighting::main_fs`, or just their function name.
+fn is_entry_point_match(name: &str, entry: &str) -> bool {
+    name == entry || entry.rsplit("::").next() == Some(name)
+}
+
+/// Like [`is_entry_point_match`], but `pattern` can contain `*` wildcards, eg `debug_*` or
+/// `tests::*`.
+fn is_entry_point_match_glob(pattern: &str, entry: &str) -> bool {
+    glob_match(pattern, entry)
+        || entry
+            .rsplit("::")
+            .next()
+            .is_some_and(|name| glob_match(pattern, name))
+}
+
+/// Whether `text` matches `pattern`, where `*` matches any run of characters.
+#[must_use]
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let Some(first) = parts.next() else {
+        return text.is_empty();
+    };
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts = parts.collect::<Vec<_>>();
+    let Some(last) = parts.pop() else {
+        // No wildcards, so it must be an exact match.
+        return rest.is_empty();
+    };
+    for part in parts {
+        let Some(index) = rest.find(part) else {
+            return false;
+        };
+        rest = rest
+            .get(index.saturating_add(part.len())..)
+            .unwrap_or_default();
+    }
+    rest.ends_with(last)
+}
+
+/// The error for a failed compile, with `spirv-builder-cli`'s `stderr`, which has its own exit
+/// code when it's because of the `Cargo.lock`.
+fn build_failure(stderr: &[u8]) -> anyhow::Error {
+    let error = anyhow::anyhow!("build failed");
+    if crate::exit::is_lockfile_conflict(&String::from_utf8_lossy(stderr)) {
+        crate::exit::Failure::LockfileConflict(error).into()
+    } else {
+        error
+    }
+}
+
+/// The name of the subdirectory of the output dir that `target` is built into, when building for
+/// more than one. A target spec given by its path is named after its file, and anything that
+/// isn't safe in a file name is replaced, so that it's always just under the output dir.
+fn target_subdir(target: &str) -> String {
+    let path = std::path::Path::new(target);
+    let name = if path
+        .extension()
+        .is_some_and(|extension| extension == "json")
+    {
+        path.file_stem()
+    } else {
+        path.file_name()
+    };
+    let sanitised = name
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default()
+        .chars()
+        .map(|character| {
+            if character.is_ascii_alphanumeric() || matches!(character, '-' | '_' | '.') {
+                character
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    if sanitised.chars().all(|character| character == '.') {
+        "target".to_owned()
+    } else {
+        sanitised
+    }
+}
+
+/// Flags from the environment followed by the configured ones, so that the configured ones take
+/// precedence where they conflict.
+fn join_flags(inherited: Option<&str>, flags: &str) -> String {
+    match inherited.map(str::trim) {
+        Some(inherited_flags) if !inherited_flags.is_empty() => {
+            format!("{inherited_flags} {}", flags.trim())
+        }
+        _ => flags.trim().to_owned(),
+    }
 }
 
 #[cfg(test)]
 mod test {
     use clap::Parser as _;
 
+    use spirv_builder_cli::{
+        args::{BuildArgs, ManifestFormat, ManifestPaths, Variant},
+        Linkage,
+    };
+
     use crate::{Cli, Command};
 
     #[test_log::test]
@@ -178,6 +1272,7 @@ mod test {
         ];
         if let Cli {
             command: Command::Build(build),
+            ..
         } = Cli::parse_from(args)
         {
             assert_eq!(shader_crate_path, build.install.spirv_install.shader_crate);
@@ -192,4 +1287,309 @@ mod test {
             panic!("was not a build command");
         }
     }
+
+    #[test_log::test]
+    fn passing_flags_on_to_cargo() {
+        let args = [
+            "cargo-gpu",
+            "build",
+            "--cargo-flags",
+            "--locked -Zbuild-std=core",
+            "-v",
+            "--",
+            "--offline",
+            "-v",
+        ];
+        let env_args = Cli::without_output_args(args.map(String::from).to_vec());
+        let Command::Build(mut build) = Cli::parse_from(env_args).command else {
+            panic!("was not a build command");
+        };
+        let mut spirv_builder_cli = std::process::Command::new("spirv-builder-cli");
+        build.configure_env(&mut spirv_builder_cli).unwrap();
+        let cargo_flags = spirv_builder_cli
+            .get_envs()
+            .find(|(name, _)| *name == "RUSTGPU_CARGOFLAGS")
+            .and_then(|(_, value)| value);
+        assert_eq!(
+            cargo_flags.and_then(std::ffi::OsStr::to_str),
+            Some("--locked -Zbuild-std=core --offline -v")
+        );
+
+        build.build_args.cargo_args = vec!["--config".to_owned(), "build.jobs = 2".to_owned()];
+        assert!(build
+            .configure_env(&mut std::process::Command::new("spirv-builder-cli"))
+            .is_err());
+    }
+
+    #[test_log::test]
+    fn classifying_the_captured_stderr_of_a_failed_build() {
+        let mut failing_build = crate::hooks::shell(
+            "echo 'error: failed to parse lock file at: /shaders/Cargo.lock' >&2; exit 101",
+        );
+        failing_build.stderr(crate::output::child_stderr());
+        let output = crate::log_file::output(&mut failing_build, false).unwrap();
+        assert!(!output.status.success());
+        let lockfile_conflict: i32 = 4;
+        assert_eq!(
+            crate::exit::code(&super::build_failure(&output.stderr)),
+            lockfile_conflict
+        );
+        assert_eq!(
+            crate::exit::code(&super::build_failure(b"error: could not compile `shaders`")),
+            crate::exit::GENERAL
+        );
+    }
+
+    #[test_log::test]
+    fn naming_the_subdirectory_of_each_target() {
+        use super::target_subdir;
+
+        assert_eq!(
+            target_subdir("spirv-unknown-vulkan1.2"),
+            "spirv-unknown-vulkan1.2"
+        );
+        assert_eq!(
+            target_subdir("/specs/spirv-unknown-vulkan1.3.json"),
+            "spirv-unknown-vulkan1.3"
+        );
+        assert_eq!(target_subdir("specs/my target.json"), "my_target");
+        assert_eq!(target_subdir(".."), "target");
+        assert_eq!(target_subdir("/"), "target");
+    }
+
+    #[test_log::test]
+    fn adding_configured_rustflags_to_inherited_ones() {
+        let Command::Build(build) = Cli::parse_from([
+            "cargo-gpu",
+            "build",
+            "--rustflags",
+            "-Cdebug-assertions=on",
+            "--codegen-args",
+            "--no-early-report-zombies",
+        ])
+        .command
+        else {
+            panic!("was not a build command");
+        };
+        assert_eq!(
+            build.build_args.rustflags.as_deref(),
+            Some("-Cdebug-assertions=on")
+        );
+        assert_eq!(
+            build.build_args.codegen_args.as_deref(),
+            Some("--no-early-report-zombies")
+        );
+        assert_eq!(
+            super::join_flags(Some("-Zshare-generics "), " -Copt-level=1"),
+            "-Zshare-generics -Copt-level=1"
+        );
+        assert_eq!(
+            super::join_flags(Some(""), "-Copt-level=1"),
+            "-Copt-level=1"
+        );
+        assert_eq!(super::join_flags(None, "-Copt-level=1"), "-Copt-level=1");
+    }
+
+    #[test_log::test]
+    fn parsing_variants() {
+        let build = BuildArgs::parse_from([
+            "",
+            "--variant",
+            "shadows:features=pcf,soft",
+            "--variant",
+            "minimal:no-default-features",
+        ]);
+        assert_eq!(
+            build.variants,
+            [
+                Variant {
+                    name: "shadows".to_owned(),
+                    features: vec!["pcf".to_owned(), "soft".to_owned()],
+                    no_default_features: false,
+                },
+                Variant {
+                    name: "minimal".to_owned(),
+                    features: vec![],
+                    no_default_features: true,
+                }
+            ]
+        );
+
+        for invalid in ["", "../up", "shadows:pcf"] {
+            assert!(
+                BuildArgs::try_parse_from(["", "--variant", invalid]).is_err(),
+                "`{invalid}` should be invalid"
+            );
+        }
+    }
+
+    #[test_log::test]
+    fn matching_entry_points() {
+        assert!(super::is_entry_point_match("main_fs", "main_fs"));
+        assert!(super::is_entry_point_match("main_fs", "lighting::main_fs"));
+        assert!(super::is_entry_point_match(
+            "lighting::main_fs",
+            "lighting::main_fs"
+        ));
+        assert!(!super::is_entry_point_match("main", "main_fs"));
+        assert!(!super::is_entry_point_match(
+            "lighting",
+            "lighting::main_fs"
+        ));
+    }
+
+    #[test_log::test]
+    fn excluding_entry_points_with_globs() {
+        assert!(super::is_entry_point_match_glob("debug_*", "debug_normals"));
+        assert!(super::is_entry_point_match_glob(
+            "debug_*",
+            "lighting::debug_normals"
+        ));
+        assert!(super::is_entry_point_match_glob(
+            "tests::*",
+            "tests::main_cs"
+        ));
+        assert!(!super::is_entry_point_match_glob("tests::*", "main_cs"));
+        assert!(super::is_entry_point_match_glob("*_fs", "main_fs"));
+        assert!(super::is_entry_point_match_glob("main_*_fs", "main_lit_fs"));
+        assert!(!super::is_entry_point_match_glob("main_*_fs", "main_fs"));
+        assert!(super::is_entry_point_match_glob("main_fs", "main_fs"));
+        assert!(!super::is_entry_point_match_glob("main", "main_fs"));
+    }
+
+    #[test_log::test]
+    fn rendering_output_file_templates() {
+        let values = [("name", "shaders"), ("entry", "main_fs"), ("hash", "0123")];
+        assert_eq!(
+            super::render_output_file_template("{name}.spv", &values).unwrap(),
+            "shaders.spv"
+        );
+        assert_eq!(
+            super::render_output_file_template("{entry}-{hash}.spv", &values).unwrap(),
+            "main_fs-0123.spv"
+        );
+        super::render_output_file_template("{nope}.spv", &values).unwrap_err();
+        super::render_output_file_template("{entry.spv", &values).unwrap_err();
+        super::render_output_file_template("shaders/{entry}.spv", &values).unwrap_err();
+    }
+
+    #[test_log::test]
+    fn serializing_manifest_formats() {
+        let binding = spirv_builder_cli::DescriptorBinding {
+            set: 0,
+            binding: 1,
+            descriptor_type: "uniform_buffer".to_owned(),
+            count: None,
+            name: None,
+            read_only: false,
+            write_only: false,
+            image: None,
+        };
+        let linkage = [Linkage::new("main_fs", "shaders/main.spv")
+            .with_reflection("fragment".to_owned(), vec![binding])];
+
+        let manifest = spirv_builder_cli::Manifest::new(linkage.to_vec());
+        let toml = super::serialize_manifest(&manifest, ManifestFormat::Toml).unwrap();
+        assert!(
+            toml.starts_with(
+                "schema_version = 1\npaths = \"relative-to-crate\"\n\n[[entry_points]]\n"
+            ),
+            "{toml}"
+        );
+        assert!(
+            toml.contains("source_path = \"shaders/main.spv\"\n"),
+            "{toml}"
+        );
+        assert!(
+            toml.contains("[[entry_points.bindings]]\nset = 0\nbinding = 1\n"),
+            "{toml}"
+        );
+
+        let ron = super::serialize_manifest(&manifest, ManifestFormat::Ron).unwrap();
+        let parsed: spirv_builder_cli::Manifest = ron::from_str(&ron).unwrap();
+        assert_eq!(parsed.entry_points, linkage);
+
+        let auto_manifest = spirv_builder_cli::Manifest {
+            auto_shader_target: Some(spirv_builder_cli::AutoShaderTarget {
+                target: "spirv-unknown-vulkan1.3".to_owned(),
+                device: Some("NVIDIA GeForce RTX 3060".to_owned()),
+                vulkan_version: Some("1.3".to_owned()),
+            }),
+            ..manifest
+        };
+        let auto_toml = super::serialize_manifest(&auto_manifest, ManifestFormat::Toml).unwrap();
+        assert!(
+            auto_toml.contains("[auto_shader_target]\ntarget = \"spirv-unknown-vulkan1.3\"\n"),
+            "{auto_toml}"
+        );
+        let auto_json = super::serialize_manifest(&auto_manifest, ManifestFormat::Json).unwrap();
+        assert_eq!(
+            spirv_builder_cli::Manifest::from_json(&auto_json).unwrap(),
+            auto_manifest
+        );
+    }
+
+    #[test_log::test]
+    fn parsing_manifests_of_every_schema_version() {
+        let linkage = vec![Linkage::new("main_fs", "shaders/main.spv")];
+        let manifest = spirv_builder_cli::Manifest::new(linkage.clone());
+        let json = super::serialize_manifest(&manifest, ManifestFormat::Json).unwrap();
+        let current = spirv_builder_cli::Manifest::from_json(&json).unwrap();
+        assert_eq!(current, manifest);
+        assert_eq!(
+            current.schema_version,
+            spirv_builder_cli::MANIFEST_SCHEMA_VERSION
+        );
+
+        // Before the schema was versioned, the manifest was just the array of entry points.
+        let unversioned = spirv_builder_cli::Manifest::from_json(
+            r#"[{"source_path":"shaders/main.spv","entry_point":"main_fs","wgsl_entry_point":"main_fs"}]"#,
+        )
+        .unwrap();
+        assert_eq!(unversioned.schema_version, 0);
+        assert_eq!(unversioned.entry_points, linkage);
+
+        // Fields added later are ignored by older readers, and fields that are missing get
+        // their defaults.
+        let newer = spirv_builder_cli::Manifest::from_json(
+            r#"{"schema_version":1,"entry_points":[{"source_path":"shaders/main.spv","entry_point":"main_fs","wgsl_entry_point":"main_fs","added_later":true}]}"#,
+        )
+        .unwrap();
+        assert_eq!(newer.entry_points, linkage);
+    }
+
+    #[test_log::test]
+    fn changing_what_manifest_paths_are_relative_to() {
+        let shader_crate = std::path::absolute("shaders").unwrap();
+        let manifest_dir = std::path::absolute("out").unwrap();
+        let linkage = [
+            Linkage::new("main_fs", "out/main.spv").with_transpiled_source_path(
+                spirv_builder_cli::args::TranspileTarget::Wgsl,
+                "out/main_fs.wgsl",
+            ),
+        ];
+        let paths_of = |paths| {
+            let manifest =
+                super::manifest_with_paths(&linkage, paths, &shader_crate, &manifest_dir);
+            assert_eq!(manifest.paths, paths);
+            let entry = manifest.entry_points.into_iter().next().unwrap();
+            (entry.source_path, entry.wgsl_source_path.unwrap())
+        };
+
+        assert_eq!(
+            paths_of(ManifestPaths::RelativeToCrate),
+            ("out/main.spv".to_owned(), "out/main_fs.wgsl".to_owned())
+        );
+        assert_eq!(
+            paths_of(ManifestPaths::RelativeToManifest),
+            (
+                "../shaders/out/main.spv".to_owned(),
+                "../shaders/out/main_fs.wgsl".to_owned()
+            )
+        );
+        assert_eq!(
+            paths_of(ManifestPaths::Absolute).0,
+            shader_crate.join("out/main.spv").display().to_string()
+        );
+    }
 }
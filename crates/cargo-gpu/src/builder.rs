@@ -0,0 +1,207 @@
+//! Library API for compiling shader crates, eg from a host crate's `build.rs`.
+
+use crate::{build::Build, config::Config};
+use spirv_builder_cli::Linkage;
+
+/// Compile a shader crate to SPIR-V, the library equivalent of `cargo gpu build`.
+///
+/// Just like the CLI, any config found in the shader crate's `[package.metadata.rust-gpu.*]`
+/// sections is used as the base config, which the options set here then override.
+#[derive(Debug, Clone)]
+pub struct Builder {
+    /// Directory containing the shader crate to compile.
+    shader_crate: std::path::PathBuf,
+    /// CLI-style arguments, these get merged with the shader crate's config in exactly the
+    /// same way as arguments passed to `cargo gpu build`.
+    args: Vec<String>,
+}
+
+#[expect(
+    clippy::missing_inline_in_public_items,
+    reason = "Each method is only called once per build, which dwarfs any call overhead"
+)]
+#[expect(
+    clippy::impl_trait_in_params,
+    reason = "Taking `impl AsRef` makes the builder as easy to call as `std::process::Command`"
+)]
+impl Builder {
+    /// Create a builder for the shader crate at the given path.
+    pub fn new(shader_crate: impl AsRef<std::path::Path>) -> Self {
+        Self {
+            shader_crate: shader_crate.as_ref().to_path_buf(),
+            args: Vec::new(),
+        }
+    }
+
+    /// Add a raw `cargo gpu build` argument, eg `--relax-block-layout`. Useful for options that
+    /// don't have a dedicated method.
+    #[must_use]
+    pub fn arg(mut self, arg: impl AsRef<str>) -> Self {
+        self.args.push(arg.as_ref().to_owned());
+        self
+    }
+
+    /// Add an argument that takes a value, eg `--shader-target spirv-unknown-vulkan1.2`.
+    fn arg_with_value(self, name: &str, value: impl AsRef<str>) -> Self {
+        self.arg(name).arg(value)
+    }
+
+    /// Shader target, eg "spirv-unknown-vulkan1.2".
+    #[must_use]
+    pub fn shader_target(self, target: impl AsRef<str>) -> Self {
+        self.arg_with_value("--shader-target", target)
+    }
+
+    /// Path to the output directory for the compiled shaders.
+    #[must_use]
+    pub fn output_dir(self, path: impl AsRef<std::path::Path>) -> Self {
+        self.arg_with_value("--output-dir", path.as_ref().display().to_string())
+    }
+
+    /// Name of the manifest file written to the output directory.
+    #[must_use]
+    pub fn manifest_file(self, name: impl AsRef<str>) -> Self {
+        self.arg_with_value("--manifest-file", name)
+    }
+
+    /// Write a Makefile-style depfile listing every file that the shaders were built from.
+    #[must_use]
+    pub fn emit_depfile(self, path: impl AsRef<std::path::Path>) -> Self {
+        self.arg_with_value("--emit-depfile", path.as_ref().display().to_string())
+    }
+
+    /// Print `cargo:rerun-if-changed` and `cargo:warning` directives, for when building from a
+    /// `build.rs`.
+    #[must_use]
+    pub fn build_script_mode(self) -> Self {
+        self.arg("--build-script-mode")
+    }
+
+    /// Compile shaders in debug mode.
+    #[must_use]
+    pub fn debug(self) -> Self {
+        self.arg("--debug")
+    }
+
+    /// Treat warnings as errors during compilation.
+    #[must_use]
+    pub fn deny_warnings(self) -> Self {
+        self.arg("--deny-warnings")
+    }
+
+    /// Compile one .spv file per entry point.
+    #[must_use]
+    pub fn multimodule(self) -> Self {
+        self.arg("--multimodule")
+    }
+
+    /// Disable the shader crate's cargo default-features.
+    #[must_use]
+    pub fn no_default_features(self) -> Self {
+        self.arg("--no-default-features")
+    }
+
+    /// Enable the shader crate's cargo feature.
+    #[must_use]
+    pub fn feature(self, feature: impl AsRef<str>) -> Self {
+        self.arg_with_value("--features", feature)
+    }
+
+    /// Enable a SPIR-V capability.
+    #[must_use]
+    pub fn capability(self, capability: spirv_builder_cli::spirv::Capability) -> Self {
+        // The `Debug` formatting is what `--capability` parses
+        let name = format!("{capability:?}");
+        self.arg_with_value("--capability", name)
+    }
+
+    /// Enable a SPIR-V extension.
+    #[must_use]
+    pub fn extension(self, extension: impl AsRef<str>) -> Self {
+        self.arg_with_value("--extension", extension)
+    }
+
+    /// Source of the `spirv-builder` dependency, eg "<https://github.com/Rust-GPU/rust-gpu>".
+    #[must_use]
+    pub fn spirv_builder_source(self, source: impl AsRef<str>) -> Self {
+        self.arg_with_value("--spirv-builder-source", source)
+    }
+
+    /// Version of the `spirv-builder` dependency.
+    #[must_use]
+    pub fn spirv_builder_version(self, version: impl AsRef<str>) -> Self {
+        self.arg_with_value("--spirv-builder-version", version)
+    }
+
+    /// Rust toolchain channel to use to build `spirv-builder`.
+    #[must_use]
+    pub fn rust_toolchain(self, channel: impl AsRef<str>) -> Self {
+        self.arg_with_value("--rust-toolchain", channel)
+    }
+
+    /// Don't prompt before installing any required Rust toolchain. Prompting isn't possible from
+    /// a `build.rs`, so this is usually what you want there.
+    #[must_use]
+    pub fn auto_install_rust_toolchain(self) -> Self {
+        self.arg("--auto-install-rust-toolchain")
+    }
+
+    /// Force `spirv-builder-cli` and `rustc_codegen_spirv` to be rebuilt.
+    #[must_use]
+    pub fn force_spirv_cli_rebuild(self) -> Self {
+        self.arg("--force-spirv-cli-rebuild")
+    }
+
+    /// The final config, after merging the shader crate's config with the options set here.
+    fn to_build(&self) -> anyhow::Result<Build> {
+        let mut env_args = vec![
+            "cargo-gpu".to_owned(),
+            "build".to_owned(),
+            "--shader-crate".to_owned(),
+            self.shader_crate.display().to_string(),
+        ];
+        env_args.extend(self.args.iter().cloned());
+        log::debug!("library build with args: {env_args:?}");
+
+        Config::clap_command_with_cargo_config(&self.shader_crate, env_args)
+    }
+
+    /// Install the `rust-gpu` compiler if needed, compile the shader crate, and write its
+    /// manifest. Returns the linkage of every compiled entry point.
+    ///
+    /// # Errors
+    /// If the shader crate's config or the options are invalid, or if installing or compiling
+    /// fails.
+    pub fn build(&self) -> anyhow::Result<Vec<Linkage>> {
+        let mut build = self.to_build()?;
+        build.build_args.watch = false;
+        build.run()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_log::test]
+    fn options_override_cargo_config() {
+        let shader_crate_path = crate::test::shader_crate_test_path();
+
+        let build = Builder::new(&shader_crate_path)
+            .shader_target("spirv-unknown-spv1.3")
+            .manifest_file("shaders.json")
+            .debug()
+            .capability(spirv_builder_cli::spirv::Capability::Int8)
+            .to_build()
+            .unwrap();
+
+        assert_eq!(build.install.spirv_install.shader_crate, shader_crate_path);
+        assert_eq!(build.build_args.shader_target, ["spirv-unknown-spv1.3"]);
+        assert_eq!(build.build_args.manifest_file, "shaders.json");
+        assert!(build.build_args.debug);
+        assert_eq!(
+            build.build_args.capability,
+            vec![spirv_builder_cli::spirv::Capability::Int8]
+        );
+    }
+}
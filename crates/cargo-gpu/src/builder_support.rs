@@ -0,0 +1,161 @@
+//! What the `rust-gpu` version's `spirv-builder` can do, found by looking at its source.
+//!
+//! `spirv-builder-cli` is compiled with a Cargo feature for each `SpirvBuilder` interface. Which
+//! one to use was once decided by the date of the `rust-gpu` commit, but that's wrong for forks
+//! and backports, so now the checked-out source is searched for the builder methods that each
+//! feature calls. The date is only used when there's no source to search, eg for crates.io
+//! releases.
+
+use spirv_builder_cli::args::{BuildArgs, ShaderPanicStrategy};
+
+/// Where `spirv-builder`'s source is in a `rust-gpu` checkout.
+const SOURCE_DIR: &str = "crates/spirv-builder/src";
+
+/// The shader target that every version of `spirv-builder` can build for.
+const DEFAULT_SHADER_TARGET: &str = "spirv-unknown-vulkan1.2";
+
+/// The `SpirvBuilder` methods that `spirv-builder-cli` calls, if they exist.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[expect(
+    clippy::struct_excessive_bools,
+    reason = "Each method is there or it isn't"
+)]
+pub struct BuilderSupport {
+    /// `rustc_codegen_spirv_location()`, for using an installed codegen backend. Versions
+    /// without it predate `cargo gpu`.
+    pub codegen_backend_location: bool,
+    /// `target_spec()`, for building for targets other than the default one.
+    pub target_spec: bool,
+    /// `shader_crate_features()` and `shader_crate_default_features()`.
+    pub shader_crate_features: bool,
+    /// `shader_panic_strategy()`.
+    pub shader_panic_strategy: bool,
+}
+
+impl BuilderSupport {
+    /// Search the `spirv-builder` source in the `rust-gpu` `checkout`. Returns `None` if there's
+    /// no source there.
+    #[must_use]
+    pub fn detect(checkout: &std::path::Path) -> Option<Self> {
+        let entries = std::fs::read_dir(checkout.join(SOURCE_DIR)).ok()?;
+        let source = entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == "rs"))
+            .filter_map(|path| std::fs::read_to_string(path).ok())
+            .collect::<Vec<_>>()
+            .join("\n");
+        (!source.is_empty()).then(|| Self::from_source(&source))
+    }
+
+    /// What the `spirv-builder` source code in `source` supports.
+    #[must_use]
+    pub fn from_source(source: &str) -> Self {
+        let has_method = |name: &str| source.contains(&format!("fn {name}("));
+        Self {
+            codegen_backend_location: has_method("rustc_codegen_spirv_location"),
+            target_spec: has_method("target_spec"),
+            shader_crate_features: has_method("shader_crate_features")
+                && has_method("shader_crate_default_features"),
+            shader_panic_strategy: has_method("shader_panic_strategy"),
+        }
+    }
+
+    /// The `spirv-builder-cli` Cargo feature that matches this interface.
+    #[must_use]
+    pub const fn cli_feature(self) -> &'static str {
+        if !self.codegen_backend_location {
+            "spirv-builder-pre-cli"
+        } else if self.shader_panic_strategy {
+            "spirv-builder-0_11"
+        } else {
+            "spirv-builder-0_10"
+        }
+    }
+
+    /// The flags in `build` that this `spirv-builder` can't do anything with.
+    #[must_use]
+    pub fn unsupported_flags(self, build: &BuildArgs) -> Vec<&'static str> {
+        let is_supported = self.codegen_backend_location;
+        let mut flags = Vec::new();
+        if !(is_supported && self.target_spec)
+            && build
+                .shader_target
+                .iter()
+                .any(|target| target != DEFAULT_SHADER_TARGET)
+        {
+            flags.push("--shader-target");
+        }
+        if !(is_supported && self.shader_crate_features) {
+            if build.no_default_features {
+                flags.push("--no-default-features");
+            }
+            if !build.features.is_empty() {
+                flags.push("--features");
+            }
+        }
+        if !(is_supported && self.shader_panic_strategy)
+            && build.shader_panic_strategy != ShaderPanicStrategy::SilentExit
+        {
+            flags.push("--shader-panic-strategy");
+        }
+        flags
+    }
+
+    /// Warn about each flag in `build` that this `spirv-builder` ignores.
+    pub fn warn_about_unsupported_flags(self, build: &BuildArgs) {
+        for flag in self.unsupported_flags(build) {
+            crate::user_output!(
+                "warning: this version of `rust-gpu`'s `spirv-builder` doesn't support `{flag}`, \
+                 it will be ignored\n"
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use clap::Parser as _;
+
+    #[test_log::test]
+    fn detecting_what_spirv_builder_supports() {
+        let pre_cli = BuilderSupport::from_source(
+            "impl SpirvBuilder {\n    pub fn new(path_to_crate: impl AsRef<Path>) -> Self {}\n}",
+        );
+        assert_eq!(pre_cli, BuilderSupport::default());
+        assert_eq!(pre_cli.cli_feature(), "spirv-builder-pre-cli");
+
+        let v0_10 = BuilderSupport::from_source(
+            "pub fn rustc_codegen_spirv_location(mut self, path: PathBuf) -> Self {}\n\
+             pub fn target_spec(mut self, p: impl AsRef<Path>) -> Self {}\n\
+             pub fn shader_crate_default_features(mut self, default: bool) -> Self {}\n\
+             pub fn shader_crate_features(mut self, features: Vec<String>) -> Self {}",
+        );
+        assert_eq!(v0_10.cli_feature(), "spirv-builder-0_10");
+        let v0_11 = BuilderSupport::from_source(
+            "pub fn rustc_codegen_spirv_location(mut self, path: PathBuf) -> Self {}\n\
+             pub fn shader_panic_strategy(mut self, s: ShaderPanicStrategy) -> Self {}",
+        );
+        assert_eq!(v0_11.cli_feature(), "spirv-builder-0_11");
+
+        let build = BuildArgs::parse_from([
+            "",
+            "--shader-target",
+            "spirv-unknown-vulkan1.3",
+            "--features",
+            "extra",
+            "--shader-panic-strategy",
+            "debug-printf",
+        ]);
+        assert_eq!(
+            pre_cli.unsupported_flags(&build),
+            ["--shader-target", "--features", "--shader-panic-strategy"]
+        );
+        assert_eq!(v0_10.unsupported_flags(&build), ["--shader-panic-strategy"]);
+        assert_eq!(
+            v0_11.unsupported_flags(&build),
+            ["--shader-target", "--features"]
+        );
+    }
+}
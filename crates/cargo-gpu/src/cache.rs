@@ -0,0 +1,293 @@
+//! Share compiled shaders through a remote cache, so that CI and teammates can download shaders
+//! that have already been compiled somewhere else instead of compiling them again.
+//!
+//! Entries are keyed by the build's fingerprint, see [`crate::fingerprint`]. Each entry has an
+//! `index.json` that lists the build's linkage and every file that the build wrote to the output
+//! dir, along with each file's SHA-256. The files themselves are stored next to the index, named
+//! by their SHA-256, and every downloaded file must match its checksum before it gets written.
+//!
+//! So for example:
+//!
+//!   `<remote-cache>/<fingerprint>/index.json`
+//!   `<remote-cache>/<fingerprint>/<sha256 of shader.spv>`.
+
+use anyhow::Context as _;
+use spirv_builder_cli::Linkage;
+
+/// Compiled shaders are usually small, but a whole crate's worth of modules can add up.
+const MAX_DOWNLOAD_SIZE: u64 = 256 * 1024 * 1024;
+
+/// Somewhere that cache entries can be stored and fetched from.
+pub trait Backend {
+    /// Fetch the object at `key`, or `None` if there isn't one.
+    ///
+    /// # Errors
+    /// If the backend couldn't be reached.
+    fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>>;
+
+    /// Store `bytes` at `key`.
+    ///
+    /// # Errors
+    /// If the backend couldn't be reached or refused the object.
+    fn put(&self, key: &str, bytes: &[u8]) -> anyhow::Result<()>;
+}
+
+/// A cache served over HTTP, where objects are fetched with `GET` and stored with `PUT`.
+pub struct Http {
+    /// URL that keys are appended to.
+    base_url: String,
+}
+
+impl Http {
+    /// The URL of the object at `key`.
+    fn url(&self, key: &str) -> String {
+        format!("{}/{key}", self.base_url.trim_end_matches('/'))
+    }
+}
+
+impl Backend for Http {
+    fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        let url = self.url(key);
+        log::debug!("fetching {url}");
+        let mut response = match ureq::get(&url).call() {
+            Ok(response) => response,
+            Err(ureq::Error::StatusCode(404)) => return Ok(None),
+            Err(error) => return Err(error).with_context(|| format!("could not fetch {url}")),
+        };
+        let bytes = response
+            .body_mut()
+            .with_config()
+            .limit(MAX_DOWNLOAD_SIZE)
+            .read_to_vec()
+            .with_context(|| format!("could not read response body from {url}"))?;
+        Ok(Some(bytes))
+    }
+
+    fn put(&self, key: &str, bytes: &[u8]) -> anyhow::Result<()> {
+        let url = self.url(key);
+        log::debug!("uploading {url}");
+        ureq::put(&url)
+            .send(bytes)
+            .with_context(|| format!("could not upload {url}"))?;
+        Ok(())
+    }
+}
+
+/// A cache in a directory, eg on a network share.
+pub struct Directory {
+    /// Directory that keys are relative to.
+    root: std::path::PathBuf,
+}
+
+impl Backend for Directory {
+    fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        let path = self.root.join(key);
+        match std::fs::read(&path) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(error) => {
+                Err(error).with_context(|| format!("could not read '{}'", path.display()))
+            }
+        }
+    }
+
+    fn put(&self, key: &str, bytes: &[u8]) -> anyhow::Result<()> {
+        let path = self.root.join(key);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        // Write to a temporary file first so that readers never see a partial object.
+        let temporary = std::path::PathBuf::from(format!("{}.upload", path.display()));
+        std::fs::write(&temporary, bytes)
+            .with_context(|| format!("could not write '{}'", temporary.display()))?;
+        std::fs::rename(&temporary, &path)?;
+        Ok(())
+    }
+}
+
+/// The backend for a `--remote-cache` location, either an HTTP(S) URL or a directory.
+#[must_use]
+pub fn backend(location: &str) -> Box<dyn Backend> {
+    if location.starts_with("http://") || location.starts_with("https://") {
+        Box::new(Http {
+            base_url: location.to_owned(),
+        })
+    } else {
+        Box::new(Directory {
+            root: std::path::PathBuf::from(location.trim_start_matches("file://")),
+        })
+    }
+}
+
+/// The index of a cache entry.
+#[derive(serde::Deserialize, serde::Serialize)]
+struct Index {
+    /// The linkage that the build produced.
+    linkage: Vec<Linkage>,
+    /// Every file that the build wrote to the output dir.
+    files: Vec<IndexedFile>,
+}
+
+/// A file in a cache entry.
+#[derive(serde::Deserialize, serde::Serialize)]
+struct IndexedFile {
+    /// Path relative to the output dir, with forward slashes.
+    path: String,
+    /// Lowercase hex SHA-256 of the file's contents, also the file's key in the cache entry.
+    sha256: String,
+}
+
+/// Download the build with `fingerprint` into `output_dir`, returning its linkage, or `None` if
+/// the cache doesn't have it.
+///
+/// Files are only written once all of them have been downloaded and verified, so an error never
+/// leaves a partial build behind.
+///
+/// # Errors
+/// If the backend couldn't be reached, or the entry is invalid.
+pub fn download(
+    backend: &dyn Backend,
+    fingerprint: &str,
+    output_dir: &std::path::Path,
+) -> anyhow::Result<Option<Vec<Linkage>>> {
+    let Some(index_bytes) = backend.get(&format!("{fingerprint}/index.json"))? else {
+        log::debug!("remote cache doesn't have {fingerprint}");
+        return Ok(None);
+    };
+    let index: Index =
+        serde_json::from_slice(&index_bytes).context("could not parse remote cache index")?;
+
+    let mut downloaded = Vec::new();
+    for file in &index.files {
+        let relative = relative_path::RelativePath::new(&file.path);
+        anyhow::ensure!(
+            relative
+                .components()
+                .all(|component| matches!(component, relative_path::Component::Normal(_))),
+            "remote cache entry has an invalid path `{}`",
+            file.path
+        );
+        let bytes = backend
+            .get(&format!("{fingerprint}/{}", file.sha256))?
+            .with_context(|| format!("remote cache entry is missing `{}`", file.path))?;
+        let actual = crate::prebuilt::sha256_hex(&bytes);
+        anyhow::ensure!(
+            actual == file.sha256,
+            "checksum mismatch for `{}` in remote cache, expected {} but got {actual}",
+            file.path,
+            file.sha256
+        );
+        downloaded.push((relative.to_path(output_dir), bytes));
+    }
+
+    for (path, bytes) in downloaded {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        crate::atomic::write(&path, bytes)?;
+        log::debug!("downloaded '{}' from remote cache", path.display());
+    }
+
+    Ok(Some(index.linkage))
+}
+
+/// Upload the build with `fingerprint`. `files` are every file that the build wrote, and they
+/// must all be in `output_dir`.
+///
+/// The index is uploaded last, so that the entry only becomes visible once it's complete.
+///
+/// # Errors
+/// If the backend couldn't be reached, or a file couldn't be read.
+pub fn upload(
+    backend: &dyn Backend,
+    fingerprint: &str,
+    output_dir: &std::path::Path,
+    files: &[std::path::PathBuf],
+    linkage: &[Linkage],
+) -> anyhow::Result<()> {
+    use relative_path::PathExt as _;
+
+    let mut indexed_files = Vec::new();
+    for path in files {
+        let relative = path
+            .relative_to(output_dir)
+            .with_context(|| format!("'{}' is not in the output dir", path.display()))?;
+        anyhow::ensure!(
+            !relative.as_str().starts_with(".."),
+            "'{}' is not in the output dir",
+            path.display()
+        );
+        let bytes =
+            std::fs::read(path).with_context(|| format!("could not read '{}'", path.display()))?;
+        let sha256 = crate::prebuilt::sha256_hex(&bytes);
+        backend.put(&format!("{fingerprint}/{sha256}"), &bytes)?;
+        indexed_files.push(IndexedFile {
+            path: relative.into_string(),
+            sha256,
+        });
+    }
+
+    let index = serde_json::to_vec_pretty(&serde_json::json!({
+        "linkage": linkage,
+        "files": indexed_files,
+    }))?;
+    backend.put(&format!("{fingerprint}/index.json"), &index)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Make an empty directory in the test cache.
+    fn empty_dir(name: &str) -> std::path::PathBuf {
+        let dir = crate::cache_dir().unwrap().join(name);
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir).unwrap();
+        }
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test_log::test]
+    fn round_trip_through_a_directory() {
+        let remote = empty_dir("remote-cache");
+        let backend = backend(&format!("file://{}", remote.display()));
+
+        let output_dir = empty_dir("remote-cache-output");
+        let module = output_dir.join("shader.spv");
+        std::fs::write(&module, b"spirv").unwrap();
+        let manifest = output_dir.join("manifest.json");
+        std::fs::write(&manifest, "[]").unwrap();
+        let linkage = vec![Linkage::new("main_fs", "shader.spv")];
+        upload(
+            backend.as_ref(),
+            "abc",
+            &output_dir,
+            &[module, manifest],
+            &linkage,
+        )
+        .unwrap();
+
+        let other_output_dir = empty_dir("remote-cache-other-output");
+        assert!(download(backend.as_ref(), "def", &other_output_dir)
+            .unwrap()
+            .is_none());
+        let downloaded = download(backend.as_ref(), "abc", &other_output_dir).unwrap();
+        assert_eq!(downloaded, Some(linkage));
+        assert_eq!(
+            std::fs::read(other_output_dir.join("shader.spv")).unwrap(),
+            b"spirv"
+        );
+        assert!(other_output_dir.join("manifest.json").is_file());
+
+        // Tampered files aren't written.
+        let blob = remote
+            .join("abc")
+            .join(crate::prebuilt::sha256_hex(b"spirv"));
+        std::fs::write(blob, b"evil").unwrap();
+        let tampered_output_dir = empty_dir("remote-cache-tampered-output");
+        download(backend.as_ref(), "abc", &tampered_output_dir).unwrap_err();
+        assert!(!tampered_output_dir.join("manifest.json").exists());
+    }
+}
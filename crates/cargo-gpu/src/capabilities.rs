@@ -0,0 +1,253 @@
+//! Find which of a module's declared capabilities each entry point actually needs.
+//!
+//! A module declares every capability that `rust-gpu` was asked for, whether or not anything
+//! uses it. So the instructions, types and decorations that are reachable from an entry point
+//! are checked against the SPIR-V grammar, and a declared capability counts as used if one of
+//! them requires it, or if it's implied by one that's used.
+
+use rspirv::dr::{Instruction, Module, Operand};
+use rspirv::spirv::{Capability, Op, Word};
+use spirv_builder_cli::Linkage;
+
+/// The capabilities that the module declares and that the entry point needs, by name, in the
+/// order that the module declares them.
+#[must_use]
+pub fn used_by_entry_point(module: &Module, entry_point: &Instruction) -> Vec<String> {
+    let mut required = std::collections::HashSet::new();
+    for instruction in instructions_of_entry_point(module, entry_point) {
+        required.extend(required_by(instruction));
+    }
+    // Capabilities that are used imply the ones that they depend on, eg `Geometry` implies
+    // `Shader`.
+    let mut pending = required.iter().copied().collect::<Vec<_>>();
+    while let Some(capability) = pending.pop() {
+        for implied in Operand::Capability(capability).required_capabilities() {
+            if required.insert(implied) {
+                pending.push(implied);
+            }
+        }
+    }
+
+    module
+        .capabilities
+        .iter()
+        .filter_map(|instruction| match instruction.operands.first() {
+            Some(Operand::Capability(capability)) if required.contains(capability) => {
+                Some(format!("{capability:?}"))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// The capabilities that were asked for with `--capability` but that no entry point uses.
+#[must_use]
+pub fn unused(
+    requested: &[spirv_builder_cli::spirv::Capability],
+    linkage: &[Linkage],
+) -> Vec<String> {
+    requested
+        .iter()
+        .map(|capability| format!("{capability:?}"))
+        .filter(|name| {
+            !linkage
+                .iter()
+                .any(|entry| entry.capabilities.contains(name))
+        })
+        .collect()
+}
+
+/// Warn about each capability that was asked for with `--capability` but that isn't used.
+pub fn warn_about_unused(requested: &[spirv_builder_cli::spirv::Capability], linkage: &[Linkage]) {
+    for capability in unused(requested, linkage) {
+        crate::user_output!(
+            "warning: the capability `{capability}` was asked for, but no entry point uses it\n"
+        );
+    }
+}
+
+/// Every capability that an instruction could need, from its opcode, its operands and, for
+/// number types, its width.
+fn required_by(instruction: &Instruction) -> Vec<Capability> {
+    let mut capabilities = instruction.class.capabilities.to_vec();
+    for operand in &instruction.operands {
+        capabilities.extend(operand.required_capabilities());
+    }
+    let width = match instruction.operands.first() {
+        Some(Operand::LiteralInt32(width)) => Some(*width),
+        _ => None,
+    };
+    match (instruction.class.opcode, width) {
+        (Op::TypeInt, Some(8)) => capabilities.push(Capability::Int8),
+        (Op::TypeInt, Some(16)) => capabilities.push(Capability::Int16),
+        (Op::TypeInt, Some(64)) => capabilities.push(Capability::Int64),
+        (Op::TypeFloat, Some(16)) => capabilities.push(Capability::Float16),
+        (Op::TypeFloat, Some(64)) => capabilities.push(Capability::Float64),
+        _ => (),
+    }
+    capabilities
+}
+
+/// The instructions that matter to an entry point: itself, its execution modes, the memory model,
+/// the functions that it can call, the global types and values that they refer to, and the
+/// decorations on all of them.
+fn instructions_of_entry_point<'module>(
+    module: &'module Module,
+    entry_point: &'module Instruction,
+) -> Vec<&'module Instruction> {
+    let Some(entry_function) = entry_point.operands.get(1).and_then(Operand::id_ref_any) else {
+        return Vec::new();
+    };
+    let reachable = crate::reflect::reachable_functions(module, entry_function);
+    let function_instructions = module
+        .functions
+        .iter()
+        .filter(|function| {
+            function
+                .def
+                .as_ref()
+                .and_then(|def| def.result_id)
+                .is_some_and(|id| reachable.contains(&id))
+        })
+        .flat_map(|function| {
+            function.def.iter().chain(&function.parameters).chain(
+                function
+                    .blocks
+                    .iter()
+                    .flat_map(|block| block.label.iter().chain(&block.instructions)),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let mut ids = std::collections::HashSet::new();
+    for instruction in function_instructions.iter().copied().chain([entry_point]) {
+        ids.extend(referenced_ids(instruction));
+    }
+    // Global types and values can refer to each other, eg a pointer to a struct of vectors.
+    loop {
+        let before = ids.len();
+        for global in &module.types_global_values {
+            if global.result_id.is_some_and(|id| ids.contains(&id)) {
+                ids.extend(referenced_ids(global));
+            }
+        }
+        if ids.len() == before {
+            break;
+        }
+    }
+
+    let globals = module
+        .types_global_values
+        .iter()
+        .filter(|global| global.result_id.is_some_and(|id| ids.contains(&id)));
+    let annotations = module.annotations.iter().filter(|annotation| {
+        annotation
+            .operands
+            .first()
+            .and_then(Operand::id_ref_any)
+            .is_some_and(|id| ids.contains(&id))
+    });
+    let execution_modes = module
+        .execution_modes
+        .iter()
+        .filter(|mode| mode.operands.first().and_then(Operand::id_ref_any) == Some(entry_function));
+
+    core::iter::once(entry_point)
+        .chain(&module.memory_model)
+        .chain(execution_modes)
+        .chain(globals)
+        .chain(annotations)
+        .chain(function_instructions)
+        .collect()
+}
+
+/// The ids that an instruction refers to, including its result type.
+fn referenced_ids(instruction: &Instruction) -> impl Iterator<Item = Word> + '_ {
+    instruction
+        .operands
+        .iter()
+        .filter_map(Operand::id_ref_any)
+        .chain(instruction.result_type)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_log::test]
+    fn finding_the_capabilities_that_entry_points_use() {
+        let mut builder = rspirv::dr::Builder::new();
+        for capability in [Capability::Shader, Capability::Int8, Capability::Int64] {
+            builder.capability(capability);
+        }
+        builder.memory_model(
+            rspirv::spirv::AddressingModel::Logical,
+            rspirv::spirv::MemoryModel::GLSL450,
+        );
+        let void = builder.type_void();
+        let function_type = builder.type_function(void, vec![]);
+        let byte = builder.type_int(8, 0);
+        let byte_pointer = builder.type_pointer(None, rspirv::spirv::StorageClass::Private, byte);
+        let bytes = builder.variable(
+            byte_pointer,
+            None,
+            rspirv::spirv::StorageClass::Private,
+            None,
+        );
+
+        for (name, uses_bytes) in [("main_fs", false), ("main_cs", true)] {
+            let main = builder
+                .begin_function(
+                    void,
+                    None,
+                    rspirv::spirv::FunctionControl::NONE,
+                    function_type,
+                )
+                .unwrap();
+            builder.begin_block(None).unwrap();
+            if uses_bytes {
+                builder.load(byte, None, bytes, None, vec![]).unwrap();
+            }
+            builder.ret().unwrap();
+            builder.end_function().unwrap();
+            let model = if uses_bytes {
+                rspirv::spirv::ExecutionModel::GLCompute
+            } else {
+                rspirv::spirv::ExecutionModel::Fragment
+            };
+            builder.entry_point(model, main, name, vec![]);
+        }
+        let module = builder.module();
+
+        let used = module
+            .entry_points
+            .iter()
+            .map(|entry_point| used_by_entry_point(&module, entry_point))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            used,
+            [
+                vec!["Shader".to_owned()],
+                vec!["Shader".to_owned(), "Int8".to_owned()]
+            ]
+        );
+
+        let linkage = ["main_fs", "main_cs"]
+            .into_iter()
+            .zip(used)
+            .map(|(entry, capabilities)| {
+                Linkage::new(entry, "shaders.spv").with_capabilities(capabilities)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            unused(
+                &[
+                    spirv_builder_cli::spirv::Capability::Int8,
+                    spirv_builder_cli::spirv::Capability::Int64
+                ],
+                &linkage
+            ),
+            ["Int64"]
+        );
+    }
+}
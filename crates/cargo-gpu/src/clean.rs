@@ -0,0 +1,384 @@
+//! `cargo gpu clean`, remove artifacts from the cache directory.
+
+use crate::{
+    bundled_target_spec_dir, cache_dir, checkouts_dir, disk_size, format_size,
+    global_config::CacheConfig, installs_dir, repos_dir, target_spec_dir,
+};
+
+/// `cargo gpu clean`.
+///
+/// Without any arguments the entire cache directory is removed.
+#[derive(clap::Parser, Debug)]
+#[expect(clippy::struct_excessive_bools, reason = "They're all CLI flags")]
+pub struct Clean {
+    /// Remove installed `spirv-builder-cli` and `rustc_codegen_spirv` binary pairs. Only the
+    /// installs with the given directory names are removed, or all of them if no names are given.
+    #[clap(long, num_args = 0.., value_name = "INSTALL")]
+    pub installs: Option<Vec<String>>,
+
+    /// Remove the cached checkouts of the `rust-gpu` repo, and the clones that they share.
+    #[clap(long)]
+    pub checkouts: bool,
+
+    /// Remove the target spec files, both those of each install and the bundled ones.
+    #[clap(long)]
+    pub target_specs: bool,
+
+    /// Remove everything in the cache directory.
+    #[clap(long)]
+    pub all: bool,
+
+    /// Only list what would be removed and how much space would be reclaimed.
+    #[clap(long)]
+    pub dry_run: bool,
+}
+
+impl Clean {
+    /// Entrypoint.
+    pub fn run(&self) -> anyhow::Result<()> {
+        let mut total = 0;
+        for path in self.paths_to_remove()? {
+            let size = disk_size(&path)?;
+            total += size;
+            if self.dry_run {
+                crate::user_output!("Would remove {} ({})\n", path.display(), format_size(size));
+            } else {
+                log::debug!("removing '{}'", path.display());
+                std::fs::remove_dir_all(&path)?;
+                crate::user_output!("Removed {} ({})\n", path.display(), format_size(size));
+            }
+        }
+
+        if self.dry_run {
+            crate::user_output!("Would reclaim {}\n", format_size(total));
+        } else {
+            crate::user_output!("Reclaimed {}\n", format_size(total));
+        }
+
+        Ok(())
+    }
+
+    /// All the existing paths that should be removed.
+    fn paths_to_remove(&self) -> anyhow::Result<Vec<std::path::PathBuf>> {
+        let is_selective = self.installs.is_some() || self.checkouts || self.target_specs;
+        if self.all || !is_selective {
+            let cache = cache_dir()?;
+            return Ok(if cache.exists() { vec![cache] } else { vec![] });
+        }
+
+        let mut paths = Vec::new();
+        if let Some(names) = &self.installs {
+            let installs = installs_dir()?;
+            if names.is_empty() {
+                paths.push(installs);
+            } else {
+                for name in names {
+                    // Only ever a directory directly in the installs dir, never `..` or an
+                    // absolute path that leads out of the cache.
+                    let mut components = std::path::Path::new(name).components();
+                    anyhow::ensure!(
+                        matches!(
+                            (components.next(), components.next()),
+                            (Some(std::path::Component::Normal(_)), None)
+                        ),
+                        "'{name}' isn't the name of an install"
+                    );
+                    let path = installs.join(name);
+                    anyhow::ensure!(
+                        path.is_dir(),
+                        "no install named '{name}' in '{}'",
+                        installs.display()
+                    );
+                    paths.push(path);
+                }
+            }
+        }
+        if self.checkouts {
+            paths.push(checkouts_dir()?);
+            paths.push(repos_dir()?);
+        }
+        if self.target_specs {
+            paths.push(bundled_target_spec_dir()?);
+            paths.extend(
+                crate::install::installed_pairs()?
+                    .iter()
+                    .map(|pair| target_spec_dir(&pair.path)),
+            );
+        }
+
+        paths.retain(|path| path.exists());
+        Ok(paths)
+    }
+}
+
+/// Something in the cache directory that garbage collection can remove.
+struct Collectable {
+    /// The install or checkout directory.
+    path: std::path::PathBuf,
+    /// Size on disk, in bytes.
+    size: u64,
+    /// When it was last used.
+    last_used: std::time::SystemTime,
+}
+
+/// Every install and checkout in the cache directory, least recently used first.
+fn collectables() -> anyhow::Result<Vec<Collectable>> {
+    let mut collectables = Vec::new();
+    let pairs = crate::install::installed_pairs()?;
+    for pair in &pairs {
+        collectables.push(Collectable {
+            path: pair.path.clone(),
+            size: pair.size,
+            last_used: pair.last_used.unwrap_or(std::time::UNIX_EPOCH),
+        });
+    }
+
+    let checkouts = checkouts_dir()?;
+    if checkouts.is_dir() {
+        for maybe_entry in std::fs::read_dir(&checkouts)? {
+            let path = maybe_entry?.path();
+            // Skip the lock files next to each checkout.
+            if !path.is_dir() {
+                continue;
+            }
+            // A checkout is in use for as long as any install that was made from it.
+            let last_used = pairs
+                .iter()
+                .filter(|pair| {
+                    pair.metadata
+                        .as_ref()
+                        .is_some_and(|metadata| metadata.checkout.as_ref() == Some(&path))
+                })
+                .filter_map(|pair| pair.last_used)
+                .chain(std::fs::metadata(&path).and_then(|metadata| metadata.modified()))
+                .max()
+                .unwrap_or(std::time::UNIX_EPOCH);
+            collectables.push(Collectable {
+                size: disk_size(&path)?,
+                path,
+                last_used,
+            });
+        }
+    }
+
+    collectables.sort_by_key(|collectable| collectable.last_used);
+    Ok(collectables)
+}
+
+/// Remove the least recently used installs and checkouts that are beyond the limits set in the
+/// `[cache]` table of the global config.
+///
+/// Anything used since `keep_since` is always kept, as it's part of the current build. Returns
+/// the removed paths and how much space was reclaimed.
+pub fn collect_garbage(
+    config: &CacheConfig,
+    keep_since: std::time::SystemTime,
+) -> anyhow::Result<(Vec<std::path::PathBuf>, u64)> {
+    /// Seconds in a day.
+    const DAY: u64 = 60 * 60 * 24;
+
+    let maybe_max_size = config
+        .max_cache_size
+        .as_ref()
+        .map(crate::global_config::Size::to_bytes)
+        .transpose()?;
+    let maybe_oldest = config
+        .max_age_days
+        .and_then(|days| days.checked_mul(DAY))
+        .and_then(|seconds| {
+            std::time::SystemTime::now().checked_sub(core::time::Duration::from_secs(seconds))
+        });
+    if maybe_max_size.is_none() && maybe_oldest.is_none() {
+        return Ok((Vec::new(), 0));
+    }
+
+    let cache = cache_dir()?;
+    let mut total = if cache.exists() {
+        disk_size(&cache)?
+    } else {
+        0
+    };
+    let mut removed = Vec::new();
+    let mut reclaimed: u64 = 0;
+    for collectable in collectables()? {
+        if collectable.last_used >= keep_since {
+            continue;
+        }
+        let is_too_old = maybe_oldest.is_some_and(|oldest| collectable.last_used < oldest);
+        let is_too_big = maybe_max_size.is_some_and(|max_size| total > max_size);
+        if !is_too_old && !is_too_big {
+            continue;
+        }
+
+        // Something that another `cargo gpu` is installing or checking out is in use.
+        let Some(_lock) = crate::try_lock(&collectable.path)? else {
+            log::debug!(
+                "not garbage collecting '{}', as it's in use",
+                collectable.path.display()
+            );
+            continue;
+        };
+        log::debug!(
+            "garbage collecting '{}' (too old: {is_too_old}, cache too big: {is_too_big})",
+            collectable.path.display()
+        );
+        std::fs::remove_dir_all(&collectable.path)?;
+        total = total.saturating_sub(collectable.size);
+        reclaimed = reclaimed.saturating_add(collectable.size);
+        removed.push(collectable.path);
+    }
+
+    Ok((removed, reclaimed))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use clap::Parser as _;
+
+    /// Make a cache directory with a couple of installs and a checkout.
+    fn setup_cache() -> std::path::PathBuf {
+        crate::test::tests_teardown();
+        let installs = installs_dir().unwrap();
+        for name in ["install-a", "install-b"] {
+            std::fs::create_dir_all(installs.join(name)).unwrap();
+            std::fs::write(installs.join(name).join("spirv-builder-cli"), "cli").unwrap();
+        }
+        std::fs::create_dir_all(checkouts_dir().unwrap().join("rust-gpu")).unwrap();
+        installs
+    }
+
+    #[test_log::test]
+    fn dry_run_removes_nothing() {
+        let installs = setup_cache();
+        let clean = Clean::parse_from(["clean", "--all", "--dry-run"]);
+        assert_eq!(clean.paths_to_remove().unwrap(), vec![cache_dir().unwrap()]);
+        clean.run().unwrap();
+        assert!(installs.join("install-a").is_dir());
+    }
+
+    #[test_log::test]
+    fn removes_only_named_installs() {
+        let installs = setup_cache();
+        Clean::parse_from(["clean", "--installs", "install-a"])
+            .run()
+            .unwrap();
+        assert!(!installs.join("install-a").exists());
+        assert!(installs.join("install-b").is_dir());
+        assert!(checkouts_dir().unwrap().is_dir());
+    }
+
+    #[test_log::test]
+    fn refuses_install_names_outside_the_cache() {
+        let installs = setup_cache();
+        let outside = installs.join("install-a").canonicalize().unwrap();
+        for name in [
+            "..",
+            "install-a/../..",
+            "./install-a",
+            outside.to_str().unwrap(),
+        ] {
+            let clean = Clean::parse_from(["clean", "--installs", name]);
+            assert_eq!(
+                clean.paths_to_remove().unwrap_err().to_string(),
+                format!("'{name}' isn't the name of an install")
+            );
+        }
+        assert!(installs.join("install-a").is_dir());
+    }
+
+    /// Set when a path was last used.
+    fn set_last_used(path: &std::path::Path, time: std::time::SystemTime) {
+        std::fs::File::open(path)
+            .unwrap()
+            .set_modified(time)
+            .unwrap();
+    }
+
+    #[test_log::test]
+    fn garbage_collecting_old_and_big_installs() {
+        let installs = setup_cache();
+        let now = std::time::SystemTime::now();
+        let days_ago = |days: u64| now - core::time::Duration::from_secs(days * 60 * 60 * 24);
+        set_last_used(&installs.join("install-a"), days_ago(40));
+        set_last_used(&installs.join("install-b"), days_ago(2));
+        set_last_used(&checkouts_dir().unwrap().join("rust-gpu"), days_ago(1));
+
+        let (removed, _) = collect_garbage(
+            &CacheConfig {
+                max_cache_size: None,
+                max_age_days: Some(30),
+                ..CacheConfig::default()
+            },
+            now,
+        )
+        .unwrap();
+        assert_eq!(removed, vec![installs.join("install-a")]);
+
+        // The cache is too big, so the least recently used is removed first, and anything used
+        // by the current build is kept.
+        let (removed_for_size, _) = collect_garbage(
+            &CacheConfig {
+                max_cache_size: Some(crate::global_config::Size::Bytes(0)),
+                max_age_days: None,
+                ..CacheConfig::default()
+            },
+            days_ago(1),
+        )
+        .unwrap();
+        assert_eq!(removed_for_size, vec![installs.join("install-b")]);
+        assert!(checkouts_dir().unwrap().join("rust-gpu").is_dir());
+    }
+
+    #[test_log::test]
+    fn garbage_collecting_skips_installs_in_use() {
+        let installs = setup_cache();
+        let now = std::time::SystemTime::now();
+        let long_ago = now - core::time::Duration::from_hours(40 * 24);
+        set_last_used(&installs.join("install-a"), long_ago);
+        set_last_used(&installs.join("install-b"), long_ago);
+        set_last_used(&checkouts_dir().unwrap().join("rust-gpu"), now);
+
+        let in_use = crate::lock(&installs.join("install-a")).unwrap();
+        let (removed, _) = collect_garbage(
+            &CacheConfig {
+                max_cache_size: None,
+                max_age_days: Some(30),
+                ..CacheConfig::default()
+            },
+            now,
+        )
+        .unwrap();
+        drop(in_use);
+        assert_eq!(removed, vec![installs.join("install-b")]);
+        assert!(installs.join("install-a").is_dir());
+    }
+
+    #[test_log::test]
+    fn unknown_install_is_an_error() {
+        setup_cache();
+        let clean = Clean::parse_from(["clean", "--installs", "nope"]);
+        clean.paths_to_remove().unwrap_err();
+    }
+
+    #[test_log::test]
+    fn removes_the_target_specs_of_every_install() {
+        let installs = setup_cache();
+        for spec_dir in [
+            target_spec_dir(&installs.join("install-a")),
+            bundled_target_spec_dir().unwrap(),
+        ] {
+            std::fs::create_dir_all(&spec_dir).unwrap();
+            std::fs::write(spec_dir.join("spirv-unknown-vulkan1.2.json"), "{}").unwrap();
+        }
+        Clean::parse_from(["clean", "--target-specs"])
+            .run()
+            .unwrap();
+        assert!(!target_spec_dir(&installs.join("install-a")).exists());
+        assert!(!bundled_target_spec_dir().unwrap().exists());
+        assert!(installs
+            .join("install-a")
+            .join("spirv-builder-cli")
+            .is_file());
+    }
+}
@@ -0,0 +1,995 @@
+//! Generate host code from the reflected shaders, so that host crates don't have to describe
+//! their shaders' bindings by hand.
+//!
+//! The generated files are meant to be `include!`ed, so they only contain items and plain `//`
+//! comments.
+
+use core::fmt::Write as _;
+
+use anyhow::Context as _;
+use spirv_builder_cli::{
+    args::{CodegenTarget, EmbedMode},
+    DescriptorBinding, ImageInfo, Linkage,
+};
+
+/// The path that the code for `target` gets written to, next to the manifest.
+#[must_use]
+pub fn output_path(manifest_path: &std::path::Path, target: CodegenTarget) -> std::path::PathBuf {
+    let extension = match target {
+        CodegenTarget::Wgpu => "wgpu.rs",
+        CodegenTarget::Ash => "ash.rs",
+        CodegenTarget::CHeader => "h",
+    };
+    manifest_path.with_extension(extension)
+}
+
+/// Generate the code for `target` from the manifest's entries, writing it next to the manifest.
+/// Returns the path of the written file, see [`output_path`].
+pub fn write(
+    linkage: &[Linkage],
+    target: CodegenTarget,
+    manifest_path: &std::path::Path,
+) -> anyhow::Result<std::path::PathBuf> {
+    let code = match target {
+        CodegenTarget::Wgpu => wgpu_module(linkage)?,
+        CodegenTarget::Ash => ash_module(linkage)?,
+        CodegenTarget::CHeader => c_header(linkage, manifest_path)?,
+    };
+    let path = output_path(manifest_path, target);
+    crate::atomic::write(&path, code)
+        .with_context(|| format!("could not write generated code '{}'", path.display()))?;
+    Ok(path)
+}
+
+/// Write the manifest as a Rust module, `include!`able instead of parsing the JSON manifest.
+/// It's written next to the JSON manifest, with an `.rs` extension. Returns the path of the
+/// written file.
+pub fn write_rust_manifest(
+    linkage: &[Linkage],
+    manifest_path: &std::path::Path,
+    shader_crate: &std::path::Path,
+) -> anyhow::Result<std::path::PathBuf> {
+    let path = manifest_path.with_extension("rs");
+    let manifest_dir =
+        std::path::absolute(path.parent().unwrap_or_else(|| std::path::Path::new("./")))?;
+    let code = rust_manifest(linkage, &manifest_dir, &std::path::absolute(shader_crate)?)?;
+    crate::atomic::write(&path, code)
+        .with_context(|| format!("could not write Rust manifest '{}'", path.display()))?;
+    Ok(path)
+}
+
+/// Write the manifest for Bevy's asset system, next to the JSON manifest with a `.bevy.ron`
+/// extension. Returns the path of the written file.
+pub fn write_bevy_manifest(
+    linkage: &[Linkage],
+    manifest_path: &std::path::Path,
+    shader_crate: &std::path::Path,
+) -> anyhow::Result<std::path::PathBuf> {
+    let path = manifest_path.with_extension("bevy.ron");
+    let manifest_dir =
+        std::path::absolute(path.parent().unwrap_or_else(|| std::path::Path::new("./")))?;
+    let contents = bevy_manifest(linkage, &manifest_dir, &std::path::absolute(shader_crate)?)?;
+    crate::atomic::write(&path, contents)
+        .with_context(|| format!("could not write Bevy manifest '{}'", path.display()))?;
+    Ok(path)
+}
+
+/// The path that `--embed` writes its Rust module to, next to the manifest.
+#[must_use]
+pub fn embed_path(manifest_path: &std::path::Path) -> std::path::PathBuf {
+    manifest_path.with_extension("embed.rs")
+}
+
+/// Write a Rust module embedding every entry point's module, for `--embed`, so that the host
+/// crate doesn't have to read or ship any shader files. Returns the path of the written file,
+/// see [`embed_path`].
+pub fn write_embedded(
+    linkage: &[Linkage],
+    mode: EmbedMode,
+    manifest_path: &std::path::Path,
+    shader_crate: &std::path::Path,
+) -> anyhow::Result<std::path::PathBuf> {
+    let path = embed_path(manifest_path);
+    let manifest_dir =
+        std::path::absolute(path.parent().unwrap_or_else(|| std::path::Path::new("./")))?;
+    let code = embedded_modules(
+        "--embed",
+        linkage,
+        mode,
+        &manifest_dir,
+        &std::path::absolute(shader_crate)?,
+    )?;
+    crate::atomic::write(&path, code)
+        .with_context(|| format!("could not write embedded modules '{}'", path.display()))?;
+    Ok(path)
+}
+
+/// A Rust module with a submodule for each entry point, containing its name, its stage and its
+/// module's bytes. `manifest_dir` and `shader_crate` must be absolute.
+fn rust_manifest(
+    linkage: &[Linkage],
+    manifest_dir: &std::path::Path,
+    shader_crate: &std::path::Path,
+) -> anyhow::Result<String> {
+    embedded_modules(
+        "--manifest-format rust",
+        linkage,
+        EmbedMode::IncludeBytes,
+        manifest_dir,
+        shader_crate,
+    )
+}
+
+/// A Rust module with a submodule for each entry point, whose module's bytes are embedded
+/// according to `mode`. `flag` is the `cargo gpu build` flag that generated it. `manifest_dir`
+/// and `shader_crate` must be absolute.
+fn embedded_modules(
+    flag: &str,
+    linkage: &[Linkage],
+    mode: EmbedMode,
+    manifest_dir: &std::path::Path,
+    shader_crate: &std::path::Path,
+) -> anyhow::Result<String> {
+    let mut code = rust_module_header(flag, linkage)?;
+    for entry in linkage {
+        rust_entry_point_module(&mut code, entry)?;
+        if let Some(stage) = &entry.stage {
+            writeln!(
+                code,
+                "    /// The shader stage, eg \"vertex\" or \"fragment\"."
+            )?;
+            writeln!(code, "    pub const STAGE: &str = \"{stage}\";")?;
+        }
+
+        if let Some(compression) = &entry.compression {
+            writeln!(
+                code,
+                "    /// How `SPIRV` is compressed, it has to be decompressed before it's used."
+            )?;
+            writeln!(
+                code,
+                "    pub const COMPRESSION: &str = \"{}\";",
+                compression.scheme
+            )?;
+            writeln!(
+                code,
+                "    /// The compressed SPIR-V module containing the entry point."
+            )?;
+        } else {
+            writeln!(
+                code,
+                "    /// The SPIR-V module containing the entry point."
+            )?;
+        }
+        let spv_path = shader_crate.join(&entry.source_path);
+        let bytes = match mode {
+            EmbedMode::IncludeBytes => include_bytes(&spv_path, manifest_dir),
+            EmbedMode::Literal => byte_literal(
+                &std::fs::read(&spv_path)
+                    .with_context(|| format!("could not read '{}'", spv_path.display()))?,
+            )?,
+        };
+        writeln!(code, "    pub const SPIRV: &[u8] = {bytes};")?;
+        writeln!(code, "}}")?;
+    }
+    Ok(code)
+}
+
+/// The manifest written by `--manifest-format bevy`.
+#[derive(serde::Serialize)]
+struct BevyManifest {
+    /// Every entry point, in the manifest's order.
+    shaders: Vec<BevyShader>,
+}
+
+/// An entry point in the Bevy manifest.
+#[derive(serde::Serialize)]
+struct BevyShader {
+    /// A name for the entry point that's unique in the manifest, eg for keying its pipeline.
+    label: String,
+    /// The module's asset path, ready for `AssetServer::load`.
+    path: String,
+    /// The entry point's name in its module, for the pipeline descriptor.
+    entry_point: String,
+    /// The entry point's stage, if it was reflected.
+    stage: Option<String>,
+}
+
+/// The Bevy manifest as RON, with the modules' paths relative to the nearest `assets/` directory
+/// above `manifest_dir`, or to `manifest_dir` itself if there's none. `manifest_dir` and
+/// `shader_crate` must be absolute.
+fn bevy_manifest(
+    linkage: &[Linkage],
+    manifest_dir: &std::path::Path,
+    shader_crate: &std::path::Path,
+) -> anyhow::Result<String> {
+    use relative_path::PathExt as _;
+
+    let assets_dir = manifest_dir
+        .ancestors()
+        .find(|dir| dir.file_name() == Some(std::ffi::OsStr::new("assets")))
+        .unwrap_or_else(|| {
+            log::warn!(
+                "'{}' isn't in an `assets/` directory, so the Bevy manifest's paths are relative \
+                 to it instead",
+                manifest_dir.display()
+            );
+            manifest_dir
+        });
+    let shaders = linkage
+        .iter()
+        .map(|entry| {
+            let module = shader_crate.join(&entry.source_path);
+            let label = entry.variant.as_ref().map_or_else(
+                || rust_ident(&entry.entry_point),
+                |variant| rust_ident(&format!("{variant}::{}", entry.entry_point)),
+            );
+            BevyShader {
+                label,
+                path: module.relative_to(assets_dir).map_or_else(
+                    |_| module.display().to_string(),
+                    relative_path::RelativePathBuf::into_string,
+                ),
+                entry_point: entry.entry_point.clone(),
+                stage: entry.stage.clone(),
+            }
+        })
+        .collect();
+    let ron = ron::ser::to_string_pretty(&BevyManifest { shaders }, ron::ser::PrettyConfig::new())?;
+    Ok(format!(
+        "// Generated by `cargo gpu build --manifest-format bevy`, do not edit.\n{ron}\n"
+    ))
+}
+
+/// An `include_bytes!` of a file, which is relative to the file it's in, so relative to
+/// `manifest_dir`.
+fn include_bytes(path: &std::path::Path, manifest_dir: &std::path::Path) -> String {
+    use relative_path::PathExt as _;
+
+    let include_path = path.relative_to(manifest_dir).map_or_else(
+        |_| path.display().to_string(),
+        relative_path::RelativePathBuf::into_string,
+    );
+    format!("include_bytes!(\"{}\")", include_path.escape_default())
+}
+
+/// A literal byte array, 16 bytes to a line.
+fn byte_literal(bytes: &[u8]) -> anyhow::Result<String> {
+    let mut literal = String::from("&[\n");
+    for line in bytes.chunks(16) {
+        let line_bytes = line
+            .iter()
+            .map(|byte| format!("0x{byte:02x}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        writeln!(literal, "        {line_bytes},")?;
+    }
+    literal.push_str("    ]");
+    Ok(literal)
+}
+
+/// A valid Rust identifier for an entry point, eg `deeply_nested_main_fs` for
+/// `deeply::nested::main_fs`.
+fn rust_ident(entry_point: &str) -> String {
+    let ident = entry_point
+        .chars()
+        .map(|character| {
+            if character.is_ascii_alphanumeric() {
+                character.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>()
+        .replace("__", "_");
+    if ident.starts_with(|character: char| character.is_ascii_digit()) {
+        format!("_{ident}")
+    } else {
+        ident
+    }
+}
+
+/// The entry point's bindings, grouped by descriptor set.
+fn bindings_by_set(entry: &Linkage) -> std::collections::BTreeMap<u32, Vec<&DescriptorBinding>> {
+    let mut sets = std::collections::BTreeMap::<u32, Vec<&DescriptorBinding>>::new();
+    for binding in &entry.bindings {
+        sets.entry(binding.set).or_default().push(binding);
+    }
+    sets
+}
+
+/// The start of a generated Rust module, listing all the entry points. `flag` is the
+/// `cargo gpu build` flag that generated it.
+fn rust_module_header(flag: &str, linkage: &[Linkage]) -> anyhow::Result<String> {
+    let mut code = format!("// Generated by `cargo gpu build {flag}`, do not edit.\n\n");
+    writeln!(code, "/// All the shader entry points.")?;
+    writeln!(code, "pub const ENTRY_POINTS: &[&str] = &[")?;
+    for entry in linkage {
+        writeln!(code, "    \"{}\",", entry.entry_point.escape_default())?;
+    }
+    writeln!(code, "];")?;
+    Ok(code)
+}
+
+/// Start the Rust submodule for an entry point, with its name and its module's path.
+fn rust_entry_point_module(code: &mut String, entry: &Linkage) -> anyhow::Result<()> {
+    writeln!(code)?;
+    writeln!(code, "/// `{}`", entry.entry_point)?;
+    writeln!(code, "pub mod {} {{", rust_ident(&entry.entry_point))?;
+    writeln!(code, "    /// The entry point's name in its module.")?;
+    writeln!(
+        code,
+        "    pub const ENTRY_POINT: &str = \"{}\";",
+        entry.entry_point.escape_default()
+    )?;
+    writeln!(code, "    /// Path to the entry point's module file.")?;
+    writeln!(
+        code,
+        "    pub const SOURCE_PATH: &str = \"{}\";",
+        entry.source_path.escape_default()
+    )?;
+    if let Some(sha256) = &entry.sha256 {
+        writeln!(
+            code,
+            "    /// The SHA-256 of the entry point's module file."
+        )?;
+        writeln!(code, "    pub const SHA256: &str = \"{sha256}\";")?;
+    }
+    Ok(())
+}
+
+/// A Rust module with a submodule for each entry point, containing its name, its module's path,
+/// its stage and a `wgpu::BindGroupLayoutDescriptor` for each descriptor set that it uses.
+fn wgpu_module(linkage: &[Linkage]) -> anyhow::Result<String> {
+    let mut code = rust_module_header("--codegen wgpu", linkage)?;
+    for entry in linkage {
+        let stages = wgpu_stages(entry.stage.as_deref());
+        rust_entry_point_module(&mut code, entry)?;
+        writeln!(code, "    pub const STAGE: wgpu::ShaderStages = {stages};")?;
+
+        for (set, bindings) in bindings_by_set(entry) {
+            writeln!(code)?;
+            writeln!(
+                code,
+                "    pub const BIND_GROUP_LAYOUT_{set}: wgpu::BindGroupLayoutDescriptor<'static> ="
+            )?;
+            writeln!(code, "        wgpu::BindGroupLayoutDescriptor {{")?;
+            writeln!(
+                code,
+                "            label: Some(\"{} set {set}\"),",
+                entry.entry_point.escape_default()
+            )?;
+            writeln!(code, "            entries: &[")?;
+            for binding in bindings {
+                let Some(binding_type) = wgpu_binding_type(binding) else {
+                    log::warn!(
+                        "`{}` binding {} in set {set} is a {}, which wgpu doesn't support",
+                        entry.entry_point,
+                        binding.binding,
+                        binding.descriptor_type
+                    );
+                    writeln!(
+                        code,
+                        "                // Binding {} is a {}, which wgpu doesn't support.",
+                        binding.binding, binding.descriptor_type
+                    )?;
+                    continue;
+                };
+                let count = match binding.count {
+                    Some(count) if count > 1 => format!("core::num::NonZeroU32::new({count})"),
+                    Some(_) | None => "None".to_owned(),
+                };
+                writeln!(code, "                wgpu::BindGroupLayoutEntry {{")?;
+                writeln!(code, "                    binding: {},", binding.binding)?;
+                writeln!(code, "                    visibility: {stages},")?;
+                writeln!(code, "                    ty: {binding_type},")?;
+                writeln!(code, "                    count: {count},")?;
+                writeln!(code, "                }},")?;
+            }
+            writeln!(code, "            ],")?;
+            writeln!(code, "        }};")?;
+        }
+        writeln!(code, "}}")?;
+    }
+
+    Ok(code)
+}
+
+/// The `wgpu::ShaderStages` for a reflected stage. wgpu has no tessellation or geometry shaders.
+fn wgpu_stages(stage: Option<&str>) -> &'static str {
+    match stage {
+        Some("vertex") => "wgpu::ShaderStages::VERTEX",
+        Some("fragment") => "wgpu::ShaderStages::FRAGMENT",
+        Some("compute") => "wgpu::ShaderStages::COMPUTE",
+        Some("task") => "wgpu::ShaderStages::TASK",
+        Some("mesh") => "wgpu::ShaderStages::MESH",
+        Some(_) | None => "wgpu::ShaderStages::NONE",
+    }
+}
+
+/// The `wgpu::BindingType` for a binding, or `None` for descriptors that wgpu can't bind.
+fn wgpu_binding_type(binding: &DescriptorBinding) -> Option<String> {
+    let binding_type = match binding.descriptor_type.as_str() {
+        "uniform_buffer" => "wgpu::BindingType::Buffer { \
+            ty: wgpu::BufferBindingType::Uniform, \
+            has_dynamic_offset: false, \
+            min_binding_size: None \
+        }"
+        .to_owned(),
+        "storage_buffer" => format!(
+            "wgpu::BindingType::Buffer {{ \
+                ty: wgpu::BufferBindingType::Storage {{ read_only: {} }}, \
+                has_dynamic_offset: false, \
+                min_binding_size: None \
+            }}",
+            binding.read_only
+        ),
+        "sampler" => "wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering)".to_owned(),
+        // wgpu has no combined image samplers, so these need a separate sampler binding too.
+        "sampled_image" | "combined_image_sampler" => {
+            let image = binding.image.as_ref()?;
+            let sample_type = if image.depth {
+                "wgpu::TextureSampleType::Depth"
+            } else {
+                match image.sampled_type.as_str() {
+                    "sint" => "wgpu::TextureSampleType::Sint",
+                    "uint" => "wgpu::TextureSampleType::Uint",
+                    _ => "wgpu::TextureSampleType::Float { filterable: true }",
+                }
+            };
+            format!(
+                "wgpu::BindingType::Texture {{ \
+                    sample_type: {sample_type}, \
+                    view_dimension: {}, \
+                    multisampled: {} \
+                }}",
+                wgpu_view_dimension(image)?,
+                image.multisampled
+            )
+        }
+        "storage_image" => {
+            let image = binding.image.as_ref()?;
+            let access = if binding.read_only {
+                "ReadOnly"
+            } else if binding.write_only {
+                "WriteOnly"
+            } else {
+                "ReadWrite"
+            };
+            format!(
+                "wgpu::BindingType::StorageTexture {{ \
+                    access: wgpu::StorageTextureAccess::{access}, \
+                    format: wgpu::TextureFormat::{}, \
+                    view_dimension: {} \
+                }}",
+                wgpu_texture_format(&image.format)?,
+                wgpu_view_dimension(image)?
+            )
+        }
+        "acceleration_structure" => {
+            "wgpu::BindingType::AccelerationStructure { vertex_return: false }".to_owned()
+        }
+        _ => return None,
+    };
+    Some(binding_type)
+}
+
+/// A Rust module with a submodule for each entry point, containing its name, its module's path,
+/// its stage, the `ash::vk::DescriptorSetLayoutBinding`s for each descriptor set that it uses, and
+/// its push constant range.
+fn ash_module(linkage: &[Linkage]) -> anyhow::Result<String> {
+    let mut code = rust_module_header("--codegen ash", linkage)?;
+    for entry in linkage {
+        let stages = ash_stages(entry.stage.as_deref());
+        rust_entry_point_module(&mut code, entry)?;
+        writeln!(
+            code,
+            "    /// The entry point's name, for `ash::vk::PipelineShaderStageCreateInfo::name`."
+        )?;
+        writeln!(
+            code,
+            "    pub const ENTRY_POINT_C: &core::ffi::CStr = c\"{}\";",
+            entry.entry_point.escape_default()
+        )?;
+        writeln!(
+            code,
+            "    pub const STAGE: ash::vk::ShaderStageFlags = {stages};"
+        )?;
+
+        if let Some(size) = entry.push_constant_size {
+            writeln!(code)?;
+            writeln!(
+                code,
+                "    pub const PUSH_CONSTANT_RANGE: ash::vk::PushConstantRange = \
+                    ash::vk::PushConstantRange {{"
+            )?;
+            writeln!(code, "        stage_flags: {stages},")?;
+            writeln!(code, "        offset: 0,")?;
+            writeln!(code, "        size: {size},")?;
+            writeln!(code, "    }};")?;
+        }
+
+        for (set, bindings) in bindings_by_set(entry) {
+            writeln!(code)?;
+            writeln!(
+                code,
+                "    pub const DESCRIPTOR_SET_LAYOUT_BINDINGS_{set}: \
+                    &[ash::vk::DescriptorSetLayoutBinding<'static>] = &["
+            )?;
+            for binding in bindings {
+                let Some(descriptor_type) = ash_descriptor_type(&binding.descriptor_type) else {
+                    log::warn!(
+                        "`{}` binding {} in set {set} has an unknown descriptor type `{}`",
+                        entry.entry_point,
+                        binding.binding,
+                        binding.descriptor_type
+                    );
+                    writeln!(
+                        code,
+                        "        // Binding {} has an unknown descriptor type `{}`.",
+                        binding.binding, binding.descriptor_type
+                    )?;
+                    continue;
+                };
+                writeln!(code, "        ash::vk::DescriptorSetLayoutBinding {{")?;
+                writeln!(code, "            binding: {},", binding.binding)?;
+                writeln!(
+                    code,
+                    "            descriptor_type: ash::vk::DescriptorType::{descriptor_type},"
+                )?;
+                if let Some(count) = binding.count {
+                    writeln!(code, "            descriptor_count: {count},")?;
+                } else {
+                    writeln!(
+                        code,
+                        "            // A runtime array, set the count to the most descriptors \
+                            that will be bound."
+                    )?;
+                    writeln!(code, "            descriptor_count: 1,")?;
+                }
+                writeln!(code, "            stage_flags: {stages},")?;
+                writeln!(code, "            p_immutable_samplers: core::ptr::null(),")?;
+                writeln!(code, "            _marker: core::marker::PhantomData,")?;
+                writeln!(code, "        }},")?;
+            }
+            writeln!(code, "    ];")?;
+        }
+        writeln!(code, "}}")?;
+    }
+
+    Ok(code)
+}
+
+/// The `ash::vk::ShaderStageFlags` for a reflected stage.
+fn ash_stages(stage: Option<&str>) -> &'static str {
+    match stage {
+        Some("vertex") => "ash::vk::ShaderStageFlags::VERTEX",
+        Some("fragment") => "ash::vk::ShaderStageFlags::FRAGMENT",
+        Some("compute") => "ash::vk::ShaderStageFlags::COMPUTE",
+        Some("tessellation_control") => "ash::vk::ShaderStageFlags::TESSELLATION_CONTROL",
+        Some("tessellation_evaluation") => "ash::vk::ShaderStageFlags::TESSELLATION_EVALUATION",
+        Some("geometry") => "ash::vk::ShaderStageFlags::GEOMETRY",
+        Some("task") => "ash::vk::ShaderStageFlags::TASK_EXT",
+        Some("mesh") => "ash::vk::ShaderStageFlags::MESH_EXT",
+        Some("ray_generation") => "ash::vk::ShaderStageFlags::RAYGEN_KHR",
+        Some("intersection") => "ash::vk::ShaderStageFlags::INTERSECTION_KHR",
+        Some("any_hit") => "ash::vk::ShaderStageFlags::ANY_HIT_KHR",
+        Some("closest_hit") => "ash::vk::ShaderStageFlags::CLOSEST_HIT_KHR",
+        Some("miss") => "ash::vk::ShaderStageFlags::MISS_KHR",
+        Some("callable") => "ash::vk::ShaderStageFlags::CALLABLE_KHR",
+        Some(_) | None => "ash::vk::ShaderStageFlags::ALL",
+    }
+}
+
+/// The `ash::vk::DescriptorType` constant for a reflected descriptor type.
+fn ash_descriptor_type(descriptor_type: &str) -> Option<&'static str> {
+    let ash_type = match descriptor_type {
+        "sampler" => "SAMPLER",
+        "combined_image_sampler" => "COMBINED_IMAGE_SAMPLER",
+        "sampled_image" => "SAMPLED_IMAGE",
+        "storage_image" => "STORAGE_IMAGE",
+        "uniform_texel_buffer" => "UNIFORM_TEXEL_BUFFER",
+        "storage_texel_buffer" => "STORAGE_TEXEL_BUFFER",
+        "uniform_buffer" => "UNIFORM_BUFFER",
+        "storage_buffer" => "STORAGE_BUFFER",
+        "acceleration_structure" => "ACCELERATION_STRUCTURE_KHR",
+        _ => return None,
+    };
+    Some(ash_type)
+}
+
+/// A C string literal, escaping anything that isn't printable ASCII.
+fn c_string(string: &str) -> anyhow::Result<String> {
+    let mut literal = String::from("\"");
+    for byte in string.bytes() {
+        match byte {
+            b'"' | b'\\' => {
+                literal.push('\\');
+                literal.push(char::from(byte));
+            }
+            b' '..=b'~' => literal.push(char::from(byte)),
+            // Octal escapes are at most 3 digits, unlike hex ones which would swallow any
+            // following hex digits.
+            _ => write!(literal, "\\{byte:03o}")?,
+        }
+    }
+    literal.push('"');
+    Ok(literal)
+}
+
+/// A C header with macros for each entry point's name, module path, stage, push constant size and
+/// the set and binding of each of its resources. The macros are prefixed with the entry point's
+/// name, eg `MAIN_FS_ENTRY_POINT`.
+fn c_header(linkage: &[Linkage], manifest_path: &std::path::Path) -> anyhow::Result<String> {
+    let guard = format!(
+        "{}_H",
+        rust_ident(
+            &manifest_path
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+        )
+        .to_uppercase()
+    );
+    let mut code =
+        String::from("// Generated by `cargo gpu build --codegen c-header`, do not edit.\n\n");
+    writeln!(code, "#ifndef {guard}")?;
+    writeln!(code, "#define {guard}")?;
+    writeln!(code)?;
+    writeln!(code, "#define SHADER_ENTRY_POINT_COUNT {}", linkage.len())?;
+    writeln!(code, "static const char *const SHADER_ENTRY_POINTS[] = {{")?;
+    for entry in linkage {
+        writeln!(code, "    {},", c_string(&entry.entry_point)?)?;
+    }
+    writeln!(code, "}};")?;
+
+    for entry in linkage {
+        let prefix = rust_ident(&entry.entry_point).to_uppercase();
+        let file_name = entry
+            .source_path
+            .rsplit('/')
+            .next()
+            .unwrap_or(&entry.source_path);
+        writeln!(code)?;
+        writeln!(code, "// `{}`", entry.entry_point)?;
+        writeln!(
+            code,
+            "#define {prefix}_ENTRY_POINT {}",
+            c_string(&entry.entry_point)?
+        )?;
+        writeln!(
+            code,
+            "#define {prefix}_SOURCE_PATH {}",
+            c_string(&entry.source_path)?
+        )?;
+        writeln!(code, "#define {prefix}_FILE_NAME {}", c_string(file_name)?)?;
+        if let Some(stage) = &entry.stage {
+            writeln!(code, "#define {prefix}_STAGE {}", c_string(stage)?)?;
+        }
+        if let Some(size) = entry.push_constant_size {
+            writeln!(code, "#define {prefix}_PUSH_CONSTANT_SIZE {size}")?;
+        }
+        for binding in &entry.bindings {
+            let name = binding.name.as_ref().map_or_else(
+                || format!("SET{}_BINDING{}", binding.set, binding.binding),
+                |name| rust_ident(name).to_uppercase(),
+            );
+            writeln!(code, "// {}", binding.descriptor_type)?;
+            writeln!(code, "#define {prefix}_{name}_SET {}", binding.set)?;
+            writeln!(code, "#define {prefix}_{name}_BINDING {}", binding.binding)?;
+            if let Some(count) = binding.count {
+                writeln!(code, "#define {prefix}_{name}_COUNT {count}")?;
+            }
+        }
+    }
+
+    writeln!(code)?;
+    writeln!(code, "#endif // {guard}")?;
+    Ok(code)
+}
+
+/// The `wgpu::TextureViewDimension` for an image.
+fn wgpu_view_dimension(image: &ImageInfo) -> Option<&'static str> {
+    let dimension = match (image.dimension.as_str(), image.arrayed) {
+        ("1d", false) => "wgpu::TextureViewDimension::D1",
+        ("2d", false) => "wgpu::TextureViewDimension::D2",
+        ("2d", true) => "wgpu::TextureViewDimension::D2Array",
+        ("3d", false) => "wgpu::TextureViewDimension::D3",
+        ("cube", false) => "wgpu::TextureViewDimension::Cube",
+        ("cube", true) => "wgpu::TextureViewDimension::CubeArray",
+        _ => return None,
+    };
+    Some(dimension)
+}
+
+/// The `wgpu::TextureFormat` variant for a SPIR-V image format, as named by `rspirv`.
+fn wgpu_texture_format(format: &str) -> Option<&'static str> {
+    let wgpu_format = match format {
+        "Rgba32f" => "Rgba32Float",
+        "Rgba16f" => "Rgba16Float",
+        "R32f" => "R32Float",
+        "Rgba8" => "Rgba8Unorm",
+        "Rgba8Snorm" => "Rgba8Snorm",
+        "Rg32f" => "Rg32Float",
+        "Rg16f" => "Rg16Float",
+        "R11fG11fB10f" => "Rg11b10Ufloat",
+        "R16f" => "R16Float",
+        "Rgba16" => "Rgba16Unorm",
+        "Rgb10A2" => "Rgb10a2Unorm",
+        "Rg16" => "Rg16Unorm",
+        "Rg8" => "Rg8Unorm",
+        "R16" => "R16Unorm",
+        "R8" => "R8Unorm",
+        "Rgba16Snorm" => "Rgba16Snorm",
+        "Rg16Snorm" => "Rg16Snorm",
+        "Rg8Snorm" => "Rg8Snorm",
+        "R16Snorm" => "R16Snorm",
+        "R8Snorm" => "R8Snorm",
+        "Rgba32i" => "Rgba32Sint",
+        "Rgba16i" => "Rgba16Sint",
+        "Rgba8i" => "Rgba8Sint",
+        "R32i" => "R32Sint",
+        "Rg32i" => "Rg32Sint",
+        "Rg16i" => "Rg16Sint",
+        "Rg8i" => "Rg8Sint",
+        "R16i" => "R16Sint",
+        "R8i" => "R8Sint",
+        "Rgba32ui" => "Rgba32Uint",
+        "Rgba16ui" => "Rgba16Uint",
+        "Rgba8ui" => "Rgba8Uint",
+        "R32ui" => "R32Uint",
+        "Rgb10a2ui" => "Rgb10a2Uint",
+        "Rg32ui" => "Rg32Uint",
+        "Rg16ui" => "Rg16Uint",
+        "Rg8ui" => "Rg8Uint",
+        "R16ui" => "R16Uint",
+        "R8ui" => "R8Uint",
+        "R64ui" => "R64Uint",
+        _ => return None,
+    };
+    Some(wgpu_format)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A fragment shader sampling a texture, and a compute shader writing to a storage buffer.
+    fn manifest() -> Vec<Linkage> {
+        vec![
+            Linkage::new("main_fs", "shaders/main.spv").with_reflection(
+                "fragment".to_owned(),
+                vec![
+                    DescriptorBinding {
+                        set: 0,
+                        binding: 0,
+                        descriptor_type: "sampled_image".to_owned(),
+                        count: Some(1),
+                        name: Some("texture".to_owned()),
+                        read_only: false,
+                        write_only: false,
+                        image: Some(ImageInfo {
+                            dimension: "2d".to_owned(),
+                            arrayed: false,
+                            depth: false,
+                            multisampled: false,
+                            sampled_type: "float".to_owned(),
+                            format: "Unknown".to_owned(),
+                        }),
+                    },
+                    DescriptorBinding {
+                        set: 0,
+                        binding: 1,
+                        descriptor_type: "uniform_texel_buffer".to_owned(),
+                        count: Some(1),
+                        name: None,
+                        read_only: false,
+                        write_only: false,
+                        image: None,
+                    },
+                ],
+            ),
+            Linkage::new("compute::main_cs", "shaders/main.spv")
+                .with_reflection(
+                    "compute".to_owned(),
+                    vec![DescriptorBinding {
+                        set: 1,
+                        binding: 0,
+                        descriptor_type: "storage_buffer".to_owned(),
+                        count: Some(4),
+                        name: None,
+                        read_only: true,
+                        write_only: false,
+                        image: None,
+                    }],
+                )
+                .with_push_constant_size(Some(20)),
+        ]
+    }
+
+    #[test_log::test]
+    fn rust_idents_for_entry_points() {
+        assert_eq!(rust_ident("main_fs"), "main_fs");
+        assert_eq!(
+            rust_ident("deeply::nested::main_fs"),
+            "deeply_nested_main_fs"
+        );
+        assert_eq!(rust_ident("2d-blit"), "_2d_blit");
+    }
+
+    #[test_log::test]
+    fn wgpu_bind_group_layouts() {
+        let code = wgpu_module(&manifest()).unwrap();
+        assert!(
+            code.contains("pub const ENTRY_POINTS: &[&str] = &[\n    \"main_fs\",\n    \"compute::main_cs\",\n];"),
+            "{code}"
+        );
+        assert!(code.contains("pub mod compute_main_cs {"), "{code}");
+        assert!(
+            code.contains("pub const STAGE: wgpu::ShaderStages = wgpu::ShaderStages::FRAGMENT;"),
+            "{code}"
+        );
+        assert!(
+            code.contains(
+                "pub const BIND_GROUP_LAYOUT_0: wgpu::BindGroupLayoutDescriptor<'static> ="
+            ),
+            "{code}"
+        );
+        assert!(
+            code.contains("view_dimension: wgpu::TextureViewDimension::D2"),
+            "{code}"
+        );
+        assert!(
+            code.contains("// Binding 1 is a uniform_texel_buffer, which wgpu doesn't support."),
+            "{code}"
+        );
+        assert!(
+            code.contains(
+                "pub const BIND_GROUP_LAYOUT_1: wgpu::BindGroupLayoutDescriptor<'static> ="
+            ),
+            "{code}"
+        );
+        assert!(code.contains("Storage { read_only: true }"), "{code}");
+        assert!(
+            code.contains("count: core::num::NonZeroU32::new(4),"),
+            "{code}"
+        );
+    }
+
+    #[test_log::test]
+    fn ash_descriptor_set_layout_bindings() {
+        let code = ash_module(&manifest()).unwrap();
+        assert!(
+            code.contains("pub const ENTRY_POINT_C: &core::ffi::CStr = c\"main_fs\";"),
+            "{code}"
+        );
+        assert!(
+            code.contains("descriptor_type: ash::vk::DescriptorType::SAMPLED_IMAGE,"),
+            "{code}"
+        );
+        assert!(
+            code.contains("descriptor_type: ash::vk::DescriptorType::UNIFORM_TEXEL_BUFFER,"),
+            "{code}"
+        );
+        assert!(
+            code.contains("pub const DESCRIPTOR_SET_LAYOUT_BINDINGS_1:"),
+            "{code}"
+        );
+        assert!(code.contains("descriptor_count: 4,"), "{code}");
+        assert!(code.contains("stage_flags: ash::vk::ShaderStageFlags::COMPUTE,\n        offset: 0,\n        size: 20,"), "{code}");
+    }
+
+    #[test_log::test]
+    fn rust_manifest_includes_modules() {
+        let shader_crate = std::path::absolute("shaders").unwrap();
+        let code = rust_manifest(&manifest(), &shader_crate.join("out"), &shader_crate).unwrap();
+        assert!(code.starts_with("// Generated by `cargo gpu build --manifest-format rust`"));
+        assert!(code.contains("pub mod main_fs {"), "{code}");
+        assert!(
+            code.contains("pub const STAGE: &str = \"fragment\";"),
+            "{code}"
+        );
+        assert!(
+            code.contains("pub const SPIRV: &[u8] = include_bytes!(\"../shaders/main.spv\");"),
+            "{code}"
+        );
+    }
+
+    #[test_log::test]
+    fn bevy_manifest_paths_are_relative_to_assets() {
+        let shader_crate = std::path::absolute("game").unwrap();
+        let linkage = [
+            Linkage::new("main_fs", "assets/shaders/main.spv")
+                .with_reflection("fragment".to_owned(), vec![]),
+            Linkage::new("main_vs", "assets/shaders/minimal/main.spv")
+                .with_variant("minimal".to_owned(), vec![]),
+        ];
+        let ron = bevy_manifest(
+            &linkage,
+            &shader_crate.join("assets/shaders"),
+            &shader_crate,
+        )
+        .unwrap();
+        assert!(ron.starts_with("// Generated by `cargo gpu build --manifest-format bevy`"));
+        assert!(ron.contains("label: \"main_fs\",\n"), "{ron}");
+        assert!(ron.contains("path: \"shaders/main.spv\",\n"), "{ron}");
+        assert!(ron.contains("stage: Some(\"fragment\"),\n"), "{ron}");
+        assert!(ron.contains("label: \"minimal_main_vs\",\n"), "{ron}");
+        assert!(
+            ron.contains("path: \"shaders/minimal/main.spv\",\n"),
+            "{ron}"
+        );
+
+        let ron_without_assets =
+            bevy_manifest(&linkage, &shader_crate.join("assets_out"), &shader_crate).unwrap();
+        assert!(
+            ron_without_assets.contains("path: \"../assets/shaders/main.spv\",\n"),
+            "{ron_without_assets}"
+        );
+    }
+
+    #[test_log::test]
+    fn embedding_modules_as_literals() {
+        let shader_crate = crate::cache_dir().unwrap().join("embed");
+        std::fs::create_dir_all(shader_crate.join("shaders")).unwrap();
+        let spv: Vec<u8> = (0..20).collect();
+        std::fs::write(shader_crate.join("shaders/main.spv"), &spv).unwrap();
+
+        let linkage = [Linkage::new("main_fs", "shaders/main.spv")];
+        let code = embedded_modules(
+            "--embed",
+            &linkage,
+            EmbedMode::Literal,
+            &shader_crate,
+            &shader_crate,
+        )
+        .unwrap();
+        assert!(code.starts_with("// Generated by `cargo gpu build --embed`"));
+        assert!(
+            code.contains(
+                "    pub const SPIRV: &[u8] = &[\n        0x00, 0x01, 0x02, 0x03, 0x04, 0x05, \
+                 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f,\n        0x10, \
+                 0x11, 0x12, 0x13,\n    ];\n"
+            ),
+            "{code}"
+        );
+        crate::test::tests_teardown();
+    }
+
+    #[test_log::test]
+    fn c_header_macros() {
+        let code = c_header(&manifest(), std::path::Path::new("out/manifest.json")).unwrap();
+        assert!(
+            code.contains("#ifndef MANIFEST_H\n#define MANIFEST_H\n"),
+            "{code}"
+        );
+        assert!(
+            code.contains("#define SHADER_ENTRY_POINT_COUNT 2\n"),
+            "{code}"
+        );
+        assert!(
+            code.contains("#define COMPUTE_MAIN_CS_ENTRY_POINT \"compute::main_cs\"\n"),
+            "{code}"
+        );
+        assert!(
+            code.contains("#define MAIN_FS_FILE_NAME \"main.spv\"\n"),
+            "{code}"
+        );
+        assert!(
+            code.contains("#define MAIN_FS_TEXTURE_BINDING 0\n"),
+            "{code}"
+        );
+        assert!(
+            code.contains("#define COMPUTE_MAIN_CS_SET1_BINDING0_COUNT 4\n"),
+            "{code}"
+        );
+        assert!(
+            code.contains("#define COMPUTE_MAIN_CS_PUSH_CONSTANT_SIZE 20\n"),
+            "{code}"
+        );
+        assert_eq!(
+            c_string("a\"b\\c\u{e9}").unwrap(),
+            "\"a\\\"b\\\\c\\303\\251\""
+        );
+    }
+}
@@ -0,0 +1,313 @@
+//! Check that `--capability` and `--extension` make sense for the shader target before compiling.
+//!
+//! Asking for eg ray tracing on Vulkan 1.0 then fails straight away, rather than with a cryptic
+//! error from `rust-gpu` minutes into the build.
+//!
+//! Only the SPIR-V version of the target is checked, against the version that each capability
+//! became core in or that the extension that enables it needs. Targets that aren't known, like
+//! custom target specs, aren't checked at all.
+
+use spirv_builder_cli::args::BuildArgs;
+use spirv_builder_cli::spirv::Capability;
+
+/// A SPIR-V version, as (major, minor).
+type Version = (u8, u8);
+
+/// The SPIR-V version of each known target environment, lowest first within each family.
+const TARGET_VERSIONS: &[(&str, Version)] = &[
+    ("vulkan1.0", (1, 0)),
+    ("vulkan1.1", (1, 3)),
+    ("vulkan1.1spv1.4", (1, 4)),
+    ("vulkan1.2", (1, 5)),
+    ("vulkan1.3", (1, 6)),
+    ("vulkan1.4", (1, 6)),
+    ("spv1.0", (1, 0)),
+    ("spv1.1", (1, 1)),
+    ("spv1.2", (1, 2)),
+    ("spv1.3", (1, 3)),
+    ("spv1.4", (1, 4)),
+    ("spv1.5", (1, 5)),
+    ("spv1.6", (1, 6)),
+    ("opengl4.0", (1, 0)),
+    ("opengl4.1", (1, 0)),
+    ("opengl4.2", (1, 0)),
+    ("opengl4.3", (1, 0)),
+    ("opengl4.5", (1, 0)),
+];
+
+/// Capabilities that need a newer SPIR-V than 1.0: the version they became core in, if they did,
+/// and the extension that enables them on earlier versions.
+const CAPABILITIES: &[(Capability, Option<Version>, &str)] = &[
+    (Capability::GroupNonUniform, Some((1, 3)), ""),
+    (Capability::GroupNonUniformVote, Some((1, 3)), ""),
+    (Capability::GroupNonUniformArithmetic, Some((1, 3)), ""),
+    (Capability::GroupNonUniformBallot, Some((1, 3)), ""),
+    (Capability::GroupNonUniformShuffle, Some((1, 3)), ""),
+    (Capability::GroupNonUniformShuffleRelative, Some((1, 3)), ""),
+    (Capability::GroupNonUniformClustered, Some((1, 3)), ""),
+    (Capability::GroupNonUniformQuad, Some((1, 3)), ""),
+    (
+        Capability::StorageBuffer16BitAccess,
+        Some((1, 3)),
+        "SPV_KHR_16bit_storage",
+    ),
+    (
+        Capability::UniformAndStorageBuffer16BitAccess,
+        Some((1, 3)),
+        "SPV_KHR_16bit_storage",
+    ),
+    (
+        Capability::StoragePushConstant16,
+        Some((1, 3)),
+        "SPV_KHR_16bit_storage",
+    ),
+    (
+        Capability::StorageInputOutput16,
+        Some((1, 3)),
+        "SPV_KHR_16bit_storage",
+    ),
+    (
+        Capability::DeviceGroup,
+        Some((1, 3)),
+        "SPV_KHR_device_group",
+    ),
+    (Capability::MultiView, Some((1, 3)), "SPV_KHR_multiview"),
+    (
+        Capability::VariablePointers,
+        Some((1, 3)),
+        "SPV_KHR_variable_pointers",
+    ),
+    (
+        Capability::VariablePointersStorageBuffer,
+        Some((1, 3)),
+        "SPV_KHR_variable_pointers",
+    ),
+    (
+        Capability::DrawParameters,
+        Some((1, 3)),
+        "SPV_KHR_shader_draw_parameters",
+    ),
+    (
+        Capability::StorageBuffer8BitAccess,
+        Some((1, 5)),
+        "SPV_KHR_8bit_storage",
+    ),
+    (
+        Capability::UniformAndStorageBuffer8BitAccess,
+        Some((1, 5)),
+        "SPV_KHR_8bit_storage",
+    ),
+    (
+        Capability::StoragePushConstant8,
+        Some((1, 5)),
+        "SPV_KHR_8bit_storage",
+    ),
+    (
+        Capability::VulkanMemoryModel,
+        Some((1, 5)),
+        "SPV_KHR_vulkan_memory_model",
+    ),
+    (
+        Capability::PhysicalStorageBufferAddresses,
+        Some((1, 5)),
+        "SPV_KHR_physical_storage_buffer",
+    ),
+    (
+        Capability::ShaderNonUniform,
+        Some((1, 5)),
+        "SPV_EXT_descriptor_indexing",
+    ),
+    (
+        Capability::RuntimeDescriptorArray,
+        Some((1, 5)),
+        "SPV_EXT_descriptor_indexing",
+    ),
+    (
+        Capability::DemoteToHelperInvocation,
+        Some((1, 6)),
+        "SPV_EXT_demote_to_helper_invocation",
+    ),
+    (
+        Capability::DotProduct,
+        Some((1, 6)),
+        "SPV_KHR_integer_dot_product",
+    ),
+    (Capability::RayTracingKHR, None, "SPV_KHR_ray_tracing"),
+    (Capability::RayQueryKHR, None, "SPV_KHR_ray_query"),
+    (Capability::MeshShadingEXT, None, "SPV_EXT_mesh_shader"),
+];
+
+/// Extensions that need a newer SPIR-V than 1.0.
+const EXTENSIONS: &[(&str, Version)] = &[
+    ("SPV_KHR_ray_tracing", (1, 4)),
+    ("SPV_KHR_ray_query", (1, 4)),
+    ("SPV_EXT_mesh_shader", (1, 4)),
+];
+
+/// Check the capabilities and extensions in `build_args` against its shader target.
+pub fn check(build_args: &BuildArgs) -> anyhow::Result<()> {
+    let target = build_args.single_shader_target();
+    let Some(env) = target_env(target) else {
+        log::debug!("not checking capabilities against the unknown target `{target}`");
+        return Ok(());
+    };
+    let Some(version) = spirv_version(env) else {
+        return Ok(());
+    };
+
+    for extension in &build_args.extension {
+        let needed = extension_version(extension);
+        anyhow::ensure!(
+            version >= needed,
+            "the extension `{extension}` {}",
+            needs(env, needed)
+        );
+    }
+    for capability in &build_args.capability {
+        let Some((_, core_in, extension)) = CAPABILITIES
+            .iter()
+            .find(|(known, _, _)| known == capability)
+        else {
+            continue;
+        };
+        let is_enabled_by_extension =
+            !extension.is_empty() && build_args.extension.iter().any(|given| given == extension);
+        let needed = match core_in {
+            Some(core_version) if !is_enabled_by_extension => *core_version,
+            _ => extension_version(extension),
+        };
+        anyhow::ensure!(
+            version >= needed,
+            "the capability `{capability:?}` {}",
+            needs(env, needed)
+        );
+    }
+    Ok(())
+}
+
+/// The target environment of a shader target, eg "vulkan1.2" for "spirv-unknown-vulkan1.2",
+/// or `None` if it isn't a known one. Also accepts the path to a target's spec file.
+fn target_env(shader_target: &str) -> Option<&str> {
+    let name = shader_target
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or(shader_target)
+        .trim_end_matches(".json");
+    name.strip_prefix("spirv-unknown-")
+}
+
+/// The SPIR-V version of a known target environment.
+fn spirv_version(env: &str) -> Option<Version> {
+    TARGET_VERSIONS
+        .iter()
+        .find(|(known, _)| *known == env)
+        .map(|(_, version)| *version)
+}
+
+/// The SPIR-V version that an extension needs.
+fn extension_version(extension: &str) -> Version {
+    EXTENSIONS
+        .iter()
+        .find(|(known, _)| *known == extension)
+        .map_or((1, 0), |(_, version)| *version)
+}
+
+/// The rest of the error for something that needs SPIR-V `needed`, which the target `env`
+/// doesn't have, suggesting the lowest target in the same family that does.
+fn needs(env: &str, needed: Version) -> String {
+    let family = env
+        .split(|character: char| !character.is_ascii_alphabetic())
+        .next()
+        .unwrap_or(env);
+    let (major, minor) = needed;
+    TARGET_VERSIONS
+        .iter()
+        .find(|(candidate, version)| candidate.starts_with(family) && *version >= needed)
+        .map_or_else(
+            || format!("needs SPIR-V {major}.{minor}, which no `{family}` target has"),
+            |(candidate, _)| format!("requires spirv-unknown-{candidate} or later"),
+        )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use clap::Parser as _;
+
+    /// Check the build args given on the command line, with the error as a string.
+    fn checked(args: &[&str]) -> Result<(), String> {
+        let build_args = BuildArgs::parse_from(core::iter::once(&"").chain(args));
+        check(&build_args).map_err(|error| error.to_string())
+    }
+
+    #[test_log::test]
+    fn checking_capabilities_against_the_target() {
+        let ray_tracing = [
+            "--capability",
+            "RayTracingKHR",
+            "--extension",
+            "SPV_KHR_ray_tracing",
+            "--shader-target",
+        ];
+        assert_eq!(
+            checked(&[&ray_tracing[..], &["spirv-unknown-vulkan1.0"]].concat()),
+            Err(
+                "the extension `SPV_KHR_ray_tracing` requires spirv-unknown-vulkan1.1spv1.4 or \
+                 later"
+                    .to_owned()
+            )
+        );
+        assert_eq!(
+            checked(&[&ray_tracing[..], &["spirv-unknown-vulkan1.2"]].concat()),
+            Ok(())
+        );
+
+        assert_eq!(
+            checked(&[
+                "--capability",
+                "GroupNonUniformBallot",
+                "--shader-target",
+                "spirv-unknown-spv1.2",
+            ]),
+            Err(
+                "the capability `GroupNonUniformBallot` requires spirv-unknown-spv1.3 or later"
+                    .to_owned()
+            )
+        );
+
+        // An extension makes a capability available before it became core.
+        assert_eq!(
+            checked(&[
+                "--capability",
+                "VulkanMemoryModel",
+                "--extension",
+                "SPV_KHR_vulkan_memory_model",
+                "--shader-target",
+                "spirv-unknown-vulkan1.0",
+            ]),
+            Ok(())
+        );
+        assert_eq!(
+            checked(&[
+                "--capability",
+                "VulkanMemoryModel",
+                "--shader-target",
+                "spirv-unknown-opengl4.5",
+            ]),
+            Err(
+                "the capability `VulkanMemoryModel` needs SPIR-V 1.5, which no `opengl` target has"
+                    .to_owned()
+            )
+        );
+
+        assert_eq!(
+            checked(&[
+                "--capability",
+                "RayQueryKHR",
+                "--shader-target",
+                "/somewhere/custom-target.json",
+            ]),
+            Ok(())
+        );
+    }
+}
@@ -0,0 +1,132 @@
+//! `--compress`, shrink the emitted modules, eg for web and mobile deployments where the size of
+//! the shipped shaders matters.
+//!
+//! Modules are compressed last, after everything that reads them, ie validation, reflection,
+//! transpiling and `--stats`. The uncompressed `.spv` is then replaced by eg a `.spv.zst`.
+
+use anyhow::Context as _;
+use spirv_builder_cli::{args::Compression, Linkage, ModuleCompression};
+
+/// SPIR-V modules are small and only compressed once, so it's worth compressing them as much as
+/// `zstd` can without its slower "ultra" levels.
+const ZSTD_LEVEL: i32 = 19;
+
+/// Compress the module at `spv_path`, replacing it with the compressed file. Returns the
+/// compressed file's path and how it was compressed.
+///
+/// # Errors
+/// If the module can't be read, compressed or written.
+pub fn module(
+    spv_path: &std::path::Path,
+    scheme: Compression,
+) -> anyhow::Result<(std::path::PathBuf, ModuleCompression)> {
+    let bytes = std::fs::read(spv_path)
+        .with_context(|| format!("could not read SPIR-V file '{}'", spv_path.display()))?;
+    let compressed = compress_bytes(&bytes, scheme)
+        .with_context(|| format!("could not compress '{}'", spv_path.display()))?;
+    let compressed_path = with_extension(spv_path, scheme);
+    crate::atomic::write(&compressed_path, &compressed)?;
+    std::fs::remove_file(spv_path)?;
+    log::debug!(
+        "compressed {} from {} to {} bytes",
+        spv_path.display(),
+        bytes.len(),
+        compressed.len()
+    );
+    Ok((
+        compressed_path,
+        ModuleCompression {
+            scheme: scheme.name().to_owned(),
+            size: u64::try_from(bytes.len())?,
+            compressed_size: u64::try_from(compressed.len())?,
+        },
+    ))
+}
+
+/// Compress the bytes of a module.
+fn compress_bytes(bytes: &[u8], scheme: Compression) -> anyhow::Result<Vec<u8>> {
+    match scheme {
+        Compression::Zstd => Ok(zstd::bulk::compress(bytes, ZSTD_LEVEL)?),
+    }
+}
+
+/// Decompress a module file that was compressed as `compression` says.
+///
+/// # Errors
+/// If the scheme isn't known or the file isn't valid for it.
+pub fn decompress(bytes: &[u8], compression: &ModuleCompression) -> anyhow::Result<Vec<u8>> {
+    match compression.scheme.as_str() {
+        "zstd" => Ok(zstd::bulk::decompress(
+            bytes,
+            usize::try_from(compression.size)?,
+        )?),
+        scheme => anyhow::bail!("unknown compression scheme `{scheme}`"),
+    }
+}
+
+/// The path of the compressed file, eg `shader.spv.zst` for `shader.spv`.
+fn with_extension(path: &std::path::Path, scheme: Compression) -> std::path::PathBuf {
+    let mut compressed = path.as_os_str().to_owned();
+    compressed.push(".");
+    compressed.push(scheme.extension());
+    compressed.into()
+}
+
+/// Compress each entry point's module and point its linkage at the compressed file. Modules are
+/// only compressed once, even when many entry points share them.
+///
+/// # Errors
+/// If any of the modules can't be compressed.
+pub fn modules(
+    linkage: Vec<Linkage>,
+    scheme: Compression,
+    shader_crate: &std::path::Path,
+) -> anyhow::Result<Vec<Linkage>> {
+    let mut by_module = std::collections::HashMap::new();
+    linkage
+        .into_iter()
+        .map(|mut entry| {
+            if !by_module.contains_key(&entry.source_path) {
+                let (compressed_path, compression) =
+                    module(&shader_crate.join(&entry.source_path), scheme)?;
+                let sha256 = crate::prebuilt::sha256_hex(&std::fs::read(compressed_path)?);
+                by_module.insert(entry.source_path.clone(), (compression, sha256));
+            }
+            let (compression, sha256) = by_module
+                .get(&entry.source_path)
+                .context("module should have been compressed already")?
+                .clone();
+            entry.source_path = format!("{}.{}", entry.source_path, scheme.extension());
+            Ok(entry.with_sha256(sha256).with_compression(compression))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_log::test]
+    fn compressing_modules() {
+        let dir = crate::cache_dir().unwrap().join("compress");
+        std::fs::create_dir_all(&dir).unwrap();
+        let spv_path = dir.join("shader.spv");
+        let header: [u32; 5] = [0x0723_0203, 0x0001_0000, 0, 1, 0];
+        let bytes = header
+            .repeat(64)
+            .iter()
+            .flat_map(|word| word.to_le_bytes())
+            .collect::<Vec<_>>();
+        std::fs::write(&spv_path, &bytes).unwrap();
+
+        let (compressed_path, compression) = module(&spv_path, Compression::Zstd).unwrap();
+        assert_eq!(compressed_path, dir.join("shader.spv.zst"));
+        assert!(!spv_path.exists());
+        assert_eq!(compression.scheme, "zstd");
+        assert_eq!(compression.size, u64::try_from(bytes.len()).unwrap());
+        assert!(compression.compressed_size < compression.size);
+        let compressed = std::fs::read(&compressed_path).unwrap();
+        assert_eq!(decompress(&compressed, &compression).unwrap(), bytes);
+        crate::test::tests_teardown();
+    }
+}
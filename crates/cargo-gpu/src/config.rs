@@ -2,9 +2,73 @@
 use anyhow::Context as _;
 use clap::Parser as _;
 
-/// Config
+/// Config.
 pub struct Config;
 
+/// Where a config value came from, in order of increasing precedence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    /// The default value of the CLI argument.
+    Default,
+    /// The global config file, see [`crate::global_config`].
+    Global,
+    /// `[workspace.metadata.rust-gpu.*]` in the workspace's `Cargo.toml`.
+    Workspace,
+    /// `[package.metadata.rust-gpu.*]` in the shader crate's `Cargo.toml`.
+    Crate,
+    /// The build profile chosen with `--profile`, from `[*.metadata.rust-gpu.profile.<name>]`.
+    Profile,
+    /// The command line.
+    Cli,
+}
+
+impl core::fmt::Display for Source {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Default => "default",
+            Self::Global => "global config",
+            Self::Workspace => "workspace Cargo.toml",
+            Self::Crate => "crate Cargo.toml",
+            Self::Profile => "profile",
+            Self::Cli => "command line",
+        }
+        .fmt(f)
+    }
+}
+
+/// `cargo gpu config`.
+///
+/// Takes the same arguments as `cargo gpu build`.
+#[derive(clap::Parser, Debug)]
+pub struct Effective {
+    /// The build, as it would be configured by the command line alone.
+    #[clap(flatten)]
+    build: crate::build::Build,
+}
+
+impl Effective {
+    /// Entrypoint.
+    pub fn run(&self, mut env_args: Vec<String>) -> anyhow::Result<()> {
+        env_args = env_args
+            .into_iter()
+            .filter(|arg| arg != "config")
+            .collect::<Vec<_>>();
+        let values =
+            Config::with_provenance(&self.build.install.spirv_install.shader_crate, env_args)?;
+
+        #[expect(
+            clippy::print_stdout,
+            reason = "The output of this command could potentially be used in a script, \
+                      so we _don't_ want to use `crate::user_output`, as that prefixes a crab."
+        )]
+        for (key, value, source) in values {
+            println!("{key} = {value}  # {source}");
+        }
+
+        Ok(())
+    }
+}
+
 impl Config {
     /// Get all the config defaults as JSON.
     pub fn defaults_as_json() -> anyhow::Result<serde_json::Value> {
@@ -14,7 +78,13 @@ impl Config {
 
     /// Convert CLI args to their serde JSON representation.
     fn cli_args_to_json(env_args: Vec<String>) -> anyhow::Result<serde_json::Value> {
-        let mut cli_args_json = serde_json::to_value(crate::build::Build::parse_from(env_args))?;
+        Self::build_to_json(&crate::build::Build::parse_from(env_args))
+    }
+
+    /// Convert parsed CLI args to their serde JSON representation, in the same shape as the
+    /// shader crate's `Cargo.toml` metadata.
+    fn build_to_json(cli_args: &crate::build::Build) -> anyhow::Result<serde_json::Value> {
+        let mut cli_args_json = serde_json::to_value(cli_args)?;
 
         // Move `/install/spirv_install` to `/install`
         let spirv_install = cli_args_json
@@ -45,13 +115,32 @@ impl Config {
         shader_crate_path: &std::path::PathBuf,
         mut env_args: Vec<String>,
     ) -> anyhow::Result<crate::build::Build> {
-        let mut config = crate::metadata::Metadata::as_json(shader_crate_path)?;
-
         env_args = env_args
             .into_iter()
             .filter(|arg| !(arg == "build" || arg == "install"))
             .collect::<Vec<_>>();
-        let cli_args_json = Self::cli_args_to_json(env_args)?;
+        Self::merge_with_cargo_config(shader_crate_path, Self::cli_args_to_json(env_args)?)
+    }
+
+    /// Like [`Self::clap_command_with_cargo_config`], for the commands that take the same
+    /// arguments as `cargo gpu install` along with their own, eg `cargo gpu upgrade`. `install`
+    /// is what was parsed from the command line.
+    pub fn install_with_cargo_config(
+        shader_crate_path: &std::path::PathBuf,
+        install: &crate::install::Install,
+    ) -> anyhow::Result<crate::build::Build> {
+        let mut cli_args = crate::build::Build::parse_from([String::new()]);
+        cli_args.install = install.clone();
+        Self::merge_with_cargo_config(shader_crate_path, Self::build_to_json(&cli_args)?)
+    }
+
+    /// Merge the JSON of the CLI args over the shader crate's config.
+    fn merge_with_cargo_config(
+        shader_crate_path: &std::path::PathBuf,
+        cli_args_json: serde_json::Value,
+    ) -> anyhow::Result<crate::build::Build> {
+        let mut config =
+            crate::metadata::Metadata::as_json(shader_crate_path, Self::profile(&cli_args_json))?;
 
         Self::json_merge(&mut config, cli_args_json, None)?;
 
@@ -74,8 +163,68 @@ impl Config {
         Ok(args)
     }
 
+    /// Every config value for the shader crate, as `cargo gpu build` would see them after merging,
+    /// along with where each value came from. Keys are like `build.output-dir`.
+    pub fn with_provenance(
+        shader_crate_path: &std::path::PathBuf,
+        mut env_args: Vec<String>,
+    ) -> anyhow::Result<Vec<(String, serde_json::Value, Source)>> {
+        env_args = env_args
+            .into_iter()
+            .filter(|arg| !(arg == "build" || arg == "install"))
+            .collect::<Vec<_>>();
+        let cli_args_json = Self::cli_args_to_json(env_args)?;
+        let mut layers = crate::metadata::Metadata::config_layers(
+            shader_crate_path,
+            Self::profile(&cli_args_json),
+        )?;
+        layers.push((Source::Cli, cli_args_json));
+        Self::provenance(&Self::defaults_as_json()?, &layers)
+    }
+
+    /// The build profile chosen on the command line, if any.
+    fn profile(cli_args_json: &serde_json::Value) -> Option<&str> {
+        cli_args_json.pointer("/build/profile")?.as_str()
+    }
+
+    /// Find where each of the default config's values is overridden, following the same rules
+    /// as [`Self::json_merge`].
+    fn provenance(
+        defaults: &serde_json::Value,
+        layers: &[(Source, serde_json::Value)],
+    ) -> anyhow::Result<Vec<(String, serde_json::Value, Source)>> {
+        let mut values = Vec::new();
+        for section in ["build", "install"] {
+            let serde_json::Value::Object(options) = defaults
+                .get(section)
+                .with_context(|| format!("`{section}` not found in default config"))?
+            else {
+                anyhow::bail!("`{section}` in the default config isn't an object");
+            };
+            for (option, default) in options {
+                let pointer = format!("/{section}/{option}");
+                let mut value = default;
+                let mut source = Source::Default;
+                for (layer_source, layer) in layers {
+                    if let Some(layer_value) = layer.pointer(&pointer) {
+                        if layer_value != default {
+                            value = layer_value;
+                            source = *layer_source;
+                        }
+                    }
+                }
+                values.push((
+                    format!("{section}.{}", option.replace('_', "-")),
+                    value.clone(),
+                    source,
+                ));
+            }
+        }
+        Ok(values)
+    }
+
     /// Merge 2 JSON objects. But only if the incoming patch value isn't the default value.
-    /// Inspired by: <https://stackoverflow.com/a/47142105/575773>
+    /// Inspired by: <https://stackoverflow.com/a/47142105/575773>.
     pub fn json_merge(
         left_in: &mut serde_json::Value,
         right_in: serde_json::Value,
@@ -83,8 +232,16 @@ impl Config {
     ) -> anyhow::Result<()> {
         let defaults = Self::defaults_as_json()?;
 
+        // Tables that default to being empty, like `spec-constants`, have keys of the user's
+        // choosing, so they're replaced whole rather than merged key by key.
+        let is_free_form_table = maybe_pointer
+            .and_then(|pointer| defaults.pointer(pointer))
+            .and_then(serde_json::Value::as_object)
+            .is_some_and(serde_json::Map::is_empty);
         match (left_in, right_in) {
-            (left @ &mut serde_json::Value::Object(_), serde_json::Value::Object(right)) => {
+            (left @ &mut serde_json::Value::Object(_), serde_json::Value::Object(right))
+                if !is_free_form_table =>
+            {
                 let left_as_object = left
                     .as_object_mut()
                     .context("Unreachable, we've already proved it's an object")?;
@@ -144,6 +301,24 @@ mod test {
         assert!(args.install.spirv_install.auto_install_rust_toolchain);
     }
 
+    #[test_log::test]
+    fn output_format_is_not_config() {
+        let shader_crate_path = crate::test::shader_crate_test_path();
+
+        let args = Config::clap_command_with_cargo_config(
+            &shader_crate_path,
+            vec![
+                "gpu".to_owned(),
+                "build".to_owned(),
+                "--output-format".to_owned(),
+                "json".to_owned(),
+                "--debug".to_owned(),
+            ],
+        )
+        .unwrap();
+        assert!(args.build_args.debug);
+    }
+
     #[test_log::test]
     fn booleans_from_cargo() {
         let shader_crate_path = crate::test::shader_crate_test_path();
@@ -242,6 +417,122 @@ mod test {
         );
     }
 
+    #[test_log::test]
+    fn variants_from_cargo() {
+        let shader_crate_path = crate::test::shader_crate_test_path();
+        let mut file = crate::test::overwrite_shader_cargo_toml(&shader_crate_path);
+        file.write_all(
+            [
+                "[[package.metadata.rust-gpu.build.variants]]",
+                "name = \"low\"",
+                "features = [\"low-quality\"]",
+                "no-default-features = true",
+            ]
+            .join("\n")
+            .as_bytes(),
+        )
+        .unwrap();
+
+        let args = Config::clap_command_with_cargo_config(&shader_crate_path, vec![]).unwrap();
+        assert_eq!(
+            args.build_args.variants,
+            [spirv_builder_cli::args::Variant {
+                name: "low".to_owned(),
+                features: vec!["low-quality".to_owned()],
+                no_default_features: true,
+            }]
+        );
+    }
+
+    #[test_log::test]
+    fn spec_constants_from_cargo_and_cli() {
+        let shader_crate_path = crate::test::shader_crate_test_path();
+        let mut file = crate::test::overwrite_shader_cargo_toml(&shader_crate_path);
+        file.write_all(
+            [
+                "[package.metadata.rust-gpu.build]",
+                "spec-constants = { WORKGROUP_SIZE = 128, USE_FOG = true, SCALE = 0.5 }",
+            ]
+            .join("\n")
+            .as_bytes(),
+        )
+        .unwrap();
+
+        let args = Config::clap_command_with_cargo_config(&shader_crate_path, vec![]).unwrap();
+        assert_eq!(
+            args.build_args.spec_constants,
+            [
+                ("SCALE".to_owned(), "0.5".to_owned()),
+                ("USE_FOG".to_owned(), "true".to_owned()),
+                ("WORKGROUP_SIZE".to_owned(), "128".to_owned()),
+            ]
+        );
+
+        let cli_args = Config::clap_command_with_cargo_config(
+            &shader_crate_path,
+            vec![
+                "gpu".to_owned(),
+                "build".to_owned(),
+                "--spec-constant".to_owned(),
+                "0=64".to_owned(),
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            cli_args.build_args.spec_constants,
+            [("0".to_owned(), "64".to_owned())]
+        );
+    }
+
+    #[test_log::test]
+    fn hooks_and_env_from_cargo() {
+        let shader_crate_path = crate::test::shader_crate_test_path();
+        let mut file = crate::test::overwrite_shader_cargo_toml(&shader_crate_path);
+        file.write_all(
+            [
+                "[package.metadata.rust-gpu.build]",
+                "pre-build = [\"./generate.sh\"]",
+                "post-build = [\"./upload.sh {manifest}\"]",
+                "env = { LEVEL_COUNT = 12, LEVEL_NAME = \"intro\" }",
+            ]
+            .join("\n")
+            .as_bytes(),
+        )
+        .unwrap();
+
+        let args = Config::clap_command_with_cargo_config(&shader_crate_path, vec![]).unwrap();
+        assert_eq!(args.build_args.pre_build, ["./generate.sh"]);
+        assert_eq!(args.build_args.post_build, ["./upload.sh {manifest}"]);
+        assert_eq!(
+            args.build_args.env,
+            [
+                ("LEVEL_COUNT".to_owned(), "12".to_owned()),
+                ("LEVEL_NAME".to_owned(), "intro".to_owned()),
+            ]
+        );
+    }
+
+    #[test_log::test]
+    fn entry_point_renames_from_cargo() {
+        let shader_crate_path = crate::test::shader_crate_test_path();
+        let mut file = crate::test::overwrite_shader_cargo_toml(&shader_crate_path);
+        file.write_all(
+            [
+                "[package.metadata.rust-gpu.build]",
+                "entry-point-renames = { \"shaders::main_fs\" = \"fragment_main\" }",
+            ]
+            .join("\n")
+            .as_bytes(),
+        )
+        .unwrap();
+
+        let args = Config::clap_command_with_cargo_config(&shader_crate_path, vec![]).unwrap();
+        assert_eq!(
+            args.build_args.entry_point_renames,
+            [("shaders::main_fs".to_owned(), "fragment_main".to_owned())]
+        );
+    }
+
     #[test_log::test]
     fn rename_manifest_parse() {
         let shader_crate_path = crate::test::shader_crate_test_path();
@@ -258,4 +549,73 @@ mod test {
         .unwrap();
         assert_eq!(args.build_args.manifest_file, "mymanifest".to_owned());
     }
+
+    #[test_log::test]
+    fn provenance_of_each_value() {
+        let defaults = Config::defaults_as_json().unwrap();
+        let layers = [
+            (
+                Source::Global,
+                serde_json::json!({ "build": { "debug": true, "multimodule": true } }),
+            ),
+            (
+                Source::Crate,
+                serde_json::json!({ "build": { "multimodule": true, "manifest_file": "a.json" } }),
+            ),
+            (
+                Source::Cli,
+                Config::cli_args_to_json(vec![
+                    "gpu".to_owned(),
+                    "--manifest-file".to_owned(),
+                    "b.json".to_owned(),
+                ])
+                .unwrap(),
+            ),
+        ];
+        let values = Config::provenance(&defaults, &layers).unwrap();
+        let find = |key: &str| {
+            values
+                .iter()
+                .find(|(value_key, _, _)| value_key == key)
+                .map(|(_, value, source)| (value.clone(), *source))
+                .unwrap()
+        };
+        assert_eq!(
+            find("build.debug"),
+            (serde_json::json!(true), Source::Global)
+        );
+        assert_eq!(
+            find("build.multimodule"),
+            (serde_json::json!(true), Source::Crate)
+        );
+        assert_eq!(
+            find("build.manifest-file"),
+            (serde_json::json!("b.json"), Source::Cli)
+        );
+        assert_eq!(
+            find("install.auto-install-rust-toolchain"),
+            (serde_json::json!(false), Source::Default)
+        );
+    }
+
+    #[test_log::test]
+    fn shader_targets_from_cargo() {
+        let shader_crate_path = crate::test::shader_crate_test_path();
+        let mut file = crate::test::overwrite_shader_cargo_toml(&shader_crate_path);
+        file.write_all(
+            [
+                "[package.metadata.rust-gpu.build]",
+                "shader-target = [\"spirv-unknown-vulkan1.2\", \"spirv-unknown-spv1.5\"]",
+            ]
+            .join("\n")
+            .as_bytes(),
+        )
+        .unwrap();
+
+        let args = Config::clap_command_with_cargo_config(&shader_crate_path, vec![]).unwrap();
+        assert_eq!(
+            args.build_args.shader_target,
+            ["spirv-unknown-vulkan1.2", "spirv-unknown-spv1.5"]
+        );
+    }
 }
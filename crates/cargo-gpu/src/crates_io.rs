@@ -0,0 +1,174 @@
+//! Find out about a `rust-gpu` release on crates.io, without cloning the `rust-gpu` repo.
+//!
+//! The release date comes from the crates.io API. The Rust toolchain comes from the published
+//! `rustc_codegen_spirv` crate, whose build script has a copy of `rust-gpu`'s
+//! `rust-toolchain.toml`. Both are saved in the release's checkout dir, so that they're only
+//! downloaded once, and so that the checkout can be read just like a cloned repo.
+
+use std::io::Read as _;
+
+use anyhow::Context as _;
+
+/// The crates.io API.
+pub const API_URL: &str = "https://crates.io/api/v1/crates";
+
+/// Where crates.io serves the `.crate` files of published crates.
+const DOWNLOAD_URL: &str = "https://static.crates.io/crates";
+
+/// The crate whose package contains the release's Rust toolchain.
+const CODEGEN_CRATE: &str = "rustc_codegen_spirv";
+
+/// crates.io asks that API clients identify themselves.
+const USER_AGENT: &str = concat!(
+    "cargo-gpu/",
+    env!("CARGO_PKG_VERSION"),
+    " (https://github.com/Rust-GPU/cargo-gpu)"
+);
+
+/// The file in the checkout dir that the release date is saved to.
+const RELEASE_DATE_FILE: &str = "release-date";
+
+/// `.crate` files of `rustc_codegen_spirv` are a few MB at most.
+const MAX_DOWNLOAD_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Make sure that `dir` has the `rust-toolchain.toml` of the `rust-gpu` release `version`, and
+/// return the release's date.
+///
+/// # Errors
+/// If crates.io can't be reached, or the release doesn't have what's needed.
+pub fn fetch_release(version: &str, dir: &std::path::Path) -> anyhow::Result<chrono::NaiveDate> {
+    std::fs::create_dir_all(dir)?;
+
+    let toolchain_path = dir.join("rust-toolchain.toml");
+    let date_path = dir.join(RELEASE_DATE_FILE);
+    let saved_date = std::fs::read_to_string(&date_path)
+        .ok()
+        .and_then(|saved| saved.trim().parse().ok());
+    if !toolchain_path.exists() || saved_date.is_none() {
+        crate::offline::ensure_nothing_missing(&[format!(
+            "the toolchain and date of `rust-gpu` {version} from crates.io"
+        )])?;
+    }
+
+    if !toolchain_path.exists() {
+        crate::user_output!("Fetching `rust-gpu` {version} from crates.io...\n");
+        let toolchain = crate::timings::time("fetch rust-gpu toolchain", version, || {
+            fetch_toolchain(version)
+        })?;
+        std::fs::write(&toolchain_path, toolchain)?;
+    }
+
+    if let Some(date) = saved_date {
+        return Ok(date);
+    }
+    let url = format!("{API_URL}/spirv-std/{version}");
+    let response = String::from_utf8(download(&url)?)?;
+    let date = release_date_from_api(&response)
+        .with_context(|| format!("couldn't find the date of `rust-gpu` {version} in {url}"))?;
+    std::fs::write(&date_path, date.to_string())?;
+    Ok(date)
+}
+
+/// Download the release's `rustc_codegen_spirv` crate and find its `rust-toolchain.toml`.
+fn fetch_toolchain(version: &str) -> anyhow::Result<String> {
+    let url = format!("{DOWNLOAD_URL}/{CODEGEN_CRATE}/{CODEGEN_CRATE}-{version}.crate");
+    let bytes = download(&url)?;
+    let root = std::path::PathBuf::from(format!("{CODEGEN_CRATE}-{version}"));
+
+    let mut build_script = None;
+    let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(bytes.as_slice()));
+    for maybe_entry in archive.entries()? {
+        let mut entry = maybe_entry?;
+        let path = entry.path()?.into_owned();
+        let Ok(relative) = path.strip_prefix(&root) else {
+            continue;
+        };
+        if relative == std::path::Path::new("rust-toolchain.toml") {
+            let mut contents = String::new();
+            entry.read_to_string(&mut contents)?;
+            return Ok(contents);
+        }
+        if relative == std::path::Path::new("build.rs") {
+            let mut contents = String::new();
+            entry.read_to_string(&mut contents)?;
+            build_script = Some(contents);
+        }
+    }
+
+    build_script
+        .as_deref()
+        .and_then(toolchain_from_build_script)
+        .with_context(|| format!("couldn't find a Rust toolchain in {url}"))
+}
+
+/// `GET` the contents of a URL.
+///
+/// # Errors
+/// If the URL can't be downloaded.
+pub fn download(url: &str) -> anyhow::Result<Vec<u8>> {
+    log::debug!("downloading {url}");
+    let mut response = ureq::get(url)
+        .header("User-Agent", USER_AGENT)
+        .call()
+        .with_context(|| format!("could not download {url}"))?;
+    let bytes = response
+        .body_mut()
+        .with_config()
+        .limit(MAX_DOWNLOAD_SIZE)
+        .read_to_vec()
+        .with_context(|| format!("could not read response body from {url}"))?;
+    Ok(bytes)
+}
+
+/// The `rust-toolchain.toml` embedded in `rustc_codegen_spirv`'s build script, eg:
+///
+///   `const REQUIRED_RUST_TOOLCHAIN: &str = r#"[toolchain] channel = "nightly-2023-05-27" ..."#;`.
+fn toolchain_from_build_script(source: &str) -> Option<String> {
+    let (_, after_name) = source.split_once("REQUIRED_RUST_TOOLCHAIN")?;
+    let (_, raw_string) = after_name.split_once("r#\"")?;
+    let (toolchain, _) = raw_string.split_once("\"#")?;
+    Some(toolchain.trim_start().to_owned())
+}
+
+/// The date of a release, from the crates.io API's response about a version of a crate.
+fn release_date_from_api(response: &str) -> Option<chrono::NaiveDate> {
+    let json: serde_json::Value = serde_json::from_str(response).ok()?;
+    let created_at = json.pointer("/version/created_at")?.as_str()?;
+    Some(
+        chrono::DateTime::parse_from_rfc3339(created_at)
+            .ok()?
+            .date_naive(),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_log::test]
+    fn finding_the_toolchain_in_a_build_script() {
+        let build_script = [
+            "/// Current `rust-toolchain.toml` file",
+            "//const REQUIRED_RUST_TOOLCHAIN: &str = include_str!(\"../../rust-toolchain.toml\");",
+            "const REQUIRED_RUST_TOOLCHAIN: &str = r#\"[toolchain]",
+            "channel = \"nightly-2023-05-27\"",
+            "components = [\"rust-src\", \"rustc-dev\", \"llvm-tools\"]",
+            "\"#;",
+        ]
+        .join("\n");
+        let toolchain = toolchain_from_build_script(&build_script).unwrap();
+        assert!(toolchain.starts_with("[toolchain]\nchannel = \"nightly-2023-05-27\""));
+        assert!(toolchain_from_build_script("fn main() {}").is_none());
+    }
+
+    #[test_log::test]
+    fn parsing_the_release_date() {
+        let response =
+            r#"{"version":{"num":"0.9.0","created_at":"2023-08-04T17:53:14.139046+00:00"}}"#;
+        assert_eq!(
+            release_date_from_api(response),
+            chrono::NaiveDate::from_ymd_opt(2023, 8, 4)
+        );
+        assert!(release_date_from_api(r#"{"errors":[]}"#).is_none());
+    }
+}
@@ -0,0 +1,134 @@
+//! `--debug-info`, keep the Rust source files and lines in the compiled modules, so that
+//! `RenderDoc`, Nsight and the like can show the Rust source of a captured shader.
+//!
+//! That's `spirv-builder`'s full SPIR-V metadata, its `OpSource`, `OpString` and `OpLine`
+//! instructions, which can make up most of a module, so each module's size gets a warning.
+
+use anyhow::Context as _;
+use rspirv::binary::Assemble as _;
+use spirv_builder_cli::args::{BuildArgs, SpirvMetadata};
+
+/// Have `spirv-builder` emit the source debug info, and check that nothing else in `build_args`
+/// would strip it again.
+pub fn prepare(build_args: &mut BuildArgs) -> anyhow::Result<()> {
+    if !build_args.debug_info {
+        return Ok(());
+    }
+    anyhow::ensure!(
+        !build_args.reproducible,
+        "`--debug-info` can't be used with `--reproducible`, which strips debug info"
+    );
+    anyhow::ensure!(
+        !build_args.strip,
+        "`--debug-info` can't be used with `--strip`, which strips debug info"
+    );
+    anyhow::ensure!(
+        !build_args
+            .opt_pass
+            .iter()
+            .any(|pass| pass.trim_start_matches("--") == "strip-debug"),
+        "`--debug-info` can't be used with `--opt-pass strip-debug`"
+    );
+    build_args.spirv_metadata = SpirvMetadata::Full;
+    Ok(())
+}
+
+/// Warn about the size of the module at `path`, and how much of it is debug info.
+pub fn warn_about_size(path: &std::path::Path) -> anyhow::Result<()> {
+    let bytes =
+        std::fs::read(path).with_context(|| format!("could not read '{}'", path.display()))?;
+    let size = u64::try_from(bytes.len())?;
+    let module = rspirv::dr::load_bytes(bytes)
+        .map_err(|error| anyhow::anyhow!("could not parse '{}': {error}", path.display()))?;
+    let debug_size = debug_info_size(&module);
+    crate::user_output!(
+        "warning: '{}' is {}, {} of which is debug info from `--debug-info`, don't ship it\n",
+        path.display(),
+        crate::format_size(size),
+        crate::format_size(debug_size)
+    );
+    Ok(())
+}
+
+/// The size in bytes of a module's source debug instructions.
+fn debug_info_size(module: &rspirv::dr::Module) -> u64 {
+    let is_debug_info = |instruction: &&rspirv::dr::Instruction| {
+        matches!(
+            instruction.class.opcode,
+            rspirv::spirv::Op::Source
+                | rspirv::spirv::Op::SourceContinued
+                | rspirv::spirv::Op::SourceExtension
+                | rspirv::spirv::Op::String
+                | rspirv::spirv::Op::Line
+                | rspirv::spirv::Op::NoLine
+                | rspirv::spirv::Op::ModuleProcessed
+        )
+    };
+    let function_instructions = module
+        .functions
+        .iter()
+        .flat_map(|function| &function.blocks)
+        .flat_map(|block| &block.instructions);
+    let words: usize = module
+        .debug_string_source
+        .iter()
+        .chain(&module.debug_module_processed)
+        .chain(&module.types_global_values)
+        .chain(function_instructions)
+        .filter(is_debug_info)
+        .map(|instruction| instruction.assemble().len())
+        .sum();
+    u64::try_from(words).unwrap_or(u64::MAX).saturating_mul(4)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use clap::Parser as _;
+
+    #[test_log::test]
+    fn measuring_debug_info() {
+        let mut builder = rspirv::dr::Builder::new();
+        let file = builder.string("src/lib.rs");
+        builder.source(
+            rspirv::spirv::SourceLanguage::Unknown,
+            0,
+            Some(file),
+            None::<String>,
+        );
+        let void = builder.type_void();
+        let function_type = builder.type_function(void, vec![]);
+        builder
+            .begin_function(
+                void,
+                None,
+                rspirv::spirv::FunctionControl::NONE,
+                function_type,
+            )
+            .unwrap();
+        builder.begin_block(None).unwrap();
+        builder.line(file, 12, 4);
+        builder.ret().unwrap();
+        builder.end_function().unwrap();
+        let module = builder.module();
+
+        // `OpString` with its 3 words of string is 5 words, `OpSource` with a file is 4 and
+        // `OpLine` is 4.
+        assert_eq!(debug_info_size(&module), 13 * 4);
+    }
+
+    #[test_log::test]
+    fn debug_info_conflicts_with_stripping() {
+        let mut build_args = BuildArgs::parse_from(["", "--debug-info"]);
+        prepare(&mut build_args).unwrap();
+        assert!(matches!(build_args.spirv_metadata, SpirvMetadata::Full));
+
+        let mut stripping =
+            BuildArgs::parse_from(["", "--debug-info", "--opt-pass", "strip-debug"]);
+        prepare(&mut stripping).unwrap_err();
+        let mut reproducible = BuildArgs::parse_from(["", "--debug-info", "--reproducible"]);
+        prepare(&mut reproducible).unwrap_err();
+        let mut stripped = BuildArgs::parse_from(["", "--debug-info", "--strip"]);
+        prepare(&mut stripped).unwrap_err();
+    }
+}
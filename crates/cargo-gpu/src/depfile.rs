@@ -0,0 +1,201 @@
+//! `--emit-depfile`, a Makefile-style `.d` file listing every input of a shader build, so that
+//! `build.rs` scripts, Ninja and Bazel can declare exactly what should trigger a rebuild.
+//!
+//! The sources come from the dep-info that `cargo` writes next to the compiled shader crate,
+//! which combines `rustc`'s `--emit=dep-info` for the crate and its path dependencies. So it
+//! includes files pulled in with `include_str!` and the like, and leaves out anything in `src`
+//! that isn't compiled. The `Cargo.toml`s, `build.rs`s and `Cargo.lock` are added, as they're
+//! not in the dep-info but can change the build all the same.
+
+use anyhow::Context as _;
+
+/// Every file that the compiled `modules` were built from. `local_inputs` are the local
+/// packages and the `Cargo.lock`, from [`crate::metadata::Metadata::local_inputs`].
+pub fn inputs(
+    modules: &[&std::path::Path],
+    local_inputs: Vec<std::path::PathBuf>,
+) -> anyhow::Result<Vec<std::path::PathBuf>> {
+    let mut inputs = Vec::new();
+    for input in local_inputs {
+        if input.is_file() {
+            inputs.push(input);
+            continue;
+        }
+        for name in ["Cargo.toml", "build.rs"] {
+            let path = input.join(name);
+            if path.is_file() {
+                inputs.push(path);
+            }
+        }
+    }
+    let mut dep_infos = modules
+        .iter()
+        .map(|module| {
+            dep_info_path(module)
+                .with_context(|| format!("couldn't find the dep-info of '{}'", module.display()))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    dep_infos.sort();
+    dep_infos.dedup();
+    for dep_info in dep_infos {
+        let contents = std::fs::read_to_string(&dep_info)
+            .with_context(|| format!("could not read '{}'", dep_info.display()))?;
+        inputs.extend(parse_dep_info(&contents));
+    }
+    inputs.sort();
+    inputs.dedup();
+    Ok(inputs)
+}
+
+/// The dep-info that `cargo` wrote for the compiled `module`. A single module is the artifact
+/// itself, eg `release/shaders.spv`, with `release/shaders.d` next to it. Multiple modules are
+/// in a `.spv.dir` named after the crate, eg `release/deps/shaders-1a2b.spv.dir/main_fs.spv`.
+fn dep_info_path(module: &std::path::Path) -> Option<std::path::PathBuf> {
+    let single = module.with_extension("d");
+    if single.is_file() {
+        return Some(single);
+    }
+    let multimodule_dir = module
+        .ancestors()
+        .find(|ancestor| ancestor.to_string_lossy().ends_with(".spv.dir"))?;
+    let file_name = multimodule_dir
+        .file_name()?
+        .to_str()?
+        .strip_suffix(".spv.dir")?;
+    // `rustc`'s own outputs in `deps` have a hash after the crate's name.
+    let crate_name = file_name
+        .rsplit_once('-')
+        .map_or(file_name, |(name, _)| name);
+    let mut dir = multimodule_dir.parent()?;
+    if dir.file_name().is_some_and(|name| name == "deps") {
+        dir = dir.parent()?;
+    }
+    Some(dir.join(format!("{crate_name}.d"))).filter(|path| path.is_file())
+}
+
+/// The prerequisites of every rule in a Makefile-style dep-info file.
+fn parse_dep_info(contents: &str) -> Vec<std::path::PathBuf> {
+    let joined = contents.replace("\\\n", " ").replace("\\\r\n", " ");
+    let mut prerequisites = Vec::new();
+    for line in joined.lines() {
+        if line.starts_with('#') {
+            continue;
+        }
+        let Some((_, rest)) = line
+            .split_once(": ")
+            .or_else(|| line.strip_suffix(':').map(|target| (target, "")))
+        else {
+            continue;
+        };
+        let mut path = String::new();
+        let mut chars = rest.chars().peekable();
+        while let Some(character) = chars.next() {
+            match character {
+                '\\' if chars.peek() == Some(&' ') => {
+                    path.push(' ');
+                    chars.next();
+                }
+                ' ' => {
+                    if !path.is_empty() {
+                        prerequisites.push(std::path::PathBuf::from(core::mem::take(&mut path)));
+                    }
+                }
+                _ => path.push(character),
+            }
+        }
+        if !path.is_empty() {
+            prerequisites.push(std::path::PathBuf::from(path));
+        }
+    }
+    prerequisites
+}
+
+/// Write a depfile at `path`, declaring that `target` depends on every one of `inputs`.
+pub fn write(
+    path: &std::path::Path,
+    target: &std::path::Path,
+    inputs: &[std::path::PathBuf],
+) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    crate::atomic::write(path, render(target, inputs))
+        .with_context(|| format!("could not write depfile '{}'", path.display()))?;
+    log::info!("wrote depfile to '{}'", path.display());
+    Ok(())
+}
+
+/// The contents of a depfile, with one input per line.
+fn render(target: &std::path::Path, inputs: &[std::path::PathBuf]) -> String {
+    let mut lines = vec![format!("{}:", escape(target))];
+    lines.extend(inputs.iter().map(|input| format!("  {}", escape(input))));
+    format!("{}\n", lines.join(" \\\n"))
+}
+
+/// Escape a path for a Makefile rule.
+fn escape(path: &std::path::Path) -> String {
+    path.to_string_lossy()
+        .replace('\\', "/")
+        .replace('$', "$$")
+        .replace('#', "\\#")
+        .replace(' ', "\\ ")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_log::test]
+    fn reading_dep_info() {
+        let dep_info = "/target/release/shaders.spv: /shaders/src/lib.rs \\\n  \
+                        /my\\ shaders/src/noise.wgsl /shared/src/lib.rs\n\
+                        \n\
+                        # env-dep:SHADER_DEFINES\n\
+                        /shaders/src/lib.rs:\n";
+        assert_eq!(
+            parse_dep_info(dep_info),
+            [
+                "/shaders/src/lib.rs",
+                "/my shaders/src/noise.wgsl",
+                "/shared/src/lib.rs",
+            ]
+            .map(std::path::PathBuf::from)
+        );
+    }
+
+    #[test_log::test]
+    fn finding_the_dep_info_of_modules() {
+        let target = crate::cache_dir().unwrap().join("dep-info");
+        let release = target.join("release");
+        let multimodule = release.join("deps").join("shaders-1a2b.spv.dir");
+        std::fs::create_dir_all(&multimodule).unwrap();
+        let dep_info = release.join("shaders.d");
+        std::fs::write(&dep_info, "").unwrap();
+
+        assert_eq!(
+            dep_info_path(&release.join("shaders.spv")),
+            Some(dep_info.clone())
+        );
+        assert_eq!(
+            dep_info_path(&multimodule.join("main_fs.spv")),
+            Some(dep_info)
+        );
+        assert_eq!(dep_info_path(&release.join("other.spv")), None);
+        crate::test::tests_teardown();
+    }
+
+    #[test_log::test]
+    fn rendering_a_depfile() {
+        let depfile = render(
+            std::path::Path::new("/out/manifest.json"),
+            &[
+                std::path::PathBuf::from("/shaders/Cargo.toml"),
+                std::path::PathBuf::from("/my shaders/src/lib.rs"),
+            ],
+        );
+        assert_eq!(
+            depfile,
+            "/out/manifest.json: \\\n  /shaders/Cargo.toml \\\n  /my\\ shaders/src/lib.rs\n"
+        );
+    }
+}
@@ -0,0 +1,570 @@
+//! `cargo gpu diff`, compare the entry points and modules of two builds, eg for reviewing shader
+//! changes in PRs.
+//!
+//! Builds keep the manifest and modules of the build before them in the output dir, so a build
+//! can also be compared with the one before it.
+
+use anyhow::Context as _;
+use rspirv::binary::Disassemble as _;
+use spirv_builder_cli::{args::ManifestPaths, Linkage, Manifest, ModuleStats};
+
+/// The dir in the output dir that the build before the current one is kept in.
+pub const PREVIOUS_DIR: &str = ".cargo-gpu-previous";
+
+/// The manifest's name when a build is given as its output dir, and in [`PREVIOUS_DIR`].
+const MANIFEST_FILE: &str = "manifest.json";
+
+/// The most lines of disassembly to compare at once, beyond which the whole of both modules is
+/// shown as changed rather than using a lot of memory to find their differences.
+const MAX_DIFF_CELLS: usize = 16 * 1024 * 1024;
+
+/// `cargo gpu diff`.
+#[derive(clap::Parser, Debug)]
+pub struct Diff {
+    /// The old and the new build, or just the new build to compare it with the build before it.
+    /// Each is a manifest written by `cargo gpu build`, or the output dir that it's in.
+    #[clap(required = true, num_args = 1..=2)]
+    builds: Vec<std::path::PathBuf>,
+
+    /// Directory containing the shader crate. Paths in a manifest are relative to it.
+    #[clap(long, default_value = "./")]
+    shader_crate: std::path::PathBuf,
+
+    /// Also show how the disassembly of each changed module changed.
+    #[clap(long)]
+    disassembly: bool,
+}
+
+/// An entry point in a build.
+#[derive(Debug, Clone)]
+struct EntryPoint {
+    /// Path to the entry point's module.
+    module: std::path::PathBuf,
+    /// The SHA-256 of the module, if the manifest has it.
+    sha256: Option<String>,
+    /// Statistics about the module, if it could be read or the manifest has them.
+    stats: Option<ModuleStats>,
+}
+
+/// A build's entry points, by their name, prefixed with their variant if they have one.
+type EntryPoints = std::collections::BTreeMap<String, EntryPoint>;
+
+impl Diff {
+    /// Entrypoint.
+    pub fn run(&self) -> anyhow::Result<()> {
+        let (old_path, new_path) = match self.builds.as_slice() {
+            [new] => {
+                let manifest_path = manifest_path(new);
+                let previous = manifest_path
+                    .parent()
+                    .unwrap_or_else(|| std::path::Path::new("./"))
+                    .join(PREVIOUS_DIR)
+                    .join(MANIFEST_FILE);
+                anyhow::ensure!(
+                    previous.is_file(),
+                    "there's no build before '{}' to compare it with",
+                    manifest_path.display()
+                );
+                (previous, manifest_path)
+            }
+            [old, new] => (manifest_path(old), manifest_path(new)),
+            _ => anyhow::bail!("expected one or two builds to compare"),
+        };
+        let old = entry_points(&old_path, &self.shader_crate)?;
+        let new = entry_points(&new_path, &self.shader_crate)?;
+
+        let changes = describe(&old, &new);
+        #[expect(
+            clippy::print_stdout,
+            reason = "The diff is meant to be piped, so no crab prefix"
+        )]
+        {
+            println!("--- {}", old_path.display());
+            println!("+++ {}", new_path.display());
+            if changes.is_empty() {
+                println!("No changes");
+            }
+            for change in &changes {
+                println!("{change}");
+            }
+        }
+
+        if self.disassembly {
+            let mut shown = std::collections::HashSet::new();
+            for (name, new_entry) in &new {
+                let Some(old_entry) = old.get(name) else {
+                    continue;
+                };
+                if is_changed(old_entry, new_entry)
+                    && shown.insert((&old_entry.module, &new_entry.module))
+                {
+                    print_disassembly_diff(&old_entry.module, &new_entry.module)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Keep the build at `manifest_path` in [`PREVIOUS_DIR`] next to it, before it's replaced.
+///
+/// Its manifest and modules are copied. Builds that can't be read are skipped, as they couldn't
+/// be compared with anyway.
+pub fn keep_previous(
+    manifest_path: &std::path::Path,
+    shader_crate: &std::path::Path,
+) -> anyhow::Result<()> {
+    if !manifest_path.is_file() {
+        return Ok(());
+    }
+    let manifest = match read_manifest(manifest_path) {
+        Ok(manifest) => manifest,
+        Err(error) => {
+            log::debug!("not keeping the previous build: {error:?}");
+            return Ok(());
+        }
+    };
+    let manifest_dir = manifest_path
+        .parent()
+        .unwrap_or_else(|| std::path::Path::new("./"));
+    let previous_dir = manifest_dir.join(PREVIOUS_DIR);
+    if previous_dir.exists() {
+        std::fs::remove_dir_all(&previous_dir).with_context(|| {
+            format!(
+                "could not remove the previous build '{}'",
+                previous_dir.display()
+            )
+        })?;
+    }
+    std::fs::create_dir_all(&previous_dir)?;
+
+    // Modules are kept by their file name, unless another module already has it.
+    let mut kept: std::collections::HashMap<std::path::PathBuf, String> =
+        std::collections::HashMap::new();
+    let mut entry_points = Vec::new();
+    for entry in &manifest.entry_points {
+        let module = manifest.resolve_path(&entry.source_path, shader_crate, manifest_dir);
+        if !module.is_file() {
+            log::debug!(
+                "not keeping '{}' of the previous build, it's gone",
+                module.display()
+            );
+            continue;
+        }
+        let name = if let Some(name) = kept.get(&module) {
+            name.clone()
+        } else {
+            let file_name = module
+                .file_name()
+                .context("module has no file name")?
+                .to_string_lossy()
+                .into_owned();
+            let name = if kept.values().any(|other| *other == file_name) {
+                format!("{}-{file_name}", kept.len())
+            } else {
+                file_name
+            };
+            crate::atomic::copy(&module, &previous_dir.join(&name))?;
+            kept.insert(module, name.clone());
+            name
+        };
+        entry_points.push(Linkage {
+            source_path: name,
+            ..entry.clone()
+        });
+    }
+
+    let previous = Manifest {
+        paths: ManifestPaths::RelativeToManifest,
+        ..Manifest::new(entry_points)
+    };
+    crate::atomic::write(
+        &previous_dir.join(MANIFEST_FILE),
+        serde_json::to_string_pretty(&previous)?,
+    )?;
+    log::debug!("kept the previous build in '{}'", previous_dir.display());
+    Ok(())
+}
+
+/// The manifest of the build at `path`, which is either the manifest or the dir that it's in.
+#[must_use]
+pub fn manifest_path(path: &std::path::Path) -> std::path::PathBuf {
+    if path.is_dir() {
+        path.join(MANIFEST_FILE)
+    } else {
+        path.to_path_buf()
+    }
+}
+
+/// Read the JSON manifest at `path`.
+pub fn read_manifest(path: &std::path::Path) -> anyhow::Result<Manifest> {
+    Manifest::from_json(
+        &std::fs::read_to_string(path)
+            .with_context(|| format!("could not open manifest '{}'", path.display()))?,
+    )
+    .with_context(|| format!("could not parse manifest '{}'", path.display()))
+}
+
+/// The entry points of the build with the manifest at `path`.
+fn entry_points(
+    path: &std::path::Path,
+    shader_crate: &std::path::Path,
+) -> anyhow::Result<EntryPoints> {
+    let manifest = read_manifest(path)?;
+    let manifest_dir = path.parent().unwrap_or_else(|| std::path::Path::new("./"));
+    let mut stats_by_module = std::collections::HashMap::new();
+    let mut entry_points = EntryPoints::new();
+    for entry in &manifest.entry_points {
+        let module = manifest.resolve_path(&entry.source_path, shader_crate, manifest_dir);
+        let stats = stats_by_module
+            .entry(module.clone())
+            .or_insert_with(|| {
+                // Compressed modules can't be read, but the manifest may have their stats.
+                crate::stats::of_module(&module, core::time::Duration::ZERO)
+                    .inspect_err(|error| log::debug!("couldn't read the module's stats: {error:?}"))
+                    .ok()
+                    .or_else(|| entry.stats.clone())
+            })
+            .clone();
+        let name = entry.variant.as_ref().map_or_else(
+            || entry.entry_point.clone(),
+            |variant| format!("{variant}::{}", entry.entry_point),
+        );
+        entry_points.insert(
+            name,
+            EntryPoint {
+                module,
+                sha256: entry.sha256.clone(),
+                stats,
+            },
+        );
+    }
+    Ok(entry_points)
+}
+
+/// Whether an entry point's module changed between the builds.
+fn is_changed(old: &EntryPoint, new: &EntryPoint) -> bool {
+    if let (Some(old_sha256), Some(new_sha256)) = (&old.sha256, &new.sha256) {
+        return old_sha256 != new_sha256;
+    }
+    match (&old.stats, &new.stats) {
+        (Some(old_stats), Some(new_stats)) => {
+            old_stats.size != new_stats.size
+                || old_stats.instruction_count != new_stats.instruction_count
+                || old_stats.capabilities != new_stats.capabilities
+        }
+        _ => true,
+    }
+}
+
+/// A line for each entry point that was added, removed or changed between the builds.
+fn describe(old: &EntryPoints, new: &EntryPoints) -> Vec<String> {
+    let mut changes = Vec::new();
+    for (name, old_entry) in old {
+        if !new.contains_key(name) {
+            changes.push(format!("- {name} ({})", old_entry.module.display()));
+        }
+    }
+    for (name, new_entry) in new {
+        let Some(old_entry) = old.get(name) else {
+            changes.push(format!("+ {name} ({})", new_entry.module.display()));
+            continue;
+        };
+        if !is_changed(old_entry, new_entry) {
+            continue;
+        }
+        let (Some(old_stats), Some(new_stats)) = (&old_entry.stats, &new_entry.stats) else {
+            changes.push(format!("~ {name}: module changed"));
+            continue;
+        };
+        let mut details = vec![
+            format!(
+                "{} -> {} ({})",
+                crate::format_size(old_stats.size),
+                crate::format_size(new_stats.size),
+                size_delta(old_stats.size, new_stats.size)
+            ),
+            format!(
+                "{} -> {} instructions ({:+})",
+                old_stats.instruction_count,
+                new_stats.instruction_count,
+                i128::from(new_stats.instruction_count) - i128::from(old_stats.instruction_count)
+            ),
+        ];
+        for capability in &new_stats.capabilities {
+            if !old_stats.capabilities.contains(capability) {
+                details.push(format!("+{capability}"));
+            }
+        }
+        for capability in &old_stats.capabilities {
+            if !new_stats.capabilities.contains(capability) {
+                details.push(format!("-{capability}"));
+            }
+        }
+        changes.push(format!("~ {name}: {}", details.join(", ")));
+    }
+    changes
+}
+
+/// How much bigger or smaller a module got, eg "+1.0 KiB".
+fn size_delta(old: u64, new: u64) -> String {
+    if new >= old {
+        format!("+{}", crate::format_size(new - old))
+    } else {
+        format!("-{}", crate::format_size(old - new))
+    }
+}
+
+/// Print the lines of disassembly that differ between two modules.
+fn print_disassembly_diff(old: &std::path::Path, new: &std::path::Path) -> anyhow::Result<()> {
+    let old_text = disassemble(old)?;
+    let new_text = disassemble(new)?;
+    let old_lines = old_text.lines().collect::<Vec<_>>();
+    let new_lines = new_text.lines().collect::<Vec<_>>();
+
+    #[expect(
+        clippy::print_stdout,
+        reason = "The diff is meant to be piped, so no crab prefix"
+    )]
+    {
+        println!("\n--- {}\n+++ {}", old.display(), new.display());
+        let mut is_in_hunk = false;
+        for line in diff_lines(&old_lines, &new_lines) {
+            match line {
+                Line::Same(_) => is_in_hunk = false,
+                Line::Removed(text) | Line::Added(text) => {
+                    if !is_in_hunk {
+                        println!("@@");
+                        is_in_hunk = true;
+                    }
+                    let sign = if matches!(line, Line::Removed(_)) {
+                        '-'
+                    } else {
+                        '+'
+                    };
+                    println!("{sign}{text}");
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The disassembly of the module at `path`.
+fn disassemble(path: &std::path::Path) -> anyhow::Result<String> {
+    let bytes =
+        std::fs::read(path).with_context(|| format!("could not read '{}'", path.display()))?;
+    let module = rspirv::dr::load_bytes(bytes)
+        .map_err(|error| anyhow::anyhow!("could not parse '{}': {error}", path.display()))?;
+    Ok(module.disassemble())
+}
+
+/// A line of a diff.
+#[derive(Debug, PartialEq, Eq)]
+enum Line<'text> {
+    /// A line in both texts.
+    Same(&'text str),
+    /// A line only in the old text.
+    Removed(&'text str),
+    /// A line only in the new text.
+    Added(&'text str),
+}
+
+/// The lines of `old` and `new`, marked with which of them they're in, using their longest
+/// common subsequence.
+fn diff_lines<'text>(old: &[&'text str], new: &[&'text str]) -> Vec<Line<'text>> {
+    let prefix = old
+        .iter()
+        .zip(new)
+        .take_while(|(old_line, new_line)| old_line == new_line)
+        .count();
+    let (old_rest, new_rest) = (
+        old.get(prefix..).unwrap_or_default(),
+        new.get(prefix..).unwrap_or_default(),
+    );
+    let suffix = old_rest
+        .iter()
+        .rev()
+        .zip(new_rest.iter().rev())
+        .take_while(|(old_line, new_line)| old_line == new_line)
+        .count();
+    let old_middle = old_rest.get(..old_rest.len() - suffix).unwrap_or_default();
+    let new_middle = new_rest.get(..new_rest.len() - suffix).unwrap_or_default();
+
+    let mut lines = old
+        .iter()
+        .take(prefix)
+        .map(|line| Line::Same(line))
+        .collect::<Vec<_>>();
+    if old_middle.len().saturating_mul(new_middle.len()) > MAX_DIFF_CELLS {
+        lines.extend(old_middle.iter().map(|line| Line::Removed(line)));
+        lines.extend(new_middle.iter().map(|line| Line::Added(line)));
+    } else {
+        lines.extend(longest_common_subsequence(old_middle, new_middle));
+    }
+    lines.extend(
+        old_rest
+            .iter()
+            .skip(old_middle.len())
+            .map(|line| Line::Same(line)),
+    );
+    lines
+}
+
+/// Diff two texts' lines by finding their longest common subsequence.
+fn longest_common_subsequence<'text>(old: &[&'text str], new: &[&'text str]) -> Vec<Line<'text>> {
+    // `lengths[i][j]` is the length of the longest common subsequence of `old[i..]` and
+    // `new[j..]`.
+    let mut lengths: Vec<Vec<usize>> = vec![vec![0; new.len() + 1]; old.len() + 1];
+    for (old_index, old_line) in old.iter().enumerate().rev() {
+        for (new_index, new_line) in new.iter().enumerate().rev() {
+            let longest = if old_line == new_line {
+                cell(&lengths, old_index + 1, new_index + 1) + 1
+            } else {
+                cell(&lengths, old_index + 1, new_index).max(cell(
+                    &lengths,
+                    old_index,
+                    new_index + 1,
+                ))
+            };
+            if let Some(length) = lengths
+                .get_mut(old_index)
+                .and_then(|lengths_row| lengths_row.get_mut(new_index))
+            {
+                *length = longest;
+            }
+        }
+    }
+    let length = |row: usize, column: usize| cell(&lengths, row, column);
+
+    let mut lines = Vec::new();
+    let (mut old_index, mut new_index) = (0, 0);
+    loop {
+        match (old.get(old_index), new.get(new_index)) {
+            (Some(old_line), Some(new_line)) if old_line == new_line => {
+                lines.push(Line::Same(old_line));
+                old_index += 1;
+                new_index += 1;
+            }
+            (Some(old_line), Some(_))
+                if length(old_index + 1, new_index) >= length(old_index, new_index + 1) =>
+            {
+                lines.push(Line::Removed(old_line));
+                old_index += 1;
+            }
+            (Some(old_line), None) => {
+                lines.push(Line::Removed(old_line));
+                old_index += 1;
+            }
+            (_, Some(new_line)) => {
+                lines.push(Line::Added(new_line));
+                new_index += 1;
+            }
+            (None, None) => break,
+        }
+    }
+    lines
+}
+
+/// `lengths[row][column]`, or 0 past the end of `lengths`.
+fn cell(lengths: &[Vec<usize>], row: usize, column: usize) -> usize {
+    lengths
+        .get(row)
+        .and_then(|lengths_row| lengths_row.get(column))
+        .copied()
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// An entry point in the module at `module` with the given size, instruction count and
+    /// capabilities.
+    fn entry_point(
+        module: &str,
+        size: u64,
+        instruction_count: u64,
+        capabilities: &[&str],
+    ) -> EntryPoint {
+        EntryPoint {
+            module: std::path::PathBuf::from(module),
+            sha256: None,
+            stats: Some(ModuleStats {
+                size,
+                instruction_count,
+                capabilities: capabilities.iter().map(ToString::to_string).collect(),
+                compile_ms: 0,
+            }),
+        }
+    }
+
+    #[test_log::test]
+    fn describing_changed_entry_points() {
+        let old = EntryPoints::from([
+            (
+                "main_fs".to_owned(),
+                entry_point("main_fs.spv", 2048, 100, &["Shader"]),
+            ),
+            (
+                "main_vs".to_owned(),
+                entry_point("main_vs.spv", 1024, 50, &["Shader"]),
+            ),
+            (
+                "old_cs".to_owned(),
+                entry_point("old_cs.spv", 1024, 50, &["Shader"]),
+            ),
+        ]);
+        let new = EntryPoints::from([
+            (
+                "main_fs".to_owned(),
+                entry_point("main_fs.spv", 3072, 120, &["Shader", "Int64"]),
+            ),
+            (
+                "main_vs".to_owned(),
+                entry_point("main_vs.spv", 1024, 50, &["Shader"]),
+            ),
+            (
+                "new_cs".to_owned(),
+                entry_point("new_cs.spv", 512, 20, &["Shader"]),
+            ),
+        ]);
+        assert_eq!(
+            describe(&old, &new),
+            [
+                "- old_cs (old_cs.spv)",
+                "~ main_fs: 2.0 KiB -> 3.0 KiB (+1.0 KiB), 100 -> 120 instructions (+20), +Int64",
+                "+ new_cs (new_cs.spv)",
+            ]
+        );
+        assert!(describe(&new, &new).is_empty());
+    }
+
+    #[test_log::test]
+    fn diffing_lines() {
+        let old = [
+            "OpCapability Shader",
+            "%1 = OpTypeVoid",
+            "OpReturn",
+            "OpFunctionEnd",
+        ];
+        let new = [
+            "OpCapability Shader",
+            "%1 = OpTypeBool",
+            "OpReturn",
+            "OpKill",
+            "OpFunctionEnd",
+        ];
+        assert_eq!(
+            diff_lines(&old, &new),
+            [
+                Line::Same("OpCapability Shader"),
+                Line::Removed("%1 = OpTypeVoid"),
+                Line::Added("%1 = OpTypeBool"),
+                Line::Same("OpReturn"),
+                Line::Added("OpKill"),
+                Line::Same("OpFunctionEnd"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,202 @@
+//! `cargo gpu dis`, disassemble compiled shaders to SPIR-V assembly text.
+
+use anyhow::Context as _;
+use rspirv::binary::Disassemble as _;
+
+/// `cargo gpu dis`.
+#[derive(clap::Parser, Debug)]
+pub struct Dis {
+    /// A `.spv` file, or a manifest written by `cargo gpu build` to disassemble every module in it.
+    path: std::path::PathBuf,
+
+    /// Only disassemble the given entry point, along with all the global declarations and the
+    /// functions that it calls.
+    #[clap(long)]
+    entry_point: Option<String>,
+
+    /// Directory containing the shader crate. Paths in a manifest are relative to it.
+    #[clap(long, default_value = "./")]
+    shader_crate: std::path::PathBuf,
+}
+
+impl Dis {
+    /// Entrypoint.
+    pub fn run(&self) -> anyhow::Result<()> {
+        for spv_path in self.modules()? {
+            let bytes = std::fs::read(&spv_path)
+                .with_context(|| format!("could not read '{}'", spv_path.display()))?;
+            let mut module = rspirv::dr::load_bytes(bytes).map_err(|error| {
+                anyhow::anyhow!("could not parse '{}': {error}", spv_path.display())
+            })?;
+            if let Some(entry_point) = &self.entry_point {
+                retain_entry_point(&mut module, entry_point);
+            }
+
+            #[expect(
+                clippy::print_stdout,
+                reason = "The disassembly is meant to be piped, so no crab prefix"
+            )]
+            {
+                println!("; {}", spv_path.display());
+                println!("{}\n", module.disassemble());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The `.spv` files to disassemble.
+    fn modules(&self) -> anyhow::Result<Vec<std::path::PathBuf>> {
+        if self
+            .path
+            .extension()
+            .is_none_or(|extension| extension != "json")
+        {
+            return Ok(vec![self.path.clone()]);
+        }
+
+        let manifest = spirv_builder_cli::Manifest::from_json(
+            &std::fs::read_to_string(&self.path)
+                .with_context(|| format!("could not open manifest '{}'", self.path.display()))?,
+        )
+        .with_context(|| format!("could not parse manifest '{}'", self.path.display()))?;
+
+        let manifest_dir = self
+            .path
+            .parent()
+            .unwrap_or_else(|| std::path::Path::new("./"));
+        let mut modules = Vec::new();
+        for entry in manifest.entry_points {
+            if self
+                .entry_point
+                .as_ref()
+                .is_some_and(|entry_point| *entry_point != entry.entry_point)
+            {
+                continue;
+            }
+
+            // Paths are relative to the shader crate, unless the shader crate and the output dir
+            // are on different drives.
+            let in_shader_crate = self.shader_crate.join(&entry.source_path);
+            let path = if in_shader_crate.is_file() {
+                in_shader_crate
+            } else {
+                manifest_dir.join(&entry.source_path)
+            };
+            if !modules.contains(&path) {
+                modules.push(path);
+            }
+        }
+        anyhow::ensure!(
+            !modules.is_empty(),
+            "no matching entry points in manifest '{}'",
+            self.path.display()
+        );
+
+        Ok(modules)
+    }
+}
+
+/// Remove every entry point other than `name` from the module, along with any functions that
+/// aren't reachable from it. Global declarations are kept as they are.
+fn retain_entry_point(module: &mut rspirv::dr::Module, name: &str) {
+    let maybe_entry_function = module.entry_points.iter().find_map(|entry_point| {
+        let is_match = matches!(
+            entry_point.operands.get(2),
+            Some(rspirv::dr::Operand::LiteralString(entry_name)) if entry_name == name
+        );
+        if is_match {
+            entry_point.operands.get(1)?.id_ref_any()
+        } else {
+            None
+        }
+    });
+    let Some(entry_function) = maybe_entry_function else {
+        log::warn!("entry point `{name}` not found in module");
+        return;
+    };
+
+    let reachable = crate::reflect::reachable_functions(module, entry_function);
+
+    module.entry_points.retain(|instruction| {
+        instruction
+            .operands
+            .get(1)
+            .and_then(rspirv::dr::Operand::id_ref_any)
+            == Some(entry_function)
+    });
+    module.execution_modes.retain(|instruction| {
+        instruction
+            .operands
+            .first()
+            .and_then(rspirv::dr::Operand::id_ref_any)
+            == Some(entry_function)
+    });
+    module.functions.retain(|function| {
+        function
+            .def
+            .as_ref()
+            .and_then(|def| def.result_id)
+            .is_some_and(|id| reachable.contains(&id))
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Build a module with two entry points that both call a shared function.
+    fn two_entry_points() -> rspirv::dr::Module {
+        let mut builder = rspirv::dr::Builder::new();
+        builder.capability(rspirv::spirv::Capability::Shader);
+        builder.memory_model(
+            rspirv::spirv::AddressingModel::Logical,
+            rspirv::spirv::MemoryModel::GLSL450,
+        );
+        let void = builder.type_void();
+        let function_type = builder.type_function(void, vec![]);
+
+        let shared = builder
+            .begin_function(
+                void,
+                None,
+                rspirv::spirv::FunctionControl::NONE,
+                function_type,
+            )
+            .unwrap();
+        builder.begin_block(None).unwrap();
+        builder.ret().unwrap();
+        builder.end_function().unwrap();
+
+        for name in ["main_vs", "main_fs"] {
+            let main = builder
+                .begin_function(
+                    void,
+                    None,
+                    rspirv::spirv::FunctionControl::NONE,
+                    function_type,
+                )
+                .unwrap();
+            builder.begin_block(None).unwrap();
+            builder.function_call(void, None, shared, vec![]).unwrap();
+            builder.ret().unwrap();
+            builder.end_function().unwrap();
+            builder.entry_point(rspirv::spirv::ExecutionModel::Vertex, main, name, vec![]);
+        }
+        builder.module()
+    }
+
+    #[test_log::test]
+    fn filtering_by_entry_point() {
+        let mut module = two_entry_points();
+        assert_eq!(module.functions.len(), 3);
+
+        retain_entry_point(&mut module, "main_fs");
+        assert_eq!(module.entry_points.len(), 1);
+        // The entry point itself, and the function that it calls.
+        assert_eq!(module.functions.len(), 2);
+        let text = module.disassemble();
+        assert!(text.contains("\"main_fs\""), "{text}");
+        assert!(!text.contains("\"main_vs\""), "{text}");
+    }
+}
@@ -0,0 +1,426 @@
+//! `cargo gpu doctor`, check the environment for the problems that most often stop `rust-gpu`
+//! from installing or building, and suggest how to fix them.
+//!
+//! Nothing is installed or changed. Finding which `rust-gpu` the shader crate uses can still
+//! fetch it though, just as `cargo gpu install` would.
+
+use anyhow::Context as _;
+
+/// Less free space than this in the cache dir is worth a warning, as a toolchain and a compiled
+/// `spirv-builder-cli` take a few GiB between them.
+const MIN_FREE_SPACE: u64 = 5 * 1024 * 1024 * 1024;
+
+/// `Cargo.lock` version 4 can only be read by `cargo` 1.78 and later.
+const LOCKFILE_V4_MIN_CARGO_MINOR: u32 = 78;
+
+/// How a check turned out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    /// Nothing wrong.
+    Pass,
+    /// Might cause problems.
+    Warn,
+    /// Will cause problems.
+    Fail,
+}
+
+/// The result of one check of the environment.
+#[derive(Debug, serde::Serialize)]
+pub struct Check {
+    /// What was checked.
+    pub name: &'static str,
+    /// How it turned out.
+    pub status: Status,
+    /// What was found.
+    pub detail: String,
+    /// How to fix it, if it didn't pass.
+    pub fix: Option<String>,
+}
+
+impl Check {
+    /// A check that passed.
+    fn pass(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Pass,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    /// A check that didn't pass.
+    fn problem(
+        name: &'static str,
+        status: Status,
+        detail: impl Into<String>,
+        fix: impl Into<String>,
+    ) -> Self {
+        Self {
+            name,
+            status,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+}
+
+/// `cargo gpu doctor`.
+#[derive(clap::Parser, Debug)]
+pub struct Doctor {
+    /// Shader crate whose `rust-gpu` toolchain and `Cargo.lock` are checked.
+    #[clap(long, default_value = "./")]
+    pub shader_crate: std::path::PathBuf,
+
+    /// Format of the results written to stdout.
+    #[clap(long, value_enum, default_value_t)]
+    pub output_format: crate::output::Format,
+}
+
+impl Doctor {
+    /// Entrypoint.
+    pub fn run(&self) -> anyhow::Result<()> {
+        let mut checks = vec![
+            check_program(
+                "rustup",
+                Status::Fail,
+                "Install `rustup` from https://rustup.rs",
+            ),
+            check_program(
+                "git",
+                Status::Warn,
+                "Install `git`, it's needed when `cargo` fetches git dependencies with \
+                 `net.git-fetch-with-cli`",
+            ),
+            check_global_config(),
+            check_cache_dir(),
+            check_free_space(),
+        ];
+        checks.extend(self.check_shader_crate());
+
+        let failures = checks
+            .iter()
+            .filter(|check| check.status == Status::Fail)
+            .count();
+        if crate::output::is_json() {
+            crate::output::print_json(&serde_json::json!({ "checks": checks }))?;
+        } else {
+            for check in &checks {
+                let status = match check.status {
+                    Status::Pass => "pass",
+                    Status::Warn => "warn",
+                    Status::Fail => "FAIL",
+                };
+                crate::user_output!("{status} {}: {}\n", check.name, check.detail);
+                if let Some(fix) = &check.fix {
+                    crate::user_output!("     fix: {fix}\n");
+                }
+            }
+        }
+        anyhow::ensure!(failures == 0, "{failures} of the checks failed");
+        Ok(())
+    }
+
+    /// Checks of the toolchain that the shader crate's `rust-gpu` needs.
+    fn check_shader_crate(&self) -> Vec<Check> {
+        let (source, _, channel) =
+            match crate::spirv_source::SpirvSource::get_rust_gpu_deps_from_shader(
+                &self.shader_crate,
+            ) {
+                Ok(deps) => deps,
+                Err(error) => {
+                    return vec![Check::problem(
+                        "rust-gpu",
+                        Status::Fail,
+                        format!(
+                            "couldn't find which `rust-gpu` {} uses: {error:#}",
+                            self.shader_crate.display()
+                        ),
+                        "Run with `--shader-crate` pointing at a crate that depends on `spirv-std`",
+                    )];
+                }
+            };
+        let mut checks = vec![Check::pass(
+            "rust-gpu",
+            format!("{source} needs toolchain {channel}"),
+        )];
+
+        match crate::spirv_cli::SpirvCli::is_toolchain_installed(&channel) {
+            Ok(true) => {
+                checks.push(Check::pass("toolchain", format!("{channel} is installed")));
+                checks.push(check_components(&channel));
+            }
+            Ok(false) => checks.push(Check::problem(
+                "toolchain",
+                Status::Fail,
+                format!("{channel} isn't installed"),
+                "Run `cargo gpu install`, or `rustup toolchain add` it",
+            )),
+            Err(error) => checks.push(Check::problem(
+                "toolchain",
+                Status::Fail,
+                format!("{error:#}"),
+                "Check that `rustup` works",
+            )),
+        }
+
+        let cargo_minor = toolchain_cargo_minor(&channel);
+        checks.push(cargo_minor.map_or_else(
+            || {
+                Check::problem(
+                    "cargo",
+                    Status::Fail,
+                    format!("`cargo +{channel}` doesn't work"),
+                    "Put `rustup`'s `~/.cargo/bin` on PATH before any other Rust install",
+                )
+            },
+            |minor| Check::pass("cargo", format!("`cargo +{channel}` is 1.{minor}")),
+        ));
+        checks.extend(self.check_lockfile(cargo_minor));
+        checks.push(check_library_paths(&channel, std::env::vars_os()));
+        checks
+    }
+
+    /// Check that the toolchain's `cargo` can read the shader crate's `Cargo.lock`.
+    fn check_lockfile(&self, cargo_minor: Option<u32>) -> Option<Check> {
+        let shader_crate = std::path::absolute(&self.shader_crate).ok()?;
+        let lockfile = shader_crate
+            .ancestors()
+            .map(|dir| dir.join("Cargo.lock"))
+            .find(|path| path.is_file())?;
+        let version = lockfile_version(&std::fs::read_to_string(&lockfile).ok()?)?;
+        let is_readable =
+            version < 4 || cargo_minor.is_none_or(|minor| minor >= LOCKFILE_V4_MIN_CARGO_MINOR);
+        Some(if is_readable {
+            Check::pass(
+                "lockfile",
+                format!("{} is version {version}", lockfile.display()),
+            )
+        } else {
+            Check::problem(
+                "lockfile",
+                Status::Fail,
+                format!(
+                    "{} is version {version}, which the `rust-gpu` toolchain's `cargo` can't read",
+                    lockfile.display()
+                ),
+                "Change `version = 4` to `version = 3` at the top of the `Cargo.lock`",
+            )
+        })
+    }
+}
+
+/// Check that a program is on PATH by asking for its version.
+fn check_program(program: &'static str, status: Status, fix: &str) -> Check {
+    match std::process::Command::new(program)
+        .arg("--version")
+        .output()
+    {
+        Ok(output) if output.status.success() => Check::pass(
+            program,
+            String::from_utf8_lossy(&output.stdout).trim().to_owned(),
+        ),
+        Ok(_) | Err(_) => {
+            Check::problem(program, status, format!("`{program}` isn't on PATH"), fix)
+        }
+    }
+}
+
+/// Check that the global config, if there is one, can be read.
+fn check_global_config() -> Check {
+    match crate::global_config::GlobalConfig::load() {
+        Ok(_) => Check::pass("global config", "readable"),
+        Err(error) => Check::problem(
+            "global config",
+            Status::Fail,
+            format!("{error:#}"),
+            "Fix or remove the global config file",
+        ),
+    }
+}
+
+/// Check that installs can be written to the cache dir.
+fn check_cache_dir() -> Check {
+    let result = (|| {
+        let cache_dir = crate::cache_dir()?;
+        std::fs::create_dir_all(&cache_dir)?;
+        let probe = cache_dir.join(format!(".doctor-{}", std::process::id()));
+        std::fs::write(&probe, "")
+            .with_context(|| format!("{} isn't writable", cache_dir.display()))?;
+        std::fs::remove_file(&probe)?;
+        anyhow::Ok(cache_dir)
+    })();
+    match result {
+        Ok(cache_dir) => Check::pass("cache", format!("{} is writable", cache_dir.display())),
+        Err(error) => Check::problem(
+            "cache",
+            Status::Fail,
+            format!("{error:#}"),
+            "Set `CARGO_GPU_CACHE_DIR` to a writable directory",
+        ),
+    }
+}
+
+/// Check that there's enough space for a toolchain and an install.
+fn check_free_space() -> Check {
+    let Some(free) = crate::cache_dir()
+        .ok()
+        .and_then(|cache_dir| free_space(&cache_dir))
+    else {
+        return Check::pass("disk space", "couldn't be checked");
+    };
+    if free >= MIN_FREE_SPACE {
+        Check::pass("disk space", format!("{} free", crate::format_size(free)))
+    } else {
+        Check::problem(
+            "disk space",
+            Status::Warn,
+            format!("only {} free for the cache dir", crate::format_size(free)),
+            "Free some space, eg with `cargo gpu clean`",
+        )
+    }
+}
+
+/// Check that the toolchain has every component that `rust-gpu` needs.
+fn check_components(channel: &str) -> Check {
+    match crate::spirv_cli::SpirvCli::missing_components(channel) {
+        Ok(missing) if missing.is_empty() => Check::pass("components", "all installed"),
+        Ok(missing) => Check::problem(
+            "components",
+            Status::Fail,
+            format!("{} aren't installed", missing.join(", ")),
+            format!(
+                "Run `rustup component add --toolchain {channel} {}`",
+                missing.join(" ")
+            ),
+        ),
+        Err(error) => Check::problem(
+            "components",
+            Status::Fail,
+            format!("{error:#}"),
+            "Check that `rustup` works",
+        ),
+    }
+}
+
+/// Check for library paths that point into a different toolchain to the one that `rust-gpu`
+/// needs, as the codegen backend would then load the wrong `rustc_driver`.
+fn check_library_paths(
+    channel: &str,
+    vars: impl Iterator<Item = (std::ffi::OsString, std::ffi::OsString)>,
+) -> Check {
+    let stale = vars
+        .filter(|(name, _)| {
+            [
+                "LD_LIBRARY_PATH",
+                "DYLD_LIBRARY_PATH",
+                "DYLD_FALLBACK_LIBRARY_PATH",
+            ]
+            .iter()
+            .any(|library_var| name == library_var)
+        })
+        .flat_map(|(name, value)| {
+            std::env::split_paths(&value)
+                .filter(|path| {
+                    let lossy = path.to_string_lossy();
+                    lossy.contains("toolchains") && !lossy.contains(channel)
+                })
+                .map(|path| format!("{}={}", name.to_string_lossy(), path.display()))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    if stale.is_empty() {
+        Check::pass("library paths", "nothing points at another toolchain")
+    } else {
+        Check::problem(
+            "library paths",
+            Status::Warn,
+            format!("{} point at another toolchain", stale.join(", ")),
+            "Remove other toolchains from these variables before running `cargo gpu`",
+        )
+    }
+}
+
+/// The minor version of the toolchain's `cargo`, eg 78 for `cargo 1.78.0-nightly`, or `None` if
+/// it can't be run.
+fn toolchain_cargo_minor(channel: &str) -> Option<u32> {
+    let output = std::process::Command::new("cargo")
+        .arg(format!("+{channel}"))
+        .arg("--version")
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    cargo_minor(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// The minor version in the output of `cargo --version`.
+fn cargo_minor(version: &str) -> Option<u32> {
+    version
+        .split_whitespace()
+        .nth(1)?
+        .split('.')
+        .nth(1)?
+        .parse()
+        .ok()
+}
+
+/// The version of a `Cargo.lock`'s format.
+fn lockfile_version(lockfile: &str) -> Option<i64> {
+    let table: toml::Table = toml::from_str(lockfile).ok()?;
+    // Lockfiles from before version 3 don't say.
+    table
+        .get("version")
+        .map_or(Some(1), toml::Value::as_integer)
+}
+
+/// The free space on the file system of `path`, in bytes.
+#[cfg(unix)]
+fn free_space(path: &std::path::Path) -> Option<u64> {
+    let output = std::process::Command::new("df")
+        .arg("-Pk")
+        .arg(path)
+        .output()
+        .ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let kibibytes: u64 = stdout
+        .lines()
+        .nth(1)?
+        .split_whitespace()
+        .nth(3)?
+        .parse()
+        .ok()?;
+    kibibytes.checked_mul(1024)
+}
+
+/// The free space on the file system of `path`, in bytes.
+#[cfg(not(unix))]
+fn free_space(_path: &std::path::Path) -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_log::test]
+    fn diagnosing_the_environment() {
+        assert_eq!(
+            cargo_minor("cargo 1.78.0-nightly (7b7af3077 2024-02-17)"),
+            Some(78)
+        );
+        assert_eq!(lockfile_version("version = 4\n\n[[package]]\n"), Some(4));
+        assert_eq!(lockfile_version("[[package]]\nname = \"a\"\n"), Some(1));
+
+        let vars = [(
+            "LD_LIBRARY_PATH".into(),
+            "/home/user/.rustup/toolchains/stable-x86_64-unknown-linux-gnu/lib:/usr/lib".into(),
+        )];
+        let other_toolchain = check_library_paths("nightly-2024-04-24", vars.clone().into_iter());
+        assert_eq!(other_toolchain.status, Status::Warn);
+        assert!(other_toolchain.detail.contains("stable-x86_64"));
+        let same_toolchain = check_library_paths("stable", vars.into_iter());
+        assert_eq!(same_toolchain.status, Status::Pass);
+    }
+}
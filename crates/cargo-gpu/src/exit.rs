@@ -0,0 +1,121 @@
+//! Failure classes and the process exit codes that they map to.
+//!
+//! Most errors are plain `anyhow` chains and exit with 1, but failures that CI and wrapper scripts
+//! may want to handle differently are wrapped in a [`Failure`], which keeps the error's message
+//! and gives it its own exit code:
+//!
+//! | Code | Failure                                                        |
+//! |------|----------------------------------------------------------------|
+//! | 1    | Any other error                                                |
+//! | 2    | Invalid CLI arguments (from `clap`)                            |
+//! | 3    | The Rust toolchain or its components are missing               |
+//! | 4    | The shader crate's `Cargo.lock` can't be read by its toolchain |
+//! | 5    | The shader crate can't be found                                |
+//! | 6    | The codegen backend, `rustc_codegen_spirv`, failed to build    |
+//! | 7    | A compiled SPIR-V module failed validation                     |
+
+/// The exit code for errors that aren't a [`Failure`].
+pub const GENERAL: i32 = 1;
+
+/// An error of a class that has its own exit code.
+///
+/// It is transparent: its message and source are those of the error it wraps.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Failure {
+    /// The Rust toolchain or its components are missing and couldn't be installed.
+    ToolchainMissing(anyhow::Error),
+    /// The shader crate's `Cargo.lock` is in a format that its toolchain's `cargo` can't read.
+    LockfileConflict(anyhow::Error),
+    /// The shader crate doesn't exist or isn't a directory.
+    ShaderCrateNotFound(anyhow::Error),
+    /// `rustc_codegen_spirv` and `spirv-builder-cli` failed to build.
+    CodegenBackendBuild(anyhow::Error),
+    /// A compiled SPIR-V module failed validation.
+    Validation(anyhow::Error),
+}
+
+impl Failure {
+    /// The process exit code for this class of failure.
+    #[must_use]
+    pub const fn code(&self) -> i32 {
+        match self {
+            Self::ToolchainMissing(_) => 3,
+            Self::LockfileConflict(_) => 4,
+            Self::ShaderCrateNotFound(_) => 5,
+            Self::CodegenBackendBuild(_) => 6,
+            Self::Validation(_) => 7,
+        }
+    }
+
+    /// The wrapped error.
+    const fn error(&self) -> &anyhow::Error {
+        match self {
+            Self::ToolchainMissing(error)
+            | Self::LockfileConflict(error)
+            | Self::ShaderCrateNotFound(error)
+            | Self::CodegenBackendBuild(error)
+            | Self::Validation(error) => error,
+        }
+    }
+}
+
+impl core::fmt::Display for Failure {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Display::fmt(self.error(), f)
+    }
+}
+
+#[expect(
+    clippy::missing_trait_methods,
+    reason = "Only the source is forwarded, the rest are deprecated or unstable"
+)]
+impl core::error::Error for Failure {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        self.error().source()
+    }
+}
+
+/// The process exit code for an error, from the first [`Failure`] in its chain.
+#[must_use]
+pub fn code(error: &anyhow::Error) -> i32 {
+    error
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<Failure>())
+        .map_or(GENERAL, Failure::code)
+}
+
+/// Whether `cargo`'s stderr says that it couldn't read a `Cargo.lock`, usually because it was
+/// written by a newer `cargo` than the one in the shader crate's toolchain.
+#[must_use]
+pub fn is_lockfile_conflict(stderr: &str) -> bool {
+    stderr.contains("lock file version") || stderr.contains("failed to parse lock file")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use anyhow::Context as _;
+
+    #[test_log::test]
+    fn mapping_failures_to_exit_codes() {
+        let (toolchain_missing, validation_failed): (i32, i32) = (3, 7);
+        assert_eq!(code(&anyhow::anyhow!("oops")), GENERAL);
+
+        let validation: anyhow::Error = Failure::Validation(anyhow::anyhow!("bad module")).into();
+        assert_eq!(validation.to_string(), "bad module");
+        assert_eq!(code(&validation), validation_failed);
+
+        let wrapped = Err::<(), _>(Failure::ToolchainMissing(anyhow::anyhow!("no rustup")))
+            .context("could not install")
+            .unwrap_err();
+        assert_eq!(code(&wrapped), toolchain_missing);
+        assert_eq!(format!("{wrapped:#}"), "could not install: no rustup");
+
+        assert!(is_lockfile_conflict(
+            "error: failed to parse lock file at: Cargo.lock\n\
+             Caused by:\n  lock file version 4 requires `-Znext-lockfile-bump`"
+        ));
+        assert!(!is_lockfile_conflict("error: could not compile `shader`"));
+    }
+}
@@ -0,0 +1,255 @@
+//! Skip rebuilding shaders when none of their inputs have changed since the last build.
+//!
+//! The fingerprint covers the sources of the shader crate and its path dependencies, the
+//! arguments passed to `spirv-builder-cli` (which include the features, the shader target and
+//! the `rust-gpu` install) and the version of `cargo gpu` itself. Paths are made relative to the
+//! shader crate and the cache directory, so that the same build on another machine has the same
+//! fingerprint, see [`crate::cache`].
+
+use anyhow::Context as _;
+use sha2::Digest as _;
+use spirv_builder_cli::Linkage;
+
+/// The file in the output dir that records the last successful build.
+const FINGERPRINT_FILE: &str = ".cargo-gpu-fingerprint.json";
+
+/// What's recorded about the last successful build.
+#[derive(serde::Deserialize)]
+struct Record {
+    /// The fingerprint of the build's inputs.
+    fingerprint: String,
+    /// The linkage that the build produced.
+    linkage: Vec<Linkage>,
+}
+
+/// Compute the fingerprint of a build's inputs. `args` is the JSON passed to
+/// `spirv-builder-cli`.
+pub fn fingerprint(
+    shader_crate: &std::path::Path,
+    output_dir: &std::path::Path,
+    args: &str,
+) -> anyhow::Result<String> {
+    let mut hasher = sha2::Sha256::new();
+    hasher.update(env!("CARGO_PKG_VERSION"));
+    hasher.update(env!("GIT_HASH"));
+    hasher.update(
+        args.replace(&shader_crate.display().to_string(), "<shader-crate>")
+            .replace(&crate::cache_dir()?.display().to_string(), "<cache>"),
+    );
+
+    for input in input_files(shader_crate, output_dir)? {
+        hash_file(&mut hasher, &input, shader_crate)?;
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Every file that can affect the compilation of the shader crate, in a stable order.
+///
+/// That's the `Cargo.toml`, `build.rs` and sources of the crate and its path dependencies, and
+/// the workspace's `Cargo.lock`. Anything in `output_dir` is skipped, so that the outputs
+/// themselves aren't inputs when they're written into the crate.
+pub fn input_files(
+    shader_crate: &std::path::Path,
+    output_dir: &std::path::Path,
+) -> anyhow::Result<Vec<std::path::PathBuf>> {
+    files_of_inputs(
+        crate::metadata::Metadata::local_inputs(shader_crate)?,
+        output_dir,
+    )
+}
+
+/// The files of the local inputs found by [`crate::metadata::Metadata::local_inputs`], see
+/// [`input_files`].
+pub fn files_of_inputs(
+    mut inputs: Vec<std::path::PathBuf>,
+    output_dir: &std::path::Path,
+) -> anyhow::Result<Vec<std::path::PathBuf>> {
+    inputs.sort();
+    inputs.dedup();
+    let mut files = Vec::new();
+    for input in inputs {
+        if input.is_file() {
+            files.push(input);
+            continue;
+        }
+        for name in ["Cargo.toml", "build.rs", crate::pin::FILE] {
+            let path = input.join(name);
+            if path.is_file() {
+                files.push(path);
+            }
+        }
+        let src = input.join("src");
+        if src.is_dir() {
+            files_in_dir(&mut files, &src, output_dir)?;
+        }
+    }
+    Ok(files)
+}
+
+/// Hash a file's path, relative to `root`, and its contents.
+fn hash_file(
+    hasher: &mut sha2::Sha256,
+    path: &std::path::Path,
+    root: &std::path::Path,
+) -> anyhow::Result<()> {
+    use relative_path::PathExt as _;
+
+    let contents =
+        std::fs::read(path).with_context(|| format!("could not read '{}'", path.display()))?;
+    let name = path.relative_to(root).map_or_else(
+        |_| path.to_string_lossy().into_owned(),
+        relative_path::RelativePathBuf::into_string,
+    );
+    hasher.update(name);
+    hasher.update(contents.len().to_string());
+    hasher.update(contents);
+    Ok(())
+}
+
+/// The size and modification time of a file that's too big to hash on every build, eg a codegen
+/// backend that the user built themselves.
+pub fn file_stamp(path: &std::path::Path) -> anyhow::Result<String> {
+    let metadata = std::fs::metadata(path)
+        .with_context(|| format!("could not read the metadata of '{}'", path.display()))?;
+    let modified = metadata
+        .modified()?
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    Ok(format!("{} {}", metadata.len(), modified.as_nanos()))
+}
+
+/// Every file in a directory, in a stable order, skipping `skip`.
+fn files_in_dir(
+    files: &mut Vec<std::path::PathBuf>,
+    dir: &std::path::Path,
+    skip: &std::path::Path,
+) -> anyhow::Result<()> {
+    let mut paths = std::fs::read_dir(dir)
+        .with_context(|| format!("could not read '{}'", dir.display()))?
+        .map(|maybe_entry| maybe_entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    paths.sort();
+    for path in paths {
+        if path == skip {
+            continue;
+        }
+        if path.is_dir() {
+            files_in_dir(files, &path, skip)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Every file referenced by the linkage, ie the modules and any transpiled sources.
+#[must_use]
+pub fn linkage_outputs(
+    linkage: &[Linkage],
+    shader_crate: &std::path::Path,
+) -> Vec<std::path::PathBuf> {
+    let mut outputs: Vec<std::path::PathBuf> = linkage
+        .iter()
+        .flat_map(|entry| {
+            [
+                Some(&entry.source_path),
+                entry.wgsl_source_path.as_ref(),
+                entry.hlsl_source_path.as_ref(),
+                entry.glsl_source_path.as_ref(),
+                entry.msl_source_path.as_ref(),
+            ]
+            .into_iter()
+            .flatten()
+            .map(|output| shader_crate.join(output))
+        })
+        .collect();
+    outputs.sort();
+    outputs.dedup();
+    outputs
+}
+
+/// The linkage from the last build in `output_dir`, if it had the same fingerprint and all of
+/// its outputs still exist.
+///
+/// `expected_outputs` are any other files that the build should have written, eg the manifest.
+#[must_use]
+pub fn cached_linkage(
+    output_dir: &std::path::Path,
+    fingerprint: &str,
+    shader_crate: &std::path::Path,
+    expected_outputs: &[std::path::PathBuf],
+) -> Option<Vec<Linkage>> {
+    let path = output_dir.join(FINGERPRINT_FILE);
+    let record: Record = serde_json::from_slice(&std::fs::read(&path).ok()?)
+        .inspect_err(|error| log::debug!("ignoring '{}': {error}", path.display()))
+        .ok()?;
+    if record.fingerprint != fingerprint {
+        log::debug!("shader inputs have changed since the last build");
+        return None;
+    }
+
+    let outputs = linkage_outputs(&record.linkage, shader_crate);
+    if let Some(missing) = outputs
+        .into_iter()
+        .chain(expected_outputs.iter().cloned())
+        .find(|output| !output.is_file())
+    {
+        log::debug!(
+            "'{}' from the last build is missing, rebuilding",
+            missing.display()
+        );
+        return None;
+    }
+
+    Some(record.linkage)
+}
+
+/// Record a successful build's fingerprint and linkage in `output_dir`.
+pub fn write(
+    output_dir: &std::path::Path,
+    fingerprint: &str,
+    linkage: &[Linkage],
+) -> anyhow::Result<()> {
+    let path = output_dir.join(FINGERPRINT_FILE);
+    let record = serde_json::json!({
+        "fingerprint": fingerprint,
+        "linkage": linkage,
+    });
+    crate::atomic::write(&path, serde_json::to_string(&record)?)
+        .with_context(|| format!("could not write fingerprint '{}'", path.display()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_log::test]
+    fn reusing_linkage_with_the_same_fingerprint() {
+        let output_dir = crate::cache_dir().unwrap().join("fingerprint");
+        if output_dir.exists() {
+            std::fs::remove_dir_all(&output_dir).unwrap();
+        }
+        std::fs::create_dir_all(&output_dir).unwrap();
+        let module = output_dir.join("shader.spv");
+        std::fs::write(&module, b"spirv").unwrap();
+        let linkage = vec![Linkage::new("main_fs", &module)];
+        let manifest = output_dir.join("manifest.json");
+        let expected_outputs = vec![manifest.clone()];
+
+        write(&output_dir, "abc", &linkage).unwrap();
+        // The manifest hasn't been written yet.
+        assert!(cached_linkage(&output_dir, "abc", &output_dir, &expected_outputs).is_none());
+
+        std::fs::write(&manifest, "[]").unwrap();
+        assert_eq!(
+            cached_linkage(&output_dir, "abc", &output_dir, &expected_outputs),
+            Some(linkage)
+        );
+        assert!(cached_linkage(&output_dir, "def", &output_dir, &expected_outputs).is_none());
+
+        std::fs::remove_file(&module).unwrap();
+        assert!(cached_linkage(&output_dir, "abc", &output_dir, &expected_outputs).is_none());
+    }
+}
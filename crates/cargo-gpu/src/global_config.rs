@@ -0,0 +1,161 @@
+//! Config that applies to every shader crate, kept in the user's config directory.
+//!
+//! Eg, on Linux it's at `~/.config/rust-gpu/config.toml`:
+//!
+//! ```toml
+//! # Defaults for `cargo gpu build` and `cargo gpu install`, in the same format as the
+//! # `[package.metadata.rust-gpu.*]` tables of a shader crate's `Cargo.toml`, which override them.
+//! [build]
+//! deny-warnings = true
+//!
+//! [install]
+//! auto-install-rust-toolchain = true
+//!
+//! [cache]
+//! # Keep the cache somewhere other than the OS default, `CARGO_GPU_CACHE_DIR` overrides this.
+//! cache-dir = "/mnt/cache/rust-gpu"
+//! # Remove the least recently used installs and checkouts once the cache is bigger than this.
+//! max-cache-size = "10 GiB"
+//! # Remove installs and checkouts that haven't been used for this many days.
+//! max-age-days = 30
+//! ```
+
+use anyhow::Context as _;
+
+/// The global config file.
+#[derive(Debug, Default, Clone, PartialEq, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GlobalConfig {
+    /// Settings for the cache directory.
+    pub cache: CacheConfig,
+    /// Defaults for `cargo gpu build`.
+    pub build: toml::Table,
+    /// Defaults for `cargo gpu install`.
+    pub install: toml::Table,
+}
+
+/// The `[cache]` table of the global config file.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct CacheConfig {
+    /// Where to keep the cache directory. Relative paths are relative to the config file.
+    pub cache_dir: Option<std::path::PathBuf>,
+    /// The most space that the cache directory should use. Either a number of bytes or a string
+    /// like "10 GiB".
+    pub max_cache_size: Option<Size>,
+    /// How long an install or checkout can go unused before it's removed.
+    pub max_age_days: Option<u64>,
+}
+
+/// A size in bytes, optionally written with a unit.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+#[serde(untagged)]
+pub enum Size {
+    /// A plain number of bytes.
+    Bytes(u64),
+    /// A human readable size, eg "10 GiB".
+    Human(String),
+}
+
+impl Size {
+    /// The size in bytes.
+    pub fn to_bytes(&self) -> anyhow::Result<u64> {
+        match self {
+            Self::Bytes(bytes) => Ok(*bytes),
+            Self::Human(text) => parse_size(text),
+        }
+    }
+}
+
+/// Parse a size like "512 MiB" or "10GiB" into bytes.
+fn parse_size(text: &str) -> anyhow::Result<u64> {
+    let trimmed = text.trim();
+    let split_at = trimmed
+        .find(|character: char| !character.is_ascii_digit())
+        .unwrap_or(trimmed.len());
+    let (digits, unit) = trimmed.split_at(split_at);
+    let number: u64 = digits
+        .parse()
+        .with_context(|| format!("could not parse the size '{text}'"))?;
+    let multiplier: u64 = match unit.trim() {
+        "" | "B" => 1,
+        "KiB" => 1024,
+        "MiB" => 1024 * 1024,
+        "GiB" => 1024 * 1024 * 1024,
+        "TiB" => 1024 * 1024 * 1024 * 1024,
+        other => anyhow::bail!(
+            "unknown unit '{other}' in size '{text}', expected one of B, KiB, MiB, GiB or TiB"
+        ),
+    };
+    number
+        .checked_mul(multiplier)
+        .with_context(|| format!("the size '{text}' is too big"))
+}
+
+impl GlobalConfig {
+    /// The location of the global config file.
+    pub fn path() -> anyhow::Result<std::path::PathBuf> {
+        if cfg!(test) {
+            return Ok(crate::cache_dir()?.join("config.toml"));
+        }
+        Ok(directories::BaseDirs::new()
+            .with_context(|| "could not find the user home directory")?
+            .config_dir()
+            .join("rust-gpu")
+            .join("config.toml"))
+    }
+
+    /// The `[build]` and `[install]` defaults as JSON, in the same shape as the shader crate's
+    /// `Cargo.toml` metadata.
+    pub fn as_json(&self) -> anyhow::Result<serde_json::Value> {
+        Ok(serde_json::json!({
+            "build": serde_json::to_value(&self.build)?,
+            "install": serde_json::to_value(&self.install)?,
+        }))
+    }
+
+    /// Load the global config file, or the defaults if there isn't one.
+    pub fn load() -> anyhow::Result<Self> {
+        let path = Self::path()?;
+        if !path.is_file() {
+            log::debug!("no global config at '{}'", path.display());
+            return Ok(Self::default());
+        }
+
+        let contents = std::fs::read_to_string(&path)
+            .with_context(|| format!("could not read global config '{}'", path.display()))?;
+        let mut config: Self = toml::from_str(&contents)
+            .with_context(|| format!("could not parse global config '{}'", path.display()))?;
+        if let (Some(cache_dir), Some(config_dir)) = (&mut config.cache.cache_dir, path.parent()) {
+            *cache_dir = config_dir.join(&cache_dir);
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_log::test]
+    fn parsing_cache_config() {
+        let config: GlobalConfig = toml::from_str(
+            r#"
+            [cache]
+            max-cache-size = "10 GiB"
+            max-age-days = 30
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.cache.max_age_days, Some(30));
+        assert_eq!(
+            config.cache.max_cache_size.unwrap().to_bytes().unwrap(),
+            10 * 1024 * 1024 * 1024
+        );
+
+        assert_eq!(parse_size("1024").unwrap(), 1024);
+        assert_eq!(parse_size("2KiB").unwrap(), 2048);
+        parse_size("2 KB").unwrap_err();
+        toml::from_str::<GlobalConfig>("[cache]\nmax-size = 1").unwrap_err();
+    }
+}
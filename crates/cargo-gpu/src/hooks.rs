@@ -0,0 +1,133 @@
+//! `pre-build` and `post-build` hooks, shell commands that `cargo gpu build` runs around a build.
+//!
+//! They make steps like generating code from game data, or compressing and uploading the shaders,
+//! part of the build. Hooks get the `env` variables that the shader crate is compiled with, as
+//! well as the paths of the manifest and output dir.
+
+use anyhow::Context as _;
+use spirv_builder_cli::args::BuildArgs;
+
+/// Run the `pre-build` hooks, in order, from the shader crate. A hook that fails fails the build.
+pub fn pre_build(build_args: &BuildArgs, shader_crate: &std::path::Path) -> anyhow::Result<()> {
+    run("pre-build", &build_args.pre_build, build_args, shader_crate)
+}
+
+/// Run the `post-build` hooks, in order, from the shader crate. A hook that fails fails the build.
+pub fn post_build(build_args: &BuildArgs, shader_crate: &std::path::Path) -> anyhow::Result<()> {
+    run(
+        "post-build",
+        &build_args.post_build,
+        build_args,
+        shader_crate,
+    )
+}
+
+/// Run `hooks`, the `kind` hooks of the build.
+fn run(
+    kind: &str,
+    hooks: &[String],
+    build_args: &BuildArgs,
+    shader_crate: &std::path::Path,
+) -> anyhow::Result<()> {
+    if hooks.is_empty() {
+        return Ok(());
+    }
+    let manifest_path = build_args.output_dir.join(&build_args.manifest_file);
+    let values = [
+        ("manifest", manifest_path.display().to_string()),
+        ("output_dir", build_args.output_dir.display().to_string()),
+    ];
+    for hook in hooks {
+        let command = render(hook, &values);
+        crate::user_output!("Running {kind} hook `{command}`\n");
+        let status = shell(&command)
+            .current_dir(shader_crate)
+            .envs(build_args.env.iter().map(|(key, value)| (key, value)))
+            .env("CARGO_GPU_MANIFEST", &manifest_path)
+            .env("CARGO_GPU_OUTPUT_DIR", &build_args.output_dir)
+            .stdout(crate::output::child_stdout())
+            .stderr(crate::output::child_stderr())
+            .status()
+            .with_context(|| format!("couldn't run {kind} hook `{command}`"))?;
+        anyhow::ensure!(
+            status.success(),
+            "{kind} hook `{command}` failed with {status}"
+        );
+    }
+    Ok(())
+}
+
+/// A command that runs `command` with the platform's shell.
+#[must_use]
+pub fn shell(command: &str) -> std::process::Command {
+    if cfg!(target_os = "windows") {
+        let mut cmd = std::process::Command::new("cmd");
+        cmd.args(["/C", command]);
+        cmd
+    } else {
+        let mut sh = std::process::Command::new("sh");
+        sh.args(["-c", command, "cargo-gpu"]);
+        sh
+    }
+}
+
+/// Replace the `{name}` placeholders in `command` with their values, quoted for the platform's
+/// shell. Anything else in braces is left alone, as it may well be shell syntax.
+fn render(command: &str, values: &[(&str, String)]) -> String {
+    values
+        .iter()
+        .fold(command.to_owned(), |rendered, (name, value)| {
+            rendered.replace(&format!("{{{name}}}"), &quote(value))
+        })
+}
+
+/// Quote `value` so that the platform's shell passes it on as a single argument, as it is.
+fn quote(value: &str) -> String {
+    if cfg!(target_os = "windows") {
+        // Paths on Windows can't contain `"`, so there's nothing to escape.
+        format!("\"{value}\"")
+    } else {
+        format!("'{}'", value.replace('\'', r"'\''"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_log::test]
+    fn rendering_hooks() {
+        let values = [
+            ("manifest", "shaders/manifest.json".to_owned()),
+            ("output_dir", "shaders".to_owned()),
+        ];
+        assert_eq!(
+            render(
+                "./scripts/upload.sh {manifest} ${HOME} {output_dir}",
+                &values
+            ),
+            format!(
+                "./scripts/upload.sh {} ${{HOME}} {}",
+                quote("shaders/manifest.json"),
+                quote("shaders")
+            )
+        );
+    }
+
+    #[cfg(unix)]
+    #[expect(
+        clippy::literal_string_with_formatting_args,
+        reason = "The hook's placeholders look like formatting args"
+    )]
+    #[test_log::test]
+    fn running_hooks_with_spaces_and_quotes_in_paths() {
+        let output_dir = crate::cache_dir().unwrap().join("my shaders' dir");
+        std::fs::create_dir_all(&output_dir).unwrap();
+        let values = [("output_dir", output_dir.display().to_string())];
+        let command = render("test -d {output_dir} && touch {output_dir}/hooked", &values);
+        let status = shell(&command).status().unwrap();
+        assert!(status.success());
+        assert!(output_dir.join("hooked").is_file());
+        crate::test::tests_teardown();
+    }
+}
@@ -0,0 +1,134 @@
+//! `cargo gpu test`, run the shader crate's `#[cfg(test)]` tests on the CPU.
+//!
+//! `spirv-std` also compiles for the host, so shader code can be unit tested with the normal
+//! toolchain and `cargo test`. The tests get the same Cargo features, `env` variables and
+//! `pre-build` hooks as `cargo gpu build` would give the shader crate, so there's no need for a
+//! separately maintained `cargo` alias.
+
+use anyhow::Context as _;
+use spirv_builder_cli::args::BuildArgs;
+
+/// `cargo gpu test`.
+#[derive(clap::Parser, Debug)]
+pub struct Test {
+    /// Directory containing the shader crate to test.
+    #[clap(long, default_value = "./")]
+    shader_crate: std::path::PathBuf,
+
+    /// Test with the Cargo features of this variant from `[package.metadata.rust-gpu.build]` as
+    /// well.
+    #[clap(long)]
+    variant: Option<String>,
+
+    /// Test with the Cargo features of this build profile, see `cargo gpu build --profile`.
+    #[clap(long)]
+    profile: Option<String>,
+
+    /// Arguments for `cargo test`, after `--`, eg `cargo gpu test -- --release lighting`.
+    #[clap(last = true)]
+    cargo_test_args: Vec<String>,
+}
+
+impl Test {
+    /// Entrypoint.
+    pub fn run(&self) -> anyhow::Result<()> {
+        let mut config_args = vec![
+            "gpu".to_owned(),
+            "--shader-crate".to_owned(),
+            self.shader_crate.display().to_string(),
+        ];
+        if let Some(profile) = &self.profile {
+            config_args.extend(["--profile".to_owned(), profile.clone()]);
+        }
+        let build =
+            crate::config::Config::clap_command_with_cargo_config(&self.shader_crate, config_args)?;
+        crate::hooks::pre_build(&build.build_args, &self.shader_crate)?;
+
+        let mut cargo = std::process::Command::new("cargo");
+        cargo
+            .arg("test")
+            .arg("--manifest-path")
+            .arg(self.shader_crate.join("Cargo.toml"))
+            .args(feature_args(&build.build_args, self.variant.as_deref())?)
+            .args(crate::offline::cargo_args())
+            .args(&self.cargo_test_args)
+            .envs(build.build_args.env.iter().map(|(key, value)| (key, value)));
+        crate::user_output!(
+            "Testing shader crate at {} on the CPU...\n",
+            self.shader_crate.display()
+        );
+        log::debug!("running {cargo:?}");
+        let status = cargo.status().context("couldn't run `cargo test`")?;
+        anyhow::ensure!(status.success(), "the shader crate's tests failed");
+        Ok(())
+    }
+}
+
+/// The `cargo test` args for the Cargo features that the shader crate is built with, along with
+/// those of `variant`.
+fn feature_args(build_args: &BuildArgs, variant: Option<&str>) -> anyhow::Result<Vec<String>> {
+    let mut features = build_args.features.clone();
+    let mut no_default_features = build_args.no_default_features;
+    if let Some(name) = variant {
+        let found = build_args
+            .variants
+            .iter()
+            .find(|candidate| candidate.name == name)
+            .with_context(|| {
+                format!(
+                    "there's no variant `{name}`, the variants are: {}",
+                    build_args
+                        .variants
+                        .iter()
+                        .map(|candidate| candidate.name.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            })?;
+        features.extend(found.features.iter().cloned());
+        no_default_features |= found.no_default_features;
+    }
+
+    let mut args = Vec::new();
+    if !features.is_empty() {
+        args.extend(["--features".to_owned(), features.join(",")]);
+    }
+    if no_default_features {
+        args.push("--no-default-features".to_owned());
+    }
+    Ok(args)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use clap::Parser as _;
+
+    #[test_log::test]
+    fn wiring_up_features() {
+        let mut build_args =
+            crate::build::Build::parse_from(["gpu", "--features", "fog", "--no-default-features"])
+                .build_args;
+        assert_eq!(
+            feature_args(&build_args, None).unwrap(),
+            ["--features", "fog", "--no-default-features"]
+        );
+
+        build_args.no_default_features = false;
+        build_args.variants = vec![spirv_builder_cli::args::Variant {
+            name: "shadows".to_owned(),
+            features: vec!["pcf".to_owned()],
+            no_default_features: false,
+        }];
+        assert_eq!(
+            feature_args(&build_args, Some("shadows")).unwrap(),
+            ["--features", "fog,pcf"]
+        );
+        assert_eq!(
+            feature_args(&build_args, Some("sky"))
+                .unwrap_err()
+                .to_string(),
+            "there's no variant `sky`, the variants are: shadows"
+        );
+    }
+}
@@ -0,0 +1,228 @@
+//! `cargo gpu init`, turn an existing crate into a shader crate.
+//!
+//! The crate's `Cargo.toml` gets a `spirv-std` dependency and the `crate-type`s that `rust-gpu`
+//! needs, and its lib gets `no_std` when compiled for SPIR-V and a starter entry point if it
+//! doesn't have any yet. Edits keep the manifest's existing formatting and comments.
+
+use anyhow::Context as _;
+
+/// Attribute so that the lib is `no_std` for the GPU but can still use `std` on the CPU.
+const NO_STD: &str = "#![cfg_attr(target_arch = \"spirv\", no_std)]";
+
+/// Entry point for libs that don't have one.
+const STARTER_ENTRY_POINT: &str = "
+use spirv_std::glam::Vec4;
+use spirv_std::spirv;
+
+/// Starter fragment shader that fills everything with one colour.
+#[spirv(fragment)]
+pub fn main_fs(output: &mut Vec4) {
+    *output = Vec4::new(1.0, 0.0, 0.0, 1.0);
+}
+";
+
+/// `cargo gpu init`.
+#[derive(clap::Parser, Debug)]
+pub struct Init {
+    /// The crate to turn into a shader crate.
+    #[clap(default_value = "./")]
+    pub path: std::path::PathBuf,
+
+    /// The `rust-gpu` version that the crate depends on, either a released version from
+    /// crates.io, eg "0.9.0", or a commit of the `rust-gpu` repo.
+    #[clap(long, default_value = crate::new::DEFAULT_RUST_GPU_VERSION)]
+    pub rust_gpu_version: String,
+
+    /// Only show a diff of the changes that would be made.
+    #[clap(long)]
+    pub dry_run: bool,
+}
+
+impl Init {
+    /// Entrypoint.
+    pub fn run(&self) -> anyhow::Result<()> {
+        let manifest_path = self.path.join("Cargo.toml");
+        let manifest = std::fs::read_to_string(&manifest_path)
+            .with_context(|| format!("could not read '{}'", manifest_path.display()))?;
+        let mut document: toml_edit::DocumentMut = manifest
+            .parse()
+            .with_context(|| format!("could not parse '{}'", manifest_path.display()))?;
+        let lib_path = self.path.join(
+            document
+                .get("lib")
+                .and_then(|lib| lib.get("path"))
+                .and_then(toml_edit::Item::as_str)
+                .unwrap_or("src/lib.rs"),
+        );
+        let lib = if lib_path.exists() {
+            std::fs::read_to_string(&lib_path)
+                .with_context(|| format!("could not read '{}'", lib_path.display()))?
+        } else {
+            String::new()
+        };
+
+        edit_manifest(&mut document, &self.rust_gpu_version)?;
+        let changes = [
+            (manifest_path, manifest, document.to_string()),
+            (lib_path, lib.clone(), edit_lib(&lib)),
+        ];
+
+        let mut is_changed = false;
+        for (path, before, after) in changes {
+            if before == after {
+                continue;
+            }
+            is_changed = true;
+            if self.dry_run {
+                crate::user_output!("--- {}\n{}", path.display(), diff(&before, &after));
+            } else {
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                crate::atomic::write(&path, after)?;
+                crate::user_output!("Updated {}\n", path.display());
+            }
+        }
+        if !is_changed {
+            crate::user_output!("{} is already a shader crate\n", self.path.display());
+        }
+        Ok(())
+    }
+}
+
+/// Add the `spirv-std` dependency and the lib's `crate-type`s to a crate's `Cargo.toml`.
+fn edit_manifest(
+    document: &mut toml_edit::DocumentMut,
+    rust_gpu_version: &str,
+) -> anyhow::Result<()> {
+    let dependency: toml_edit::DocumentMut =
+        crate::new::spirv_std_dependency(rust_gpu_version).parse()?;
+    let dependencies = document
+        .entry("dependencies")
+        .or_insert_with(toml_edit::table)
+        .as_table_like_mut()
+        .context("`dependencies` in Cargo.toml isn't a table")?;
+    let spirv_std = dependency
+        .get("spirv-std")
+        .context("`spirv-std` dependency should have been rendered")?;
+    dependencies.insert("spirv-std", spirv_std.clone());
+
+    let lib = document
+        .entry("lib")
+        .or_insert_with(toml_edit::table)
+        .as_table_like_mut()
+        .context("`lib` in Cargo.toml isn't a table")?;
+    let crate_types = lib
+        .entry("crate-type")
+        .or_insert_with(|| toml_edit::value(toml_edit::Array::new()))
+        .as_array_mut()
+        .context("`lib.crate-type` in Cargo.toml isn't an array")?;
+    for crate_type in ["rlib", "cdylib"] {
+        if !crate_types
+            .iter()
+            .any(|existing| existing.as_str() == Some(crate_type))
+        {
+            crate_types.push(crate_type);
+        }
+    }
+    Ok(())
+}
+
+/// Make a lib `no_std` when it's compiled for SPIR-V and add a starter entry point if it doesn't
+/// have any.
+fn edit_lib(lib: &str) -> String {
+    let mut edited = String::new();
+    let mut lines = lib.lines().peekable();
+    if !lib.contains("no_std") {
+        // Inner attributes have to come after any inner doc comments and before any items.
+        while let Some(line) = lines.next_if(|line| {
+            let trimmed = line.trim_start();
+            trimmed.starts_with("//!") || trimmed.starts_with("#![")
+        }) {
+            edited.push_str(line);
+            edited.push('\n');
+        }
+        edited.push_str(NO_STD);
+        edited.push('\n');
+    }
+    for line in lines {
+        edited.push_str(line);
+        edited.push('\n');
+    }
+    if !lib.contains("#[spirv(") {
+        edited.push_str(STARTER_ENTRY_POINT);
+    }
+    edited
+}
+
+/// A line-by-line diff of `before` and `after`, with removed lines starting with `-` and added
+/// lines starting with `+`.
+#[expect(
+    clippy::indexing_slicing,
+    reason = "Every index is checked against the lengths of the lines"
+)]
+fn diff(before_text: &str, after_text: &str) -> String {
+    let before = before_text.lines().collect::<Vec<_>>();
+    let after = after_text.lines().collect::<Vec<_>>();
+
+    // The length of the longest common subsequence of every pair of suffixes.
+    let mut common: Vec<Vec<usize>> = vec![vec![0; after.len() + 1]; before.len() + 1];
+    for (before_index, before_line) in before.iter().enumerate().rev() {
+        for (after_index, after_line) in after.iter().enumerate().rev() {
+            let longest = if before_line == after_line {
+                common[before_index + 1][after_index + 1] + 1
+            } else {
+                common[before_index + 1][after_index].max(common[before_index][after_index + 1])
+            };
+            common[before_index][after_index] = longest;
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut before_index, mut after_index) = (0, 0);
+    while before_index < before.len() || after_index < after.len() {
+        if before_index < before.len()
+            && after_index < after.len()
+            && before[before_index] == after[after_index]
+        {
+            lines.push(format!(" {}", before[before_index]));
+            before_index += 1;
+            after_index += 1;
+        } else if after_index < after.len()
+            && (before_index == before.len()
+                || common[before_index][after_index + 1] >= common[before_index + 1][after_index])
+        {
+            lines.push(format!("+{}", after[after_index]));
+            after_index += 1;
+        } else {
+            lines.push(format!("-{}", before[before_index]));
+            before_index += 1;
+        }
+    }
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_log::test]
+    fn turning_a_crate_into_a_shader_crate() {
+        let mut document: toml_edit::DocumentMut = "[package]\nname = \"shaders\"\n\n\
+            # Keep me\n[lib]\ncrate-type = [\"rlib\"]\n"
+            .parse()
+            .unwrap();
+        edit_manifest(&mut document, "0.9.0").unwrap();
+        let edited = document.to_string();
+        assert!(edited.contains("# Keep me\n[lib]\ncrate-type = [\"rlib\", \"cdylib\"]\n"));
+        assert!(edited.contains("spirv-std = \"0.9.0\""));
+
+        let lib = edit_lib("//! Shaders.\n\npub fn helper() {}\n");
+        assert!(lib.starts_with(&format!("//! Shaders.\n{NO_STD}\n\npub fn helper() {{}}\n")));
+        assert!(lib.contains("#[spirv(fragment)]"));
+        assert_eq!(edit_lib(&lib), lib);
+
+        assert_eq!(diff("a\nb\nc", "a\nc\nd"), " a\n-b\n c\n+d\n");
+    }
+}
@@ -3,7 +3,10 @@ use std::io::Write as _;
 
 use anyhow::Context as _;
 
-use crate::{cache_dir, spirv_cli::SpirvCli, spirv_source::SpirvSource, target_spec_dir};
+use crate::{
+    bundled_target_spec_dir, cache_dir, disk_size, installs_dir, spirv_cli::SpirvCli,
+    spirv_source::SpirvSource, target_spec_dir,
+};
 use spirv_builder_cli::args::InstallArgs;
 
 /// These are the files needed to create the dedicated, per-shader `rust-gpu` builder create.
@@ -30,8 +33,8 @@ const SPIRV_BUILDER_FILES: &[(&str, &str)] = &[
     ),
 ];
 
-/// Metadata for the compile targets supported by `rust-gpu`
-const TARGET_SPECS: &[(&str, &str)] = &[
+/// Metadata for the compile targets supported by `rust-gpu`.
+pub const TARGET_SPECS: &[(&str, &str)] = &[
     (
         "spirv-unknown-opengl4.0.json",
         include_str!("../target-specs/spirv-unknown-opengl4.0.json"),
@@ -76,6 +79,10 @@ const TARGET_SPECS: &[(&str, &str)] = &[
         "spirv-unknown-spv1.5.json",
         include_str!("../target-specs/spirv-unknown-spv1.5.json"),
     ),
+    (
+        "spirv-unknown-spv1.6.json",
+        include_str!("../target-specs/spirv-unknown-spv1.6.json"),
+    ),
     (
         "spirv-unknown-vulkan1.0.json",
         include_str!("../target-specs/spirv-unknown-vulkan1.0.json"),
@@ -92,34 +99,288 @@ const TARGET_SPECS: &[(&str, &str)] = &[
         "spirv-unknown-vulkan1.2.json",
         include_str!("../target-specs/spirv-unknown-vulkan1.2.json"),
     ),
+    (
+        "spirv-unknown-vulkan1.3.json",
+        include_str!("../target-specs/spirv-unknown-vulkan1.3.json"),
+    ),
+    (
+        "spirv-unknown-vulkan1.4.json",
+        include_str!("../target-specs/spirv-unknown-vulkan1.4.json"),
+    ),
 ];
 
-/// `cargo gpu install`
-#[derive(clap::Parser, Debug, serde::Deserialize, serde::Serialize)]
+/// The bundled target specs that only `rust-gpu`s from [`NEWER_TARGETS_DATE`] can compile for.
+pub const NEWER_TARGET_SPECS: &[&str] = &[
+    "spirv-unknown-spv1.6.json",
+    "spirv-unknown-vulkan1.3.json",
+    "spirv-unknown-vulkan1.4.json",
+];
+
+/// The date of the first `rust-gpu` that can compile for the [`NEWER_TARGET_SPECS`], which came
+/// with the 0.11 builder interface.
+pub const NEWER_TARGETS_DATE: &str = V0_11_DATE;
+
+/// Whether a `rust-gpu` from `date` can compile for the bundled target spec `filename`. A
+/// `rust-gpu` of unknown date, like a `--codegen-backend-path` one, is assumed to be able to.
+pub fn is_bundled_spec_supported(
+    filename: &str,
+    date: Option<chrono::NaiveDate>,
+) -> anyhow::Result<bool> {
+    let Some(version_date) = date else {
+        return Ok(true);
+    };
+    Ok(!NEWER_TARGET_SPECS.contains(&filename)
+        || version_date >= chrono::NaiveDate::parse_from_str(NEWER_TARGETS_DATE, "%Y-%m-%d")?)
+}
+
+/// The name of the file in each install directory that records what was installed.
+const INSTALL_METADATA_FILE: &str = "cargo-gpu-install.json";
+
+/// What an install directory contains, so that installs can be listed without having to reverse
+/// their directory names.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct PairMetadata {
+    /// The repo or path of `rust-gpu`.
+    pub source: String,
+    /// The version or revision of `rust-gpu`.
+    pub version: String,
+    /// The toolchain channel that `rust-gpu` uses.
+    pub channel: String,
+    /// The cached checkout of the `rust-gpu` repo that the install was made from.
+    #[serde(default)]
+    pub checkout: Option<std::path::PathBuf>,
+    /// The SHA-256 of each installed artifact, keyed by its filename. Empty for installs made
+    /// before checksums were recorded.
+    #[serde(default)]
+    pub checksums: std::collections::BTreeMap<String, String>,
+    /// What the install's `spirv-builder` supports, if its source could be searched.
+    #[serde(default)]
+    pub builder_support: Option<crate::builder_support::BuilderSupport>,
+}
+
+impl PairMetadata {
+    /// The metadata of the install in `install_dir`, if it has any.
+    #[must_use]
+    pub fn read(install_dir: &std::path::Path) -> Option<Self> {
+        let contents = std::fs::read_to_string(install_dir.join(INSTALL_METADATA_FILE)).ok()?;
+        serde_json::from_str(&contents).ok()
+    }
+
+    /// Check that the artifacts in `install_dir` still match the checksums recorded when they
+    /// were installed.
+    ///
+    /// # Errors
+    /// If an artifact is missing, or has been corrupted or only partly replaced.
+    pub fn verify(&self, install_dir: &std::path::Path) -> anyhow::Result<()> {
+        for (filename, expected) in &self.checksums {
+            let path = install_dir.join(filename);
+            let bytes = std::fs::read(&path)
+                .with_context(|| format!("could not read installed '{}'", path.display()))?;
+            let actual = crate::prebuilt::sha256_hex(&bytes);
+            anyhow::ensure!(
+                &actual == expected,
+                "installed `{filename}` doesn't match its checksum, expected {expected} but got \
+                 {actual}"
+            );
+        }
+        Ok(())
+    }
+}
+
+/// The SHA-256 of each of the `artifacts`, keyed by their filenames.
+fn artifact_checksums(
+    artifacts: &[&std::path::Path],
+) -> anyhow::Result<std::collections::BTreeMap<String, String>> {
+    artifacts
+        .iter()
+        .map(|path| {
+            let filename = path
+                .file_name()
+                .with_context(|| format!("'{}' has no filename", path.display()))?;
+            let bytes = std::fs::read(path)
+                .with_context(|| format!("could not read '{}'", path.display()))?;
+            Ok((
+                filename.to_string_lossy().into_owned(),
+                crate::prebuilt::sha256_hex(&bytes),
+            ))
+        })
+        .collect()
+}
+
+/// An installed binary pair in the cache directory.
+#[derive(Debug)]
+pub struct InstalledPair {
+    /// The name of the install's directory.
+    pub name: String,
+    /// The install's directory.
+    pub path: std::path::PathBuf,
+    /// What was installed, if the install was made by a version of `cargo gpu` that records it.
+    pub metadata: Option<PairMetadata>,
+    /// Total size of the install on disk, in bytes.
+    pub size: u64,
+    /// When the install was last used to build a shader.
+    pub last_used: Option<std::time::SystemTime>,
+}
+
+/// Every installed binary pair in the cache directory, sorted by name.
+pub fn installed_pairs() -> anyhow::Result<Vec<InstalledPair>> {
+    let installs = installs_dir()?;
+    if !installs.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut pairs = Vec::new();
+    for maybe_entry in std::fs::read_dir(&installs)? {
+        let path = maybe_entry?.path();
+        if !path.is_dir() {
+            continue;
+        }
+        let metadata_path = path.join(INSTALL_METADATA_FILE);
+        let metadata = PairMetadata::read(&path);
+        // The metadata file is rewritten every time the install is used.
+        let last_used = std::fs::metadata(&metadata_path)
+            .or_else(|_| std::fs::metadata(&path))
+            .and_then(|file_metadata| file_metadata.modified())
+            .ok();
+        pairs.push(InstalledPair {
+            name: path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            path: path.clone(),
+            metadata,
+            size: disk_size(&path)?,
+            last_used,
+        });
+    }
+    pairs.sort_by(|left, right| left.name.cmp(&right.name));
+    Ok(pairs)
+}
+
+/// `cargo gpu install`.
+#[derive(clap::Parser, Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[expect(
+    clippy::struct_field_names,
+    reason = "`spirv_install` are the args of `spirv-builder-cli`'s install, not of this struct"
+)]
 pub struct Install {
-    /// CLI arguments for installing the Rust toolchain and components
+    /// CLI arguments for installing the Rust toolchain and components.
     #[clap(flatten)]
     pub spirv_install: InstallArgs,
+
+    /// Format of the result written to stdout. With `json`, progress messages go to stderr.
+    #[clap(long, value_enum, default_value_t)]
+    #[serde(skip)]
+    pub output_format: crate::output::Format,
+
+    /// Format of progress messages. With `json`, progress is reported as one JSON object per line
+    /// on stdout, similarly to `cargo build --message-format json`.
+    #[clap(long, value_enum, default_value_t)]
+    #[serde(skip)]
+    pub message_format: crate::output::MessageFormat,
+
+    /// Record how long each phase of the install or build takes, and write a report to
+    /// `cargo-gpu-timings/` in the current directory.
+    #[clap(long)]
+    #[serde(skip)]
+    pub timings: bool,
+
+    /// Install from an archive made by `cargo gpu package-install`, without accessing the
+    /// network.
+    #[clap(long)]
+    #[serde(skip)]
+    pub from_archive: Option<std::path::PathBuf>,
+
+    /// The target spec files that the install can compile for. Set by [`Install::run`].
+    #[clap(skip)]
+    #[serde(skip)]
+    pub target_spec_dir: std::path::PathBuf,
 }
 
 impl Install {
     /// Returns a [`SpirvCLI`] instance, responsible for ensuring the right version of the `spirv-builder-cli` crate.
+    ///
+    /// When the shader crate has a `rust-gpu.lock`, the `rust-gpu` that it pins is used, unless
+    /// `--spirv-builder-source`, `--spirv-builder-version` or `--rust-toolchain` are given.
     fn spirv_cli(&self, shader_crate_path: &std::path::PathBuf) -> anyhow::Result<SpirvCli> {
+        if let Some(lock) = self.pin(shader_crate_path)? {
+            log::debug!(
+                "using `rust-gpu` {} and {} from `{}`",
+                lock.revision,
+                lock.channel,
+                crate::pin::FILE
+            );
+            return SpirvCli::new(
+                shader_crate_path,
+                lock.spirv_builder_source(),
+                Some(lock.revision),
+                Some(lock.channel),
+            );
+        }
         SpirvCli::new(
             shader_crate_path,
             self.spirv_install.spirv_builder_source.clone(),
             self.spirv_install.spirv_builder_version.clone(),
             self.spirv_install.rust_toolchain.clone(),
-            self.spirv_install.auto_install_rust_toolchain,
         )
     }
 
+    /// Whether the `rust-gpu` to install is left to the shader crate, so that it can be pinned.
+    const fn is_pinnable(&self) -> bool {
+        self.spirv_install.spirv_builder_source.is_none()
+            && self.spirv_install.spirv_builder_version.is_none()
+            && self.spirv_install.rust_toolchain.is_none()
+    }
+
+    /// The shader crate's `rust-gpu.lock`, if it has one and it isn't overridden.
+    fn pin(
+        &self,
+        shader_crate_path: &std::path::Path,
+    ) -> anyhow::Result<Option<crate::pin::RustGpuLock>> {
+        if !self.is_pinnable() {
+            return Ok(None);
+        }
+        crate::pin::read(shader_crate_path)
+    }
+
+    /// Pin the installed `rust-gpu` in the shader crate's `rust-gpu.lock`, or, if it's already
+    /// pinned, check that the target specs are still the pinned ones.
+    fn write_or_check_pin(&self, spirv_version: &SpirvCli) -> anyhow::Result<()> {
+        let shader_crate = &self.spirv_install.shader_crate;
+        if !self.is_pinnable() {
+            return Ok(());
+        }
+        if let Some(lock) = crate::pin::read(shader_crate)? {
+            return lock.check_target_specs(&self.target_spec_dir);
+        }
+        let Some(lock) = crate::pin::RustGpuLock::pinning(
+            &spirv_version.source,
+            &spirv_version.channel,
+            &self.target_spec_dir,
+        )?
+        else {
+            return Ok(());
+        };
+        crate::pin::write(shader_crate, &lock)?;
+        crate::user_output!(
+            "Pinned `rust-gpu` {} and {} in '{}'\n",
+            lock.revision,
+            lock.channel,
+            shader_crate.join(crate::pin::FILE).display()
+        );
+        Ok(())
+    }
+
     /// Create the `spirv-builder-cli` crate.
     fn write_source_files(&self) -> anyhow::Result<()> {
         let spirv_cli = self.spirv_cli(&self.spirv_install.shader_crate)?;
         let checkout = spirv_cli.cached_checkout_path()?;
         std::fs::create_dir_all(checkout.join("src"))?;
         for (filename, contents) in SPIRV_BUILDER_FILES {
+            if filename == &"Cargo.lock" && crate::archive::is_vendored(&checkout) {
+                log::debug!("keeping the `Cargo.lock` that matches the vendored sources");
+                continue;
+            }
             log::debug!("writing {filename}");
             let path = checkout.join(filename);
             let mut file = std::fs::File::create(&path)?;
@@ -161,20 +422,214 @@ impl Install {
         updated.collect()
     }
 
-    /// Add the target spec files to the crate.
-    fn write_target_spec_files(&self) -> anyhow::Result<()> {
+    /// Add the target spec files to `spec_dir`. They're copied from the checked-out `rust-gpu`
+    /// repo so that they always match the compiler, falling back to the bundled specs for any that
+    /// the checkout doesn't have. Bundled specs for targets that a `rust-gpu` from `date` can't
+    /// compile for are removed, so that they aren't offered.
+    fn write_target_spec_files(
+        &mut self,
+        spec_dir: std::path::PathBuf,
+        maybe_checkout_specs: Option<std::path::PathBuf>,
+        date: Option<chrono::NaiveDate>,
+    ) -> anyhow::Result<()> {
+        std::fs::create_dir_all(&spec_dir)?;
+        let _lock = crate::lock(&spec_dir)?;
+        let copied = if let Some(checkout_specs) = maybe_checkout_specs {
+            log::debug!("copying target specs from '{}'", checkout_specs.display());
+            copy_target_specs(&checkout_specs, &spec_dir)?
+        } else {
+            log::debug!("no target specs in the `rust-gpu` checkout, using the bundled ones");
+            Vec::new()
+        };
+
         for (filename, contents) in TARGET_SPECS {
-            let path = target_spec_dir()?.join(filename);
+            if copied
+                .iter()
+                .any(|copied_filename| copied_filename == filename)
+            {
+                continue;
+            }
+            let path = spec_dir.join(filename);
+            if !is_bundled_spec_supported(filename, date)? {
+                if path.is_file() {
+                    log::debug!("removing unsupported target spec {filename}");
+                    std::fs::remove_file(&path)?;
+                }
+                continue;
+            }
             if !path.is_file() || self.spirv_install.force_spirv_cli_rebuild {
-                let mut file = std::fs::File::create(&path)?;
-                file.write_all(contents.as_bytes())?;
+                crate::atomic::write(&path, contents)?;
             }
         }
+        self.target_spec_dir = spec_dir;
+        Ok(())
+    }
+
+    /// Try to install a prebuilt binary pair, returning whether that worked. Not finding a
+    /// prebuilt pair isn't an error, because we can always fall back to compiling from source.
+    fn try_download_prebuilt(
+        &self,
+        checkout: &std::path::Path,
+        dylib_filename: &str,
+        dest_dylib_path: &std::path::Path,
+        dest_cli_path: &std::path::Path,
+    ) -> bool {
+        if !self.spirv_install.download_prebuilt {
+            return false;
+        }
+        if crate::offline::is_enabled() {
+            log::info!("not downloading a prebuilt binary pair, as we're offline");
+            return false;
+        }
+        let Some(install_name) = checkout.file_name() else {
+            return false;
+        };
+
+        crate::user_output!(
+            "Downloading prebuilt `spirv-builder-cli` and `rustc_codegen_spirv` from {}\n",
+            self.spirv_install.prebuilt_server
+        );
+        let cli_filename = format!("spirv-builder-cli{}", std::env::consts::EXE_SUFFIX);
+        let result = crate::prebuilt::download_pair(
+            &self.spirv_install.prebuilt_server,
+            &install_name.to_string_lossy(),
+            &[
+                (dylib_filename, dest_dylib_path),
+                (&cli_filename, dest_cli_path),
+            ],
+        );
+        match result {
+            Ok(()) => true,
+            Err(error) => {
+                log::warn!("could not install prebuilt binary pair: {error:?}");
+                crate::user_output!(
+                    "No usable prebuilt binary pair, compiling from source instead\n"
+                );
+                false
+            }
+        }
+    }
+
+    /// Where to compile the binary pair for the install in `checkout`. It's always given to
+    /// `cargo` explicitly, so that a `CARGO_TARGET_DIR` shared with other projects can't move the
+    /// artifacts from where they're expected.
+    fn target_dir(&self, checkout: &std::path::Path) -> anyhow::Result<std::path::PathBuf> {
+        Ok(match &self.spirv_install.install_target_dir {
+            Some(dir) => std::path::absolute(dir)?.join(
+                checkout
+                    .file_name()
+                    .context("install directory has no name")?,
+            ),
+            None => checkout.join("target"),
+        })
+    }
+
+    /// Compile the binary pair through `--install-rustc-wrapper`, if there is one. Incremental
+    /// compilation is turned off for it, as compile caches like `sccache` can't cache incremental
+    /// builds.
+    fn configure_rustc_wrapper(&self, command: &mut std::process::Command) {
+        if let Some(wrapper) = &self.spirv_install.install_rustc_wrapper {
+            log::debug!("compiling the binary pair with `{}`", wrapper.display());
+            command
+                .env("RUSTC_WRAPPER", wrapper)
+                .env("CARGO_INCREMENTAL", "0");
+        }
+    }
+
+    /// Compile the binary pair from source in the `checkout` dir, and move the artifacts to the
+    /// top of it.
+    fn compile_binary_pair(
+        &self,
+        spirv_version: &SpirvCli,
+        checkout: &std::path::Path,
+        dylib_filename: &str,
+    ) -> anyhow::Result<()> {
+        let target_dir = self.target_dir(checkout)?;
+        let release = target_dir.join("release");
+        let dylib_path = release.join(dylib_filename);
+        let dest_dylib_path = checkout.join(dylib_filename);
+        let dest_cli_path = checkout.join("spirv-builder-cli");
+
+        log::debug!(
+            "writing spirv-builder-cli source files into '{}'",
+            checkout.display()
+        );
+        self.write_source_files()?;
+
+        crate::user_output!(
+            "Compiling shader-specific `spirv-builder-cli` for {}\n",
+            self.spirv_install.shader_crate.display()
+        );
+        crate::output::emit(&crate::output::Event::Compiling {
+            package: "spirv-builder-cli".to_owned(),
+            path: checkout.to_path_buf(),
+        });
+
+        let toolchain_path = self.toolchain_path()?;
+        let mut build_command =
+            crate::spirv_cli::cargo_command(&spirv_version.channel, toolchain_path.as_deref());
+        build_command
+            .current_dir(checkout)
+            .args(["build", "--release"])
+            .args(["--no-default-features"])
+            .arg("--target-dir")
+            .arg(&target_dir)
+            .args(crate::offline::cargo_args());
+        crate::output::configure_child_color(&mut build_command);
+        self.configure_rustc_wrapper(&mut build_command);
+
+        build_command.args([
+            "--features",
+            &Self::get_required_spirv_builder_version(spirv_version)?,
+        ]);
+
+        log::debug!("building artifacts with `{build_command:?}`");
+
+        build_command
+            .stdout(crate::output::child_stdout())
+            .stderr(std::process::Stdio::inherit());
+        let build_output = crate::log_file::output(&mut build_command, false)?;
+        anyhow::ensure!(
+            build_output.status.success() || !crate::offline::is_enabled(),
+            "couldn't compile `spirv-builder-cli` offline, all of its dependencies need to \
+             already be in cargo's cache"
+        );
+        anyhow::ensure!(build_output.status.success(), "...build error!");
+
+        if dylib_path.is_file() {
+            log::info!("successfully built {}", dylib_path.display());
+            std::fs::rename(&dylib_path, &dest_dylib_path)?;
+        } else {
+            log::error!("could not find {}", dylib_path.display());
+            anyhow::bail!("spirv-builder-cli build failed");
+        }
+
+        let cli_path = if cfg!(target_os = "windows") {
+            release.join("spirv-builder-cli").with_extension("exe")
+        } else {
+            release.join("spirv-builder-cli")
+        };
+        if cli_path.is_file() {
+            log::info!("successfully built {}", cli_path.display());
+            std::fs::rename(&cli_path, &dest_cli_path)?;
+        } else {
+            log::error!("could not find {}", cli_path.display());
+            log::debug!("contents of '{}':", release.display());
+            for maybe_entry in std::fs::read_dir(&release)? {
+                let entry = maybe_entry?;
+                log::debug!("{}", entry.file_name().to_string_lossy());
+            }
+            anyhow::bail!("spirv-builder-cli build failed");
+        }
         Ok(())
     }
 
     /// Install the binary pair and return the paths, (dylib, cli).
     pub fn run(&mut self) -> anyhow::Result<std::path::PathBuf> {
+        if self.spirv_install.offline {
+            crate::offline::enable();
+        }
+
         // Ensure the cache dir exists
         let cache_dir = cache_dir()?;
         log::info!("cache directory is '{}'", cache_dir.display());
@@ -182,18 +637,23 @@ impl Install {
             format!("could not create cache directory '{}'", cache_dir.display())
         })?;
 
+        if let Some(backend) = self.spirv_install.codegen_backend_path.clone() {
+            return self.use_own_codegen_backend(&backend);
+        }
+
         let spirv_version = self.spirv_cli(&self.spirv_install.shader_crate)?;
-        spirv_version.ensure_toolchain_and_components_exist()?;
+        self.ensure_toolchain(&spirv_version.channel)?;
 
         let checkout = spirv_version.cached_checkout_path()?;
-        let release = checkout.join("target").join("release");
+        // Held until the install is complete, so that concurrent invocations don't race on
+        // writing the source files and renaming the artifacts.
+        let _lock = crate::lock(&checkout)?;
 
         let dylib_filename = format!(
             "{}rustc_codegen_spirv{}",
             std::env::consts::DLL_PREFIX,
             std::env::consts::DLL_SUFFIX
         );
-        let dylib_path = release.join(&dylib_filename);
         let dest_dylib_path = checkout.join(&dylib_filename);
         let dest_cli_path = checkout.join("spirv-builder-cli");
         if dest_dylib_path.is_file() && dest_cli_path.is_file() {
@@ -203,92 +663,446 @@ impl Install {
             );
         }
 
-        if dest_dylib_path.is_file()
+        let previous = PairMetadata::read(&checkout);
+        let is_up_to_date = dest_dylib_path.is_file()
             && dest_cli_path.is_file()
             && !self.spirv_install.force_spirv_cli_rebuild
-        {
+            && previous
+                .as_ref()
+                .is_none_or(|metadata| is_intact(metadata, &checkout));
+        if is_up_to_date {
             log::info!("...and so we are aborting the install step.");
         } else {
-            log::debug!(
-                "writing spirv-builder-cli source files into '{}'",
-                checkout.display()
-            );
-            self.write_source_files()?;
-            self.write_target_spec_files()?;
-
-            crate::user_output!(
-                "Compiling shader-specific `spirv-builder-cli` for {}\n",
-                self.spirv_install.shader_crate.display()
-            );
-
-            let mut build_command = std::process::Command::new("cargo");
-            build_command
-                .current_dir(&checkout)
-                .arg(format!("+{}", spirv_version.channel))
-                .args(["build", "--release"])
-                .args(["--no-default-features"]);
-
-            build_command.args([
-                "--features",
-                &Self::get_required_spirv_builder_version(spirv_version.date)?,
-            ]);
-
-            log::debug!("building artifacts with `{:?}`", build_command);
-
-            let build_output = build_command
-                .stdout(std::process::Stdio::inherit())
-                .stderr(std::process::Stdio::inherit())
-                .output()?;
-            anyhow::ensure!(build_output.status.success(), "...build error!");
-
-            if dylib_path.is_file() {
-                log::info!("successfully built {}", dylib_path.display());
-                std::fs::rename(&dylib_path, &dest_dylib_path)?;
+            crate::output::emit(&crate::output::Event::InstallStarted {
+                source: spirv_version.source.to_repo(),
+                version: spirv_version.source.to_version(),
+                channel: spirv_version.channel.clone(),
+            });
+            let install_name = checkout.display();
+            if crate::timings::time("download prebuilt binary pair", &install_name, || {
+                self.try_download_prebuilt(
+                    &checkout,
+                    &dylib_filename,
+                    &dest_dylib_path,
+                    &dest_cli_path,
+                )
+            }) {
+                log::debug!("installed prebuilt binary pair");
             } else {
-                log::error!("could not find {}", dylib_path.display());
-                anyhow::bail!("spirv-builder-cli build failed");
+                crate::timings::time("compile spirv-builder-cli", &install_name, || {
+                    self.compile_binary_pair(&spirv_version, &checkout, &dylib_filename)
+                })
+                .map_err(crate::exit::Failure::CodegenBackendBuild)?;
             }
+        }
 
-            let cli_path = if cfg!(target_os = "windows") {
-                release.join("spirv-builder-cli").with_extension("exe")
-            } else {
-                release.join("spirv-builder-cli")
-            };
-            if cli_path.is_file() {
-                log::info!("successfully built {}", cli_path.display());
-                std::fs::rename(&cli_path, &dest_cli_path)?;
-            } else {
-                log::error!("could not find {}", cli_path.display());
-                log::debug!("contents of '{}':", release.display());
-                for maybe_entry in std::fs::read_dir(&release)? {
-                    let entry = maybe_entry?;
-                    log::debug!("{}", entry.file_name().to_string_lossy());
+        let metadata = PairMetadata {
+            source: spirv_version.source.to_repo(),
+            version: spirv_version.source.to_version(),
+            channel: spirv_version.channel.clone(),
+            checkout: Some(spirv_version.source.to_dirname()?),
+            // Already verified if the install was up to date, so there's no need to hash again.
+            checksums: match previous {
+                Some(metadata) if is_up_to_date && !metadata.checksums.is_empty() => {
+                    metadata.checksums
                 }
-                anyhow::bail!("spirv-builder-cli build failed");
-            }
-        }
+                _ => artifact_checksums(&[&dest_dylib_path, &dest_cli_path])?,
+            },
+            builder_support: Self::builder_support(&spirv_version),
+        };
+        std::fs::write(
+            checkout.join(INSTALL_METADATA_FILE),
+            serde_json::to_string_pretty(&metadata)?,
+        )?;
+
+        // Always refresh the specs, in case the checkout's specs have changed since installing.
+        self.write_target_spec_files(
+            target_spec_dir(&checkout),
+            spirv_version.source.target_specs_dir()?,
+            Some(spirv_version.date),
+        )?;
+        self.write_or_check_pin(&spirv_version)?;
 
         self.spirv_install.dylib_path = dest_dylib_path;
 
         Ok(dest_cli_path)
     }
 
+    /// The `--toolchain-path`, if there is one, as an absolute path.
+    pub fn toolchain_path(&self) -> anyhow::Result<Option<std::path::PathBuf>> {
+        self.spirv_install
+            .toolchain_path
+            .as_ref()
+            .map(std::path::absolute)
+            .transpose()
+            .context("could not find the toolchain path")
+    }
+
+    /// Make sure that the toolchain for `channel` can build `rust-gpu`: either check the one at
+    /// `--toolchain-path`, or install it with `rustup`.
+    fn ensure_toolchain(&self, channel: &str) -> anyhow::Result<()> {
+        self.toolchain_path()?
+            .map_or_else(
+                || {
+                    SpirvCli::ensure_toolchain(
+                        channel,
+                        self.spirv_install.auto_install_rust_toolchain,
+                    )
+                },
+                |path| crate::spirv_cli::validate_toolchain_path(&path),
+            )
+            .map_err(|error| crate::exit::Failure::ToolchainMissing(error).into())
+    }
+
+    /// Skip installing, and build with a `rustc_codegen_spirv` that the user built themselves.
+    /// Returns the `spirv-builder-cli` to run it with.
+    fn use_own_codegen_backend(
+        &mut self,
+        backend: &std::path::Path,
+    ) -> anyhow::Result<std::path::PathBuf> {
+        let backend_path = std::path::absolute(backend)?;
+        anyhow::ensure!(
+            backend_path.is_file(),
+            "the codegen backend '{}' doesn't exist",
+            backend_path.display()
+        );
+        let channel = self.spirv_install.rust_toolchain.clone().context(
+            "`--codegen-backend-path` needs `--rust-toolchain`, the toolchain that the backend \
+             was built with",
+        )?;
+        self.ensure_toolchain(&channel)?;
+
+        let cli_path = own_backend_cli_path(&backend_path, &channel, &installed_pairs()?)
+            .with_context(|| {
+                format!(
+                    "there's no `spirv-builder-cli` to run the codegen backend with, either put \
+                     one next to it, or run `cargo gpu install --rust-toolchain {channel}` once"
+                )
+            })?;
+        log::info!(
+            "building with the codegen backend '{}' and '{}'",
+            backend_path.display(),
+            cli_path.display()
+        );
+        self.write_target_spec_files(bundled_target_spec_dir()?, None, None)?;
+        self.spirv_install.dylib_path = backend_path;
+        Ok(cli_path)
+    }
+
+    /// What the `spirv-builder` in the `rust-gpu` checkout supports, if it has its source.
+    fn builder_support(spirv_version: &SpirvCli) -> Option<crate::builder_support::BuilderSupport> {
+        let checkout = spirv_version.source.to_dirname().ok()?;
+        crate::builder_support::BuilderSupport::detect(&checkout)
+    }
+
     /// The `spirv-builder` crate from the main `rust-gpu` repo hasn't always been setup to
     /// interact with `cargo-gpu`. Older versions don't have the same `SpirvBuilder` interface. So
-    /// here we choose the right Cargo feature to enable/disable code in `spirv-builder-cli`.
-    ///
-    /// TODO:
-    ///   * Warn the user that certain `cargo-gpu` features aren't available when building with
-    ///     older versions of `spirv-builder`, eg setting the target spec.
-    fn get_required_spirv_builder_version(date: chrono::NaiveDate) -> anyhow::Result<String> {
+    /// here we choose the right Cargo feature to enable/disable code in `spirv-builder-cli`, from
+    /// what the `spirv-builder` source supports, or else from the version's date.
+    fn get_required_spirv_builder_version(spirv_version: &SpirvCli) -> anyhow::Result<String> {
+        if let Some(support) = Self::builder_support(spirv_version) {
+            log::debug!("`spirv-builder` supports {support:?}");
+            return Ok(support.cli_feature().to_owned());
+        }
+        log::debug!("no `spirv-builder` source to search, choosing its interface by date");
+        Self::spirv_builder_version_by_date(spirv_version.date)
+    }
+
+    /// The `spirv-builder-cli` feature for a `rust-gpu` version from its date, for when there's
+    /// no source to search.
+    fn spirv_builder_version_by_date(date: chrono::NaiveDate) -> anyhow::Result<String> {
         let parse_date = chrono::NaiveDate::parse_from_str;
-        let pre_cli_date = parse_date("2024-04-24", "%Y-%m-%d")?;
+        let pre_cli_date = parse_date(PRE_CLI_DATE, "%Y-%m-%d")?;
+        let v0_11_date = parse_date(V0_11_DATE, "%Y-%m-%d")?;
 
         Ok(if date < pre_cli_date {
             "spirv-builder-pre-cli"
-        } else {
+        } else if date < v0_11_date {
             "spirv-builder-0_10"
+        } else {
+            "spirv-builder-0_11"
         }
         .into())
     }
 }
+
+/// The date of the first `rust-gpu` that `cargo gpu` can drive, earlier ones need the
+/// `spirv-builder-pre-cli` feature.
+const PRE_CLI_DATE: &str = "2024-04-24";
+
+/// The date from which `spirv-builder` has the builder options of the 0.11 releases, which are
+/// also those of `rust-gpu`'s main branch.
+const V0_11_DATE: &str = "2025-03-29";
+
+/// Whether an install's artifacts match their recorded checksums. If they don't, they're
+/// installed again, rather than leaving the build to fail in some baffling way.
+fn is_intact(metadata: &PairMetadata, install_dir: &std::path::Path) -> bool {
+    let result = crate::timings::time("verify binary pair", install_dir.display(), || {
+        metadata.verify(install_dir)
+    });
+    match result {
+        Ok(()) => true,
+        Err(error) => {
+            crate::user_output!("{error:#}, installing it again\n");
+            false
+        }
+    }
+}
+
+/// Copy every target spec JSON file in `from` into `to`, returning the copied filenames. Files
+/// that are already up to date aren't rewritten.
+fn copy_target_specs(from: &std::path::Path, to: &std::path::Path) -> anyhow::Result<Vec<String>> {
+    let mut copied = Vec::new();
+    for maybe_entry in std::fs::read_dir(from)
+        .with_context(|| format!("could not read target specs in '{}'", from.display()))?
+    {
+        let path = maybe_entry?.path();
+        if path.extension().is_none_or(|extension| extension != "json") {
+            continue;
+        }
+        let Some(filename) = path.file_name() else {
+            continue;
+        };
+        let contents = std::fs::read(&path)
+            .with_context(|| format!("could not read target spec '{}'", path.display()))?;
+        let dest = to.join(filename);
+        if std::fs::read(&dest).ok().as_ref() != Some(&contents) {
+            crate::atomic::write(&dest, &contents)?;
+        }
+        copied.push(filename.to_string_lossy().into_owned());
+    }
+    Ok(copied)
+}
+
+/// The `spirv-builder-cli` to run a user's own codegen backend with: the one next to the backend,
+/// or else the first installed one for the same toolchain.
+fn own_backend_cli_path(
+    backend: &std::path::Path,
+    channel: &str,
+    pairs: &[InstalledPair],
+) -> Option<std::path::PathBuf> {
+    let next_to_backend = backend.with_file_name("spirv-builder-cli");
+    if next_to_backend.is_file() {
+        return Some(next_to_backend);
+    }
+    pairs
+        .iter()
+        .filter(|pair| {
+            pair.metadata
+                .as_ref()
+                .is_some_and(|metadata| metadata.channel == channel)
+        })
+        .map(|pair| pair.path.join("spirv-builder-cli"))
+        .find(|cli_path| cli_path.is_file())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_log::test]
+    fn gating_newer_target_specs_on_the_rust_gpu_date() {
+        let date = |date: &str| Some(chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap());
+        assert!(
+            is_bundled_spec_supported("spirv-unknown-vulkan1.2.json", date("2024-04-24")).unwrap()
+        );
+        assert!(
+            !is_bundled_spec_supported("spirv-unknown-vulkan1.3.json", date("2024-04-24")).unwrap()
+        );
+        assert!(is_bundled_spec_supported(
+            "spirv-unknown-vulkan1.4.json",
+            date(NEWER_TARGETS_DATE)
+        )
+        .unwrap());
+        assert!(is_bundled_spec_supported("spirv-unknown-spv1.6.json", None).unwrap());
+        for filename in NEWER_TARGET_SPECS {
+            assert!(TARGET_SPECS.iter().any(|(bundled, _)| bundled == filename));
+        }
+    }
+
+    #[test_log::test]
+    fn choosing_the_spirv_builder_feature_by_date() {
+        let feature = |date: &str| {
+            Install::spirv_builder_version_by_date(
+                chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap(),
+            )
+            .unwrap()
+        };
+        assert_eq!(feature("2023-08-04"), "spirv-builder-pre-cli");
+        assert_eq!(feature(PRE_CLI_DATE), "spirv-builder-0_10");
+        assert_eq!(feature("2025-03-28"), "spirv-builder-0_10");
+        assert_eq!(feature(V0_11_DATE), "spirv-builder-0_11");
+        assert_eq!(feature("2026-10-01"), "spirv-builder-0_11");
+    }
+
+    #[test_log::test]
+    fn listing_installed_pairs() {
+        crate::test::tests_teardown();
+        let installs = installs_dir().unwrap();
+        let with_metadata = installs.join("0_10_0+nightly-2024-04-24");
+        std::fs::create_dir_all(&with_metadata).unwrap();
+        let metadata = PairMetadata {
+            source: "https://github.com/Rust-GPU/rust-gpu".to_owned(),
+            version: "0.10.0".to_owned(),
+            channel: "nightly-2024-04-24".to_owned(),
+            checkout: None,
+            checksums: std::collections::BTreeMap::new(),
+            builder_support: None,
+        };
+        std::fs::write(
+            with_metadata.join(INSTALL_METADATA_FILE),
+            serde_json::to_string(&metadata).unwrap(),
+        )
+        .unwrap();
+        std::fs::create_dir_all(installs.join("an-old-install")).unwrap();
+
+        let pairs = installed_pairs().unwrap();
+        let [new, old] = pairs.as_slice() else {
+            panic!("expected 2 installs, got {pairs:#?}");
+        };
+        assert_eq!(new.name, "0_10_0+nightly-2024-04-24");
+        assert_eq!(new.metadata, Some(metadata));
+        assert!(new.last_used.is_some());
+        assert_eq!(old.name, "an-old-install");
+        assert_eq!(old.metadata, None);
+    }
+
+    #[test_log::test]
+    fn verifying_artifact_checksums() {
+        let install_dir = cache_dir().unwrap().join("verifying_artifact_checksums");
+        std::fs::create_dir_all(&install_dir).unwrap();
+        let cli_path = install_dir.join("spirv-builder-cli");
+        std::fs::write(&cli_path, "a binary").unwrap();
+
+        let metadata = PairMetadata {
+            source: "https://github.com/Rust-GPU/rust-gpu".to_owned(),
+            version: "0.10.0".to_owned(),
+            channel: "nightly-2024-04-24".to_owned(),
+            checkout: None,
+            checksums: artifact_checksums(&[&cli_path]).unwrap(),
+            builder_support: None,
+        };
+        assert!(metadata.checksums.contains_key("spirv-builder-cli"));
+        metadata.verify(&install_dir).unwrap();
+        assert!(is_intact(&metadata, &install_dir));
+
+        std::fs::write(&cli_path, "a truncated bin").unwrap();
+        assert!(metadata.verify(&install_dir).is_err());
+        assert!(!is_intact(&metadata, &install_dir));
+        std::fs::remove_file(&cli_path).unwrap();
+        assert!(metadata.verify(&install_dir).is_err());
+    }
+
+    #[test_log::test]
+    fn choosing_where_to_compile_the_binary_pair() {
+        use clap::Parser as _;
+
+        let checkout = std::path::Path::new("/cache/rust-gpu/0_10_0+nightly-2024-04-24");
+        let default = Install::parse_from(["cargo-gpu"]);
+        assert_eq!(
+            default.target_dir(checkout).unwrap(),
+            checkout.join("target")
+        );
+        let scratch =
+            Install::parse_from(["cargo-gpu", "--install-target-dir", "/scratch/targets"]);
+        assert_eq!(
+            scratch.target_dir(checkout).unwrap(),
+            std::path::Path::new("/scratch/targets/0_10_0+nightly-2024-04-24")
+        );
+    }
+
+    #[test_log::test]
+    fn compiling_the_binary_pair_through_a_rustc_wrapper() {
+        use clap::Parser as _;
+
+        let envs = |install: &Install| {
+            let mut command = std::process::Command::new("cargo");
+            install.configure_rustc_wrapper(&mut command);
+            command
+                .get_envs()
+                .map(|(name, value)| {
+                    (
+                        name.to_string_lossy().into_owned(),
+                        value.map(|set| set.to_string_lossy().into_owned()),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(envs(&Install::parse_from(["cargo-gpu"])), []);
+        assert_eq!(
+            envs(&Install::parse_from([
+                "cargo-gpu",
+                "--install-rustc-wrapper",
+                "sccache"
+            ])),
+            [
+                ("CARGO_INCREMENTAL".to_owned(), Some("0".to_owned())),
+                ("RUSTC_WRAPPER".to_owned(), Some("sccache".to_owned()))
+            ]
+        );
+    }
+
+    #[test_log::test]
+    fn finding_a_cli_for_an_own_codegen_backend() {
+        let root = cache_dir().unwrap().join("own_codegen_backend");
+        if root.exists() {
+            std::fs::remove_dir_all(&root).unwrap();
+        }
+        let backend_dir = root.join("rust-gpu").join("target").join("release");
+        let installed = root.join("installed");
+        std::fs::create_dir_all(&backend_dir).unwrap();
+        std::fs::create_dir_all(&installed).unwrap();
+        let backend = backend_dir.join("librustc_codegen_spirv.so");
+        std::fs::write(&backend, "a backend").unwrap();
+        std::fs::write(installed.join("spirv-builder-cli"), "a cli").unwrap();
+        let pair = |channel: &str| InstalledPair {
+            name: "installed".to_owned(),
+            path: installed.clone(),
+            metadata: Some(PairMetadata {
+                source: "https://github.com/Rust-GPU/rust-gpu".to_owned(),
+                version: "0.10.0".to_owned(),
+                channel: channel.to_owned(),
+                checkout: None,
+                checksums: std::collections::BTreeMap::new(),
+                builder_support: None,
+            }),
+            size: 0,
+            last_used: None,
+        };
+
+        let pairs = [pair("nightly-2024-04-24")];
+        assert_eq!(
+            own_backend_cli_path(&backend, "nightly-2024-04-24", &pairs),
+            Some(installed.join("spirv-builder-cli"))
+        );
+        assert_eq!(
+            own_backend_cli_path(&backend, "nightly-2025-01-01", &pairs),
+            None
+        );
+
+        std::fs::write(backend_dir.join("spirv-builder-cli"), "a cli").unwrap();
+        assert_eq!(
+            own_backend_cli_path(&backend, "nightly-2025-01-01", &pairs),
+            Some(backend_dir.join("spirv-builder-cli"))
+        );
+    }
+
+    #[test_log::test]
+    fn copying_target_specs_from_a_checkout() {
+        let root = cache_dir().unwrap().join("copying_target_specs");
+        let from = root.join("checkout");
+        let to = root.join("target-specs");
+        std::fs::create_dir_all(&from).unwrap();
+        std::fs::create_dir_all(&to).unwrap();
+        std::fs::write(from.join("spirv-unknown-vulkan1.4.json"), "{}").unwrap();
+        std::fs::write(from.join("README.md"), "not a spec").unwrap();
+
+        let copied = copy_target_specs(&from, &to).unwrap();
+        assert_eq!(copied, ["spirv-unknown-vulkan1.4.json"]);
+        assert_eq!(
+            std::fs::read_to_string(to.join("spirv-unknown-vulkan1.4.json")).unwrap(),
+            "{}"
+        );
+        assert!(!to.join("README.md").exists());
+    }
+}
@@ -0,0 +1,85 @@
+//! `--jobs`, building several shader crates, targets or variants at once.
+//!
+//! Each build runs its own `spirv-builder-cli` in a thread of its own. Only installing is done
+//! one at a time, see [`install_lock`], because builds that use the same version of `rust-gpu`
+//! share the same binary pair.
+
+/// Held while installing, so that parallel builds don't install the same binary pair at once.
+static INSTALL: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+/// Wait for any other build to finish installing, and stop them installing until the returned
+/// guard is dropped.
+pub fn install_lock() -> std::sync::MutexGuard<'static, ()> {
+    INSTALL
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+/// The number of jobs to run at once: `jobs`, or else the number of CPUs.
+#[must_use]
+pub fn count(jobs: Option<usize>) -> usize {
+    jobs.unwrap_or_else(|| std::thread::available_parallelism().map_or(1, usize::from))
+        .max(1)
+}
+
+/// Run `job` on each of `tasks`, with at most `jobs` running at once, returning the results in
+/// the same order as the tasks.
+pub fn run<T: Send, R: Send, J: Fn(T) -> R + Sync>(jobs: usize, tasks: Vec<T>, job: J) -> Vec<R> {
+    let total = tasks.len();
+    let queue = std::sync::Mutex::new(tasks.into_iter().enumerate());
+    let results = std::sync::Mutex::new(
+        core::iter::repeat_with(|| None)
+            .take(total)
+            .collect::<Vec<Option<R>>>(),
+    );
+    std::thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, total.max(1)) {
+            scope.spawn(|| loop {
+                let next = queue
+                    .lock()
+                    .unwrap_or_else(std::sync::PoisonError::into_inner)
+                    .next();
+                let Some((index, task)) = next else {
+                    break;
+                };
+                let result = job(task);
+                if let Some(slot) = results
+                    .lock()
+                    .unwrap_or_else(std::sync::PoisonError::into_inner)
+                    .get_mut(index)
+                {
+                    *slot = Some(result);
+                }
+            });
+        }
+    });
+    results
+        .into_inner()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_log::test]
+    fn running_jobs_in_parallel() {
+        let running = core::sync::atomic::AtomicUsize::new(0);
+        let most_running = core::sync::atomic::AtomicUsize::new(0);
+        let results = run(2, (0..6).collect(), |task: u32| {
+            let now = running.fetch_add(1, core::sync::atomic::Ordering::SeqCst) + 1;
+            most_running.fetch_max(now, core::sync::atomic::Ordering::SeqCst);
+            std::thread::sleep(core::time::Duration::from_millis(20));
+            running.fetch_sub(1, core::sync::atomic::Ordering::SeqCst);
+            task * 10
+        });
+        assert_eq!(results, [0, 10, 20, 30, 40, 50]);
+        assert!(most_running.into_inner() <= 2);
+
+        assert_eq!(count(Some(0)), 1);
+        assert_eq!(count(Some(3)), 3);
+    }
+}
@@ -0,0 +1,917 @@
+//! Rust GPU shader crate builder.
+//!
+//! This program manages installations of `spirv-builder-cli` and `rustc_codegen_spirv`.
+//! It uses these tools to compile Rust code into SPIR-V.
+//!
+//! # How it works
+//!
+//! In order to build shader crates, we must invoke cargo/rustc with a special backend
+//! that performs the SPIR-V code generation. This backend is a dynamic library known
+//! by its project name `rustc_codegen_spirv`. The name of the artifact itself is
+//! OS-dependent.
+//!
+//! There are a lot of special flags to wrangle and so we use a command line program
+//! that wraps `cargo` to perform the building of shader crates. This cli program is
+//! called `spirv-builder-cli`, which itself is a cli wrapper around the `spirv-builder`
+//! library.
+//!
+//! ## Where the binaries are
+//!
+//! `cargo-gpu` maintains different versions `spirv-builder-cli` and `rustc_codegen_spirv`
+//! in a cache dir. The location is OS-dependent, for example on macOS it's in
+//! `~/Library/Caches/rust-gpu`, but it can be moved with the `CARGO_GPU_CACHE_DIR` environment
+//! variable. Specific versions live inside the cache dir, prefixed by their `spirv-builder`
+//! cargo dependency and rust toolchain pair.
+//!
+//! Building a specific "binary pair" of `spirv-builder-cli` and `rustc_codegen_spirv`
+//! happens when there is no existing pair that matches the computed prefix, or if
+//! a force rebuild is specified on the command line.
+//!
+//! ## Building the "binary pairs"
+//!
+//! The source of `spirv-builder-cli` lives alongside this source file, in crate that
+//! is not included in the workspace. That same source code is also included statically
+//! in **this** source file.
+//!
+//! When `spirv-builder-cli` needs to be built, a new directory is created in the cache
+//! where the source to `spirv-builder-cli` is copied into, containing the specific cargo
+//! dependency for `spirv-builder` and the matching rust toolchain channel.
+//!
+//! Then `cargo` is invoked in that cache directory to build the pair of artifacts, which
+//! are then put into the top level of that cache directory.
+//!
+//! This pair of artifacts is then used to build shader crates.
+//!
+//! ## Building shader crates
+//!
+//! `cargo-gpu` takes a path to a shader crate to build, as well as a path to a directory
+//! to put the compiled `spv` source files. It also takes a path to an output mainifest
+//! file where all shader entry points will be mapped to their `spv` source files. This
+//! manifest file can be used by build scripts (`build.rs` files) to generate linkage or
+//! conduct other post-processing, like converting the `spv` files into `wgsl` files,
+//! for example.
+//!
+//! ## Using `cargo-gpu` as a library
+//!
+//! Everything the `cargo gpu` CLI does is also available from this crate, so shaders can be
+//! compiled from a host crate's `build.rs` without shelling out to the CLI:
+//!
+//! ```no_run
+//! let shaders = cargo_gpu::Builder::new("../shaders")
+//!     .shader_target("spirv-unknown-vulkan1.2")
+//!     .build()?;
+//! for linkage in shaders {
+//!     println!("cargo:warning={} -> {}", linkage.entry_point, linkage.source_path);
+//! }
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+#![expect(
+    clippy::pub_use,
+    reason = "Re-exports so that library users don't need to know about our internal crates"
+)]
+
+use anyhow::Context as _;
+
+use archive::PackageInstall;
+use build::Build;
+use clean::Clean;
+use diff::Diff;
+use dis::Dis;
+use doctor::Doctor;
+use host_test::Test;
+use init::Init;
+use install::Install;
+use new::New;
+#[cfg(feature = "run")]
+use run::Run;
+use self_update::SelfUpdate;
+use serve::Serve;
+use show::Show;
+use uninstall::Uninstall;
+use upgrade::Upgrade;
+use verify::Verify;
+
+mod archive;
+mod atomic;
+mod auto_target;
+mod build;
+mod builder;
+mod builder_support;
+mod cache;
+mod capabilities;
+mod clean;
+mod codegen;
+mod compatibility;
+mod compress;
+mod config;
+mod crates_io;
+mod debug_info;
+mod depfile;
+mod diff;
+mod dis;
+mod doctor;
+mod exit;
+mod fingerprint;
+mod global_config;
+mod hooks;
+mod host_test;
+mod init;
+mod install;
+mod jobs;
+mod link;
+mod log_file;
+mod metadata;
+mod new;
+mod notify;
+mod offline;
+mod optimize;
+mod output;
+mod pack;
+mod pin;
+mod pipeline;
+mod prebuilt;
+mod reflect;
+mod rename;
+mod reproducible;
+#[cfg(feature = "run")]
+mod run;
+mod self_update;
+mod serve;
+mod show;
+mod spec_constants;
+mod spirv_cli;
+mod spirv_source;
+mod stats;
+mod strip;
+mod timings;
+mod transpile;
+mod uninstall;
+mod upgrade;
+mod validate;
+mod verify;
+mod watch;
+mod workspace_manifest;
+
+pub use builder::Builder;
+pub use spirv_builder_cli::{
+    DescriptorBinding, ImageInfo, Linkage, Manifest, ModuleCompression, ModuleStats, PackedModule,
+    ShaderModule, MANIFEST_SCHEMA_VERSION,
+};
+
+/// Central function to write to the user.
+macro_rules! user_output {
+    ($($args: tt)*) => {
+        $crate::output::user_message(format_args!($($args)*))
+    }
+}
+pub(crate) use user_output;
+
+/// All of the available subcommands for `cargo gpu`.
+#[derive(clap::Subcommand)]
+enum Command {
+    /// Create a new shader crate from the template, ready to build.
+    New(New),
+
+    /// Turn an existing crate into a shader crate.
+    Init(Init),
+
+    /// Install rust-gpu compiler artifacts.
+    Install(Install),
+
+    /// Install, then bundle everything needed to install again without a network into an
+    /// archive, for `cargo gpu install --from-archive`.
+    PackageInstall(PackageInstall),
+
+    /// Move a shader crate to another version of `rust-gpu`, and install it.
+    Upgrade(Upgrade),
+
+    /// Compile a shader crate to SPIR-V.
+    Build(Build),
+
+    /// Run the shader crate's tests on the CPU with `cargo test`, with the same Cargo features
+    /// that it's built with.
+    Test(Test),
+
+    /// Dispatch a compute entry point of a built module on the GPU, and dump its buffers.
+    #[cfg(feature = "run")]
+    Run(Run),
+
+    /// Show some useful values.
+    Show(Show),
+
+    /// Print the effective config for a shader crate and where each value came from. Takes the
+    /// same arguments as `build`.
+    Config(config::Effective),
+
+    /// Remove cached `rust-gpu` artifacts.
+    Clean(Clean),
+
+    /// Remove specific installed `rust-gpu` compiler artifacts.
+    Uninstall(Uninstall),
+
+    /// Disassemble compiled shaders to SPIR-V assembly.
+    Dis(Dis),
+
+    /// Compare the entry points and modules of two builds, or of a build and the one before it.
+    Diff(Diff),
+
+    /// Check that a manifest matches its files: that they exist, their hashes match, the modules
+    /// pass validation and WGSL entry point names are unique.
+    Verify(Verify),
+
+    /// Check the environment for common problems with installing and building, and suggest fixes.
+    Doctor(Doctor),
+
+    /// Update `cargo gpu` itself to a newer release, or to a given one.
+    SelfUpdate(SelfUpdate),
+
+    /// Keep running and build shader crates on request, over JSON-RPC on a local socket.
+    Serve(Serve),
+
+    /// A hidden command that can be used to recursively print out all the subcommand help messages:
+    ///   `cargo gpu dump-usage`
+    /// Useful for updating the README.
+    #[clap(hide(true))]
+    DumpUsage,
+}
+
+impl Command {
+    /// Run the command.
+    ///
+    /// `env_args` are the raw CLI args, they're needed so that they can be merged with any
+    /// config from the shader crate's `Cargo.toml`.
+    pub fn run(&self, env_args: Vec<String>) -> anyhow::Result<()> {
+        let started = std::time::SystemTime::now();
+        let timer = std::time::Instant::now();
+        self.configure_output()?;
+        match self {
+            Self::Install(install) => {
+                if let Some(archive) = &install.from_archive {
+                    archive::unpack(archive)?;
+                    offline::enable();
+                }
+                let shader_crate_path = &install.spirv_install.shader_crate;
+                let mut command =
+                    config::Config::clap_command_with_cargo_config(shader_crate_path, env_args)?;
+                if let Some(path) = &command.install.spirv_install.log_file {
+                    log_file::open(path)?;
+                }
+                log::debug!(
+                    "installing with final merged arguments: {:#?}",
+                    command.install
+                );
+                let cli_path = command.install.run()?;
+                if output::is_json() {
+                    let install_dir = cli_path.parent().context("install has no directory")?;
+                    output::print_json(&serde_json::json!({
+                        "cache_directory": cache_dir()?,
+                        "install_directory": install_dir,
+                        "spirv_builder_cli": cli_path,
+                        "installed": install::PairMetadata::read(install_dir),
+                    }))?;
+                }
+            }
+            Self::Build(build) if build.workspace => {
+                let workspace_path = &build.install.spirv_install.shader_crate;
+                let shader_crates = Build::run_workspace(workspace_path, &env_args)?;
+                crate::user_output!("Built {} shader crates\n", shader_crates.len());
+                if output::is_json() {
+                    let results = shader_crates
+                        .iter()
+                        .map(|(shader_crate, linkage)| {
+                            serde_json::json!({
+                                "name": shader_crate.name,
+                                "shader_crate": shader_crate.path,
+                                "modules": linkage,
+                            })
+                        })
+                        .collect::<Vec<_>>();
+                    output::print_json(&serde_json::json!({
+                        "shader_crates": results,
+                        "elapsed_secs": timer.elapsed().as_secs_f64(),
+                    }))?;
+                }
+                Self::collect_garbage(started)?;
+            }
+            Self::Build(build) => {
+                let shader_crate_path = build.selected_shader_crate()?;
+                let mut command =
+                    config::Config::clap_command_with_cargo_config(&shader_crate_path, env_args)?;
+                command.install.spirv_install.shader_crate = shader_crate_path;
+                if let Some(path) = &command.install.spirv_install.log_file {
+                    log_file::open(path)?;
+                }
+                log::debug!("building with final merged arguments: {command:#?}");
+
+                //  When watching, do one normal run first to setup the `manifest.json` file.
+                let is_watching = command.build_args.watch;
+                command.build_args.watch = false;
+                let original = command.clone();
+                let linkage = command.run()?;
+                command.update_workspace_manifest(&linkage)?;
+                if output::is_json() {
+                    output::print_json(&serde_json::json!({
+                        "shader_crate": command.install.spirv_install.shader_crate,
+                        "output_dir": command.build_args.output_dir,
+                        "modules": linkage,
+                        "elapsed_secs": timer.elapsed().as_secs_f64(),
+                    }))?;
+                }
+                if is_watching {
+                    watch::watch(original)?;
+                }
+                Self::collect_garbage(started)?;
+            }
+            Self::PackageInstall(package) => package.run()?,
+            Self::Upgrade(upgrade) => upgrade.run()?,
+            Self::Config(effective) => effective.run(env_args)?,
+            Self::Show(show) => show.run()?,
+            Self::Clean(clean) => clean.run()?,
+            Self::Uninstall(uninstall) => uninstall.run()?,
+            Self::Dis(dis) => dis.run()?,
+            Self::Diff(diff) => diff.run()?,
+            Self::Verify(verify) => verify.run()?,
+            Self::Test(test) => test.run()?,
+            #[cfg(feature = "run")]
+            Self::Run(run) => run.run()?,
+            Self::Doctor(doctor) => doctor.run()?,
+            Self::New(new) => new.run()?,
+            Self::Init(init) => init.run()?,
+            Self::Serve(serve) => serve.run()?,
+            Self::SelfUpdate(self_update) => self_update.run()?,
+            Self::DumpUsage => dump_full_usage_for_readme()?,
+        }
+
+        Self::write_timings()
+    }
+
+    /// Write the timings report, if timings were recorded.
+    fn write_timings() -> anyhow::Result<()> {
+        if timings::is_enabled() {
+            let [json_path, html_path] = timings::write_report()?;
+            crate::user_output!(
+                "Wrote timings to {} and {}\n",
+                html_path.display(),
+                json_path.display()
+            );
+        }
+        Ok(())
+    }
+
+    /// Set the formats that the command's result and progress should be written in, whether to
+    /// record timings or stay offline, and where to write the log file.
+    fn configure_output(&self) -> anyhow::Result<()> {
+        let (format, message_format) = match self {
+            Self::Install(install)
+            | Self::PackageInstall(PackageInstall { install, .. })
+            | Self::Upgrade(Upgrade { install, .. }) => {
+                if install.timings {
+                    timings::enable();
+                }
+                if install.spirv_install.offline {
+                    offline::enable();
+                }
+                if let Some(path) = &install.spirv_install.log_file {
+                    log_file::open(path)?;
+                }
+                (install.output_format, install.message_format)
+            }
+            Self::Build(build) => {
+                if build.install.timings {
+                    timings::enable();
+                }
+                if build.install.spirv_install.offline {
+                    offline::enable();
+                }
+                if let Some(path) = &build.install.spirv_install.log_file {
+                    log_file::open(path)?;
+                }
+                (build.install.output_format, build.install.message_format)
+            }
+            Self::Show(show) => (show.output_format, output::MessageFormat::Human),
+            Self::Doctor(doctor) => (doctor.output_format, output::MessageFormat::Human),
+            Self::Config(_)
+            | Self::Clean(_)
+            | Self::Uninstall(_)
+            | Self::Dis(_)
+            | Self::Diff(_)
+            | Self::Verify(_)
+            | Self::Test(_)
+            | Self::New(_)
+            | Self::Init(_)
+            | Self::Serve(_)
+            | Self::SelfUpdate(_)
+            | Self::DumpUsage => (output::Format::Text, output::MessageFormat::Human),
+            #[cfg(feature = "run")]
+            Self::Run(_) => (output::Format::Text, output::MessageFormat::Human),
+        };
+        output::set_format(format);
+        output::set_message_format(message_format);
+        Ok(())
+    }
+
+    /// After a successful build, prune the cache according to the global config. Anything used
+    /// since `started` is kept.
+    fn collect_garbage(started: std::time::SystemTime) -> anyhow::Result<()> {
+        let config = global_config::GlobalConfig::load()?;
+        let (removed, reclaimed) = clean::collect_garbage(&config.cache, started)?;
+        if !removed.is_empty() {
+            crate::user_output!(
+                "Removed {} unused cache entries, reclaiming {}\n",
+                removed.len(),
+                format_size(reclaimed)
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Run the `cargo gpu` CLI with the process's arguments, which is all that the `cargo-gpu` binary
+/// does. Exits the process with a failure code if the command fails.
+#[expect(
+    clippy::missing_inline_in_public_items,
+    reason = "It's only called once, from the binary's `main()`"
+)]
+pub fn main() {
+    #[cfg(debug_assertions)]
+    std::env::set_var("RUST_BACKTRACE", "1");
+
+    let env_args = std::env::args()
+        .filter(|arg| {
+            // Calling our `main()` with the cargo subcommand `cargo gpu` passes "gpu"
+            // as the first parameter, so we want to ignore it.
+            arg != "gpu"
+        })
+        .collect::<Vec<_>>();
+    let cli = <Cli as clap::Parser>::parse_from(env_args.clone());
+
+    crate::output::set_quiet(cli.quiet);
+    crate::output::set_no_emoji(cli.no_emoji);
+    crate::output::set_color(cli.color);
+
+    let mut logger = env_logger::builder();
+    if let Some(level) = cli.log_level() {
+        logger.filter_level(level);
+    }
+    match crate::output::color() {
+        crate::output::Color::Auto => (),
+        crate::output::Color::Always => {
+            logger.write_style(env_logger::WriteStyle::Always);
+        }
+        crate::output::Color::Never => {
+            logger.write_style(env_logger::WriteStyle::Never);
+        }
+    }
+    let terminal = logger.build();
+    log::set_max_level(terminal.filter());
+    if let Err(error) = log::set_boxed_logger(Box::new(crate::log_file::Tee { terminal })) {
+        #[expect(clippy::print_stderr, reason = "There's no logger to report it with")]
+        {
+            eprintln!("Couldn't set up logging: {error}");
+        };
+    }
+
+    if let Err(error) = run_cli(&cli, env_args) {
+        log::error!("{error:?}");
+
+        #[expect(
+            clippy::print_stderr,
+            reason = "Our central place for outputting error messages"
+        )]
+        {
+            eprintln!("Error: {error}");
+
+            #[expect(clippy::restriction, reason = "Our central place for safely exiting")]
+            std::process::exit(crate::exit::code(&error));
+        };
+    }
+}
+
+/// Wrappable "main" to catch errors.
+fn run_cli(cli: &Cli, env_args: Vec<String>) -> anyhow::Result<()> {
+    log::trace!("CLI args: {env_args:#?}");
+    cli.command.run(Cli::without_output_args(env_args))
+}
+
+/// The `cargo gpu` CLI.
+#[derive(clap::Parser)]
+#[clap(author, version, about, subcommand_required = true)]
+struct Cli {
+    /// The command to run.
+    #[clap(subcommand)]
+    pub command: Command,
+
+    /// Only report errors, leaving out the crab's progress messages.
+    #[clap(short, long, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Log what's happening, `-vv` for debug logs and `-vvv` for everything. Takes the place of
+    /// setting `RUST_LOG`.
+    #[clap(short, long, global = true, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+
+    /// Whether to colour output, including `cargo`'s. `auto` respects `NO_COLOR`.
+    #[clap(long, value_enum, global = true, default_value_t)]
+    pub color: output::Color,
+
+    /// Leave out the crab at the start of messages.
+    #[clap(long, global = true)]
+    pub no_emoji: bool,
+}
+
+impl Cli {
+    /// The log level that `--quiet` or `--verbose` chose, if either was passed.
+    #[must_use]
+    pub const fn log_level(&self) -> Option<log::LevelFilter> {
+        if self.quiet {
+            return Some(log::LevelFilter::Error);
+        }
+        match self.verbose {
+            0 => None,
+            1 => Some(log::LevelFilter::Info),
+            2 => Some(log::LevelFilter::Debug),
+            _ => Some(log::LevelFilter::Trace),
+        }
+    }
+
+    /// The CLI args without the ones that only the top level `Cli` knows about, like `--quiet`
+    /// and `--color`, so that the rest can be merged with the shader crate's config.
+    #[must_use]
+    pub fn without_output_args(env_args: Vec<String>) -> Vec<String> {
+        let mut args = Vec::new();
+        let mut is_color_value = false;
+        let mut env_args_iter = env_args.into_iter();
+        for arg in env_args_iter.by_ref() {
+            if arg == "--" {
+                // Everything after `--` is passed on to `cargo`.
+                args.push(arg);
+                break;
+            }
+            if core::mem::take(&mut is_color_value) {
+                continue;
+            }
+            let is_short_flags = arg.len() > 1
+                && arg.starts_with('-')
+                && arg.chars().skip(1).all(|char| char == 'v' || char == 'q');
+            if arg == "--color" {
+                is_color_value = true;
+                continue;
+            }
+            let is_output_arg = is_short_flags
+                || ["--quiet", "--verbose", "--no-emoji"].contains(&arg.as_str())
+                || arg.starts_with("--color=");
+            if !is_output_arg {
+                args.push(arg);
+            }
+        }
+        args.extend(env_args_iter);
+        args
+    }
+}
+
+/// The root of the cache directory, where all the `rust-gpu` artifacts are kept.
+///
+/// It's the `CARGO_GPU_CACHE_DIR` environment variable if set, then the `cache-dir` in the global
+/// config, and otherwise the OS's default cache directory. It's only worked out once, as it's
+/// needed all over, eg for every fingerprint.
+fn cache_dir() -> anyhow::Result<std::path::PathBuf> {
+    /// The cache dir, or why it couldn't be worked out.
+    static CACHE_DIR: std::sync::OnceLock<Result<std::path::PathBuf, String>> =
+        std::sync::OnceLock::new();
+
+    if cfg!(test) {
+        let thread_id = std::thread::current().id();
+        let id = format!("{thread_id:?}").replace('(', "-").replace(')', "");
+        return Ok(default_cache_dir()?.join("tests").join(id));
+    }
+    CACHE_DIR
+        .get_or_init(|| configured_cache_dir().map_err(|error| format!("{error:#}")))
+        .clone()
+        .map_err(anyhow::Error::msg)
+}
+
+/// The cache dir from the environment, the global config or the default, see [`cache_dir`].
+fn configured_cache_dir() -> anyhow::Result<std::path::PathBuf> {
+    choose_cache_dir(
+        std::env::var_os("CARGO_GPU_CACHE_DIR"),
+        global_config::GlobalConfig::load()?.cache.cache_dir,
+        default_cache_dir()?,
+    )
+}
+
+/// The OS's default cache directory for `rust-gpu` artifacts.
+fn default_cache_dir() -> anyhow::Result<std::path::PathBuf> {
+    Ok(directories::BaseDirs::new()
+        .with_context(|| "could not find the user home directory")?
+        .cache_dir()
+        .join("rust-gpu"))
+}
+
+/// Pick the cache directory from, in order of precedence, the environment, the global config
+/// and the default.
+fn choose_cache_dir(
+    maybe_env: Option<std::ffi::OsString>,
+    maybe_config: Option<std::path::PathBuf>,
+    default: std::path::PathBuf,
+) -> anyhow::Result<std::path::PathBuf> {
+    if let Some(env) = maybe_env.filter(|env| !env.is_empty()) {
+        return Ok(std::path::absolute(std::path::PathBuf::from(env))?);
+    }
+    Ok(maybe_config.unwrap_or(default))
+}
+
+/// Location of the installed `spirv-builder-cli` and `rustc_codegen_spirv` binary pairs.
+fn installs_dir() -> anyhow::Result<std::path::PathBuf> {
+    Ok(cache_dir()?.join("spirv-builder-cli"))
+}
+
+/// Location of the cached checkouts of the `rust-gpu` repo.
+fn checkouts_dir() -> anyhow::Result<std::path::PathBuf> {
+    Ok(cache_dir()?.join("rust-gpu-repo"))
+}
+
+/// Location of the bare clones of each `rust-gpu` repo, that the checkouts are worktrees of.
+fn repos_dir() -> anyhow::Result<std::path::PathBuf> {
+    Ok(cache_dir()?.join("rust-gpu-repos"))
+}
+
+/// Location of the target spec metadata files of the install in `install_dir`. Each install has
+/// its own, so that concurrent builds with different versions of `rust-gpu` don't overwrite each
+/// other's specs.
+fn target_spec_dir(install_dir: &std::path::Path) -> std::path::PathBuf {
+    install_dir.join("target-specs")
+}
+
+/// Location of the bundled target spec metadata files, for building with a codegen backend that
+/// wasn't installed by `cargo gpu`.
+fn bundled_target_spec_dir() -> anyhow::Result<std::path::PathBuf> {
+    Ok(cache_dir()?.join("target-specs"))
+}
+
+/// Take an exclusive advisory lock on `<path>.lock`, so that concurrent `cargo gpu` invocations
+/// (eg parallel `build.rs` scripts in one workspace) take turns at modifying `path`. If another
+/// invocation holds the lock then this waits for it. The lock is released when the returned file
+/// is dropped.
+fn lock(path: &std::path::Path) -> anyhow::Result<std::fs::File> {
+    let (file, lock_path) = open_lock_file(path)?;
+    match file.try_lock() {
+        Ok(()) => {}
+        Err(std::fs::TryLockError::WouldBlock) => {
+            crate::user_output!(
+                "Waiting for another `cargo gpu` to release {}...\n",
+                lock_path.display()
+            );
+            file.lock()
+                .with_context(|| format!("could not lock '{}'", lock_path.display()))?;
+        }
+        Err(std::fs::TryLockError::Error(error)) => {
+            return Err(error).with_context(|| format!("could not lock '{}'", lock_path.display()));
+        }
+    }
+    log::debug!("locked '{}'", lock_path.display());
+    Ok(file)
+}
+
+/// Like [`lock`], but instead of waiting for another invocation to release the lock, returns
+/// `None`.
+fn try_lock(path: &std::path::Path) -> anyhow::Result<Option<std::fs::File>> {
+    let (file, lock_path) = open_lock_file(path)?;
+    match file.try_lock() {
+        Ok(()) => {
+            log::debug!("locked '{}'", lock_path.display());
+            Ok(Some(file))
+        }
+        Err(std::fs::TryLockError::WouldBlock) => Ok(None),
+        Err(std::fs::TryLockError::Error(error)) => {
+            Err(error).with_context(|| format!("could not lock '{}'", lock_path.display()))
+        }
+    }
+}
+
+/// Open, creating it if need be, the `<path>.lock` file that guards `path`.
+fn open_lock_file(path: &std::path::Path) -> anyhow::Result<(std::fs::File, std::path::PathBuf)> {
+    let lock_path = std::path::PathBuf::from(format!("{}.lock", path.display()));
+    if let Some(parent) = lock_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let file = std::fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .with_context(|| format!("could not open lock file '{}'", lock_path.display()))?;
+    Ok((file, lock_path))
+}
+
+/// Convenience function for internal use. Dumps all the CLI usage instructions. Useful for
+/// updating the README.
+fn dump_full_usage_for_readme() -> anyhow::Result<()> {
+    use clap::CommandFactory as _;
+    let mut command = Cli::command();
+
+    let mut buffer: Vec<u8> = Vec::default();
+    command.build();
+
+    write_help(&mut buffer, &mut command, 0)?;
+    user_output!("{}", String::from_utf8(buffer)?);
+
+    Ok(())
+}
+
+/// Recursive function to print the usage instructions for each subcommand.
+fn write_help(
+    buffer: &mut impl std::io::Write,
+    cmd: &mut clap::Command,
+    depth: usize,
+) -> anyhow::Result<()> {
+    if cmd.get_name() == "help" {
+        return Ok(());
+    }
+
+    let mut command = cmd.get_name().to_owned();
+    let indent_depth = if depth == 0 || depth == 1 { 0 } else { depth };
+    let indent = " ".repeat(indent_depth * 4);
+    writeln!(
+        buffer,
+        "\n{}* {}{}",
+        indent,
+        command.remove(0).to_uppercase(),
+        command
+    )?;
+
+    for line in cmd.render_long_help().to_string().lines() {
+        writeln!(buffer, "{indent}  {line}")?;
+    }
+
+    for sub in cmd.get_subcommands_mut() {
+        writeln!(buffer)?;
+        write_help(buffer, sub, depth + 1)?;
+    }
+
+    Ok(())
+}
+
+/// The total size of all the files in a directory, or the size of a single file.
+fn disk_size(path: &std::path::Path) -> anyhow::Result<u64> {
+    let metadata = std::fs::symlink_metadata(path)?;
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+
+    let mut total = 0;
+    for maybe_entry in std::fs::read_dir(path)? {
+        total += disk_size(&maybe_entry?.path())?;
+    }
+    Ok(total)
+}
+
+/// Human readable size, eg "1.5 GiB".
+#[expect(
+    clippy::float_arithmetic,
+    reason = "Precision isn't important when displaying sizes"
+)]
+fn format_size(bytes: u64) -> String {
+    /// Bytes in a kibibyte.
+    const KIBIBYTE: f64 = 1024.0;
+
+    #[expect(
+        clippy::as_conversions,
+        clippy::cast_precision_loss,
+        reason = "Precision isn't important when displaying sizes"
+    )]
+    let mut size = bytes as f64;
+    for unit in ["B", "KiB", "MiB", "GiB"] {
+        if size < KIBIBYTE {
+            return format!("{size:.1} {unit}");
+        }
+        size /= KIBIBYTE;
+    }
+    format!("{size:.1} TiB")
+}
+
+/// Returns a string suitable to use as a directory.
+///
+/// Created from the spirv-builder source dep and the rustc channel.
+fn to_dirname(text: &str) -> String {
+    text.replace(
+        [std::path::MAIN_SEPARATOR, '\\', '/', '.', ':', '@', '='],
+        "_",
+    )
+    .split(['{', '}', ' ', '\n', '"', '\''])
+    .collect::<Vec<_>>()
+    .concat()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cache_dir;
+    use std::io::Write as _;
+
+    pub fn shader_crate_template_path() -> std::path::PathBuf {
+        let project_base = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        project_base.join("../shader-crate-template")
+    }
+
+    pub fn shader_crate_test_path() -> std::path::PathBuf {
+        let shader_crate_path = crate::cache_dir().unwrap().join("shader_crate");
+        copy_dir_all(shader_crate_template_path(), shader_crate_path.clone()).unwrap();
+        shader_crate_path
+    }
+
+    pub fn overwrite_shader_cargo_toml(shader_crate_path: &std::path::Path) -> std::fs::File {
+        let cargo_toml = shader_crate_path.join("Cargo.toml");
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .truncate(true)
+            .open(cargo_toml)
+            .unwrap();
+        writeln!(file, "[package]").unwrap();
+        writeln!(file, "name = \"test\"").unwrap();
+        file
+    }
+
+    #[test_log::test]
+    fn choosing_the_cache_dir() {
+        let default = std::path::PathBuf::from("/default");
+        let config = Some(std::path::PathBuf::from("/config"));
+        assert_eq!(
+            crate::choose_cache_dir(Some("/env".into()), config.clone(), default.clone()).unwrap(),
+            std::path::PathBuf::from("/env")
+        );
+        assert_eq!(
+            crate::choose_cache_dir(Some("".into()), config, default.clone()).unwrap(),
+            std::path::PathBuf::from("/config")
+        );
+        assert_eq!(
+            crate::choose_cache_dir(None, None, default.clone()).unwrap(),
+            default
+        );
+    }
+
+    #[test_log::test]
+    fn choosing_the_output_level() {
+        use clap::Parser as _;
+
+        let cli = crate::Cli::parse_from(["cargo-gpu", "show", "commitsh", "-vv"]);
+        assert_eq!(cli.log_level(), Some(log::LevelFilter::Debug));
+        let quiet = crate::Cli::parse_from(["cargo-gpu", "--quiet", "show", "commitsh"]);
+        assert_eq!(quiet.log_level(), Some(log::LevelFilter::Error));
+        assert!(crate::Cli::try_parse_from(["cargo-gpu", "-q", "-v", "show", "commitsh"]).is_err());
+
+        let args = [
+            "cargo-gpu",
+            "-vv",
+            "build",
+            "-q",
+            "--color",
+            "never",
+            "--output-dir",
+            "shaders",
+            "--verbose",
+            "--no-emoji",
+            "--color=always",
+        ];
+        assert_eq!(
+            crate::Cli::without_output_args(args.map(String::from).to_vec()),
+            ["cargo-gpu", "build", "--output-dir", "shaders"]
+        );
+    }
+
+    #[test_log::test]
+    fn locking_is_exclusive() {
+        let path = cache_dir().unwrap().join("locked");
+        let held = crate::lock(&path).unwrap();
+        let other = std::fs::File::open(format!("{}.lock", path.display())).unwrap();
+        assert!(matches!(
+            other.try_lock(),
+            Err(std::fs::TryLockError::WouldBlock)
+        ));
+        drop(held);
+        other.try_lock().unwrap();
+    }
+
+    pub fn tests_teardown() {
+        let cache_dir = cache_dir().unwrap();
+        if !cache_dir.exists() {
+            return;
+        }
+        std::fs::remove_dir_all(cache_dir).unwrap();
+    }
+
+    pub fn copy_dir_all(
+        src: impl AsRef<std::path::Path>,
+        dst: impl AsRef<std::path::Path>,
+    ) -> anyhow::Result<()> {
+        std::fs::create_dir_all(&dst)?;
+        for maybe_entry in std::fs::read_dir(src)? {
+            let entry = maybe_entry?;
+            let ty = entry.file_type()?;
+            if ty.is_dir() {
+                copy_dir_all(entry.path(), dst.as_ref().join(entry.file_name()))?;
+            } else {
+                std::fs::copy(entry.path(), dst.as_ref().join(entry.file_name()))?;
+            }
+        }
+        Ok(())
+    }
+}
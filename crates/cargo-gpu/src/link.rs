@@ -0,0 +1,81 @@
+//! `--output-link`, link compiled modules into the output dir rather than copying them.
+
+use anyhow::Context as _;
+use spirv_builder_cli::args::OutputLink;
+
+/// Put the module at `source` in the output dir at `destination`, atomically replacing anything
+/// that's already there.
+///
+/// # Errors
+/// If the module can't be copied or linked.
+pub fn put(
+    source: &std::path::Path,
+    destination: &std::path::Path,
+    how: OutputLink,
+) -> anyhow::Result<()> {
+    log::debug!(
+        "putting {} at {} with `--output-link {how:?}`",
+        source.display(),
+        destination.display()
+    );
+    match how {
+        OutputLink::Copy => crate::atomic::copy(source, destination),
+        OutputLink::Hardlink => crate::atomic::replace(destination, |temporary| {
+            if let Err(error) = std::fs::hard_link(source, temporary) {
+                log::warn!(
+                    "couldn't hard link '{}', copying it instead: {error}",
+                    source.display()
+                );
+                std::fs::copy(source, temporary)?;
+            }
+            Ok(())
+        }),
+        OutputLink::Symlink => {
+            let original = std::path::absolute(source)?;
+            crate::atomic::replace(destination, |temporary| {
+                symlink(&original, temporary)
+                    .with_context(|| format!("could not symlink '{}'", source.display()))
+            })
+        }
+    }
+}
+
+/// Symlink a file.
+#[cfg(unix)]
+fn symlink(original: &std::path::Path, link: &std::path::Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(original, link)
+}
+
+/// Symlink a file.
+#[cfg(windows)]
+fn symlink(original: &std::path::Path, link: &std::path::Path) -> std::io::Result<()> {
+    std::os::windows::fs::symlink_file(original, link)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_log::test]
+    fn linking_modules_into_the_output_dir() {
+        let dir = crate::cache_dir().unwrap().join("link");
+        std::fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("target.spv");
+        let destination = dir.join("output.spv");
+        std::fs::write(&source, [1, 2, 3, 4]).unwrap();
+
+        put(&source, &destination, OutputLink::Symlink).unwrap();
+        assert!(destination.is_symlink());
+        // Replacing a link with a copy mustn't write through it.
+        put(&source, &destination, OutputLink::Copy).unwrap();
+        assert!(!destination.is_symlink());
+        std::fs::write(&destination, [5, 6, 7, 8]).unwrap();
+        assert_eq!(std::fs::read(&source).unwrap(), [1, 2, 3, 4]);
+
+        put(&source, &destination, OutputLink::Hardlink).unwrap();
+        assert_eq!(std::fs::read(&destination).unwrap(), [1, 2, 3, 4]);
+        crate::atomic::write(&destination, [5, 6, 7, 8]).unwrap();
+        assert_eq!(std::fs::read(&source).unwrap(), [1, 2, 3, 4]);
+        crate::test::tests_teardown();
+    }
+}
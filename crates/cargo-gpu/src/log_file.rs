@@ -0,0 +1,186 @@
+//! `--log-file`, a timestamped record of everything that happened, for looking into failures
+//! after the fact, eg on CI.
+//!
+//! Once [`open`] has been called, every log record at debug level and up, every message for the
+//! user, and the stdout and stderr of the `cargo` and `rustup` processes that are run are
+//! written to the file, whatever the terminal is showing.
+
+use std::io::{BufRead as _, Write as _};
+
+/// The log file, once it's been opened.
+static FILE: std::sync::Mutex<Option<std::fs::File>> = std::sync::Mutex::new(None);
+
+/// Start writing to the log file at `path`, replacing anything that's already there. Does
+/// nothing if a log file is already open, so that the command line takes precedence over config.
+pub fn open(path: &std::path::Path) -> anyhow::Result<()> {
+    let mut file = FILE
+        .lock()
+        .map_err(|_poisoned| anyhow::anyhow!("log file lock is poisoned"))?;
+    if file.is_some() {
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    *file = Some(
+        std::fs::File::create(path)
+            .map_err(|error| anyhow::anyhow!("could not create '{}': {error}", path.display()))?,
+    );
+    drop(file);
+    // The file gets debug records, even when the terminal doesn't.
+    log::set_max_level(log::max_level().max(log::LevelFilter::Debug));
+    log::info!("logging to {}", path.display());
+    Ok(())
+}
+
+/// Whether a log file is open.
+#[must_use]
+pub fn is_open() -> bool {
+    FILE.lock().is_ok_and(|file| file.is_some())
+}
+
+/// Write a line to the log file, if there is one, prefixed with the time and where it came from,
+/// eg "stderr" or "DEBUG `cargo_gpu::install`".
+pub fn write(source: &str, line: &str) {
+    let Ok(mut maybe_file) = FILE.lock() else {
+        return;
+    };
+    let Some(file) = maybe_file.as_mut() else {
+        return;
+    };
+    let now = chrono::DateTime::<chrono::Utc>::from(std::time::SystemTime::now())
+        .to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
+    // There's nowhere left to report a failure to log.
+    drop(writeln!(file, "{now} {source}: {}", line.trim_end()));
+}
+
+/// A logger that passes records on to the terminal's logger, and also writes them to the log
+/// file, if there is one.
+pub struct Tee<L> {
+    /// The terminal's logger, eg `env_logger`'s.
+    pub terminal: L,
+}
+
+impl<L: log::Log> log::Log for Tee<L> {
+    fn enabled(&self, metadata: &log::Metadata<'_>) -> bool {
+        self.terminal.enabled(metadata) || (metadata.level() <= log::Level::Debug && is_open())
+    }
+
+    fn log(&self, record: &log::Record<'_>) {
+        if self.terminal.enabled(record.metadata()) {
+            self.terminal.log(record);
+        }
+        if is_open()
+            && (record.level() <= log::Level::Debug || self.terminal.enabled(record.metadata()))
+        {
+            write(
+                &format!("{} {}", record.level(), record.target()),
+                &record.args().to_string(),
+            );
+        }
+    }
+
+    fn flush(&self) {
+        self.terminal.flush();
+    }
+}
+
+/// Run a command to completion like [`std::process::Command::output`], also writing its stdout
+/// and stderr to the log file, if there is one.
+///
+/// Its stdout goes where [`crate::output::child_stdout`] sends it. Its stderr is always returned,
+/// so that failures can be told apart, and unless `is_stderr_captured`, as with
+/// [`crate::output::child_stderr`] in build script mode, it's also passed on to our stderr as it
+/// comes.
+pub fn output(
+    command: &mut std::process::Command,
+    is_stderr_captured: bool,
+) -> std::io::Result<std::process::Output> {
+    if is_open() {
+        command.stdout(std::process::Stdio::piped());
+    }
+    let mut child = command.stderr(std::process::Stdio::piped()).spawn()?;
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+
+    let stdout_thread = std::thread::spawn(move || {
+        let Some(pipe) = stdout else {
+            return;
+        };
+        for line in std::io::BufReader::new(pipe)
+            .split(b'\n')
+            .map_while(Result::ok)
+        {
+            write("stdout", &String::from_utf8_lossy(&line));
+            // Like an inherited stdout, output that can't be shown is dropped.
+            drop(if crate::output::is_stdout_reserved() {
+                writeln_bytes(&mut std::io::stderr(), &line)
+            } else {
+                writeln_bytes(&mut std::io::stdout(), &line)
+            });
+        }
+    });
+
+    let mut captured = Vec::new();
+    if let Some(pipe) = stderr {
+        for line in std::io::BufReader::new(pipe)
+            .split(b'\n')
+            .map_while(Result::ok)
+        {
+            write("stderr", &String::from_utf8_lossy(&line));
+            if !is_stderr_captured {
+                drop(writeln_bytes(&mut std::io::stderr(), &line));
+            }
+            captured.extend_from_slice(&line);
+            captured.push(b'\n');
+        }
+    }
+    if stdout_thread.join().is_err() {
+        log::warn!("couldn't pass on all of the stdout of `{command:?}`");
+    }
+
+    Ok(std::process::Output {
+        status: child.wait()?,
+        stdout: Vec::new(),
+        stderr: captured,
+    })
+}
+
+/// Write a line of a child process's output, which may not be UTF-8.
+fn writeln_bytes(writer: &mut impl std::io::Write, line: &[u8]) -> std::io::Result<()> {
+    writer.write_all(line)?;
+    writer.write_all(b"\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_log::test]
+    fn teeing_a_command_into_the_log_file() {
+        let dir = crate::cache_dir().unwrap();
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("cargo-gpu.log");
+        open(&path).unwrap();
+        assert!(is_open());
+
+        let mut command = std::process::Command::new("sh");
+        command.args(["-c", "echo out; echo err >&2; exit 3"]);
+        let result = output(&mut command, true).unwrap();
+        assert!(!result.status.success());
+        assert_eq!(result.stderr, b"err\n");
+        write("DEBUG cargo_gpu", "a message\n");
+
+        let logged = std::fs::read_to_string(&path).unwrap();
+        let lines = logged.lines().collect::<Vec<_>>();
+        assert!(lines.iter().any(|line| line.ends_with(" stdout: out")));
+        assert!(lines.iter().any(|line| line.ends_with(" stderr: err")));
+        assert!(lines
+            .iter()
+            .any(|line| line.ends_with(" DEBUG cargo_gpu: a message")));
+        assert!(lines
+            .iter()
+            .all(|line| line.contains('T') && line.contains("Z ")));
+        crate::test::tests_teardown();
+    }
+}
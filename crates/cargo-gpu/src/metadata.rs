@@ -1,7 +1,17 @@
-//! Get config from the shader crate's `Cargo.toml` `[*.metadata.rust-gpu.*]`
+//! Get config from the shader crate's `Cargo.toml` `[*.metadata.rust-gpu.*]`.
 
+use anyhow::Context as _;
 use serde_json::Value;
 
+/// A crate in a workspace that depends on `spirv-std`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShaderCrate {
+    /// The package name of the crate.
+    pub name: String,
+    /// The directory containing the crate's `Cargo.toml`.
+    pub path: std::path::PathBuf,
+}
+
 /// `Metadata` refers to the `[metadata.*]` section of `Cargo.toml` that `cargo` formally
 /// ignores so that packages can implement their own behaviour with it.
 #[derive(Debug)]
@@ -14,84 +24,361 @@ impl Metadata {
     ///
     /// First we generate the CLI arg defaults as JSON. Then on top of those we merge any config
     /// from the workspace `Cargo.toml`, then on top of those we merge any config from the shader
-    /// crate's `Cargo.toml`.
-    pub fn as_json(path: &std::path::PathBuf) -> anyhow::Result<serde_json::Value> {
+    /// crate's `Cargo.toml`, and then the build profile, if there is one.
+    pub fn as_json(
+        path: &std::path::PathBuf,
+        profile: Option<&str>,
+    ) -> anyhow::Result<serde_json::Value> {
         let cargo_json = Self::get_cargo_toml_as_json(path)?;
-        let config = Self::merge_configs(&cargo_json, path)?;
+        let config = Self::merge_configs(&cargo_json, path, profile)?;
         Ok(config)
     }
 
+    /// The root of the workspace that the crate at `path` belongs to.
+    pub fn workspace_root(path: &std::path::PathBuf) -> anyhow::Result<std::path::PathBuf> {
+        let cargo_json = Self::get_cargo_toml_as_json(path)?;
+        cargo_json
+            .pointer("/workspace_root")
+            .and_then(Value::as_str)
+            .map(std::path::PathBuf::from)
+            .context("`cargo metadata` has no `workspace_root`")
+    }
+
+    /// Find all the shader crates in the workspace that the crate at `path` belongs to. Shader
+    /// crates are those that depend on `spirv-std`.
+    pub fn shader_crates(path: &std::path::PathBuf) -> anyhow::Result<Vec<ShaderCrate>> {
+        let cargo_json = Self::get_cargo_toml_as_json(path)?;
+        Ok(Self::shader_crates_from_json(&cargo_json))
+    }
+
+    /// Find the shader crate called `name` in the workspace that the crate at `path` belongs to.
+    pub fn shader_crate_named(
+        path: &std::path::PathBuf,
+        name: &str,
+    ) -> anyhow::Result<ShaderCrate> {
+        let shader_crates = Self::shader_crates(path)?;
+        Self::find_shader_crate(shader_crates, name, path)
+    }
+
+    /// Find the shader crate called `name`, with an error that lists the other candidates.
+    fn find_shader_crate(
+        shader_crates: Vec<ShaderCrate>,
+        name: &str,
+        path: &std::path::Path,
+    ) -> anyhow::Result<ShaderCrate> {
+        let names = shader_crates
+            .iter()
+            .map(|shader_crate| format!("`{}`", shader_crate.name))
+            .collect::<Vec<_>>();
+        let maybe_shader_crate = shader_crates
+            .into_iter()
+            .find(|shader_crate| shader_crate.name == name);
+        maybe_shader_crate.with_context(|| {
+            if names.is_empty() {
+                format!(
+                    "there's no shader crate called `{name}` in the workspace at '{}', or any \
+                     other shader crates (crates depending on `spirv-std`)",
+                    path.display()
+                )
+            } else {
+                format!(
+                    "there's no shader crate called `{name}` in the workspace at '{}', the shader \
+                     crates are: {}",
+                    path.display(),
+                    names.join(", ")
+                )
+            }
+        })
+    }
+
+    /// The local files and directories that building the crate at `path` depends on: the crate
+    /// itself, every crate that it depends on by path, directly or not, and the workspace's
+    /// `Cargo.lock`.
+    pub fn local_inputs(path: &std::path::Path) -> anyhow::Result<Vec<std::path::PathBuf>> {
+        let cargo_json = Self::get_cargo_metadata_with_deps(path)?;
+        Ok(Self::local_inputs_from_json(&cargo_json, path))
+    }
+
+    /// Find the local inputs of the crate at `path` in the output of `cargo metadata`, by
+    /// following the path dependencies in its dependency graph.
+    fn local_inputs_from_json(
+        json: &serde_json::Value,
+        path: &std::path::Path,
+    ) -> Vec<std::path::PathBuf> {
+        let mut inputs = vec![path.to_path_buf()];
+        if let Some(workspace_root) = json.pointer("/workspace_root").and_then(Value::as_str) {
+            inputs.push(std::path::Path::new(workspace_root).join("Cargo.lock"));
+        }
+
+        let packages = json
+            .pointer("/packages")
+            .and_then(Value::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        // Packages without a `source` are local, ie path dependencies and workspace members.
+        let local_dirs = packages
+            .iter()
+            .filter(|package| package.pointer("/source").is_none_or(Value::is_null))
+            .filter_map(|package| {
+                let manifest_path = package.pointer("/manifest_path")?.as_str()?;
+                let dir = std::path::Path::new(manifest_path).parent()?;
+                Some((package_id(package)?, dir.to_path_buf()))
+            })
+            .collect::<std::collections::HashMap<_, _>>();
+        let Some(root) = local_dirs
+            .iter()
+            .find(|(_, dir)| dir.as_path() == path)
+            .map(|(id, _)| *id)
+        else {
+            return inputs;
+        };
+        let dependencies = json
+            .pointer("/resolve/nodes")
+            .and_then(Value::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|node| {
+                let ids = node
+                    .pointer("/dependencies")?
+                    .as_array()?
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .collect::<Vec<_>>();
+                Some((package_id(node)?, ids))
+            })
+            .collect::<std::collections::HashMap<_, _>>();
+
+        // Only local packages can depend on other local packages, so the walk stops at the
+        // first package from a registry or git repo.
+        let mut visited = std::collections::HashSet::from([root]);
+        let mut queue = std::collections::VecDeque::from([root]);
+        while let Some(id) = queue.pop_front() {
+            for dependency in dependencies.get(id).into_iter().flatten() {
+                if let Some(dir) = local_dirs.get(dependency) {
+                    if visited.insert(*dependency) {
+                        inputs.push(dir.clone());
+                        queue.push_back(*dependency);
+                    }
+                }
+            }
+        }
+
+        inputs
+    }
+
+    /// Find the shader crates in the output of `cargo metadata`.
+    fn shader_crates_from_json(json: &serde_json::Value) -> Vec<ShaderCrate> {
+        let Some(serde_json::Value::Array(packages)) = json.pointer("/packages") else {
+            return Vec::new();
+        };
+
+        let mut shader_crates = packages
+            .iter()
+            .filter_map(|package| {
+                let serde_json::Value::Array(dependencies) = package.pointer("/dependencies")?
+                else {
+                    return None;
+                };
+                // `name` is always the real package name, even when the dependency is renamed.
+                let is_shader_crate = dependencies.iter().any(|dependency| {
+                    dependency.pointer("/name").and_then(Value::as_str) == Some("spirv-std")
+                });
+                if !is_shader_crate {
+                    return None;
+                }
+
+                let name = package.pointer("/name")?.as_str()?.to_owned();
+                let manifest_path = package.pointer("/manifest_path")?.as_str()?;
+                let path = std::path::Path::new(manifest_path).parent()?.to_path_buf();
+                Some(ShaderCrate { name, path })
+            })
+            .collect::<Vec<_>>();
+        shader_crates.sort_by(|left, right| left.name.cmp(&right.name));
+
+        log::debug!("found shader crates: {shader_crates:#?}");
+        shader_crates
+    }
+
     /// Convert JSON keys from kebab case to snake case. Eg: `a-b` to `a_b`.
     ///
     /// Detection of keys for serde deserialization must match the case in the Rust structs.
     /// However clap defaults to detecting CLI args in kebab case. So here we do the conversion.
     fn keys_to_snake_case(json: &mut serde_json::Value) {
-        let serde_json::Value::Object(object) = json else {
-            return;
-        };
-
-        *object = core::mem::take(object)
-            .into_iter()
-            .map(|(key, mut value)| {
-                if let serde_json::Value::Object(_) = value {
-                    Self::keys_to_snake_case(&mut value);
+        match json {
+            serde_json::Value::Object(object) => {
+                *object = core::mem::take(object)
+                    .into_iter()
+                    .map(|(key, mut value)| {
+                        Self::keys_to_snake_case(&mut value);
+                        (key.replace('-', "_"), value)
+                    })
+                    .collect();
+            }
+            // Eg the tables in `[[package.metadata.rust-gpu.build.variants]]`.
+            serde_json::Value::Array(array) => {
+                for value in array {
+                    Self::keys_to_snake_case(value);
                 }
-                (key.replace('-', "_"), value)
-            })
-            .collect();
+            }
+            serde_json::Value::Null
+            | serde_json::Value::Bool(_)
+            | serde_json::Value::Number(_)
+            | serde_json::Value::String(_) => (),
+        }
     }
 
-    /// Merge the various source of config: defaults, workspace and shader crate.
+    /// Merge the various source of config: defaults, global, workspace and shader crate.
     fn merge_configs(
         cargo_json: &serde_json::Value,
         path: &std::path::Path,
+        profile: Option<&str>,
     ) -> anyhow::Result<serde_json::Value> {
         let mut metadata = crate::config::Config::defaults_as_json()?;
-        crate::config::Config::json_merge(
-            &mut metadata,
-            {
-                log::debug!("looking for workspace metadata");
-                let ws_meta = Self::get_workspace_metadata(cargo_json);
-                log::trace!("workspace_metadata: {ws_meta:#?}");
-                ws_meta
-            },
-            None,
-        )?;
-        crate::config::Config::json_merge(
-            &mut metadata,
-            {
-                log::debug!("looking for crate metadata");
-                let mut crate_meta = Self::get_crate_metadata(cargo_json, path)?;
-                log::trace!("crate_metadata: {crate_meta:#?}");
-                if let Some(output_path) = crate_meta.pointer_mut("/build/output_dir") {
-                    log::debug!("found output-dir path in crate metadata: {:?}", output_path);
-                    if let Some(output_dir) = output_path.clone().as_str() {
-                        let new_output_path = path.join(output_dir);
-                        *output_path = Value::String(format!("{}", new_output_path.display()));
-                        log::debug!(
-                            "setting that to be relative to the Cargo.toml it was found in: {}",
-                            new_output_path.display()
-                        );
-                    }
-                }
-                crate_meta
-            },
-            None,
-        )?;
+        for (_, layer) in Self::layers(cargo_json, path, profile)? {
+            crate::config::Config::json_merge(&mut metadata, layer, None)?;
+        }
 
         Ok(metadata)
     }
 
-    /// Convert a `Cargo.toml` to JSON
+    /// The config from the global config file, the workspace and shader crate `Cargo.toml`s and
+    /// the build profile, in order of increasing precedence.
+    pub fn config_layers(
+        path: &std::path::PathBuf,
+        profile: Option<&str>,
+    ) -> anyhow::Result<Vec<(crate::config::Source, serde_json::Value)>> {
+        let cargo_json = Self::get_cargo_toml_as_json(path)?;
+        Self::layers(&cargo_json, path, profile)
+    }
+
+    /// See [`Self::config_layers`]. The profile is the one given, or else the one that the crate
+    /// or workspace config chooses with `build.profile`.
+    fn layers(
+        cargo_json: &serde_json::Value,
+        path: &std::path::Path,
+        profile: Option<&str>,
+    ) -> anyhow::Result<Vec<(crate::config::Source, serde_json::Value)>> {
+        log::debug!("looking for global config");
+        let mut global_meta = crate::global_config::GlobalConfig::load()?.as_json()?;
+        Self::keys_to_snake_case(&mut global_meta);
+        log::trace!("global_config: {global_meta:#?}");
+
+        log::debug!("looking for workspace metadata");
+        let mut ws_meta = Self::get_workspace_metadata(cargo_json);
+        log::trace!("workspace_metadata: {ws_meta:#?}");
+        let ws_profiles = Self::take_profiles(&mut ws_meta);
+
+        log::debug!("looking for crate metadata");
+        let mut crate_meta = Self::get_crate_metadata(cargo_json, path)?;
+        log::trace!("crate_metadata: {crate_meta:#?}");
+        let crate_profiles = Self::take_profiles(&mut crate_meta);
+        Self::make_output_dir_relative(&mut crate_meta, "/build/output_dir", path);
+
+        let maybe_profile = profile.map(ToOwned::to_owned).or_else(|| {
+            [&crate_meta, &ws_meta]
+                .into_iter()
+                .find_map(|meta| meta.pointer("/build/profile")?.as_str())
+                .map(ToOwned::to_owned)
+        });
+
+        let mut layers = vec![
+            (crate::config::Source::Global, global_meta),
+            (crate::config::Source::Workspace, ws_meta),
+            (crate::config::Source::Crate, crate_meta),
+        ];
+        if let Some(name) = maybe_profile {
+            // Profile names have had their dashes turned into underscores like every other key.
+            let key = name.replace('-', "_");
+            let ws_profile = ws_profiles.get(&key).cloned();
+            let mut crate_profile = crate_profiles.get(&key).cloned();
+            anyhow::ensure!(
+                ws_profile.is_some() || crate_profile.is_some(),
+                "there's no `[package.metadata.rust-gpu.profile.{name}]` or \
+                 `[workspace.metadata.rust-gpu.profile.{name}]`"
+            );
+            log::debug!("using the build profile `{name}`");
+            if let Some(crate_build) = crate_profile.as_mut() {
+                Self::make_output_dir_relative(crate_build, "/output_dir", path);
+            }
+            // The crate's profile overrides the workspace's profile of the same name, key by key.
+            let mut profile_build = serde_json::Map::new();
+            for profile_config in ws_profile.into_iter().chain(crate_profile) {
+                if let Value::Object(options) = profile_config {
+                    profile_build.extend(options);
+                }
+            }
+            let profile_meta = serde_json::json!({ "build": profile_build });
+            layers.push((crate::config::Source::Profile, profile_meta));
+        }
+        Ok(layers)
+    }
+
+    /// Remove the `profile` table from some metadata, returning each profile's build config by
+    /// name.
+    fn take_profiles(meta: &mut serde_json::Value) -> serde_json::Map<String, serde_json::Value> {
+        match meta
+            .as_object_mut()
+            .and_then(|object| object.remove("profile"))
+        {
+            Some(serde_json::Value::Object(profiles)) => profiles,
+            _ => serde_json::Map::new(),
+        }
+    }
+
+    /// Make an `output-dir` in a shader crate's `Cargo.toml` relative to the crate.
+    fn make_output_dir_relative(
+        meta: &mut serde_json::Value,
+        pointer: &str,
+        path: &std::path::Path,
+    ) {
+        if let Some(output_path) = meta.pointer_mut(pointer) {
+            log::debug!("found output-dir path in crate metadata: {output_path:?}");
+            if let Some(output_dir) = output_path.clone().as_str() {
+                let new_output_path = path.join(output_dir);
+                *output_path = Value::String(format!("{}", new_output_path.display()));
+                log::debug!(
+                    "setting that to be relative to the Cargo.toml it was found in: {}",
+                    new_output_path.display()
+                );
+            }
+        }
+    }
+
+    /// The output of `cargo metadata` for the crate at `path`, including the whole dependency
+    /// graph.
+    fn get_cargo_metadata_with_deps(path: &std::path::Path) -> anyhow::Result<serde_json::Value> {
+        let cargo_toml_path = path.join("Cargo.toml");
+        log::debug!(
+            "Querying Cargo metadata with dependencies for {}",
+            cargo_toml_path.display()
+        );
+        let output_cargo = std::process::Command::new("cargo")
+            .args(["metadata", "--format-version", "1", "--manifest-path"])
+            .arg(&cargo_toml_path)
+            .args(crate::offline::cargo_args())
+            .output()?;
+        anyhow::ensure!(
+            output_cargo.status.success(),
+            "could not run `cargo metadata` on {cargo_toml_path:?}: {}",
+            String::from_utf8_lossy(&output_cargo.stderr).trim()
+        );
+        Ok(serde_json::from_slice(&output_cargo.stdout)?)
+    }
+
+    /// Convert a `Cargo.toml` to JSON.
     //
     // TODO: reuse for getting the default `rust-gpu` source and toolchain.
     fn get_cargo_toml_as_json(path: &std::path::PathBuf) -> anyhow::Result<serde_json::Value> {
         let cargo_toml_path = path.join("Cargo.toml");
         if !cargo_toml_path.exists() {
-            anyhow::bail!("{path:?} must be a shader crate directory");
+            return Err(crate::exit::Failure::ShaderCrateNotFound(anyhow::anyhow!(
+                "{path:?} must be a shader crate directory"
+            ))
+            .into());
         }
 
-        log::debug!("Querying Cargo metadata for {cargo_toml_path:?}");
+        log::debug!("Querying Cargo metadata for {}", cargo_toml_path.display());
         let output_cargo = std::process::Command::new("cargo")
             .args([
                 "metadata",
@@ -99,6 +386,7 @@ impl Metadata {
                 "--manifest-path",
                 cargo_toml_path.display().to_string().as_ref(),
             ])
+            .args(crate::offline::cargo_args())
             .output()?;
         anyhow::ensure!(
             output_cargo.status.success(),
@@ -108,7 +396,7 @@ impl Metadata {
         Ok(serde_json::from_slice(&output_cargo.stdout)?)
     }
 
-    /// Get any `rust-gpu` metadata set in the root workspace `Cargo.toml`
+    /// Get any `rust-gpu` metadata set in the root workspace `Cargo.toml`.
     fn get_workspace_metadata(json: &serde_json::Value) -> serde_json::Value {
         let empty_json_object = serde_json::json!({});
         let mut metadata = json
@@ -120,7 +408,7 @@ impl Metadata {
         metadata.clone()
     }
 
-    /// Get any `rust-gpu` metadata set in the crate's `Cargo.toml`
+    /// Get any `rust-gpu` metadata set in the crate's `Cargo.toml`.
     fn get_crate_metadata(
         json: &serde_json::Value,
         path: &std::path::Path,
@@ -156,6 +444,12 @@ impl Metadata {
     }
 }
 
+/// The `id` of a package, or of a node in the dependency graph, in the output of
+/// `cargo metadata`.
+fn package_id(package: &serde_json::Value) -> Option<&str> {
+    package.pointer("/id")?.as_str()
+}
+
 #[expect(
     clippy::indexing_slicing,
     reason = "We don't need to be so strict in tests"
@@ -167,7 +461,7 @@ mod test {
     #[test_log::test]
     fn generates_defaults() {
         let json = serde_json::json!({});
-        let configs = Metadata::merge_configs(&json, std::path::Path::new("./")).unwrap();
+        let configs = Metadata::merge_configs(&json, std::path::Path::new("./"), None).unwrap();
         assert_eq!(configs["build"]["debug"], serde_json::Value::Bool(false));
         assert_eq!(
             configs["install"]["auto_install_rust_toolchain"],
@@ -187,7 +481,7 @@ mod test {
                 }
             }}}
         );
-        let configs = Metadata::merge_configs(&json, std::path::Path::new("./")).unwrap();
+        let configs = Metadata::merge_configs(&json, std::path::Path::new("./"), None).unwrap();
         assert_eq!(configs["build"]["debug"], serde_json::Value::Bool(true));
         assert_eq!(
             configs["install"]["auto_install_rust_toolchain"],
@@ -211,11 +505,190 @@ mod test {
                 "manifest_path": std::fs::canonicalize(marker).unwrap()
             }]}
         );
-        let configs = Metadata::merge_configs(&json, marker.parent().unwrap()).unwrap();
+        let configs = Metadata::merge_configs(&json, marker.parent().unwrap(), None).unwrap();
         assert_eq!(configs["build"]["debug"], serde_json::Value::Bool(true));
         assert_eq!(
             configs["install"]["auto_install_rust_toolchain"],
             serde_json::Value::Bool(true)
         );
     }
+
+    #[test_log::test]
+    fn can_select_a_build_profile() {
+        let marker = std::path::Path::new("./Cargo.toml");
+        let json = serde_json::json!(
+            {
+                "metadata": { "rust-gpu": {
+                    "build": { "shader-target": "spirv-unknown-vulkan1.0" },
+                    "profile": { "web": {
+                        "shader-target": "spirv-unknown-vulkan1.1",
+                        "debug": true
+                    }}
+                }},
+                "packages": [{
+                    "metadata": { "rust-gpu": { "profile": { "web": {
+                        "output-dir": "web",
+                        "capability": ["Int8"]
+                    }}}},
+                    "manifest_path": std::fs::canonicalize(marker).unwrap()
+                }]
+            }
+        );
+        let path = marker.parent().unwrap();
+
+        let without_profile = Metadata::merge_configs(&json, path, None).unwrap();
+        assert_eq!(
+            without_profile["build"]["shader_target"],
+            "spirv-unknown-vulkan1.0"
+        );
+        assert!(without_profile.get("profile").is_none());
+
+        let web = Metadata::merge_configs(&json, path, Some("web")).unwrap();
+        assert_eq!(web["build"]["debug"], serde_json::Value::Bool(true));
+        assert_eq!(web["build"]["shader_target"], "spirv-unknown-vulkan1.1");
+        assert_eq!(web["build"]["capability"], serde_json::json!(["Int8"]));
+        assert_eq!(web["build"]["output_dir"], "./web");
+
+        let error = Metadata::merge_configs(&json, path, Some("release")).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "there's no `[package.metadata.rust-gpu.profile.release]` or \
+             `[workspace.metadata.rust-gpu.profile.release]`"
+        );
+    }
+
+    #[test_log::test]
+    fn finds_local_inputs() {
+        // `shaders` depends on `shared`, which depends on `maths`, all by path.
+        let json = serde_json::json!(
+            {
+                "workspace_root": "/workspace",
+                "packages": [
+                    {
+                        "id": "shaders",
+                        "source": null,
+                        "manifest_path": "/workspace/shaders/Cargo.toml"
+                    },
+                    {
+                        "id": "shared",
+                        "source": null,
+                        "manifest_path": "/workspace/shared/Cargo.toml"
+                    },
+                    {
+                        "id": "maths",
+                        "source": null,
+                        "manifest_path": "/elsewhere/maths/Cargo.toml"
+                    },
+                    {
+                        "id": "spirv-std",
+                        "source": "git+https://github.com/Rust-GPU/rust-gpu",
+                        "manifest_path": "/git/checkouts/spirv-std/Cargo.toml"
+                    },
+                    {
+                        "id": "unrelated",
+                        "source": null,
+                        "manifest_path": "/workspace/unrelated/Cargo.toml"
+                    }
+                ],
+                "resolve": {
+                    "nodes": [
+                        { "id": "shaders", "dependencies": ["shared", "spirv-std"] },
+                        { "id": "shared", "dependencies": ["maths", "spirv-std"] },
+                        { "id": "maths", "dependencies": [] },
+                        { "id": "spirv-std", "dependencies": [] },
+                        { "id": "unrelated", "dependencies": ["shaders"] }
+                    ]
+                }
+            }
+        );
+        let inputs =
+            Metadata::local_inputs_from_json(&json, std::path::Path::new("/workspace/shaders"));
+        assert_eq!(
+            inputs,
+            [
+                "/workspace/shaders",
+                "/workspace/Cargo.lock",
+                "/workspace/shared",
+                "/elsewhere/maths"
+            ]
+            .map(std::path::PathBuf::from)
+        );
+    }
+
+    #[test_log::test]
+    fn finds_shader_crates_in_workspace() {
+        let json = serde_json::json!(
+            { "packages": [
+                {
+                    "name": "host",
+                    "manifest_path": "/workspace/host/Cargo.toml",
+                    "dependencies": [{ "name": "wgpu", "rename": null }]
+                },
+                {
+                    "name": "shaders",
+                    "manifest_path": "/workspace/shaders/Cargo.toml",
+                    "dependencies": [{ "name": "spirv-std", "rename": null }]
+                },
+                {
+                    "name": "more-shaders",
+                    "manifest_path": "/workspace/more-shaders/Cargo.toml",
+                    "dependencies": [{ "name": "spirv-std", "rename": "gpu" }]
+                }
+            ]}
+        );
+        let shader_crates = Metadata::shader_crates_from_json(&json);
+        assert_eq!(
+            shader_crates,
+            vec![
+                ShaderCrate {
+                    name: "more-shaders".to_owned(),
+                    path: std::path::PathBuf::from("/workspace/more-shaders"),
+                },
+                ShaderCrate {
+                    name: "shaders".to_owned(),
+                    path: std::path::PathBuf::from("/workspace/shaders"),
+                },
+            ]
+        );
+    }
+
+    #[test_log::test]
+    fn selecting_a_shader_crate_by_name() {
+        let shader_crates = vec![
+            ShaderCrate {
+                name: "more-shaders".to_owned(),
+                path: std::path::PathBuf::from("/workspace/more-shaders"),
+            },
+            ShaderCrate {
+                name: "shaders".to_owned(),
+                path: std::path::PathBuf::from("/workspace/shaders"),
+            },
+        ];
+        let workspace = std::path::Path::new("/workspace");
+        assert_eq!(
+            Metadata::find_shader_crate(shader_crates.clone(), "shaders", workspace)
+                .unwrap()
+                .path,
+            std::path::PathBuf::from("/workspace/shaders")
+        );
+        assert_eq!(
+            Metadata::find_shader_crate(shader_crates, "host", workspace)
+                .unwrap_err()
+                .to_string(),
+            "there's no shader crate called `host` in the workspace at '/workspace', the shader \
+             crates are: `more-shaders`, `shaders`"
+        );
+    }
+
+    #[test_log::test]
+    fn discovers_shader_crate_template() {
+        let shader_crate_path = crate::test::shader_crate_template_path();
+        let shader_crates = Metadata::shader_crates(&shader_crate_path).unwrap();
+        assert_eq!(shader_crates.len(), 1);
+        assert_eq!(shader_crates[0].name, "rust-gpu-shader-crate-template");
+        assert_eq!(
+            shader_crates[0].path.canonicalize().unwrap(),
+            shader_crate_path.canonicalize().unwrap()
+        );
+    }
 }
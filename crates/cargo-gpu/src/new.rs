@@ -0,0 +1,186 @@
+//! `cargo gpu new`, scaffold a shader crate from the template in this repo, so that getting
+//! started doesn't mean copying files by hand.
+
+use anyhow::Context as _;
+
+/// The template's `Cargo.toml`, which documents every `[package.metadata.rust-gpu]` option.
+const TEMPLATE_MANIFEST: &str = include_str!("../../shader-crate-template/Cargo.toml");
+
+/// The template's other files.
+const TEMPLATE_FILES: &[(&str, &str)] = &[
+    (
+        "src/lib.rs",
+        include_str!("../../shader-crate-template/src/lib.rs"),
+    ),
+    (
+        "README.md",
+        include_str!("../../shader-crate-template/README.md"),
+    ),
+    (
+        ".gitignore",
+        include_str!("../../shader-crate-template/.gitignore"),
+    ),
+];
+
+/// The template's name for itself, which is replaced with the new crate's name.
+const TEMPLATE_NAME: &str = "rust-gpu-shader-crate-template";
+
+/// The `rust-gpu` version that new shader crates depend on, unless they choose another.
+pub const DEFAULT_RUST_GPU_VERSION: &str = "82a0f69";
+
+/// `cargo gpu new`.
+#[derive(clap::Parser, Debug)]
+pub struct New {
+    /// Where to create the shader crate. It mustn't exist yet, or must be empty.
+    pub path: std::path::PathBuf,
+
+    /// The crate's name. Defaults to the name of its directory.
+    #[clap(long)]
+    pub name: Option<String>,
+
+    /// The `rust-gpu` version that the crate depends on, either a released version from
+    /// crates.io, eg "0.9.0", or a commit of the `rust-gpu` repo.
+    #[clap(long, default_value = DEFAULT_RUST_GPU_VERSION)]
+    pub rust_gpu_version: String,
+
+    /// The shader target that the crate is built for.
+    #[clap(long, default_value = "spirv-unknown-vulkan1.2")]
+    pub shader_target: String,
+}
+
+impl New {
+    /// Entrypoint.
+    pub fn run(&self) -> anyhow::Result<()> {
+        let name = match &self.name {
+            Some(name) => name.clone(),
+            None => self
+                .path
+                .file_name()
+                .context("the new crate's path has no directory name, try `--name`")?
+                .to_string_lossy()
+                .into_owned(),
+        };
+        ensure_valid_name(&name)?;
+        anyhow::ensure!(
+            crate::install::TARGET_SPECS
+                .iter()
+                .any(|(filename, _)| filename.strip_suffix(".json") == Some(&self.shader_target)),
+            "unknown shader target `{}`, see `cargo gpu show targets`",
+            self.shader_target
+        );
+        anyhow::ensure!(
+            !self.path.exists() || std::fs::read_dir(&self.path)?.next().is_none(),
+            "'{}' already exists and isn't empty",
+            self.path.display()
+        );
+
+        let manifest = render_manifest(&name, &self.rust_gpu_version, &self.shader_target)?;
+        std::fs::create_dir_all(self.path.join("src"))?;
+        std::fs::write(self.path.join("Cargo.toml"), manifest)?;
+        for (file, contents) in TEMPLATE_FILES {
+            let path = self.path.join(file);
+            let rendered = contents.replace("# shader-crate", &format!("# {name}"));
+            std::fs::write(&path, rendered)
+                .with_context(|| format!("could not write '{}'", path.display()))?;
+        }
+
+        crate::user_output!(
+            "Created shader crate `{name}` at {}\nBuild it with `cargo gpu build --shader-crate {}`\n",
+            self.path.display(),
+            self.path.display()
+        );
+        Ok(())
+    }
+}
+
+/// Check that `name` can be the name of a crate.
+fn ensure_valid_name(name: &str) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        name.starts_with(|char: char| char.is_ascii_alphabetic())
+            && name
+                .chars()
+                .all(|char| char.is_ascii_alphanumeric() || char == '-' || char == '_'),
+        "`{name}` isn't a valid crate name, it has to start with a letter and only have letters, \
+         numbers, `-` and `_`"
+    );
+    Ok(())
+}
+
+/// The `spirv-std = ...` line of a `Cargo.toml` for a `rust-gpu` version, which is either a
+/// release on crates.io, eg "0.9.0", or a commit of the `rust-gpu` repo.
+#[must_use]
+pub fn spirv_std_dependency(rust_gpu_version: &str) -> String {
+    if is_release(rust_gpu_version) {
+        format!("spirv-std = \"{rust_gpu_version}\"")
+    } else {
+        format!(
+            "spirv-std = {{ git = \"{}\", rev = \"{rust_gpu_version}\" }}",
+            crate::spirv_source::RUST_GPU_REPO
+        )
+    }
+}
+
+/// Whether a `rust-gpu` version is a release on crates.io, eg "0.9.0", rather than a commit.
+#[must_use]
+pub fn is_release(rust_gpu_version: &str) -> bool {
+    rust_gpu_version.starts_with(|char: char| char.is_ascii_digit())
+        && rust_gpu_version.contains('.')
+}
+
+/// The template's `Cargo.toml` with the new crate's name, `spirv-std` dependency and shader
+/// target filled in.
+fn render_manifest(
+    name: &str,
+    rust_gpu_version: &str,
+    shader_target: &str,
+) -> anyhow::Result<String> {
+    let spirv_std = spirv_std_dependency(rust_gpu_version);
+    let mut rendered = Vec::new();
+    let mut is_replaced = [false; 3];
+    for line in TEMPLATE_MANIFEST.lines() {
+        if line == format!("name = \"{TEMPLATE_NAME}\"") {
+            rendered.push(format!("name = \"{name}\""));
+            is_replaced[0] = true;
+        } else if line.starts_with("spirv-std = ") {
+            rendered.push(spirv_std.clone());
+            is_replaced[1] = true;
+        } else if line.starts_with("shader-target = ") {
+            rendered.push(format!("shader-target = \"{shader_target}\""));
+            is_replaced[2] = true;
+        } else if line.starts_with("# \"v0.9\" doesn't seem to compile") {
+            // Only relevant to the template's own choice of version.
+        } else {
+            rendered.push(line.to_owned());
+        }
+    }
+    anyhow::ensure!(
+        is_replaced.iter().all(|replaced| *replaced),
+        "the shader crate template is missing its name, `spirv-std` or `shader-target`"
+    );
+    rendered.push(String::new());
+    Ok(rendered.join("\n"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_log::test]
+    fn rendering_the_template_manifest() {
+        let manifest = render_manifest("my-shaders", "0.9.0", "spirv-unknown-vulkan1.1").unwrap();
+        toml::from_str::<toml::Table>(&manifest).unwrap();
+        assert!(manifest.contains("\nname = \"my-shaders\"\n"));
+        assert!(manifest.contains("\nspirv-std = \"0.9.0\"\n"));
+        assert!(manifest.contains("\nshader-target = \"spirv-unknown-vulkan1.1\"\n"));
+        assert!(!manifest.contains(TEMPLATE_NAME));
+
+        let git_manifest =
+            render_manifest("my-shaders", "abc1234", "spirv-unknown-vulkan1.2").unwrap();
+        toml::from_str::<toml::Table>(&git_manifest).unwrap();
+        assert!(git_manifest.contains("rev = \"abc1234\""));
+
+        ensure_valid_name("my_shaders-2").unwrap();
+        ensure_valid_name("2shaders").unwrap_err();
+        ensure_valid_name("my shaders").unwrap_err();
+    }
+}
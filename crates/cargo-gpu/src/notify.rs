@@ -0,0 +1,163 @@
+//! `--notify`, tell running programs, eg a game engine, about rebuilt shaders so that they can be
+//! hot-reloaded without polling file modification times.
+//!
+//! After every successful rebuild in watch mode, the manifest's JSON is sent to every connected
+//! client, either as a text message over a WebSocket (`ws://127.0.0.1:PORT`) or as a single line
+//! over a Unix socket (`unix:/path/to/socket`).
+
+use anyhow::Context as _;
+
+/// A connected client.
+enum Client {
+    /// A WebSocket client.
+    WebSocket(Box<tungstenite::WebSocket<std::net::TcpStream>>),
+    /// A Unix socket client, which is sent one message per line.
+    #[cfg(unix)]
+    Unix(std::os::unix::net::UnixStream),
+}
+
+impl Client {
+    /// Send a message to the client.
+    fn send(&mut self, message: &str) -> anyhow::Result<()> {
+        match self {
+            Self::WebSocket(socket) => socket.send(tungstenite::Message::text(message))?,
+            #[cfg(unix)]
+            Self::Unix(stream) => {
+                use std::io::Write as _;
+                writeln!(stream, "{message}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Every client that's currently connected.
+type Clients = std::sync::Arc<std::sync::Mutex<Vec<Client>>>;
+
+/// Accepts clients in the background and sends them notifications.
+pub struct Notifier {
+    /// The clients that have connected so far.
+    clients: Clients,
+}
+
+impl Notifier {
+    /// Start listening for clients at `address`, either `ws://HOST:PORT` or `unix:PATH`.
+    ///
+    /// # Errors
+    /// If the address isn't valid or can't be listened on.
+    pub fn bind(address: &str) -> anyhow::Result<Self> {
+        let clients = Clients::default();
+        if let Some(host) = address.strip_prefix("ws://") {
+            bind_websocket(host.trim_end_matches('/'), std::sync::Arc::clone(&clients))?;
+        } else if let Some(path) = address.strip_prefix("unix:") {
+            bind_unix(std::path::Path::new(path), std::sync::Arc::clone(&clients))?;
+        } else {
+            anyhow::bail!("`--notify` should be `ws://HOST:PORT` or `unix:PATH`, got `{address}`");
+        }
+        log::info!("sending shader notifications to clients of {address}");
+        Ok(Self { clients })
+    }
+
+    /// Send `message` to every connected client, forgetting any that have disconnected.
+    pub fn send(&self, message: &str) {
+        let Ok(mut clients) = self.clients.lock() else {
+            return;
+        };
+        clients.retain_mut(|client| {
+            client
+                .send(message)
+                .inspect_err(|error| log::debug!("dropping notification client: {error}"))
+                .is_ok()
+        });
+    }
+}
+
+/// Accept WebSocket clients on `host` in the background.
+fn bind_websocket(host: &str, clients: Clients) -> anyhow::Result<()> {
+    let listener = std::net::TcpListener::bind(host)
+        .with_context(|| format!("could not listen for WebSocket clients on `{host}`"))?;
+    std::thread::spawn(move || {
+        for maybe_stream in listener.incoming() {
+            let handshake = maybe_stream
+                .map_err(anyhow::Error::from)
+                .and_then(|stream| {
+                    tungstenite::accept(stream).map_err(|error| anyhow::anyhow!("{error}"))
+                });
+            match handshake {
+                Ok(socket) => {
+                    if let Ok(mut connected) = clients.lock() {
+                        connected.push(Client::WebSocket(Box::new(socket)));
+                    }
+                }
+                Err(error) => log::warn!("couldn't accept WebSocket client: {error}"),
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Accept Unix socket clients at `path` in the background.
+#[cfg(unix)]
+fn bind_unix(path: &std::path::Path, clients: Clients) -> anyhow::Result<()> {
+    // A socket left behind by a previous run would stop us from listening.
+    if path.exists() {
+        std::fs::remove_file(path)?;
+    }
+    let listener = std::os::unix::net::UnixListener::bind(path)
+        .with_context(|| format!("could not listen for clients on '{}'", path.display()))?;
+    std::thread::spawn(move || {
+        for maybe_stream in listener.incoming() {
+            match maybe_stream {
+                Ok(stream) => {
+                    if let Ok(mut connected) = clients.lock() {
+                        connected.push(Client::Unix(stream));
+                    }
+                }
+                Err(error) => log::warn!("couldn't accept notification client: {error}"),
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Unix sockets aren't supported on this platform.
+#[cfg(not(unix))]
+fn bind_unix(path: &std::path::Path, _clients: Clients) -> anyhow::Result<()> {
+    anyhow::bail!(
+        "can't listen on '{}', Unix sockets are only supported on Unix",
+        path.display()
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_log::test]
+    fn rejecting_unknown_addresses() {
+        assert!(Notifier::bind("http://127.0.0.1:1234").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test_log::test]
+    fn notifying_unix_socket_clients() {
+        use std::io::BufRead as _;
+
+        let dir = crate::cache_dir().unwrap();
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("notify");
+        let notifier = Notifier::bind(&format!("unix:{}", path.display())).unwrap();
+        let stream = std::os::unix::net::UnixStream::connect(&path).unwrap();
+        while notifier.clients.lock().unwrap().is_empty() {
+            std::thread::sleep(core::time::Duration::from_millis(10));
+        }
+
+        notifier.send(r#"{"entry_points":[]}"#);
+        let mut line = String::new();
+        std::io::BufReader::new(stream)
+            .read_line(&mut line)
+            .unwrap();
+        assert_eq!(line, "{\"entry_points\":[]}\n");
+        crate::test::tests_teardown();
+    }
+}
@@ -0,0 +1,72 @@
+//! `--offline`, never touch the network, for air-gapped machines and hermetic CI builds.
+//!
+//! Only what's already on disk is used: cloned `rust-gpu` checkouts, installed Rust toolchains and
+//! installed binary pairs. Prebuilt pairs and remote caches are skipped, `--offline` is passed on
+//! to every `cargo` that we run, and anything that would have to be downloaded is an error.
+//!
+//! Like `cargo`, setting `CARGO_NET_OFFLINE=true` has the same effect as `--offline`.
+
+/// Whether offline mode has been chosen for this process.
+static IS_OFFLINE: core::sync::atomic::AtomicBool = core::sync::atomic::AtomicBool::new(false);
+
+/// Forbid network access for the rest of this process.
+pub fn enable() {
+    IS_OFFLINE.store(true, core::sync::atomic::Ordering::Relaxed);
+}
+
+/// Whether network access is forbidden.
+#[must_use]
+pub fn is_enabled() -> bool {
+    IS_OFFLINE.load(core::sync::atomic::Ordering::Relaxed)
+        || std::env::var("CARGO_NET_OFFLINE").is_ok_and(|value| value == "true")
+}
+
+/// The args that make `cargo` stay offline, if it should.
+#[must_use]
+pub fn cargo_args() -> &'static [&'static str] {
+    if is_enabled() {
+        &["--offline"]
+    } else {
+        &[]
+    }
+}
+
+/// Fail if anything in `missing` would have to be downloaded while offline.
+///
+/// # Errors
+/// If offline and `missing` isn't empty.
+pub fn ensure_nothing_missing(missing: &[String]) -> anyhow::Result<()> {
+    if !is_enabled() || missing.is_empty() {
+        return Ok(());
+    }
+    anyhow::bail!(
+        "can't continue offline, as these would have to be downloaded:\n{}\n\
+         Run the same command without `--offline` once to get them.",
+        missing_list(missing)
+    )
+}
+
+/// Every missing thing on its own line.
+fn missing_list(missing: &[String]) -> String {
+    missing
+        .iter()
+        .map(|item| format!("  * {item}"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_log::test]
+    fn listing_what_is_missing() {
+        assert_eq!(
+            missing_list(&[
+                "Rust toolchain nightly-2024-04-24".to_owned(),
+                "the `rust-gpu` repo".to_owned()
+            ]),
+            "  * Rust toolchain nightly-2024-04-24\n  * the `rust-gpu` repo"
+        );
+    }
+}
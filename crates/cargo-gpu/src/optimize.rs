@@ -0,0 +1,216 @@
+//! Optimize compiled SPIR-V modules with `spirv-opt`.
+//!
+//! This uses the `spirv-tools` bindings in their "installed tools" mode, so the `spirv-opt`
+//! binary from the Vulkan SDK, or the `spirv-tools` package of most distros, must be on `PATH`.
+
+use anyhow::Context as _;
+use spirv_builder_cli::args::{BuildArgs, OptLevel};
+use spirv_tools::opt::{Optimizer as _, Passes};
+
+/// All the `spirv-opt` passes that can be set with `--opt-pass`, by their `spirv-opt` flag name.
+const PASSES: &[(&str, Passes)] = &[
+    ("amd-ext-to-khr", Passes::AmdExtToKhr),
+    ("ccp", Passes::ConditionalConstantPropagation),
+    ("cfg-cleanup", Passes::CFGCleanup),
+    ("code-sink", Passes::CodeSinking),
+    ("combine-access-chains", Passes::CombineAccessChains),
+    ("compact-ids", Passes::CompactIds),
+    (
+        "convert-local-access-chains",
+        Passes::LocalAccessChainConvert,
+    ),
+    ("convert-relaxed-to-half", Passes::ConvertRelaxedToHalf),
+    ("copy-propagate-arrays", Passes::CopyPropagateArrays),
+    (
+        "descriptor-scalar-replacement",
+        Passes::DescriptorScalarReplacement,
+    ),
+    ("eliminate-dead-branches", Passes::DeadBranchElim),
+    ("eliminate-dead-code-aggressive", Passes::AggressiveDCE),
+    ("eliminate-dead-const", Passes::EliminateDeadConstant),
+    ("eliminate-dead-functions", Passes::EliminateDeadFunctions),
+    ("eliminate-dead-inserts", Passes::DeadInsertElim),
+    ("eliminate-dead-members", Passes::EliminateDeadMembers),
+    ("eliminate-dead-variables", Passes::DeadVariableElimination),
+    ("eliminate-insert-extract", Passes::InsertExtractElim),
+    ("eliminate-local-multi-store", Passes::LocalMultiStoreElim),
+    (
+        "eliminate-local-single-block",
+        Passes::LocalSingleBlockLoadStoreElim,
+    ),
+    ("eliminate-local-single-store", Passes::LocalSingleStoreElim),
+    (
+        "eliminate-redundant-line-info",
+        Passes::RedundantLineInfoElim,
+    ),
+    ("fix-storage-class", Passes::FixStorageClass),
+    ("flatten-decorations", Passes::FlattenDecoration),
+    (
+        "fold-spec-const-op-composite",
+        Passes::FoldSpecConstantOpAndComposite,
+    ),
+    ("freeze-spec-const", Passes::FreezeSpecConstantValue),
+    ("graphics-robust-access", Passes::GraphicsRobustAccess),
+    ("if-conversion", Passes::IfConversion),
+    ("inline-entry-points-exhaustive", Passes::InlineExhaustive),
+    ("inline-entry-points-opaque", Passes::InlineOpaque),
+    (
+        "local-redundancy-elimination",
+        Passes::LocalRedundancyElimination,
+    ),
+    (
+        "loop-invariant-code-motion",
+        Passes::LoopInvariantCodeMotion,
+    ),
+    ("loop-peeling", Passes::LoopPeeling),
+    ("loop-unswitch", Passes::LoopUnswitch),
+    ("merge-blocks", Passes::BlockMerge),
+    ("merge-return", Passes::MergeReturn),
+    ("private-to-local", Passes::PrivateToLocal),
+    ("propagate-line-info", Passes::PropagateLineInfo),
+    ("reduce-load-size", Passes::ReduceLoadSize),
+    ("redundancy-elimination", Passes::RedundancyElimination),
+    ("relax-float-ops", Passes::RelaxFloatOps),
+    ("remove-duplicates", Passes::RemoveDuplicates),
+    (
+        "remove-unused-interface-variables",
+        Passes::RemoveUnusedInterfaceVariables,
+    ),
+    ("replace-invalid-opcode", Passes::ReplaceInvalidOpcode),
+    ("simplify-instructions", Passes::Simplification),
+    ("ssa-rewrite", Passes::SSARewrite),
+    ("strength-reduction", Passes::StrengthReduction),
+    ("strip-debug", Passes::StripDebugInfo),
+    ("strip-nonsemantic", Passes::StripNonSemanticInfo),
+    ("unify-const", Passes::UnifyConstant),
+    ("upgrade-memory-model", Passes::UpgradeMemoryModel),
+    ("vector-dce", Passes::VectorDCE),
+    ("workaround-1209", Passes::Workaround1209),
+    ("wrap-opkill", Passes::WrapOpKill),
+];
+
+/// Find a pass by its `spirv-opt` flag name, with or without the leading `--`.
+fn pass_by_name(name: &str) -> anyhow::Result<Passes> {
+    let trimmed = name.trim_start_matches("--");
+    PASSES
+        .iter()
+        .find(|(pass_name, _)| *pass_name == trimmed)
+        .map(|(_, pass)| *pass)
+        .with_context(|| {
+            let all = PASSES
+                .iter()
+                .map(|(pass_name, _)| *pass_name)
+                .collect::<Vec<_>>();
+            format!("unknown `spirv-opt` pass `{name}`, expected one of: {all:?}")
+        })
+}
+
+/// The `spirv-tools` target environment for a shader target, eg "vulkan1.2" for
+/// "spirv-unknown-vulkan1.2". Also accepts the path to a target's spec file.
+pub fn target_env(shader_target: &str) -> anyhow::Result<spirv_tools::TargetEnv> {
+    let name = shader_target
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or(shader_target)
+        .trim_end_matches(".json");
+    let env = name.strip_prefix("spirv-unknown-").unwrap_or(name);
+    env.parse().map_err(|_err| {
+        if crate::install::NEWER_TARGET_SPECS
+            .contains(&format!("spirv-unknown-{env}.json").as_str())
+        {
+            anyhow::anyhow!(
+                "the bundled `spirv-tools` predates the target `{env}`, run `spirv-val \
+                 --target-env {env}` from a newer SPIRV-Tools instead"
+            )
+        } else {
+            anyhow::anyhow!("`spirv-tools` doesn't support the target `{env}`")
+        }
+    })
+}
+
+/// Whether any optimization has been asked for.
+#[must_use]
+pub const fn is_enabled(build_args: &BuildArgs) -> bool {
+    !matches!(build_args.opt_level, OptLevel::None) || !build_args.opt_pass.is_empty()
+}
+
+/// Run `spirv-opt` on the SPIR-V module at `source`, writing the optimized module to
+/// `destination`.
+pub fn optimize(
+    source: &std::path::Path,
+    destination: &std::path::Path,
+    build_args: &BuildArgs,
+) -> anyhow::Result<()> {
+    let bytes = std::fs::read(source)
+        .with_context(|| format!("could not read SPIR-V file '{}'", source.display()))?;
+    let binary = spirv_tools::binary::Binary::try_from(bytes)
+        .with_context(|| format!("'{}' is not a SPIR-V module", source.display()))?;
+
+    let mut spirv_opt =
+        spirv_tools::opt::create(Some(target_env(build_args.single_shader_target())?));
+    match build_args.opt_level {
+        OptLevel::None => (),
+        OptLevel::Performance => {
+            spirv_opt.register_performance_passes();
+        }
+        OptLevel::Size => {
+            spirv_opt.register_size_passes();
+        }
+    }
+    for name in &build_args.opt_pass {
+        spirv_opt.register_pass(pass_by_name(name)?);
+    }
+
+    let options = spirv_tools::opt::Options {
+        preserve_bindings: build_args.preserve_bindings,
+        ..Default::default()
+    };
+    log::debug!("optimizing {}", source.display());
+    let optimized = spirv_opt
+        .optimize(
+            &binary,
+            &mut |message: spirv_tools::error::Message| {
+                log::warn!("spirv-opt: {}", message.message);
+            },
+            Some(options),
+        )
+        .with_context(|| {
+            format!(
+                "`spirv-opt` could not optimize '{}', is it installed and on your PATH?",
+                source.display()
+            )
+        })?;
+
+    crate::atomic::write(destination, optimized.as_bytes())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_log::test]
+    fn passes_by_name() {
+        assert!(matches!(
+            pass_by_name("merge-blocks").unwrap(),
+            Passes::BlockMerge
+        ));
+        assert!(matches!(
+            pass_by_name("--strip-debug").unwrap(),
+            Passes::StripDebugInfo
+        ));
+        pass_by_name("not-a-pass").unwrap_err();
+    }
+
+    #[test_log::test]
+    fn target_envs_from_shader_targets() {
+        assert_eq!(
+            target_env("spirv-unknown-vulkan1.2").unwrap(),
+            spirv_tools::TargetEnv::Vulkan_1_2
+        );
+        assert_eq!(
+            target_env("/cache/target-specs/spirv-unknown-spv1.3.json").unwrap(),
+            spirv_tools::TargetEnv::Universal_1_3
+        );
+        target_env("spirv-unknown-nonsense").unwrap_err();
+    }
+}
@@ -0,0 +1,439 @@
+//! How results are written for the user, either as human readable text or as JSON for scripts.
+//!
+//! In JSON mode stdout is reserved for a single JSON document describing the command's result, so
+//! everything else, like `user_output!` messages and the output of child processes, goes to
+//! stderr instead.
+//!
+//! Similarly to `cargo build --message-format json`, progress can also be reported as a stream of
+//! [`Event`]s on stdout, one JSON object per line, so that IDEs and build orchestrators can show
+//! what's happening.
+//!
+//! In build script mode, for using `cargo gpu build` from a `build.rs`, stdout is reserved for
+//! `cargo:` directives instead, and the crab messages are only logged.
+
+/// The format that a command writes its result in.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    clap::ValueEnum,
+    serde::Deserialize,
+    serde::Serialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    /// Human readable text.
+    #[default]
+    Text,
+    /// A single JSON document on stdout.
+    Json,
+}
+
+/// The format that progress is reported in.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    clap::ValueEnum,
+    serde::Deserialize,
+    serde::Serialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageFormat {
+    /// Human readable messages.
+    #[default]
+    Human,
+    /// One JSON [`Event`] per line on stdout.
+    Json,
+}
+
+/// Whether to style output with colours.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Color {
+    /// Colour when writing to a terminal, unless `NO_COLOR` is set.
+    #[default]
+    Auto,
+    /// Always colour.
+    Always,
+    /// Never colour.
+    Never,
+}
+
+impl Color {
+    /// The choice after taking `NO_COLOR` into account, see <https://no-color.org>.
+    #[must_use]
+    pub fn resolve(self, no_color: Option<&std::ffi::OsStr>) -> Self {
+        match self {
+            Self::Auto if no_color.is_some_and(|value| !value.is_empty()) => Self::Never,
+            Self::Auto | Self::Always | Self::Never => self,
+        }
+    }
+
+    /// The same choice for `cargo`'s `CARGO_TERM_COLOR`.
+    #[must_use]
+    pub const fn as_cargo_term_color(self) -> &'static str {
+        match self {
+            Self::Auto => "auto",
+            Self::Always => "always",
+            Self::Never => "never",
+        }
+    }
+}
+
+/// Something that happened during an install or a build.
+///
+/// Serialized with a `reason` field naming the event, eg:
+///   `{"reason":"entry-point-emitted","entry_point":"main_fs","module":"shaders/main_fs.spv"}`.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(tag = "reason", rename_all = "kebab-case")]
+pub enum Event {
+    /// A binary pair is about to be downloaded or compiled.
+    InstallStarted {
+        /// The repo or path of `rust-gpu`.
+        source: String,
+        /// The version or revision of `rust-gpu`.
+        version: String,
+        /// The toolchain channel that `rust-gpu` uses.
+        channel: String,
+    },
+    /// A Rust toolchain was installed with `rustup`.
+    ToolchainAdded {
+        /// The toolchain's channel.
+        channel: String,
+    },
+    /// Components were added to a Rust toolchain with `rustup`.
+    ComponentsAdded {
+        /// The toolchain's channel.
+        channel: String,
+        /// The names of the components.
+        components: Vec<String>,
+    },
+    /// `cargo` is compiling a crate.
+    Compiling {
+        /// What's being compiled, eg `spirv-builder-cli` or a shader crate.
+        package: String,
+        /// The directory of the crate.
+        path: std::path::PathBuf,
+    },
+    /// A shader entry point was compiled.
+    EntryPointEmitted {
+        /// The name of the entry point.
+        entry_point: String,
+        /// The compiled module containing the entry point.
+        module: String,
+    },
+    /// A shader manifest was written.
+    ManifestWritten {
+        /// Where the manifest is.
+        path: std::path::PathBuf,
+    },
+}
+
+/// Whether JSON output has been chosen for this process.
+static IS_JSON: core::sync::atomic::AtomicBool = core::sync::atomic::AtomicBool::new(false);
+
+/// Whether JSON progress events have been chosen for this process.
+static IS_JSON_MESSAGES: core::sync::atomic::AtomicBool =
+    core::sync::atomic::AtomicBool::new(false);
+
+/// Whether build script mode has been chosen for this process.
+static IS_BUILD_SCRIPT_MODE: core::sync::atomic::AtomicBool =
+    core::sync::atomic::AtomicBool::new(false);
+
+/// Whether quiet mode has been chosen for this process.
+static IS_QUIET: core::sync::atomic::AtomicBool = core::sync::atomic::AtomicBool::new(false);
+
+/// Whether emoji have been turned off for this process.
+static IS_NO_EMOJI: core::sync::atomic::AtomicBool = core::sync::atomic::AtomicBool::new(false);
+
+/// The colour choice for this process, as a [`Color`] that's been resolved, see [`color`].
+static COLOR: core::sync::atomic::AtomicU8 = core::sync::atomic::AtomicU8::new(0);
+
+/// Choose the output format for the rest of this process.
+pub fn set_format(format: Format) {
+    IS_JSON.store(
+        format == Format::Json,
+        core::sync::atomic::Ordering::Relaxed,
+    );
+}
+
+/// Choose the progress message format for the rest of this process.
+pub fn set_message_format(format: MessageFormat) {
+    IS_JSON_MESSAGES.store(
+        format == MessageFormat::Json,
+        core::sync::atomic::Ordering::Relaxed,
+    );
+}
+
+/// Choose whether to print `cargo:` directives for a `build.rs`, for the rest of this process.
+pub fn set_build_script_mode(is_enabled: bool) {
+    IS_BUILD_SCRIPT_MODE.store(is_enabled, core::sync::atomic::Ordering::Relaxed);
+}
+
+/// Choose whether to leave out the messages for the user, for the rest of this process.
+pub fn set_quiet(is_enabled: bool) {
+    IS_QUIET.store(is_enabled, core::sync::atomic::Ordering::Relaxed);
+}
+
+/// Choose whether to leave out the crab, for the rest of this process.
+pub fn set_no_emoji(is_enabled: bool) {
+    IS_NO_EMOJI.store(is_enabled, core::sync::atomic::Ordering::Relaxed);
+}
+
+/// Choose whether to colour output, for the rest of this process. `NO_COLOR` turns colour off
+/// unless it was explicitly asked for.
+pub fn set_color(choice: Color) {
+    let stored = match choice.resolve(std::env::var_os("NO_COLOR").as_deref()) {
+        Color::Auto => 0,
+        Color::Always => 1,
+        Color::Never => 2,
+    };
+    COLOR.store(stored, core::sync::atomic::Ordering::Relaxed);
+}
+
+/// Whether to colour output.
+#[must_use]
+pub fn color() -> Color {
+    match COLOR.load(core::sync::atomic::Ordering::Relaxed) {
+        1 => Color::Always,
+        2 => Color::Never,
+        _ => Color::Auto,
+    }
+}
+
+/// Make a child `cargo`, or anything that runs `cargo`, colour its output like this process.
+pub fn configure_child_color(command: &mut std::process::Command) {
+    let choice = color();
+    if choice != Color::Auto {
+        command.env("CARGO_TERM_COLOR", choice.as_cargo_term_color());
+    }
+}
+
+/// Whether the command's result should be written as JSON.
+#[must_use]
+pub fn is_json() -> bool {
+    IS_JSON.load(core::sync::atomic::Ordering::Relaxed)
+}
+
+/// Whether progress should be reported as JSON events.
+#[must_use]
+pub fn is_json_messages() -> bool {
+    IS_JSON_MESSAGES.load(core::sync::atomic::Ordering::Relaxed)
+}
+
+/// Whether `cargo:` directives should be printed for a `build.rs`.
+#[must_use]
+pub fn is_build_script_mode() -> bool {
+    IS_BUILD_SCRIPT_MODE.load(core::sync::atomic::Ordering::Relaxed)
+}
+
+/// Whether the messages for the user are left out, for `--quiet`.
+#[must_use]
+pub fn is_quiet() -> bool {
+    IS_QUIET.load(core::sync::atomic::Ordering::Relaxed)
+}
+
+/// Whether stdout is reserved for JSON or `cargo:` directives, so that anything else has to go to
+/// stderr.
+#[must_use]
+pub fn is_stdout_reserved() -> bool {
+    is_json() || is_json_messages() || is_build_script_mode()
+}
+
+/// Write a message for the user, prefixed with a crab unless `--no-emoji` was passed, and not at
+/// all if `--quiet` was. Use [`crate::user_output!`] rather than calling this directly.
+pub fn user_message(message: core::fmt::Arguments<'_>) {
+    #[expect(clippy::non_ascii_literal, reason = "CRAB GOOD. CRAB IMPORTANT.")]
+    let crab = if IS_NO_EMOJI.load(core::sync::atomic::Ordering::Relaxed) {
+        ""
+    } else {
+        "🦀 "
+    };
+    crate::log_file::write("output", &message.to_string());
+    if is_quiet() {
+        return;
+    }
+    if is_build_script_mode() {
+        log::info!("{message}");
+        return;
+    }
+    #[expect(
+        clippy::print_stdout,
+        clippy::print_stderr,
+        reason = "This is our central place for writing to the user"
+    )]
+    if is_stdout_reserved() {
+        eprint!("{crab}{message}");
+    } else {
+        use std::io::Write as _;
+        print!("{crab}{message}");
+        if let Err(error) = std::io::stdout().flush() {
+            log::warn!("could not flush stdout: {error}");
+        }
+    }
+}
+
+/// Report a progress event, if JSON progress events have been chosen.
+pub fn emit(event: &Event) {
+    if !is_json_messages() {
+        return;
+    }
+    match serde_json::to_string(event) {
+        Ok(json) => {
+            #[expect(
+                clippy::print_stdout,
+                reason = "Progress events are part of our output"
+            )]
+            {
+                println!("{json}");
+            };
+        }
+        Err(error) => log::warn!("could not serialize progress event: {error}"),
+    }
+}
+
+/// Where a child process's stdout should go, so that it doesn't get mixed into JSON output.
+#[must_use]
+pub fn child_stdout() -> std::process::Stdio {
+    if is_stdout_reserved() {
+        std::io::stderr().into()
+    } else {
+        std::process::Stdio::inherit()
+    }
+}
+
+/// Where a child process's stderr should go. In build script mode it's captured, so that
+/// compiler warnings can be passed on to `cargo`, see [`forward_compiler_stderr`].
+#[must_use]
+pub fn child_stderr() -> std::process::Stdio {
+    if is_build_script_mode() {
+        std::process::Stdio::piped()
+    } else {
+        std::process::Stdio::inherit()
+    }
+}
+
+/// Print a `cargo:` directive for a `build.rs`, eg `rerun-if-changed=src/lib.rs`.
+pub fn cargo_directive(directive: &str) {
+    #[expect(clippy::print_stdout, reason = "Directives are read by `cargo`")]
+    {
+        println!("cargo:{directive}");
+    };
+}
+
+/// Pass on a child process's captured stderr, turning any compiler warnings into
+/// `cargo:warning=` directives so that they're shown by the `cargo build` running the `build.rs`.
+pub fn forward_compiler_stderr(stderr: &[u8]) {
+    let text = String::from_utf8_lossy(stderr);
+    #[expect(clippy::print_stderr, reason = "It's the child process's stderr")]
+    {
+        eprint!("{text}");
+    };
+    for warning in compiler_warnings(&text) {
+        cargo_directive(&format!("warning={warning}"));
+    }
+}
+
+/// The warnings in a compiler's output, each with its location if it has one, eg
+/// "unused variable: `x` (src/lib.rs:3:9)". Summaries like "`shader` (lib) generated 1 warning"
+/// are skipped.
+fn compiler_warnings(output: &str) -> Vec<String> {
+    let mut warnings = Vec::new();
+    let mut lines = output.lines().peekable();
+    while let Some(line) = lines.next() {
+        let Some(warning) = line.strip_prefix("warning: ") else {
+            continue;
+        };
+        if warning.contains(" generated ") && warning.contains(" warning") {
+            continue;
+        }
+        let maybe_location = lines
+            .peek()
+            .and_then(|next| next.trim_start().strip_prefix("--> "));
+        warnings.push(maybe_location.map_or_else(
+            || warning.to_owned(),
+            |location| format!("{warning} ({location})"),
+        ));
+    }
+    warnings
+}
+
+/// Write a command's result to stdout as JSON. It's pretty printed, unless it's going to be
+/// amongst JSON progress events, in which case it has to fit on one line.
+///
+/// # Errors
+/// If `value` can't be serialized.
+pub fn print_json<V: serde::Serialize>(value: &V) -> anyhow::Result<()> {
+    let json = if is_json_messages() {
+        serde_json::to_string(value)?
+    } else {
+        serde_json::to_string_pretty(value)?
+    };
+    #[expect(
+        clippy::print_stdout,
+        reason = "This is the one place that JSON results are written"
+    )]
+    {
+        println!("{json}");
+    };
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_log::test]
+    fn finding_compiler_warnings() {
+        let output = [
+            "   Compiling shader v0.1.0 (/shader)",
+            "warning: unused variable: `x`",
+            " --> src/lib.rs:3:9",
+            "  |",
+            "3 |     let x = 1;",
+            "  |         ^ help: if this is intentional, prefix it with an underscore: `_x`",
+            "",
+            "warning: `shader` (lib) generated 1 warning",
+            "warning: spirv-val found something odd",
+            "    Finished `release` profile [optimized] target(s) in 1.00s",
+        ]
+        .join("\n");
+        assert_eq!(
+            compiler_warnings(&output),
+            [
+                "unused variable: `x` (src/lib.rs:3:9)",
+                "spirv-val found something odd"
+            ]
+        );
+    }
+
+    #[test_log::test]
+    fn no_color_turns_off_automatic_colour() {
+        let no_color = Some(std::ffi::OsStr::new("1"));
+        assert_eq!(Color::Auto.resolve(no_color), Color::Never);
+        assert_eq!(Color::Always.resolve(no_color), Color::Always);
+        assert_eq!(
+            Color::Auto.resolve(Some(std::ffi::OsStr::new(""))),
+            Color::Auto
+        );
+        assert_eq!(Color::Auto.resolve(None), Color::Auto);
+    }
+
+    #[test_log::test]
+    fn events_are_tagged_with_their_reason() {
+        let event = Event::EntryPointEmitted {
+            entry_point: "main_fs".to_owned(),
+            module: "shaders/main_fs.spv".to_owned(),
+        };
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            r#"{"reason":"entry-point-emitted","entry_point":"main_fs","module":"shaders/main_fs.spv"}"#
+        );
+    }
+}
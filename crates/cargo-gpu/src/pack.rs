@@ -0,0 +1,91 @@
+//! `--pack`, every module of a build in a single file, for platforms where loading one file is
+//! much quicker than opening dozens of small ones.
+//!
+//! The pack is just the module files one after another, each starting on a 4 byte boundary so
+//! that uncompressed SPIR-V can be read as words straight out of it. There's no header, the index
+//! is in the manifest, which records where each entry point's module is in the pack.
+
+use anyhow::Context as _;
+use spirv_builder_cli::{Linkage, PackedModule};
+
+/// Modules in the pack start on multiples of this, the size of a SPIR-V word.
+const ALIGNMENT: usize = 4;
+
+/// Write every module in `linkage` into the pack file at `pack_path`, recording where each entry
+/// point's module is in its linkage. Modules are only packed once, even when many entry points
+/// share them.
+///
+/// # Errors
+/// If any of the modules can't be read or the pack can't be written.
+pub fn write(
+    linkage: Vec<Linkage>,
+    pack_path: &std::path::Path,
+    shader_crate: &std::path::Path,
+) -> anyhow::Result<Vec<Linkage>> {
+    use relative_path::PathExt as _;
+
+    let relative_pack_path = pack_path.relative_to(shader_crate).map_or_else(
+        |_| pack_path.display().to_string(),
+        relative_path::RelativePathBuf::into_string,
+    );
+    // Sorted, so that the same modules always make the same pack.
+    let module_paths = linkage
+        .iter()
+        .map(|entry| entry.source_path.clone())
+        .collect::<std::collections::BTreeSet<_>>();
+    let mut pack = Vec::new();
+    let mut by_module = std::collections::HashMap::new();
+    for module_path in module_paths {
+        let path = shader_crate.join(&module_path);
+        let bytes =
+            std::fs::read(&path).with_context(|| format!("could not read '{}'", path.display()))?;
+        let packed = append(&mut pack, &bytes, relative_pack_path.clone())?;
+        by_module.insert(module_path, packed);
+    }
+    crate::atomic::write(pack_path, &pack)?;
+    log::debug!(
+        "packed {} modules into {}",
+        by_module.len(),
+        pack_path.display()
+    );
+
+    linkage
+        .into_iter()
+        .map(|entry| {
+            let packed = by_module
+                .get(&entry.source_path)
+                .context("module should have been packed already")?
+                .clone();
+            Ok(entry.with_packed(packed))
+        })
+        .collect()
+}
+
+/// Add a module to the end of the pack, after any padding that it needs to be aligned.
+fn append(pack: &mut Vec<u8>, bytes: &[u8], path: String) -> anyhow::Result<PackedModule> {
+    pack.resize(pack.len().next_multiple_of(ALIGNMENT), 0);
+    let offset = u64::try_from(pack.len())?;
+    pack.extend_from_slice(bytes);
+    Ok(PackedModule {
+        path,
+        offset,
+        length: u64::try_from(bytes.len())?,
+        sha256: crate::prebuilt::sha256_hex(bytes),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_log::test]
+    fn packing_aligned_modules() {
+        let mut pack = Vec::new();
+        let first = append(&mut pack, &[1, 2, 3], "shaders.pack".to_owned()).unwrap();
+        let second = append(&mut pack, &[4, 5, 6, 7], "shaders.pack".to_owned()).unwrap();
+        assert_eq!(pack, [1, 2, 3, 0, 4, 5, 6, 7]);
+        assert_eq!((first.offset, first.length), (0, 3));
+        assert_eq!((second.offset, second.length), (4, 4));
+        assert_eq!(second.sha256, crate::prebuilt::sha256_hex(&[4, 5, 6, 7]));
+    }
+}
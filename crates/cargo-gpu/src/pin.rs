@@ -0,0 +1,195 @@
+//! `rust-gpu.lock`, pin the exact `rust-gpu` that a shader crate is built with.
+//!
+//! The first install for a shader crate writes the `rust-gpu` revision that its `spirv-std`
+//! resolved to, the toolchain channel and the hashes of the target specs into `rust-gpu.lock`
+//! next to its `Cargo.toml`. Later installs use what's pinned, like `--spirv-builder-version` and
+//! `--rust-toolchain` would, so that a `spirv-std` dependency on a loose version or a branch
+//! doesn't silently move the compiler. Deleting the file, or `cargo gpu upgrade`, pins again.
+
+use anyhow::Context as _;
+
+use crate::spirv_source::SpirvSource;
+
+/// The name of the pin file, next to the shader crate's `Cargo.toml`.
+pub const FILE: &str = "rust-gpu.lock";
+
+/// The version of the pin file's format.
+const FORMAT_VERSION: u32 = 1;
+
+/// What's written as the source of `rust-gpu` releases from crates.io.
+const CRATES_IO: &str = "crates.io";
+
+/// The comment at the top of the pin file.
+const HEADER: &str = "# The exact `rust-gpu` that `cargo gpu` builds this shader crate with. Commit this file, and\n\
+                      # delete it, or run `cargo gpu upgrade`, to move to another `rust-gpu`.\n\n";
+
+/// The contents of `rust-gpu.lock`.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub struct RustGpuLock {
+    /// The version of the file's format.
+    pub version: u32,
+    /// Where `rust-gpu` comes from, either "crates.io" or the URL of a Git repo.
+    pub source: String,
+    /// The crates.io version, or the full Git commit hash, of `rust-gpu`.
+    pub revision: String,
+    /// The Rust toolchain channel that `rust-gpu` is built with.
+    pub channel: String,
+    /// The SHA-256 of each target spec, by its file name.
+    #[serde(default)]
+    pub target_specs: std::collections::BTreeMap<String, String>,
+}
+
+impl RustGpuLock {
+    /// Pin `source` and `channel`, along with the target specs in `spec_dir`. Returns `None` for
+    /// local paths, which can't be pinned.
+    pub fn pinning(
+        source: &SpirvSource,
+        channel: &str,
+        spec_dir: &std::path::Path,
+    ) -> anyhow::Result<Option<Self>> {
+        let (pinned_source, revision) = match source {
+            SpirvSource::CratesIO(version) => (CRATES_IO.to_owned(), version.clone()),
+            SpirvSource::Git { url, .. } => (url.clone(), source.resolved_revision()?),
+            SpirvSource::Path(_) => return Ok(None),
+        };
+        Ok(Some(Self {
+            version: FORMAT_VERSION,
+            source: pinned_source,
+            revision,
+            channel: channel.to_owned(),
+            target_specs: target_spec_hashes(spec_dir)?,
+        }))
+    }
+
+    /// The `--spirv-builder-source` that the pinned `rust-gpu` is installed with.
+    #[must_use]
+    pub fn spirv_builder_source(&self) -> Option<String> {
+        (self.source != CRATES_IO).then(|| self.source.clone())
+    }
+
+    /// Check that the target specs in `spec_dir` are the pinned ones.
+    pub fn check_target_specs(&self, spec_dir: &std::path::Path) -> anyhow::Result<()> {
+        let current = target_spec_hashes(spec_dir)?;
+        let changed = self
+            .target_specs
+            .iter()
+            .filter(|(name, sha256)| current.get(*name) != Some(sha256))
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        anyhow::ensure!(
+            changed.is_empty(),
+            "the target specs {} aren't the ones pinned in `{FILE}`, delete it to pin the current \
+             ones instead",
+            changed.join(", ")
+        );
+        Ok(())
+    }
+}
+
+/// Read the shader crate's pin file, if it has one.
+pub fn read(shader_crate: &std::path::Path) -> anyhow::Result<Option<RustGpuLock>> {
+    let path = shader_crate.join(FILE);
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let contents = std::fs::read_to_string(&path)
+        .with_context(|| format!("could not read '{}'", path.display()))?;
+    let lock: RustGpuLock = toml::from_str(&contents)
+        .with_context(|| format!("could not parse '{}'", path.display()))?;
+    anyhow::ensure!(
+        lock.version <= FORMAT_VERSION,
+        "'{}' was written by a newer `cargo gpu`",
+        path.display()
+    );
+    Ok(Some(lock))
+}
+
+/// Write the shader crate's pin file.
+pub fn write(shader_crate: &std::path::Path, lock: &RustGpuLock) -> anyhow::Result<()> {
+    let path = shader_crate.join(FILE);
+    crate::atomic::write(&path, format!("{HEADER}{}", toml::to_string_pretty(lock)?))
+        .with_context(|| format!("could not write '{}'", path.display()))
+}
+
+/// Remove the shader crate's pin file, so that the next install pins again.
+pub fn remove(shader_crate: &std::path::Path) -> anyhow::Result<()> {
+    let path = shader_crate.join(FILE);
+    if path.is_file() {
+        std::fs::remove_file(&path)
+            .with_context(|| format!("could not remove '{}'", path.display()))?;
+    }
+    Ok(())
+}
+
+/// The SHA-256 of each target spec in `spec_dir`, by its file name.
+fn target_spec_hashes(
+    spec_dir: &std::path::Path,
+) -> anyhow::Result<std::collections::BTreeMap<String, String>> {
+    let mut hashes = std::collections::BTreeMap::new();
+    for maybe_entry in std::fs::read_dir(spec_dir)
+        .with_context(|| format!("could not read target specs in '{}'", spec_dir.display()))?
+    {
+        let path = maybe_entry?.path();
+        if path.extension().is_none_or(|extension| extension != "json") {
+            continue;
+        }
+        let Some(name) = path.file_name() else {
+            continue;
+        };
+        hashes.insert(
+            name.to_string_lossy().into_owned(),
+            crate::prebuilt::sha256_hex(&std::fs::read(&path)?),
+        );
+    }
+    Ok(hashes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_log::test]
+    fn pinning_and_checking_target_specs() {
+        let dir = crate::cache_dir().unwrap().join("pin");
+        let spec_dir = dir.join("target-specs");
+        std::fs::create_dir_all(&spec_dir).unwrap();
+        std::fs::write(spec_dir.join("spirv-unknown-vulkan1.2.json"), "{}").unwrap();
+
+        let lock = RustGpuLock::pinning(
+            &SpirvSource::CratesIO("0.9.0".to_owned()),
+            "nightly-2023-05-27",
+            &spec_dir,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(lock.spirv_builder_source(), None);
+        assert_eq!(
+            lock.target_specs.keys().collect::<Vec<_>>(),
+            ["spirv-unknown-vulkan1.2.json"]
+        );
+        write(&dir, &lock).unwrap();
+        assert_eq!(read(&dir).unwrap(), Some(lock.clone()));
+
+        lock.check_target_specs(&spec_dir).unwrap();
+        std::fs::write(
+            spec_dir.join("spirv-unknown-vulkan1.2.json"),
+            "{\"changed\":1}",
+        )
+        .unwrap();
+        lock.check_target_specs(&spec_dir).unwrap_err();
+
+        assert_eq!(
+            RustGpuLock::pinning(
+                &SpirvSource::Path(("/rust-gpu".to_owned(), "0.9.0".to_owned())),
+                "nightly-2023-05-27",
+                &spec_dir
+            )
+            .unwrap(),
+            None
+        );
+        remove(&dir).unwrap();
+        assert_eq!(read(&dir).unwrap(), None);
+        crate::test::tests_teardown();
+    }
+}
@@ -0,0 +1,651 @@
+#![allow(
+    clippy::missing_trait_methods,
+    reason = "processors only implement the phases that they take part in"
+)]
+//! What happens to the modules after `spirv-builder` has compiled them, as a chain of
+//! [`OutputProcessor`]s.
+//!
+//! Each processor first gets every module in turn, once it's in the output dir, and then the
+//! linkage of every entry point, once the linkage has been made from the processed modules. Their
+//! order comes from `--output-processors`, so new outputs can be added as processors without
+//! changing the rest of the build.
+
+use anyhow::Context as _;
+use spirv_builder_cli::{args::BuildArgs, args::Processor, Linkage, ShaderModule};
+
+use crate::build::Build;
+
+/// A step that the compiled modules go through on their way to the output dir and the manifest.
+pub trait OutputProcessor {
+    /// Process a module that's in the output dir, eg by changing it in place.
+    ///
+    /// # Errors
+    /// If the module can't be processed, which fails the build.
+    fn process_module(
+        &self,
+        _module: &mut OutputModule,
+        _context: &Context<'_>,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Process the linkage of every entry point, once all the modules have been processed.
+    ///
+    /// # Errors
+    /// If the linkage can't be processed, which fails the build.
+    fn process_linkage(
+        &self,
+        linkage: Vec<Linkage>,
+        _modules: &[OutputModule],
+        _context: &Context<'_>,
+    ) -> anyhow::Result<Vec<Linkage>> {
+        Ok(linkage)
+    }
+}
+
+/// What the processors need to know about the build.
+pub struct Context<'build> {
+    /// The build whose modules are being processed.
+    pub build: &'build Build,
+    /// Where the manifest is written.
+    pub manifest_path: std::path::PathBuf,
+    /// How long `spirv-builder` took to compile the modules.
+    pub compile_time: core::time::Duration,
+    /// The exact old and new name of each entry point renamed by `--rename-entry-point`.
+    pub entry_point_renames: Vec<(String, String)>,
+}
+
+impl Context<'_> {
+    /// The build's args.
+    const fn build_args(&self) -> &BuildArgs {
+        &self.build.build_args
+    }
+
+    /// The shader crate being built.
+    fn shader_crate(&self) -> &std::path::Path {
+        &self.build.install.spirv_install.shader_crate
+    }
+}
+
+/// A compiled module, as it goes through the processors.
+#[derive(Debug, Default)]
+pub struct OutputModule {
+    /// Where `spirv-builder` compiled the module to.
+    pub compiled: std::path::PathBuf,
+    /// Where the module is in the output dir, once it's been copied there.
+    pub path: std::path::PathBuf,
+    /// The entry points in the module.
+    pub entry_points: Vec<String>,
+    /// What reflection found out about each entry point, by name.
+    pub reflection: std::collections::HashMap<String, crate::reflect::EntryPointReflection>,
+    /// The `--spec-constant`s that matched a specialization constant in the module.
+    pub matched_spec_constants: Vec<String>,
+    /// The SHA-256 of the finished module.
+    pub sha256: String,
+}
+
+/// Run the compiled `shaders` through the processors. Returns the linkage of every entry point.
+///
+/// # Errors
+/// If `--output-processors` isn't a valid order, or if any of the processors fail.
+pub fn run(shaders: Vec<ShaderModule>, context: &Context<'_>) -> anyhow::Result<Vec<Linkage>> {
+    let processors = processors(context.build_args())?;
+
+    // Without `--multimodule` every entry point shares the same module, so each module is only
+    // processed once.
+    let mut modules: Vec<OutputModule> = Vec::new();
+    for shader in &shaders {
+        match modules
+            .iter_mut()
+            .find(|module| module.compiled == shader.path)
+        {
+            Some(module) => module.entry_points.push(shader.entry.clone()),
+            None => modules.push(OutputModule {
+                compiled: shader.path.clone(),
+                entry_points: vec![shader.entry.clone()],
+                ..OutputModule::default()
+            }),
+        }
+    }
+    for index in 0..modules.len() {
+        let Some(module) = modules.get_mut(index) else {
+            continue;
+        };
+        for processor in &processors {
+            processor.process_module(module, context)?;
+        }
+        let module_path = context
+            .build
+            .rename_to_output_file_template(&module.path, &module.entry_points)?;
+        module.path = module_path;
+        if context.build_args().debug_info {
+            crate::debug_info::warn_about_size(&module.path)?;
+        }
+        module.sha256 = crate::prebuilt::sha256_hex(&std::fs::read(&module.path)?);
+        let Some(processed) = modules.get(index) else {
+            continue;
+        };
+        anyhow::ensure!(
+            !modules
+                .iter()
+                .take(index)
+                .any(|other| other.path == processed.path),
+            "more than one module would be written to '{}', try adding `{{entry}}` to \
+             `--output-file-template`",
+            processed.path.display()
+        );
+    }
+
+    let mut linkage = shaders
+        .into_iter()
+        .map(|shader| {
+            let module = modules
+                .iter()
+                .find(|module| module.compiled == shader.path)
+                .context("module should have been processed already")?;
+            log::debug!(
+                "linkage of {} relative to {}",
+                module.path.display(),
+                context.shader_crate().display()
+            );
+            let mut entry_linkage = Linkage::new(
+                &shader.entry,
+                context.build.relative_to_shader_crate(&module.path),
+            )
+            .with_sha256(module.sha256.clone());
+            if let Some(reflection) = module.reflection.get(&shader.entry) {
+                entry_linkage = entry_linkage
+                    .with_reflection(reflection.stage.clone(), reflection.bindings.clone())
+                    .with_push_constant_size(reflection.push_constant_size)
+                    .with_capabilities(reflection.capabilities.clone())
+                    .with_spec_constants(reflection.spec_constants.clone());
+                log::info!(
+                    "{} uses the capabilities {:?}",
+                    shader.entry,
+                    reflection.capabilities
+                );
+            }
+            crate::output::emit(&crate::output::Event::EntryPointEmitted {
+                entry_point: entry_linkage.entry_point.clone(),
+                module: entry_linkage.source_path.clone(),
+            });
+            Ok(entry_linkage)
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    // Sorted, so that processors like `pack` and `codegen` give the same output every time.
+    linkage.sort();
+    for processor in &processors {
+        linkage = processor.process_linkage(linkage, &modules, context)?;
+    }
+    Ok(linkage)
+}
+
+/// Whether the processor changes the modules in the output dir, in which case they can't be links
+/// to the compiled modules.
+const fn modifies_modules(processor: Processor, build_args: &BuildArgs) -> bool {
+    match processor {
+        Processor::Optimize => crate::optimize::is_enabled(build_args),
+        Processor::SpecConstants => !build_args.spec_constants.is_empty(),
+        Processor::RenameEntryPoints => !build_args.entry_point_renames.is_empty(),
+        Processor::Reproducible => build_args.reproducible,
+        Processor::Strip => build_args.strip,
+        Processor::Copy
+        | Processor::Validate
+        | Processor::Reflect
+        | Processor::Transpile
+        | Processor::Stats
+        | Processor::Compress
+        | Processor::Pack
+        | Processor::Codegen => false,
+    }
+}
+
+/// The processors, in the order from `--output-processors`, or else the default order.
+fn processors(build_args: &BuildArgs) -> anyhow::Result<Vec<Box<dyn OutputProcessor>>> {
+    let order = if build_args.output_processors.is_empty() {
+        Processor::DEFAULT_ORDER.to_vec()
+    } else {
+        build_args.output_processors.clone()
+    };
+    check_order(&order, build_args)?;
+
+    let is_linkable = !order
+        .iter()
+        .any(|processor| modifies_modules(*processor, build_args));
+    Ok(order
+        .into_iter()
+        .map(|processor| -> Box<dyn OutputProcessor> {
+            match processor {
+                Processor::Copy => Box::new(CopyModules { is_linkable }),
+                Processor::Optimize => Box::new(Optimize),
+                Processor::SpecConstants => Box::new(SpecConstants),
+                Processor::RenameEntryPoints => Box::new(RenameEntryPoints),
+                Processor::Reproducible => Box::new(Reproducible::default()),
+                Processor::Validate => Box::new(Validate),
+                Processor::Reflect => Box::new(Reflect),
+                Processor::Strip => Box::new(Strip),
+                Processor::Transpile => Box::new(Transpile),
+                Processor::Stats => Box::new(Stats),
+                Processor::Compress => Box::new(Compress),
+                Processor::Pack => Box::new(Pack),
+                Processor::Codegen => Box::new(Codegen),
+            }
+        })
+        .collect())
+}
+
+/// Whether the build needs the processor, because its option is given. Only `reflect` can
+/// always be left out.
+const fn is_needed(processor: Processor, build_args: &BuildArgs) -> bool {
+    match processor {
+        Processor::Copy | Processor::Validate => true,
+        Processor::Optimize => crate::optimize::is_enabled(build_args),
+        Processor::SpecConstants => !build_args.spec_constants.is_empty(),
+        Processor::RenameEntryPoints => !build_args.entry_point_renames.is_empty(),
+        Processor::Reproducible => build_args.reproducible,
+        Processor::Reflect => false,
+        Processor::Strip => build_args.strip,
+        Processor::Transpile => !build_args.transpile.is_empty(),
+        Processor::Stats => build_args.stats,
+        Processor::Compress => build_args.compress.is_some(),
+        Processor::Pack => build_args.pack.is_some(),
+        Processor::Codegen => !build_args.codegen.is_empty(),
+    }
+}
+
+/// Check that the processors are in an order that works: `copy` first, each one only once,
+/// `compress` and `pack`, which move the modules out of the way, after those that read them, and
+/// none left out that the build needs.
+fn check_order(order: &[Processor], build_args: &BuildArgs) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        order.first() == Some(&Processor::Copy),
+        "the output processors have to start with `copy`"
+    );
+    let position = |wanted: Processor| order.iter().position(|processor| *processor == wanted);
+    for (index, processor) in order.iter().enumerate() {
+        anyhow::ensure!(
+            position(*processor) == Some(index),
+            "the output processor `{}` is given more than once",
+            name(*processor)
+        );
+    }
+    for mover in [Processor::Compress, Processor::Pack] {
+        for reader in [Processor::Transpile, Processor::Stats] {
+            if let (Some(mover_index), Some(reader_index)) = (position(mover), position(reader)) {
+                anyhow::ensure!(
+                    reader_index < mover_index,
+                    "the output processor `{}` has to come before `{}`",
+                    name(reader),
+                    name(mover)
+                );
+            }
+        }
+    }
+    for needed in Processor::DEFAULT_ORDER {
+        anyhow::ensure!(
+            !is_needed(needed, build_args) || order.contains(&needed),
+            "the output processor `{}` is needed by this build, but it's left out of the output \
+             processors",
+            name(needed)
+        );
+    }
+    Ok(())
+}
+
+/// The processor's name in `--output-processors`.
+fn name(processor: Processor) -> String {
+    clap::ValueEnum::to_possible_value(&processor)
+        .map(|value| value.get_name().to_owned())
+        .unwrap_or_default()
+}
+
+/// Copies, or links, each module into the output dir.
+struct CopyModules {
+    /// Whether no other processor changes the modules, so they can be links.
+    is_linkable: bool,
+}
+
+impl OutputProcessor for CopyModules {
+    fn process_module(
+        &self,
+        module: &mut OutputModule,
+        context: &Context<'_>,
+    ) -> anyhow::Result<()> {
+        let copy_path = context.build_args().output_dir.join(
+            module
+                .compiled
+                .file_name()
+                .context("Couldn't parse file name from shader module path")?,
+        );
+        if self.is_linkable {
+            crate::link::put(
+                &module.compiled,
+                &copy_path,
+                context.build_args().output_link,
+            )?;
+        } else {
+            crate::atomic::copy(&module.compiled, &copy_path)?;
+        }
+        module.path = copy_path;
+        Ok(())
+    }
+}
+
+/// Optimizes the modules with `spirv-opt`.
+struct Optimize;
+
+impl OutputProcessor for Optimize {
+    fn process_module(
+        &self,
+        module: &mut OutputModule,
+        context: &Context<'_>,
+    ) -> anyhow::Result<()> {
+        if crate::optimize::is_enabled(context.build_args()) {
+            crate::optimize::optimize(&module.path, &module.path, context.build_args())?;
+        }
+        Ok(())
+    }
+}
+
+/// Bakes the `--spec-constant` defaults into the modules.
+struct SpecConstants;
+
+impl OutputProcessor for SpecConstants {
+    fn process_module(
+        &self,
+        module: &mut OutputModule,
+        context: &Context<'_>,
+    ) -> anyhow::Result<()> {
+        if !context.build_args().spec_constants.is_empty() {
+            module.matched_spec_constants =
+                crate::spec_constants::apply(&module.path, &context.build_args().spec_constants)?;
+        }
+        Ok(())
+    }
+
+    /// Check that every `--spec-constant` was the name or `SpecId` of a specialization constant in
+    /// at least one module, so that typos don't go unnoticed.
+    fn process_linkage(
+        &self,
+        linkage: Vec<Linkage>,
+        modules: &[OutputModule],
+        context: &Context<'_>,
+    ) -> anyhow::Result<Vec<Linkage>> {
+        for (name, _) in &context.build_args().spec_constants {
+            anyhow::ensure!(
+                modules
+                    .iter()
+                    .any(|module| module.matched_spec_constants.contains(name)),
+                "`--spec-constant {name}` didn't match the name or `SpecId` of any specialization \
+                 constant"
+            );
+        }
+        Ok(linkage)
+    }
+}
+
+/// Renames the entry points in the modules.
+struct RenameEntryPoints;
+
+impl OutputProcessor for RenameEntryPoints {
+    fn process_module(
+        &self,
+        module: &mut OutputModule,
+        context: &Context<'_>,
+    ) -> anyhow::Result<()> {
+        if !context.entry_point_renames.is_empty() {
+            crate::rename::entry_points(&module.path, &context.entry_point_renames)?;
+        }
+        Ok(())
+    }
+}
+
+/// Strips the debug info that `--reproducible` builds can't have.
+#[derive(Default)]
+struct Reproducible {
+    /// The path remaps of the build, found for its first module.
+    remaps: std::sync::OnceLock<Vec<(String, String)>>,
+}
+
+impl OutputProcessor for Reproducible {
+    fn process_module(
+        &self,
+        module: &mut OutputModule,
+        context: &Context<'_>,
+    ) -> anyhow::Result<()> {
+        if !context.build_args().reproducible {
+            return Ok(());
+        }
+        if self.remaps.get().is_none() {
+            let found = crate::reproducible::path_remaps(
+                &context.build.install.spirv_install.shader_crate,
+            )?;
+            self.remaps.get_or_init(|| found);
+        }
+        crate::reproducible::strip_debug_info(
+            &module.path,
+            self.remaps.get().map(Vec::as_slice).unwrap_or_default(),
+        )
+    }
+}
+
+/// Validates the modules with `spirv-val`.
+struct Validate;
+
+impl OutputProcessor for Validate {
+    fn process_module(
+        &self,
+        module: &mut OutputModule,
+        context: &Context<'_>,
+    ) -> anyhow::Result<()> {
+        crate::validate::validate(&module.path, &module.entry_points, context.build_args())
+    }
+}
+
+/// Reflects on the modules' entry points, for the manifest.
+struct Reflect;
+
+impl OutputProcessor for Reflect {
+    fn process_module(
+        &self,
+        module: &mut OutputModule,
+        _context: &Context<'_>,
+    ) -> anyhow::Result<()> {
+        module.reflection = crate::reflect::reflect(&module.path).unwrap_or_else(|error| {
+            log::warn!("couldn't reflect on {}: {error:?}", module.path.display());
+            std::collections::HashMap::new()
+        });
+        Ok(())
+    }
+}
+
+/// Strips the modules with `--strip`.
+struct Strip;
+
+impl OutputProcessor for Strip {
+    fn process_module(
+        &self,
+        module: &mut OutputModule,
+        context: &Context<'_>,
+    ) -> anyhow::Result<()> {
+        if context.build_args().strip {
+            crate::strip::strip(&module.path, context.build_args())?;
+        }
+        Ok(())
+    }
+}
+
+/// Transpiles each entry point with `--transpile`.
+struct Transpile;
+
+impl OutputProcessor for Transpile {
+    fn process_linkage(
+        &self,
+        linkage: Vec<Linkage>,
+        _modules: &[OutputModule],
+        context: &Context<'_>,
+    ) -> anyhow::Result<Vec<Linkage>> {
+        let mut transpiled = std::collections::HashSet::new();
+        linkage
+            .into_iter()
+            .map(|mut entry| {
+                let path = context.shader_crate().join(&entry.source_path);
+                for target in &context.build_args().transpile {
+                    let transpiled_path =
+                        crate::transpile::output_path(&path, *target, &entry.entry_point);
+                    if !transpiled.contains(&transpiled_path) {
+                        crate::transpile::transpile(&path, *target, &entry.entry_point)?;
+                        transpiled.insert(transpiled_path.clone());
+                    }
+                    let relative_path = context.build.relative_to_shader_crate(&transpiled_path);
+                    entry = entry.with_transpiled_source_path(*target, relative_path);
+                }
+                Ok(entry)
+            })
+            .collect()
+    }
+}
+
+/// Adds the modules' statistics to the linkage with `--stats`.
+struct Stats;
+
+impl OutputProcessor for Stats {
+    fn process_linkage(
+        &self,
+        linkage: Vec<Linkage>,
+        _modules: &[OutputModule],
+        context: &Context<'_>,
+    ) -> anyhow::Result<Vec<Linkage>> {
+        if !context.build_args().stats {
+            return Ok(linkage);
+        }
+        let with_stats =
+            crate::stats::add_to_linkage(linkage, context.shader_crate(), context.compile_time)?;
+        crate::stats::print_table(&with_stats);
+        Ok(with_stats)
+    }
+}
+
+/// Compresses the modules with `--compress`.
+struct Compress;
+
+impl OutputProcessor for Compress {
+    fn process_linkage(
+        &self,
+        linkage: Vec<Linkage>,
+        _modules: &[OutputModule],
+        context: &Context<'_>,
+    ) -> anyhow::Result<Vec<Linkage>> {
+        match context.build_args().compress {
+            Some(scheme) => crate::compress::modules(linkage, scheme, context.shader_crate()),
+            None => Ok(linkage),
+        }
+    }
+}
+
+/// Packs the modules into one file with `--pack`.
+struct Pack;
+
+impl OutputProcessor for Pack {
+    fn process_linkage(
+        &self,
+        linkage: Vec<Linkage>,
+        _modules: &[OutputModule],
+        context: &Context<'_>,
+    ) -> anyhow::Result<Vec<Linkage>> {
+        match &context.build_args().pack {
+            Some(pack) => crate::pack::write(
+                linkage,
+                &context.build_args().output_dir.join(pack),
+                context.shader_crate(),
+            ),
+            None => Ok(linkage),
+        }
+    }
+}
+
+/// Generates code from the manifest with `--codegen`.
+struct Codegen;
+
+impl OutputProcessor for Codegen {
+    fn process_linkage(
+        &self,
+        linkage: Vec<Linkage>,
+        _modules: &[OutputModule],
+        context: &Context<'_>,
+    ) -> anyhow::Result<Vec<Linkage>> {
+        for target in &context.build_args().codegen {
+            let codegen_path = crate::codegen::write(&linkage, *target, &context.manifest_path)?;
+            log::info!("wrote generated code to '{}'", codegen_path.display());
+        }
+        Ok(linkage)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_log::test]
+    fn checking_the_order_of_processors() {
+        let build_args = <BuildArgs as clap::Parser>::parse_from(["", "--strip"]);
+        let check_order = |order: &[Processor]| super::check_order(order, &build_args);
+        check_order(&Processor::DEFAULT_ORDER).unwrap();
+        check_order(&[Processor::Copy, Processor::Strip, Processor::Validate]).unwrap();
+        assert_eq!(
+            check_order(&[Processor::Validate, Processor::Copy])
+                .unwrap_err()
+                .to_string(),
+            "the output processors have to start with `copy`"
+        );
+        assert_eq!(
+            check_order(&[Processor::Copy, Processor::Strip, Processor::Strip])
+                .unwrap_err()
+                .to_string(),
+            "the output processor `strip` is given more than once"
+        );
+        assert_eq!(
+            check_order(&[Processor::Copy, Processor::Pack, Processor::Transpile])
+                .unwrap_err()
+                .to_string(),
+            "the output processor `transpile` has to come before `pack`"
+        );
+    }
+
+    #[test_log::test]
+    fn refusing_to_leave_out_needed_processors() {
+        let build_args =
+            <BuildArgs as clap::Parser>::parse_from(["", "--strip", "--compress", "zstd"]);
+        let left_out = |order: &[Processor]| {
+            check_order(order, &build_args)
+                .unwrap_err()
+                .to_string()
+                .split('`')
+                .nth(1)
+                .map(str::to_owned)
+        };
+        assert_eq!(
+            left_out(&[Processor::Copy, Processor::Validate, Processor::Compress]),
+            Some("strip".to_owned())
+        );
+        assert_eq!(
+            left_out(&[Processor::Copy, Processor::Validate, Processor::Strip]),
+            Some("compress".to_owned())
+        );
+        assert_eq!(
+            left_out(&[Processor::Copy, Processor::Strip, Processor::Compress]),
+            Some("validate".to_owned())
+        );
+        check_order(
+            &[
+                Processor::Copy,
+                Processor::Strip,
+                Processor::Validate,
+                Processor::Compress,
+            ],
+            &build_args,
+        )
+        .unwrap();
+    }
+}
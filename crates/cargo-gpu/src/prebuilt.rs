@@ -0,0 +1,290 @@
+//! Download prebuilt `spirv-builder-cli` and `rustc_codegen_spirv` binary pairs, instead of
+//! compiling them from source.
+//!
+//! Prebuilt pairs are published as the assets of a GitHub-style release, with one release per
+//! version of `cargo gpu`, tagged `v<version>`. Asset names are flat, so each one is made from the
+//! name that the pair's install directory has in the cache (which is made from the `rust-gpu`
+//! source and the toolchain channel), the host's architecture and OS, and the file's name. Any
+//! character that release assets can't have becomes a `.`. So for example:
+//!
+//!   `<server>/v0.1.0/https...github.com.Rust-GPU.rust-gpu.82a0f69.nightly-2024-04-24-x86_64-linux-spirv-builder-cli`.
+//!
+//! Each release has a single `SHA256SUMS` asset, in the format written by `sha256sum`, listing
+//! every other asset. Every downloaded file must match its checksum before it gets installed.
+//! The checksums come from the same server as the binaries, so they only guard against corrupted
+//! or truncated downloads, not against a compromised server.
+
+use std::io::Write as _;
+
+use anyhow::Context as _;
+use sha2::Digest as _;
+
+/// Name of the checksums file that accompanies each prebuilt binary pair.
+const CHECKSUMS_FILENAME: &str = "SHA256SUMS";
+
+/// The binary pair artifacts are big, so we need to raise `ureq`'s default download limit.
+const MAX_DOWNLOAD_SIZE: u64 = 1024 * 1024 * 1024;
+
+/// The URL of the release that holds the prebuilt binary pairs for this version of `cargo gpu`.
+fn release_url(server: &str) -> String {
+    format!(
+        "{}/v{}",
+        server.trim_end_matches('/'),
+        env!("CARGO_PKG_VERSION")
+    )
+}
+
+/// The flat name of the release asset for one file of a prebuilt binary pair, for the current
+/// host.
+fn asset_name(install_name: &str, filename: &str) -> String {
+    format!(
+        "{install_name}-{}-{}-{filename}",
+        std::env::consts::ARCH,
+        std::env::consts::OS
+    )
+    .chars()
+    .map(|character| {
+        if character.is_ascii_alphanumeric() || matches!(character, '-' | '_' | '.') {
+            character
+        } else {
+            '.'
+        }
+    })
+    .collect()
+}
+
+/// The lowercase hex SHA-256 of some bytes.
+#[must_use]
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    sha2::Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<Vec<_>>()
+        .concat()
+}
+
+/// Parse a `sha256sum`-style file, ie lines of `<checksum>  <filename>`, into
+/// `(filename, checksum)` pairs.
+fn parse_checksums(contents: &str) -> std::collections::HashMap<String, String> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let (checksum, raw_filename) = line.trim().split_once(char::is_whitespace)?;
+            // `sha256sum` prefixes filenames with `*` when in binary mode.
+            let filename = raw_filename.trim().trim_start_matches('*');
+            Some((filename.to_owned(), checksum.to_lowercase()))
+        })
+        .collect()
+}
+
+/// `GET` the contents of a URL.
+fn download(url: &str) -> anyhow::Result<Vec<u8>> {
+    log::debug!("downloading {url}");
+    let mut response = ureq::get(url)
+        .call()
+        .with_context(|| format!("could not download {url}"))?;
+    let bytes = response
+        .body_mut()
+        .with_config()
+        .limit(MAX_DOWNLOAD_SIZE)
+        .read_to_vec()
+        .with_context(|| format!("could not read response body from {url}"))?;
+    Ok(bytes)
+}
+
+/// Download and verify a prebuilt binary pair. `files` are the filenames of the pair and the
+/// local paths that they should be installed to.
+///
+/// Files are only moved into their final location once all of them have been downloaded and
+/// verified, so an error never leaves a partial install behind.
+pub fn download_pair(
+    server: &str,
+    install_name: &str,
+    files: &[(&str, &std::path::Path)],
+) -> anyhow::Result<()> {
+    let mut temporaries = Vec::new();
+    let result = download_to_temporaries(server, install_name, files, &mut temporaries);
+    if result.is_err() {
+        for temporary in &temporaries {
+            if let Err(error) = std::fs::remove_file(temporary) {
+                log::debug!("could not remove '{}': {error}", temporary.display());
+            }
+        }
+        return result;
+    }
+
+    for (temporary, (_, destination)) in temporaries.iter().zip(files) {
+        std::fs::rename(temporary, destination)?;
+        log::info!("installed prebuilt {}", destination.display());
+    }
+
+    Ok(())
+}
+
+/// Download and verify each of the `files` to a temporary file next to its destination. Every
+/// temporary file is added to `temporaries` as soon as it's created, so that the caller can
+/// remove them if anything fails.
+fn download_to_temporaries(
+    server: &str,
+    install_name: &str,
+    files: &[(&str, &std::path::Path)],
+    temporaries: &mut Vec<std::path::PathBuf>,
+) -> anyhow::Result<()> {
+    let base_url = release_url(server);
+    let checksums_url = format!("{base_url}/{CHECKSUMS_FILENAME}");
+    let checksums = parse_checksums(&String::from_utf8(download(&checksums_url)?)?);
+
+    for (filename, destination) in files {
+        let asset = asset_name(install_name, filename);
+        let expected = checksums
+            .get(&asset)
+            .with_context(|| format!("no checksum for `{asset}` in {checksums_url}"))?;
+        let bytes = download(&format!("{base_url}/{asset}"))?;
+        let actual = sha256_hex(&bytes);
+        anyhow::ensure!(
+            &actual == expected,
+            "checksum mismatch for prebuilt `{asset}`, expected {expected} but got {actual}"
+        );
+
+        let temporary = std::path::PathBuf::from(format!("{}.download", destination.display()));
+        let mut file = std::fs::File::create(&temporary)?;
+        temporaries.push(temporary);
+        file.write_all(&bytes)?;
+        #[cfg(unix)]
+        file.set_permissions(
+            <std::fs::Permissions as std::os::unix::fs::PermissionsExt>::from_mode(0o755),
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_log::test]
+    fn checksums_parse() {
+        let contents = "\
+            ABC123  librustc_codegen_spirv.so\n\
+            def456 *spirv-builder-cli\n\
+            \n";
+        let checksums = parse_checksums(contents);
+        assert_eq!(checksums.len(), 2);
+        assert_eq!(
+            checksums.get("librustc_codegen_spirv.so"),
+            Some(&"abc123".to_owned())
+        );
+        assert_eq!(
+            checksums.get("spirv-builder-cli"),
+            Some(&"def456".to_owned())
+        );
+    }
+
+    #[test_log::test]
+    fn sha256_of_known_input() {
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test_log::test]
+    fn prebuilt_release_url() {
+        assert_eq!(
+            release_url("https://example.com/releases/download/"),
+            format!(
+                "https://example.com/releases/download/v{}",
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+    }
+
+    #[test_log::test]
+    fn prebuilt_asset_names_are_flat() {
+        let asset = asset_name(
+            "https___github_com_Rust-GPU_rust-gpu+82a0f69+nightly-2024-04-24",
+            "spirv-builder-cli",
+        );
+        assert!(!asset.contains('/'));
+        assert!(!asset.contains('+'));
+        assert!(
+            asset.starts_with("https___github_com_Rust-GPU_rust-gpu.82a0f69.nightly-2024-04-24-")
+        );
+        assert!(asset.ends_with(&format!("-{}-spirv-builder-cli", std::env::consts::OS)));
+    }
+
+    /// Serve each of the `assets` at `/v<version>/<name>` once, over plain HTTP, returning the
+    /// server's URL.
+    fn serve_assets(assets: Vec<(String, Vec<u8>)>) -> String {
+        use std::io::{BufRead as _, BufReader};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for maybe_stream in listener.incoming() {
+                let mut stream = maybe_stream.unwrap();
+                let mut request_line = String::new();
+                BufReader::new(&stream)
+                    .read_line(&mut request_line)
+                    .unwrap();
+                let path = request_line.split(' ').nth(1).unwrap_or_default();
+                let body = assets
+                    .iter()
+                    .find(|(name, _)| path == format!("{}/{name}", release_url("")))
+                    .map(|(_, body)| body.clone());
+                let status = if body.is_some() {
+                    "200 OK"
+                } else {
+                    "404 Not Found"
+                };
+                let content = body.unwrap_or_default();
+                write!(
+                    stream,
+                    "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    content.len()
+                )
+                .unwrap();
+                stream.write_all(&content).unwrap();
+            }
+        });
+        url
+    }
+
+    #[test_log::test]
+    fn failed_downloads_leave_no_temporary_files() {
+        let install_name = "rust-gpu+nightly";
+        let dylib = asset_name(install_name, "librustc_codegen_spirv.so");
+        let cli = asset_name(install_name, "spirv-builder-cli");
+        let checksums = format!(
+            "{}  {dylib}\n{}  {cli}\n",
+            sha256_hex(b"dylib"),
+            sha256_hex(b"the cli that was published")
+        );
+        let server = serve_assets(vec![
+            (CHECKSUMS_FILENAME.to_owned(), checksums.into_bytes()),
+            (dylib, b"dylib".to_vec()),
+            (cli, b"a corrupted cli".to_vec()),
+        ]);
+
+        let dir = crate::cache_dir().unwrap().join("prebuilt");
+        std::fs::create_dir_all(&dir).unwrap();
+        let result = download_pair(
+            &server,
+            install_name,
+            &[
+                (
+                    "librustc_codegen_spirv.so",
+                    &dir.join("librustc_codegen_spirv.so"),
+                ),
+                ("spirv-builder-cli", &dir.join("spirv-builder-cli")),
+            ],
+        );
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("checksum mismatch"));
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        crate::test::tests_teardown();
+    }
+}
@@ -0,0 +1,686 @@
+//! Reflect on compiled SPIR-V modules, eg to find the descriptor bindings of each entry point.
+
+use anyhow::Context as _;
+use rspirv::dr::{Instruction, Module, Operand};
+use rspirv::spirv::{Decoration, Dim, ExecutionModel, Op, StorageClass, Word};
+use spirv_builder_cli::{DescriptorBinding, ImageInfo, SpecConstant};
+
+/// What reflection found out about a single entry point.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryPointReflection {
+    /// The shader stage, eg "vertex" or "fragment".
+    pub stage: String,
+    /// The descriptor bindings used by the entry point, sorted by set and binding.
+    pub bindings: Vec<DescriptorBinding>,
+    /// The size in bytes of the entry point's push constants, if it uses any.
+    pub push_constant_size: Option<u32>,
+    /// The module's declared capabilities that the entry point uses.
+    pub capabilities: Vec<String>,
+    /// The specialization constants that the entry point uses, sorted by `SpecId`.
+    pub spec_constants: Vec<SpecConstant>,
+}
+
+/// Parse the SPIR-V module at `spv_path` and reflect on all of its entry points, keyed by
+/// entry point name.
+pub fn reflect(
+    spv_path: &std::path::Path,
+) -> anyhow::Result<std::collections::HashMap<String, EntryPointReflection>> {
+    let bytes = std::fs::read(spv_path)
+        .with_context(|| format!("could not read SPIR-V file '{}'", spv_path.display()))?;
+    let module = rspirv::dr::load_bytes(bytes)
+        .map_err(|error| anyhow::anyhow!("could not parse '{}': {error}", spv_path.display()))?;
+    Ok(reflect_module(&module))
+}
+
+/// Reflect on all the entry points of a module.
+fn reflect_module(module: &Module) -> std::collections::HashMap<String, EntryPointReflection> {
+    let mut entry_points = std::collections::HashMap::new();
+    for entry_point in &module.entry_points {
+        let (
+            Some(Operand::ExecutionModel(model)),
+            Some(Operand::IdRef(function)),
+            Some(Operand::LiteralString(name)),
+        ) = (
+            entry_point.operands.first(),
+            entry_point.operands.get(1),
+            entry_point.operands.get(2),
+        )
+        else {
+            continue;
+        };
+
+        let used_ids = used_ids(module, *function);
+        let mut bindings = module
+            .types_global_values
+            .iter()
+            .filter(|instruction| {
+                instruction.class.opcode == Op::Variable
+                    && instruction
+                        .result_id
+                        .is_some_and(|id| used_ids.contains(&id))
+            })
+            .filter_map(|variable| descriptor_binding(module, variable))
+            .collect::<Vec<_>>();
+        bindings.sort();
+        let push_constant_size = module
+            .types_global_values
+            .iter()
+            .filter(|instruction| {
+                instruction.class.opcode == Op::Variable
+                    && instruction.operands.first()
+                        == Some(&Operand::StorageClass(StorageClass::PushConstant))
+                    && instruction
+                        .result_id
+                        .is_some_and(|id| used_ids.contains(&id))
+            })
+            .find_map(|variable| {
+                let pointer_type = global(module, variable.result_type?)?;
+                type_size(module, pointer_type.operands.get(1)?.id_ref_any()?)
+            });
+
+        let used_globals = used_globals(module, &used_ids);
+        let mut spec_constants = module
+            .types_global_values
+            .iter()
+            .filter(|instruction| {
+                instruction
+                    .result_id
+                    .is_some_and(|id| used_globals.contains(&id))
+            })
+            .filter_map(|instruction| spec_constant(module, instruction))
+            .collect::<Vec<_>>();
+        spec_constants.sort();
+
+        entry_points.insert(
+            name.clone(),
+            EntryPointReflection {
+                stage: stage_name(*model),
+                bindings,
+                push_constant_size,
+                capabilities: crate::capabilities::used_by_entry_point(module, entry_point),
+                spec_constants,
+            },
+        );
+    }
+    entry_points
+}
+
+/// A friendly name for a shader stage.
+fn stage_name(model: ExecutionModel) -> String {
+    match model {
+        ExecutionModel::Vertex => "vertex".to_owned(),
+        ExecutionModel::Fragment => "fragment".to_owned(),
+        ExecutionModel::GLCompute => "compute".to_owned(),
+        ExecutionModel::TessellationControl => "tessellation_control".to_owned(),
+        ExecutionModel::TessellationEvaluation => "tessellation_evaluation".to_owned(),
+        ExecutionModel::Geometry => "geometry".to_owned(),
+        ExecutionModel::TaskNV => "task".to_owned(),
+        ExecutionModel::MeshNV => "mesh".to_owned(),
+        ExecutionModel::RayGenerationNV => "ray_generation".to_owned(),
+        ExecutionModel::IntersectionNV => "intersection".to_owned(),
+        ExecutionModel::AnyHitNV => "any_hit".to_owned(),
+        ExecutionModel::ClosestHitNV => "closest_hit".to_owned(),
+        ExecutionModel::MissNV => "miss".to_owned(),
+        ExecutionModel::CallableNV => "callable".to_owned(),
+        ExecutionModel::Kernel => "kernel".to_owned(),
+    }
+}
+
+/// The ids of all the functions that can be called from the function `entry_function`,
+/// including itself.
+#[must_use]
+pub fn reachable_functions(module: &Module, entry_function: Word) -> Vec<Word> {
+    let mut reachable = vec![entry_function];
+    let mut index = 0;
+    while let Some(function_id) = reachable.get(index).copied() {
+        index += 1;
+        let callees = module
+            .functions
+            .iter()
+            .filter(|function| {
+                function.def.as_ref().and_then(|def| def.result_id) == Some(function_id)
+            })
+            .flat_map(|function| &function.blocks)
+            .flat_map(|block| &block.instructions)
+            .filter(|instruction| instruction.class.opcode == Op::FunctionCall)
+            .filter_map(|instruction| instruction.operands.first()?.id_ref_any())
+            .collect::<Vec<_>>();
+        for callee in callees {
+            if !reachable.contains(&callee) {
+                reachable.push(callee);
+            }
+        }
+    }
+    reachable
+}
+
+/// Every id referenced by the functions reachable from `entry_function`.
+fn used_ids(module: &Module, entry_function: Word) -> std::collections::HashSet<Word> {
+    let reachable = reachable_functions(module, entry_function);
+    module
+        .functions
+        .iter()
+        .filter(|function| {
+            function
+                .def
+                .as_ref()
+                .and_then(|def| def.result_id)
+                .is_some_and(|id| reachable.contains(&id))
+        })
+        .flat_map(|function| &function.blocks)
+        .flat_map(|block| &block.instructions)
+        .flat_map(|instruction| &instruction.operands)
+        .filter_map(Operand::id_ref_any)
+        .collect()
+}
+
+/// `used_ids` along with every global that they refer to, directly or not, eg the constant length
+/// of an array type that a used variable points to.
+fn used_globals(
+    module: &Module,
+    used_ids: &std::collections::HashSet<Word>,
+) -> std::collections::HashSet<Word> {
+    let mut used = used_ids.clone();
+    let mut pending = used_ids.iter().copied().collect::<Vec<_>>();
+    while let Some(id) = pending.pop() {
+        let Some(instruction) = global(module, id) else {
+            continue;
+        };
+        let referenced = instruction
+            .result_type
+            .into_iter()
+            .chain(instruction.operands.iter().filter_map(Operand::id_ref_any));
+        for referenced_id in referenced {
+            if used.insert(referenced_id) {
+                pending.push(referenced_id);
+            }
+        }
+    }
+    used
+}
+
+/// The specialization constant that a global instruction declares, if it declares one with a
+/// `SpecId`.
+#[must_use]
+pub fn spec_constant(module: &Module, instruction: &Instruction) -> Option<SpecConstant> {
+    let default = match (instruction.class.opcode, instruction.operands.first()) {
+        (Op::SpecConstantTrue, _) => "true".to_owned(),
+        (Op::SpecConstantFalse, _) => "false".to_owned(),
+        (Op::SpecConstant, Some(value)) => {
+            literal_value(value, &scalar_type_name(module, instruction.result_type?)?)?
+        }
+        _ => return None,
+    };
+    let id = instruction.result_id?;
+    Some(SpecConstant {
+        id: decoration_value(module, id, Decoration::SpecId)?,
+        name: debug_name(module, id),
+        ty: scalar_type_name(module, instruction.result_type?)?,
+        default,
+    })
+}
+
+/// The name of a scalar type, eg "bool", "u32", "i64" or "f32".
+fn scalar_type_name(module: &Module, type_id: Word) -> Option<String> {
+    let instruction = global(module, type_id)?;
+    match (
+        instruction.class.opcode,
+        instruction.operands.first(),
+        instruction.operands.get(1),
+    ) {
+        (Op::TypeBool, _, _) => Some("bool".to_owned()),
+        (Op::TypeInt, Some(Operand::LiteralInt32(width)), Some(Operand::LiteralInt32(1))) => {
+            Some(format!("i{width}"))
+        }
+        (Op::TypeInt, Some(Operand::LiteralInt32(width)), _) => Some(format!("u{width}")),
+        (Op::TypeFloat, Some(Operand::LiteralInt32(width)), _) => Some(format!("f{width}")),
+        _ => None,
+    }
+}
+
+/// A constant's literal value as text, given the name of its type. Signed integers narrower than
+/// 64 bits are sign extended to 32 bits.
+fn literal_value(value: &Operand, type_name: &str) -> Option<String> {
+    match (value, type_name.starts_with('i')) {
+        (Operand::LiteralInt32(bits), true) => Some(bits.cast_signed().to_string()),
+        (Operand::LiteralInt64(bits), true) => Some(bits.cast_signed().to_string()),
+        (Operand::LiteralInt32(bits), false) => Some(bits.to_string()),
+        (Operand::LiteralInt64(bits), false) => Some(bits.to_string()),
+        (Operand::LiteralFloat32(float), _) => Some(float.to_string()),
+        (Operand::LiteralFloat64(float), _) => Some(float.to_string()),
+        _ => None,
+    }
+}
+
+/// The `OpName` of an id, if the module has debug names.
+fn debug_name(module: &Module, id: Word) -> Option<String> {
+    module.debug_names.iter().find_map(|debug_name| {
+        match (debug_name.operands.first(), debug_name.operands.get(1)) {
+            (Some(Operand::IdRef(target)), Some(Operand::LiteralString(found)))
+                if *target == id =>
+            {
+                Some(found.clone())
+            }
+            _ => None,
+        }
+    })
+}
+
+/// Find a global type or value instruction by its result id.
+fn global(module: &Module, id: Word) -> Option<&Instruction> {
+    module
+        .types_global_values
+        .iter()
+        .find(|instruction| instruction.result_id == Some(id))
+}
+
+/// The literal value of a decoration on `id`, eg the number of a `Binding` decoration.
+fn decoration_value(module: &Module, id: Word, decoration: Decoration) -> Option<u32> {
+    module.annotations.iter().find_map(|annotation| {
+        match (
+            annotation.operands.first(),
+            annotation.operands.get(1),
+            annotation.operands.get(2),
+        ) {
+            (
+                Some(Operand::IdRef(target)),
+                Some(Operand::Decoration(found)),
+                Some(Operand::LiteralInt32(value)),
+            ) if *target == id && *found == decoration => Some(*value),
+            _ => None,
+        }
+    })
+}
+
+/// The literal value of a decoration on a struct member, eg the number of an `Offset` decoration.
+fn member_decoration_value(
+    module: &Module,
+    struct_id: Word,
+    member: u32,
+    decoration: Decoration,
+) -> Option<u32> {
+    module.annotations.iter().find_map(|annotation| {
+        match (
+            annotation.operands.first(),
+            annotation.operands.get(1),
+            annotation.operands.get(2),
+            annotation.operands.get(3),
+        ) {
+            (
+                Some(Operand::IdRef(target)),
+                Some(Operand::LiteralInt32(found_member)),
+                Some(Operand::Decoration(found)),
+                Some(Operand::LiteralInt32(value)),
+            ) if *target == struct_id && *found_member == member && *found == decoration => {
+                Some(*value)
+            }
+            _ => None,
+        }
+    })
+}
+
+/// The size in bytes of a type, as laid out by its `Offset`, `ArrayStride` and `MatrixStride`
+/// decorations. Only types that can be in a buffer or push constant block have a size.
+fn type_size(module: &Module, type_id: Word) -> Option<u32> {
+    let instruction = global(module, type_id)?;
+    let opcode = instruction.class.opcode;
+    if opcode == Op::TypeInt || opcode == Op::TypeFloat {
+        match instruction.operands.first() {
+            Some(Operand::LiteralInt32(width)) => width.checked_div(8),
+            _ => None,
+        }
+    } else if opcode == Op::TypeVector || opcode == Op::TypeMatrix {
+        let component_size = type_size(module, instruction.operands.first()?.id_ref_any()?)?;
+        match instruction.operands.get(1) {
+            Some(Operand::LiteralInt32(count)) => component_size.checked_mul(*count),
+            _ => None,
+        }
+    } else if opcode == Op::TypeArray {
+        let length_constant = global(module, instruction.operands.get(1)?.id_ref_any()?)?;
+        let Some(Operand::LiteralInt32(length)) = length_constant.operands.first() else {
+            return None;
+        };
+        let stride = match decoration_value(module, type_id, Decoration::ArrayStride) {
+            Some(stride) => stride,
+            None => type_size(module, instruction.operands.first()?.id_ref_any()?)?,
+        };
+        stride.checked_mul(*length)
+    } else if opcode == Op::TypeStruct {
+        let mut size = 0;
+        for (member, member_type) in (0..).zip(&instruction.operands) {
+            let member_type_id = member_type.id_ref_any()?;
+            let offset = member_decoration_value(module, type_id, member, Decoration::Offset)
+                .unwrap_or(size);
+            // Matrices in blocks have their column stride on the struct member.
+            let member_size = match (
+                member_decoration_value(module, type_id, member, Decoration::MatrixStride),
+                global(module, member_type_id)?.operands.get(1),
+            ) {
+                (Some(stride), Some(Operand::LiteralInt32(columns))) => {
+                    stride.checked_mul(*columns)?
+                }
+                _ => type_size(module, member_type_id)?,
+            };
+            size = size.max(offset.checked_add(member_size)?);
+        }
+        Some(size)
+    } else {
+        None
+    }
+}
+
+/// Whether `id` has a decoration, eg `Block`.
+fn has_decoration(module: &Module, id: Word, decoration: Decoration) -> bool {
+    module.annotations.iter().any(|annotation| {
+        annotation.operands.first() == Some(&Operand::IdRef(id))
+            && annotation.operands.get(1) == Some(&Operand::Decoration(decoration))
+    })
+}
+
+/// The binding of a global variable, if it is a descriptor.
+fn descriptor_binding(module: &Module, variable: &Instruction) -> Option<DescriptorBinding> {
+    let variable_id = variable.result_id?;
+    let Some(Operand::StorageClass(storage_class)) = variable.operands.first() else {
+        return None;
+    };
+    if !matches!(
+        storage_class,
+        StorageClass::UniformConstant | StorageClass::Uniform | StorageClass::StorageBuffer
+    ) {
+        return None;
+    }
+    let set = decoration_value(module, variable_id, Decoration::DescriptorSet)?;
+    let binding = decoration_value(module, variable_id, Decoration::Binding)?;
+
+    // Variables are always pointers, so look through the pointer, and then any array.
+    let pointer_type = global(module, variable.result_type?)?;
+    let mut resource_type = global(module, pointer_type.operands.get(1)?.id_ref_any()?)?;
+    let mut count = Some(1);
+    if resource_type.class.opcode == Op::TypeArray {
+        let length = global(module, resource_type.operands.get(1)?.id_ref_any()?)?;
+        count = match length.operands.first() {
+            Some(Operand::LiteralInt32(value)) => Some(*value),
+            _ => None,
+        };
+        resource_type = global(module, resource_type.operands.first()?.id_ref_any()?)?;
+    } else if resource_type.class.opcode == Op::TypeRuntimeArray {
+        count = None;
+        resource_type = global(module, resource_type.operands.first()?.id_ref_any()?)?;
+    } else {
+        // Not an array, so it's a single resource.
+    }
+
+    let descriptor_type = match (storage_class, resource_type.class.opcode) {
+        (StorageClass::StorageBuffer, _) => "storage_buffer",
+        (StorageClass::Uniform, _) => {
+            // Before `StorageBuffer` was added to SPIR-V, storage buffers were `Uniform`s
+            // decorated with `BufferBlock`.
+            if has_decoration(module, resource_type.result_id?, Decoration::BufferBlock) {
+                "storage_buffer"
+            } else {
+                "uniform_buffer"
+            }
+        }
+        (_, Op::TypeSampler) => "sampler",
+        (_, Op::TypeSampledImage) => "combined_image_sampler",
+        (_, Op::TypeAccelerationStructureKHR) => "acceleration_structure",
+        (_, Op::TypeImage) => {
+            let is_buffer = resource_type.operands.get(1) == Some(&Operand::Dim(Dim::DimBuffer));
+            let is_storage = resource_type.operands.get(5) == Some(&Operand::LiteralInt32(2));
+            match (is_buffer, is_storage) {
+                (true, true) => "storage_texel_buffer",
+                (true, false) => "uniform_texel_buffer",
+                (false, true) => "storage_image",
+                (false, false) => "sampled_image",
+            }
+        }
+        _ => return None,
+    };
+
+    let image = if resource_type.class.opcode == Op::TypeImage {
+        image_info(module, resource_type)
+    } else if resource_type.class.opcode == Op::TypeSampledImage {
+        image_info(
+            module,
+            global(module, resource_type.operands.first()?.id_ref_any()?)?,
+        )
+    } else {
+        None
+    };
+
+    let name = debug_name(module, variable_id);
+
+    Some(DescriptorBinding {
+        set,
+        binding,
+        descriptor_type: descriptor_type.to_owned(),
+        count,
+        name,
+        read_only: has_decoration(module, variable_id, Decoration::NonWritable),
+        write_only: has_decoration(module, variable_id, Decoration::NonReadable),
+        image,
+    })
+}
+
+/// The details of an `OpTypeImage`.
+fn image_info(module: &Module, image_type: &Instruction) -> Option<ImageInfo> {
+    let sampled_type = global(module, image_type.operands.first()?.id_ref_any()?)?;
+    let sampled_type_name = match (sampled_type.class.opcode, sampled_type.operands.get(1)) {
+        (Op::TypeInt, Some(Operand::LiteralInt32(1))) => "sint",
+        (Op::TypeInt, _) => "uint",
+        _ => "float",
+    };
+    let Some(Operand::Dim(dim)) = image_type.operands.get(1) else {
+        return None;
+    };
+    let dimension = match dim {
+        Dim::Dim1D => "1d",
+        Dim::Dim2D => "2d",
+        Dim::Dim3D => "3d",
+        Dim::DimCube => "cube",
+        Dim::DimRect => "rect",
+        Dim::DimBuffer => "buffer",
+        Dim::DimSubpassData => "subpass",
+    };
+    let Some(Operand::ImageFormat(format)) = image_type.operands.get(6) else {
+        return None;
+    };
+
+    Some(ImageInfo {
+        dimension: dimension.to_owned(),
+        depth: image_type.operands.get(2) == Some(&Operand::LiteralInt32(1)),
+        arrayed: image_type.operands.get(3) == Some(&Operand::LiteralInt32(1)),
+        multisampled: image_type.operands.get(4) == Some(&Operand::LiteralInt32(1)),
+        sampled_type: sampled_type_name.to_owned(),
+        format: format!("{format:?}"),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A fragment shader that samples a texture, and a compute shader that writes to a storage
+    /// buffer and reads push constants and a spec constant.
+    #[expect(clippy::too_many_lines, reason = "these lines are fine")]
+    fn fragment_and_compute() -> Module {
+        let mut builder = rspirv::dr::Builder::new();
+        builder.capability(rspirv::spirv::Capability::Shader);
+        builder.memory_model(
+            rspirv::spirv::AddressingModel::Logical,
+            rspirv::spirv::MemoryModel::GLSL450,
+        );
+        let void = builder.type_void();
+        let function_type = builder.type_function(void, vec![]);
+        let float = builder.type_float(32);
+        let uint = builder.type_int(32, 0);
+
+        let image = builder.type_image(
+            float,
+            Dim::Dim2D,
+            0,
+            0,
+            0,
+            1,
+            rspirv::spirv::ImageFormat::Unknown,
+            None,
+        );
+        let sampled_image = builder.type_sampled_image(image);
+        let texture_pointer =
+            builder.type_pointer(None, StorageClass::UniformConstant, sampled_image);
+        let texture = builder.variable(texture_pointer, None, StorageClass::UniformConstant, None);
+        builder.decorate(
+            texture,
+            Decoration::DescriptorSet,
+            vec![Operand::LiteralInt32(0)],
+        );
+        builder.decorate(texture, Decoration::Binding, vec![Operand::LiteralInt32(1)]);
+        builder.name(texture, "texture");
+
+        let runtime_array = builder.type_runtime_array(uint);
+        let buffer_struct = builder.type_struct(vec![runtime_array]);
+        builder.decorate(buffer_struct, Decoration::Block, vec![]);
+        let buffer_pointer = builder.type_pointer(None, StorageClass::StorageBuffer, buffer_struct);
+        let buffer = builder.variable(buffer_pointer, None, StorageClass::StorageBuffer, None);
+        builder.decorate(
+            buffer,
+            Decoration::DescriptorSet,
+            vec![Operand::LiteralInt32(2)],
+        );
+        builder.decorate(buffer, Decoration::Binding, vec![Operand::LiteralInt32(0)]);
+        builder.decorate(buffer, Decoration::NonWritable, vec![]);
+
+        let vec4 = builder.type_vector(float, 4);
+        let push_constant_struct = builder.type_struct(vec![vec4, uint]);
+        builder.decorate(push_constant_struct, Decoration::Block, vec![]);
+        builder.member_decorate(
+            push_constant_struct,
+            0,
+            Decoration::Offset,
+            vec![Operand::LiteralInt32(0)],
+        );
+        builder.member_decorate(
+            push_constant_struct,
+            1,
+            Decoration::Offset,
+            vec![Operand::LiteralInt32(16)],
+        );
+        let push_constant_pointer =
+            builder.type_pointer(None, StorageClass::PushConstant, push_constant_struct);
+        let push_constants = builder.variable(
+            push_constant_pointer,
+            None,
+            StorageClass::PushConstant,
+            None,
+        );
+        let workgroup_size = builder.spec_constant_u32(uint, 64);
+        builder.decorate(
+            workgroup_size,
+            Decoration::SpecId,
+            vec![Operand::LiteralInt32(3)],
+        );
+        builder.name(workgroup_size, "WORKGROUP_SIZE");
+        let unused_scale = builder.spec_constant_f32(float, 1.0);
+        builder.decorate(
+            unused_scale,
+            Decoration::SpecId,
+            vec![Operand::LiteralInt32(4)],
+        );
+
+        for (name, model, variables) in [
+            (
+                "main_fs",
+                ExecutionModel::Fragment,
+                vec![(texture, sampled_image)],
+            ),
+            (
+                "main_cs",
+                ExecutionModel::GLCompute,
+                vec![
+                    (buffer, buffer_struct),
+                    (push_constants, push_constant_struct),
+                ],
+            ),
+        ] {
+            let main = builder
+                .begin_function(
+                    void,
+                    None,
+                    rspirv::spirv::FunctionControl::NONE,
+                    function_type,
+                )
+                .unwrap();
+            builder.begin_block(None).unwrap();
+            for (variable, variable_type) in variables {
+                builder
+                    .load(variable_type, None, variable, None, vec![])
+                    .unwrap();
+            }
+            if model == ExecutionModel::GLCompute {
+                builder
+                    .i_add(uint, None, workgroup_size, workgroup_size)
+                    .unwrap();
+            }
+            builder.ret().unwrap();
+            builder.end_function().unwrap();
+            builder.entry_point(model, main, name, vec![]);
+        }
+        builder.module()
+    }
+
+    #[test_log::test]
+    fn reflects_bindings_per_entry_point() {
+        let reflection = reflect_module(&fragment_and_compute());
+
+        assert_eq!(
+            reflection.get("main_fs"),
+            Some(&EntryPointReflection {
+                stage: "fragment".to_owned(),
+                bindings: vec![DescriptorBinding {
+                    set: 0,
+                    binding: 1,
+                    descriptor_type: "combined_image_sampler".to_owned(),
+                    count: Some(1),
+                    name: Some("texture".to_owned()),
+                    read_only: false,
+                    write_only: false,
+                    image: Some(ImageInfo {
+                        dimension: "2d".to_owned(),
+                        arrayed: false,
+                        depth: false,
+                        multisampled: false,
+                        sampled_type: "float".to_owned(),
+                        format: "Unknown".to_owned(),
+                    }),
+                }],
+                push_constant_size: None,
+                capabilities: vec!["Shader".to_owned()],
+                spec_constants: vec![],
+            })
+        );
+        assert_eq!(
+            reflection.get("main_cs"),
+            Some(&EntryPointReflection {
+                stage: "compute".to_owned(),
+                bindings: vec![DescriptorBinding {
+                    set: 2,
+                    binding: 0,
+                    descriptor_type: "storage_buffer".to_owned(),
+                    count: Some(1),
+                    name: None,
+                    read_only: true,
+                    write_only: false,
+                    image: None,
+                }],
+                push_constant_size: Some(20),
+                capabilities: vec!["Shader".to_owned()],
+                spec_constants: vec![SpecConstant {
+                    id: 3,
+                    name: Some("WORKGROUP_SIZE".to_owned()),
+                    ty: "u32".to_owned(),
+                    default: "64".to_owned(),
+                }],
+            })
+        );
+    }
+}
@@ -0,0 +1,85 @@
+//! `--rename-entry-point`, rename entry points in their compiled modules, so that the names that
+//! modules export match what engines or other tools expect.
+
+use anyhow::Context as _;
+use rspirv::binary::Assemble as _;
+use rspirv::dr::{Module, Operand};
+
+/// Rename the entry points of the module at `path` that are in `renames`, a list of their exact
+/// old names and their new ones.
+pub fn entry_points(path: &std::path::Path, renames: &[(String, String)]) -> anyhow::Result<()> {
+    let bytes =
+        std::fs::read(path).with_context(|| format!("could not read '{}'", path.display()))?;
+    let mut module = rspirv::dr::load_bytes(bytes)
+        .map_err(|error| anyhow::anyhow!("could not parse '{}': {error}", path.display()))?;
+    if rename_module(&mut module, renames) {
+        let assembled = module
+            .assemble()
+            .iter()
+            .flat_map(|word| word.to_le_bytes())
+            .collect::<Vec<_>>();
+        crate::atomic::write(path, assembled)?;
+    }
+    Ok(())
+}
+
+/// Rename the module's entry points that are in `renames`. Returns whether any were renamed.
+fn rename_module(module: &mut Module, renames: &[(String, String)]) -> bool {
+    let mut is_renamed = false;
+    for entry_point in &mut module.entry_points {
+        let Some(Operand::LiteralString(name)) = entry_point.operands.get_mut(2) else {
+            continue;
+        };
+        if let Some((old, new)) = renames.iter().find(|(old, _)| old == name) {
+            log::debug!("renaming the entry point `{old}` to `{new}`");
+            new.clone_into(name);
+            is_renamed = true;
+        }
+    }
+    is_renamed
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_log::test]
+    fn renaming_entry_points_in_a_module() {
+        let mut builder = rspirv::dr::Builder::new();
+        let void = builder.type_void();
+        let function_type = builder.type_function(void, vec![]);
+        for name in ["main_fs", "main_vs"] {
+            let main = builder
+                .begin_function(
+                    void,
+                    None,
+                    rspirv::spirv::FunctionControl::NONE,
+                    function_type,
+                )
+                .unwrap();
+            builder.begin_block(None).unwrap();
+            builder.ret().unwrap();
+            builder.end_function().unwrap();
+            builder.entry_point(rspirv::spirv::ExecutionModel::Fragment, main, name, vec![]);
+        }
+        let mut module = builder.module();
+
+        assert!(rename_module(
+            &mut module,
+            &[("main_fs".to_owned(), "fragment_main".to_owned())]
+        ));
+        let names = module
+            .entry_points
+            .iter()
+            .filter_map(|entry_point| match entry_point.operands.get(2) {
+                Some(Operand::LiteralString(name)) => Some(name.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["fragment_main", "main_vs"]);
+        assert!(!rename_module(
+            &mut module,
+            &[("main_fs".to_owned(), "fragment_main".to_owned())]
+        ));
+    }
+}
@@ -0,0 +1,391 @@
+//! `--reproducible`, builds that give byte-for-byte identical shaders on every machine, eg so
+//! that CI can sign them and anyone can check the signature by building them again.
+//!
+//! The build is refused unless `rust-gpu` is pinned to an exact version or commit, which also
+//! pins the toolchain. Environment variables that change how `cargo` and `rustc` compile are
+//! removed, and file paths and other debug info are stripped from the modules. The paths that
+//! non-semantic debug info has to keep are remapped, like `rustc`'s `--remap-path-prefix`, from
+//! the shader crate's workspace and `cargo`'s and `rustup`'s homes. Paths outside of those, eg of
+//! a path dependency elsewhere on disk, are left as they are. Finally a
+//! `build-info.json` records what went into the build and the SHA-256 of everything that came
+//! out of it, so that two builds can be compared.
+
+use anyhow::Context as _;
+use rspirv::binary::Assemble as _;
+use spirv_builder_cli::Linkage;
+
+use crate::install::PairMetadata;
+
+/// The file in the output dir describing a reproducible build.
+pub const BUILD_INFO_FILE: &str = "build-info.json";
+
+/// Environment variables that change how `cargo` and `rustc` compile, which reproducible builds
+/// don't pass on to `spirv-builder-cli`.
+const UNPINNED_ENV: &[&str] = &[
+    "RUSTFLAGS",
+    "CARGO_ENCODED_RUSTFLAGS",
+    "CARGO_BUILD_RUSTFLAGS",
+    "RUSTC_WRAPPER",
+    "RUSTC_WORKSPACE_WRAPPER",
+    "CARGO_BUILD_RUSTC_WRAPPER",
+    "CARGO_INCREMENTAL",
+    "RUSTC_BOOTSTRAP",
+    "RUSTGPU_RUSTFLAGS",
+    "RUSTGPU_CODEGEN_ARGS",
+    "RUSTGPU_CARGOFLAGS",
+];
+
+/// Everything about a reproducible build that has to match for two builds to be the same.
+#[derive(Debug, serde::Serialize)]
+struct BuildInfo<'info> {
+    /// The version of `cargo gpu`.
+    cargo_gpu_version: &'static str,
+    /// The commit of `cargo gpu`.
+    cargo_gpu_git_hash: &'static str,
+    /// The repo or path of `rust-gpu`.
+    rust_gpu_source: &'info str,
+    /// The exact version or commit of `rust-gpu`.
+    rust_gpu_version: &'info str,
+    /// The Rust toolchain that compiled the shaders.
+    toolchain: &'info str,
+    /// The shader target.
+    shader_target: String,
+    /// The shader crate's enabled features.
+    features: &'info [String],
+    /// Whether the shader crate's default features were disabled.
+    no_default_features: bool,
+    /// The fingerprint of all the build's inputs and arguments, see [`crate::fingerprint`].
+    fingerprint: &'info str,
+    /// The SHA-256 of every input file, keyed by its path relative to the shader crate.
+    inputs: std::collections::BTreeMap<String, String>,
+    /// The SHA-256 of every output file, keyed by its path relative to the output dir.
+    outputs: std::collections::BTreeMap<String, String>,
+}
+
+/// Check that an install's `rust-gpu` can't change from under the build, ie that it's a
+/// released version or a full commit hash rather than, say, a branch.
+///
+/// # Errors
+/// If `rust-gpu` isn't pinned.
+pub fn ensure_pinned(metadata: &PairMetadata) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        is_pinned(&metadata.version),
+        "`--reproducible` needs `rust-gpu` to be a released version or a full commit hash, but \
+         it's `{}` from {}",
+        metadata.version,
+        metadata.source
+    );
+    Ok(())
+}
+
+/// Whether a `rust-gpu` version or revision always refers to the same code.
+fn is_pinned(version: &str) -> bool {
+    let is_commit_hash =
+        version.len() == 40 && version.chars().all(|char| char.is_ascii_hexdigit());
+    let is_release = version.starts_with(|char: char| char.is_ascii_digit())
+        && version.contains('.')
+        && !version.contains('/');
+    is_commit_hash || is_release
+}
+
+/// Remove the environment variables that would make the compilation depend on the machine.
+pub fn pin_env(command: &mut std::process::Command) {
+    for name in UNPINNED_ENV {
+        if std::env::var_os(name).is_some() {
+            log::info!("ignoring `{name}` for a reproducible build");
+        }
+        command.env_remove(name);
+    }
+}
+
+/// The directories whose paths differ between machines, and what they're remapped to in debug
+/// info, longest first so that nested directories are remapped by the most specific prefix.
+///
+/// # Errors
+/// If the shader crate's workspace can't be found.
+pub fn path_remaps(shader_crate: &std::path::PathBuf) -> anyhow::Result<Vec<(String, String)>> {
+    let home = directories::BaseDirs::new().map(|dirs| dirs.home_dir().to_path_buf());
+    let home_or_env = |env: &str, dir: &str| {
+        std::env::var_os(env)
+            .map(std::path::PathBuf::from)
+            .or_else(|| home.as_ref().map(|home_dir| home_dir.join(dir)))
+    };
+    let mut dirs = vec![(
+        crate::metadata::Metadata::workspace_root(shader_crate)?,
+        "/workspace",
+    )];
+    dirs.extend(home_or_env("CARGO_HOME", ".cargo").map(|dir| (dir, "/cargo")));
+    dirs.extend(home_or_env("RUSTUP_HOME", ".rustup").map(|dir| (dir, "/rustup")));
+    let mut remaps = dirs
+        .into_iter()
+        .map(|(from, to)| (from.display().to_string(), to.to_owned()))
+        .collect::<Vec<_>>();
+    remaps.sort_by_key(|(from, _)| core::cmp::Reverse(from.len()));
+    Ok(remaps)
+}
+
+/// Strip the file paths, line numbers and other debug info that differ between machines from
+/// the module at `path`, remapping the paths that have to stay with `remaps`, see
+/// [`path_remaps`].
+///
+/// # Errors
+/// If the module can't be read, parsed or written.
+pub fn strip_debug_info(path: &std::path::Path, remaps: &[(String, String)]) -> anyhow::Result<()> {
+    let bytes =
+        std::fs::read(path).with_context(|| format!("could not read '{}'", path.display()))?;
+    let mut module = rspirv::dr::load_bytes(bytes)
+        .map_err(|error| anyhow::anyhow!("could not parse '{}': {error}", path.display()))?;
+    strip_module(&mut module, remaps);
+    let stripped = module
+        .assemble()
+        .iter()
+        .flat_map(|word| word.to_le_bytes())
+        .collect::<Vec<_>>();
+    crate::atomic::write(path, stripped)?;
+    Ok(())
+}
+
+/// Remove `OpSource`, `OpLine` and friends from a module, and any `OpString` that nothing else
+/// refers to once they're gone. The paths in the rest are remapped with `remaps`.
+fn strip_module(module: &mut rspirv::dr::Module, remaps: &[(String, String)]) {
+    let is_line = |instruction: &rspirv::dr::Instruction| {
+        matches!(
+            instruction.class.opcode,
+            rspirv::spirv::Op::Line | rspirv::spirv::Op::NoLine
+        )
+    };
+    module.debug_module_processed.clear();
+    module
+        .types_global_values
+        .retain(|instruction| !is_line(instruction));
+    for function in &mut module.functions {
+        for block in &mut function.blocks {
+            block
+                .instructions
+                .retain(|instruction| !is_line(instruction));
+        }
+    }
+
+    module
+        .debug_string_source
+        .retain(|instruction| instruction.class.opcode == rspirv::spirv::Op::String);
+    // Strings can also be used by non-semantic debug info, which has to keep them.
+    let referenced = module
+        .all_inst_iter()
+        .filter(|instruction| instruction.class.opcode != rspirv::spirv::Op::String)
+        .flat_map(|instruction| instruction.operands.iter())
+        .filter_map(rspirv::dr::Operand::id_ref_any)
+        .collect::<std::collections::HashSet<_>>();
+    module.debug_string_source.retain(|instruction| {
+        instruction
+            .result_id
+            .is_some_and(|id| referenced.contains(&id))
+    });
+    for operand in module
+        .debug_string_source
+        .iter_mut()
+        .flat_map(|instruction| instruction.operands.iter_mut())
+    {
+        if let rspirv::dr::Operand::LiteralString(string) = operand {
+            if let Some(remapped) = remap_path(string, remaps) {
+                *string = remapped;
+            }
+        }
+    }
+}
+
+/// `path` with the first of `remaps` that's a prefix of it replaced, if any is.
+fn remap_path(path: &str, remaps: &[(String, String)]) -> Option<String> {
+    remaps.iter().find_map(|(from, to)| {
+        let rest = path.strip_prefix(from.as_str())?;
+        (rest.is_empty() || rest.starts_with(['/', '\\'])).then(|| format!("{to}{rest}"))
+    })
+}
+
+/// Write `build-info.json` into the output dir, returning its path.
+///
+/// # Errors
+/// If any of the inputs or outputs can't be read, or the file can't be written.
+pub fn write_build_info(
+    build: &crate::build::Build,
+    metadata: &PairMetadata,
+    fingerprint: &str,
+    linkage: &[Linkage],
+    other_outputs: &[std::path::PathBuf],
+) -> anyhow::Result<std::path::PathBuf> {
+    let shader_crate = &build.install.spirv_install.shader_crate;
+    let output_dir = &build.build_args.output_dir;
+
+    let inputs = hashes(
+        &crate::fingerprint::input_files(shader_crate, output_dir)?,
+        shader_crate,
+    )?;
+    let mut output_files = crate::fingerprint::linkage_outputs(linkage, shader_crate);
+    output_files.extend(
+        other_outputs
+            .iter()
+            .filter(|output| !output.ends_with(BUILD_INFO_FILE))
+            .cloned(),
+    );
+    let outputs = hashes(&output_files, output_dir)?;
+
+    let info = BuildInfo {
+        cargo_gpu_version: env!("CARGO_PKG_VERSION"),
+        cargo_gpu_git_hash: env!("GIT_HASH"),
+        rust_gpu_source: &metadata.source,
+        rust_gpu_version: &metadata.version,
+        toolchain: &metadata.channel,
+        // By now the target is the path of its spec file.
+        shader_target: std::path::Path::new(build.build_args.single_shader_target())
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        features: &build.build_args.features,
+        no_default_features: build.build_args.no_default_features,
+        fingerprint,
+        inputs,
+        outputs,
+    };
+    let path = output_dir.join(BUILD_INFO_FILE);
+    crate::atomic::write(&path, serde_json::to_string_pretty(&info)?)?;
+    Ok(path)
+}
+
+/// The SHA-256 of each file, keyed by its path relative to `root`.
+fn hashes(
+    files: &[std::path::PathBuf],
+    root: &std::path::Path,
+) -> anyhow::Result<std::collections::BTreeMap<String, String>> {
+    use relative_path::PathExt as _;
+
+    files
+        .iter()
+        .map(|file| {
+            let bytes = std::fs::read(file)
+                .with_context(|| format!("could not read '{}'", file.display()))?;
+            let name = file.relative_to(root).map_or_else(
+                |_| file.to_string_lossy().into_owned(),
+                relative_path::RelativePathBuf::into_string,
+            );
+            Ok((name, crate::prebuilt::sha256_hex(&bytes)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_log::test]
+    fn recognising_pinned_versions() {
+        assert!(is_pinned("0.9.0"));
+        assert!(is_pinned("82a0f69008414f51d59184763146caa6850ac588"));
+        assert!(!is_pinned("main"));
+        assert!(!is_pinned("82a0f69"));
+        assert!(!is_pinned("v0.9.0"));
+    }
+
+    #[test_log::test]
+    fn stripping_debug_info() {
+        let mut builder = rspirv::dr::Builder::new();
+        builder.capability(rspirv::spirv::Capability::Shader);
+        builder.memory_model(
+            rspirv::spirv::AddressingModel::Logical,
+            rspirv::spirv::MemoryModel::GLSL450,
+        );
+        let file = builder.string("/home/someone/shaders/src/lib.rs");
+        builder.source(
+            rspirv::spirv::SourceLanguage::Unknown,
+            0,
+            Some(file),
+            None::<String>,
+        );
+        builder.line(file, 1, 1);
+        let void = builder.type_void();
+        builder.no_line();
+        let mut module = builder.module();
+
+        strip_module(&mut module, &[]);
+        assert!(module.debug_string_source.is_empty());
+        assert!(module
+            .types_global_values
+            .iter()
+            .all(|instruction| instruction.result_id == Some(void)));
+    }
+
+    /// A module like `rust-gpu` would build from a shader crate in `dir`, with non-semantic
+    /// debug info.
+    fn module_built_in(dir: &str) -> rspirv::dr::Module {
+        let mut builder = rspirv::dr::Builder::new();
+        builder.capability(rspirv::spirv::Capability::Shader);
+        builder.extension("SPV_KHR_non_semantic_info");
+        let debug_info = builder.ext_inst_import("NonSemantic.Shader.DebugInfo.100");
+        builder.memory_model(
+            rspirv::spirv::AddressingModel::Logical,
+            rspirv::spirv::MemoryModel::GLSL450,
+        );
+        let file = builder.string(format!("{dir}/shaders/src/lib.rs"));
+        let core = builder.string("/home/someone/.cargo/registry/src/spirv-std/src/lib.rs");
+        builder.line(file, 1, 1);
+        let void = builder.type_void();
+        builder.no_line();
+        for source in [file, core] {
+            let id = builder.id();
+            builder
+                .module_mut()
+                .types_global_values
+                .push(rspirv::dr::Instruction::new(
+                    rspirv::spirv::Op::ExtInst,
+                    Some(void),
+                    Some(id),
+                    vec![
+                        rspirv::dr::Operand::IdRef(debug_info),
+                        // `DebugSource`
+                        rspirv::dr::Operand::LiteralExtInstInteger(35),
+                        rspirv::dr::Operand::IdRef(source),
+                    ],
+                ));
+        }
+        builder.module()
+    }
+
+    #[test_log::test]
+    fn remapping_the_paths_that_debug_info_keeps() {
+        let remaps = |dir: &str| {
+            vec![
+                (format!("{dir}/workspace"), "/workspace".to_owned()),
+                ("/home/someone/.cargo".to_owned(), "/cargo".to_owned()),
+            ]
+        };
+        let mut here = module_built_in("/home/someone/workspace");
+        let mut there = module_built_in("/builds/ci/workspace");
+        assert_ne!(here.assemble(), there.assemble());
+
+        strip_module(&mut here, &remaps("/home/someone"));
+        strip_module(&mut there, &remaps("/builds/ci"));
+        assert_eq!(here.assemble(), there.assemble());
+        let strings = here
+            .debug_string_source
+            .iter()
+            .filter_map(|instruction| match instruction.operands.first() {
+                Some(rspirv::dr::Operand::LiteralString(string)) => Some(string.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            strings,
+            [
+                "/workspace/shaders/src/lib.rs",
+                "/cargo/registry/src/spirv-std/src/lib.rs"
+            ]
+        );
+
+        assert_eq!(
+            remap_path(
+                "/home/someone/workspace-other/lib.rs",
+                &remaps("/home/someone")
+            ),
+            None
+        );
+    }
+}
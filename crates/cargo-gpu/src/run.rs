@@ -0,0 +1,477 @@
+//! `cargo gpu run`, dispatch a compute entry point of a built module on the GPU with `wgpu`, and
+//! dump the contents of its buffers afterwards.
+//!
+//! The buffers and the number of workgroups are described by a small TOML or JSON file, eg
+//!
+//! ```toml
+//! workgroups = [4, 1, 1]
+//!
+//! [[buffers]]
+//! binding = 0
+//! f32 = [1.0, 2.0, 3.0, 4.0]
+//!
+//! [[buffers]]
+//! binding = 1
+//! zeroed = 16
+//! element = "f32"
+//! ```
+//!
+//! The dump is JSON in the same shape, so it can be committed and compared against as a golden
+//! output of the kernel. Only needs the `run` feature, so that `wgpu` isn't built otherwise.
+
+use anyhow::Context as _;
+use wgpu::util::DeviceExt as _;
+
+/// `cargo gpu run`
+#[derive(clap::Parser, Debug)]
+pub struct Run {
+    /// The compute entry point to dispatch, as it's named in the manifest.
+    #[clap(long)]
+    entry_point: String,
+
+    /// A TOML or JSON file describing the buffers to bind and the workgroups to dispatch.
+    #[clap(long)]
+    input: std::path::PathBuf,
+
+    /// A manifest written by `cargo gpu build`, or the output dir that it's in.
+    #[clap(long, default_value = "./")]
+    manifest: std::path::PathBuf,
+
+    /// Directory containing the shader crate. Paths in the manifest are relative to it.
+    #[clap(long, default_value = "./")]
+    shader_crate: std::path::PathBuf,
+
+    /// Run the entry point of this variant's module, see `cargo gpu build --variant`.
+    #[clap(long)]
+    variant: Option<String>,
+
+    /// Write the buffers after the dispatch to this file, rather than to stdout.
+    #[clap(long)]
+    output: Option<std::path::PathBuf>,
+}
+
+/// The contents of the `--input` file.
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Dispatch {
+    /// How many workgroups to dispatch in each dimension.
+    #[serde(default = "one_workgroup")]
+    workgroups: [u32; 3],
+    /// The buffers to bind.
+    #[serde(default)]
+    buffers: Vec<BufferInput>,
+}
+
+/// The default for `workgroups`.
+const fn one_workgroup() -> [u32; 3] {
+    [1, 1, 1]
+}
+
+/// A buffer to bind, and what to fill it with.
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct BufferInput {
+    /// The descriptor set.
+    #[serde(default)]
+    group: u32,
+    /// The binding within the descriptor set.
+    binding: u32,
+    /// What the buffer starts out as.
+    #[serde(flatten)]
+    contents: Contents,
+    /// How to dump the buffer, by default the type of its contents, or `u32`.
+    element: Option<Element>,
+}
+
+/// What a buffer starts out as.
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum Contents {
+    /// These values
+    U32(Vec<u32>),
+    /// These values
+    I32(Vec<i32>),
+    /// These values
+    F32(Vec<f32>),
+    /// The bytes of a file, relative to the `--input` file
+    File(std::path::PathBuf),
+    /// This many zeroed bytes
+    Zeroed(u64),
+}
+
+/// The type of the values that a buffer is dumped as.
+#[derive(Debug, Clone, Copy, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum Element {
+    /// `u32`
+    U32,
+    /// `i32`
+    I32,
+    /// `f32`
+    F32,
+}
+
+/// The contents of a buffer after the dispatch.
+#[derive(Debug, PartialEq, serde::Serialize)]
+struct BufferOutput {
+    /// The descriptor set.
+    group: u32,
+    /// The binding within the descriptor set.
+    binding: u32,
+    /// The values in the buffer.
+    #[serde(flatten)]
+    values: Values,
+}
+
+/// The values in a buffer.
+#[derive(Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum Values {
+    /// As `u32`s
+    U32(Vec<u32>),
+    /// As `i32`s
+    I32(Vec<i32>),
+    /// As `f32`s
+    F32(Vec<f32>),
+}
+
+impl Run {
+    /// Entrypoint
+    pub fn run(&self) -> anyhow::Result<()> {
+        let dispatch = read_dispatch(&self.input)?;
+        let module = self.module()?;
+        let outputs = pollster::block_on(self.dispatch(&module, &dispatch))?;
+        let json = serde_json::to_string_pretty(&serde_json::json!({ "buffers": outputs }))?;
+        match &self.output {
+            Some(path) => {
+                crate::atomic::write(path, format!("{json}\n"))
+                    .with_context(|| format!("could not write '{}'", path.display()))?;
+                crate::user_output!("Wrote the buffers to '{}'\n", path.display());
+            }
+            None => {
+                #[expect(clippy::print_stdout, reason = "the dump is the command's output")]
+                {
+                    println!("{json}");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The SPIR-V words of the module that has the entry point, from the manifest.
+    fn module(&self) -> anyhow::Result<Vec<u32>> {
+        let manifest_path = crate::diff::manifest_path(&self.manifest);
+        let manifest = crate::diff::read_manifest(&manifest_path)?;
+        let entry = manifest
+            .entry_points
+            .iter()
+            .find(|entry| {
+                entry.entry_point == self.entry_point
+                    && entry.variant.as_deref() == self.variant.as_deref()
+            })
+            .with_context(|| {
+                format!(
+                    "there's no entry point `{}` in '{}'",
+                    self.entry_point,
+                    manifest_path.display()
+                )
+            })?;
+        if let Some(stage) = &entry.stage {
+            anyhow::ensure!(
+                stage == "compute",
+                "`{}` is a {stage} entry point, only compute entry points can be run",
+                self.entry_point
+            );
+        }
+        let manifest_dir = manifest_path
+            .parent()
+            .unwrap_or_else(|| std::path::Path::new("./"));
+        let path = manifest.resolve_path(&entry.source_path, &self.shader_crate, manifest_dir);
+        let bytes =
+            std::fs::read(&path).with_context(|| format!("could not read '{}'", path.display()))?;
+        let module = match &entry.compression {
+            Some(compression) => crate::compress::decompress(&bytes, compression)
+                .with_context(|| format!("could not decompress '{}'", path.display()))?,
+            None => bytes,
+        };
+        anyhow::ensure!(
+            module.len().is_multiple_of(4),
+            "'{}' isn't a SPIR-V module",
+            path.display()
+        );
+        Ok(words(&module))
+    }
+
+    /// Dispatch the entry point of `module`, and read back every buffer afterwards.
+    async fn dispatch(
+        &self,
+        module: &[u32],
+        dispatch: &Dispatch,
+    ) -> anyhow::Result<Vec<BufferOutput>> {
+        let instance = wgpu::Instance::default();
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions::default())
+            .await
+            .context("couldn't find a GPU to run the shader on")?;
+        log::debug!("running on {:?}", adapter.get_info());
+        let (device, queue) = adapter
+            .request_device(&wgpu::DeviceDescriptor {
+                label: Some("cargo gpu run"),
+                required_limits: adapter.limits(),
+                ..Default::default()
+            })
+            .await?;
+        // Report invalid shaders and bindings as errors, rather than panicking.
+        let scope = device.push_error_scope(wgpu::ErrorFilter::Validation);
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&self.entry_point),
+            source: wgpu::ShaderSource::SpirV(module.into()),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(&self.entry_point),
+            layout: None,
+            module: &shader,
+            entry_point: Some(&self.entry_point),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            cache: None,
+        });
+
+        let mut buffers = Vec::new();
+        for input in &dispatch.buffers {
+            let contents = input.bytes()?;
+            let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(&format!("{}:{}", input.group, input.binding)),
+                contents: &contents,
+                usage: wgpu::BufferUsages::STORAGE
+                    | wgpu::BufferUsages::UNIFORM
+                    | wgpu::BufferUsages::COPY_SRC,
+            });
+            buffers.push((input, buffer));
+        }
+        let bind_groups = bind_groups(&device, &pipeline, &buffers);
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        encode_dispatch(&mut encoder, &pipeline, &bind_groups, dispatch.workgroups);
+        let mut readbacks = Vec::new();
+        for (input, buffer) in &buffers {
+            let readback = device.create_buffer(&wgpu::BufferDescriptor {
+                label: None,
+                size: buffer.size(),
+                usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            encoder.copy_buffer_to_buffer(buffer, 0, &readback, 0, buffer.size());
+            readbacks.push((*input, readback));
+        }
+        queue.submit([encoder.finish()]);
+        if let Some(error) = scope.pop().await {
+            anyhow::bail!("couldn't run `{}`: {error}", self.entry_point);
+        }
+
+        read_back(&device, readbacks)
+    }
+}
+
+/// A bind group for each of the descriptor sets that `buffers` are in.
+fn bind_groups(
+    device: &wgpu::Device,
+    pipeline: &wgpu::ComputePipeline,
+    buffers: &[(&BufferInput, wgpu::Buffer)],
+) -> Vec<(u32, wgpu::BindGroup)> {
+    let groups = buffers
+        .iter()
+        .map(|(input, _)| input.group)
+        .collect::<std::collections::BTreeSet<_>>();
+    groups
+        .into_iter()
+        .map(|group| {
+            let entries = buffers
+                .iter()
+                .filter(|(input, _)| input.group == group)
+                .map(|(input, buffer)| wgpu::BindGroupEntry {
+                    binding: input.binding,
+                    resource: buffer.as_entire_binding(),
+                })
+                .collect::<Vec<_>>();
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: None,
+                layout: &pipeline.get_bind_group_layout(group),
+                entries: &entries,
+            });
+            (group, bind_group)
+        })
+        .collect()
+}
+
+/// Record a dispatch of `workgroups` workgroups of `pipeline`.
+fn encode_dispatch(
+    encoder: &mut wgpu::CommandEncoder,
+    pipeline: &wgpu::ComputePipeline,
+    bind_groups: &[(u32, wgpu::BindGroup)],
+    workgroups: [u32; 3],
+) {
+    let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+    pass.set_pipeline(pipeline);
+    for (group, bind_group) in bind_groups {
+        pass.set_bind_group(*group, bind_group, &[]);
+    }
+    let [x, y, z] = workgroups;
+    pass.dispatch_workgroups(x, y, z);
+}
+
+/// Wait for the dispatch to finish, and read the contents of the `readbacks`, the copies of the
+/// buffers.
+fn read_back(
+    device: &wgpu::Device,
+    readbacks: Vec<(&BufferInput, wgpu::Buffer)>,
+) -> anyhow::Result<Vec<BufferOutput>> {
+    let mut outputs = Vec::new();
+    for (input, readback) in readbacks {
+        let slice = readback.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ignored = sender.send(result);
+        });
+        device.poll(wgpu::PollType::wait_indefinitely())?;
+        receiver.recv()??;
+        let bytes = slice.get_mapped_range()?.to_vec();
+        readback.unmap();
+        outputs.push(BufferOutput {
+            group: input.group,
+            binding: input.binding,
+            values: Values::from_bytes(&bytes, input.element()),
+        });
+    }
+    Ok(outputs)
+}
+
+/// Read the `--input` file at `path`, as JSON if it ends in `.json` and TOML otherwise.
+fn read_dispatch(path: &std::path::Path) -> anyhow::Result<Dispatch> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("could not read '{}'", path.display()))?;
+    let mut dispatch: Dispatch = if path
+        .extension()
+        .is_some_and(|extension| extension == "json")
+    {
+        serde_json::from_str(&contents)?
+    } else {
+        toml::from_str(&contents)?
+    };
+    let dir = path.parent().unwrap_or_else(|| std::path::Path::new("./"));
+    for input in &mut dispatch.buffers {
+        if let Contents::File(file) = &mut input.contents {
+            *file = dir.join(&*file);
+        }
+    }
+    Ok(dispatch)
+}
+
+impl BufferInput {
+    /// The bytes that the buffer starts out as, padded to a whole number of words.
+    fn bytes(&self) -> anyhow::Result<Vec<u8>> {
+        let mut bytes = match &self.contents {
+            Contents::U32(values) => values
+                .iter()
+                .flat_map(|value| value.to_le_bytes())
+                .collect(),
+            Contents::I32(values) => values
+                .iter()
+                .flat_map(|value| value.to_le_bytes())
+                .collect(),
+            Contents::F32(values) => values
+                .iter()
+                .flat_map(|value| value.to_le_bytes())
+                .collect(),
+            Contents::File(path) => std::fs::read(path)
+                .with_context(|| format!("could not read '{}'", path.display()))?,
+            Contents::Zeroed(size) => vec![0; usize::try_from(*size)?],
+        };
+        anyhow::ensure!(
+            !bytes.is_empty(),
+            "the buffer at {}:{} is empty",
+            self.group,
+            self.binding
+        );
+        bytes.resize(bytes.len().next_multiple_of(4), 0);
+        Ok(bytes)
+    }
+
+    /// The type of the values that the buffer is dumped as.
+    const fn element(&self) -> Element {
+        match (self.element, &self.contents) {
+            (Some(element), _) => element,
+            (None, Contents::I32(_)) => Element::I32,
+            (None, Contents::F32(_)) => Element::F32,
+            (None, Contents::U32(_) | Contents::File(_) | Contents::Zeroed(_)) => Element::U32,
+        }
+    }
+}
+
+impl Values {
+    /// The little endian `element`s in `bytes`.
+    fn from_bytes(bytes: &[u8], element: Element) -> Self {
+        let chunks = bytes.chunks_exact(4).filter_map(<[u8]>::first_chunk::<4>);
+        match element {
+            Element::U32 => Self::U32(chunks.copied().map(u32::from_le_bytes).collect()),
+            Element::I32 => Self::I32(chunks.copied().map(i32::from_le_bytes).collect()),
+            Element::F32 => Self::F32(chunks.copied().map(f32::from_le_bytes).collect()),
+        }
+    }
+}
+
+/// The little endian words in `bytes`.
+fn words(bytes: &[u8]) -> Vec<u32> {
+    bytes
+        .chunks_exact(4)
+        .filter_map(<[u8]>::first_chunk::<4>)
+        .copied()
+        .map(u32::from_le_bytes)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_log::test]
+    fn reading_buffers() {
+        let dispatch: Dispatch = toml::from_str(
+            r#"
+            workgroups = [4, 1, 1]
+
+            [[buffers]]
+            binding = 0
+            f32 = [1, 2.5]
+
+            [[buffers]]
+            group = 1
+            binding = 2
+            zeroed = 6
+            element = "i32"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(dispatch.workgroups, [4, 1, 1]);
+        let [input, output] = dispatch.buffers.as_slice() else {
+            panic!("expected two buffers, got {:?}", dispatch.buffers);
+        };
+
+        let bytes = input.bytes().unwrap();
+        assert_eq!(
+            Values::from_bytes(&bytes, input.element()),
+            Values::F32(vec![1.0, 2.5])
+        );
+        let zeroed = output.bytes().unwrap();
+        assert_eq!(zeroed.len(), 8);
+        assert_eq!(
+            serde_json::to_string(&BufferOutput {
+                group: output.group,
+                binding: output.binding,
+                values: Values::from_bytes(&zeroed, output.element()),
+            })
+            .unwrap(),
+            r#"{"group":1,"binding":2,"i32":[0,0]}"#
+        );
+    }
+}
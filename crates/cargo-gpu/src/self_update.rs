@@ -0,0 +1,331 @@
+//! `cargo gpu self-update`, upgrade `cargo gpu` itself to a newer release, so that pinned CI
+//! images can be moved on in a controlled way.
+//!
+//! Releases are listed by crates.io or GitHub, what changed in them comes from their GitHub
+//! release notes, and the new version is installed with `cargo install`, just like the first one.
+
+/// The GitHub repo that `cargo gpu` is released from.
+const REPO: &str = "Rust-GPU/cargo-gpu";
+
+/// The name of `cargo gpu`'s crate.
+const CRATE_NAME: &str = "cargo-gpu";
+
+/// The version of `cargo gpu` that's running.
+const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// A version as its major, minor and patch numbers.
+type Version = (u64, u64, u64);
+
+/// Where to find `cargo gpu` releases.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReleaseSource {
+    /// Releases published to crates.io (the default).
+    CratesIo,
+    /// Releases tagged in `cargo gpu`'s GitHub repo.
+    Github,
+}
+
+/// `cargo gpu self-update`.
+#[derive(clap::Parser, Debug)]
+pub struct SelfUpdate {
+    /// The version to update to, eg "0.2.0", rather than the newest release. It can also be older
+    /// than the running version.
+    #[clap(long)]
+    to: Option<String>,
+
+    /// Where to find releases and install them from.
+    #[clap(long, value_enum, default_value = "crates-io")]
+    source: ReleaseSource,
+
+    /// Only show whether there's a newer release and what changed in it, without installing it.
+    #[clap(long)]
+    check: bool,
+}
+
+/// A release of `cargo gpu`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Release {
+    /// The release's version, eg "0.2.0".
+    version: String,
+    /// The git tag of the release, eg "v0.2.0", if it's from GitHub.
+    tag: Option<String>,
+    /// The release notes, if it's from GitHub.
+    notes: Option<String>,
+}
+
+impl SelfUpdate {
+    /// Entrypoint.
+    pub fn run(&self) -> anyhow::Result<()> {
+        crate::offline::ensure_nothing_missing(&["the list of `cargo gpu` releases".to_owned()])?;
+
+        // The release notes are only on GitHub, but they're a nice to have when installing from
+        // crates.io.
+        let github_releases = match self.source {
+            ReleaseSource::Github => github_releases()?,
+            ReleaseSource::CratesIo => github_releases().unwrap_or_else(|error| {
+                log::warn!("couldn't get the release notes from GitHub: {error:?}");
+                Vec::new()
+            }),
+        };
+        let releases = match self.source {
+            ReleaseSource::Github => github_releases.clone(),
+            ReleaseSource::CratesIo => crates_io_releases()?,
+        };
+
+        let current = parse_version(CURRENT_VERSION)
+            .ok_or_else(|| anyhow::anyhow!("can't parse our own version {CURRENT_VERSION}"))?;
+        let release = self.target(&releases, current)?;
+        let Some(target) = parse_version(&release.version) else {
+            anyhow::bail!("can't parse the version of the release {}", release.version);
+        };
+        if target == current {
+            crate::user_output!("`cargo gpu` {CURRENT_VERSION} is already up to date\n");
+            return Ok(());
+        }
+
+        if target > current {
+            crate::user_output!(
+                "`cargo gpu` {} is available, this is {CURRENT_VERSION}\n",
+                release.version
+            );
+        } else {
+            crate::user_output!(
+                "Going back from `cargo gpu` {CURRENT_VERSION} to {}, without these releases:\n",
+                release.version
+            );
+        }
+        for changed in changes_between(&github_releases, current, target) {
+            crate::user_output!(
+                "\n## {}\n\n{}\n",
+                changed.version,
+                changed
+                    .notes
+                    .as_deref()
+                    .unwrap_or("No release notes")
+                    .trim()
+            );
+        }
+        if self.check {
+            return Ok(());
+        }
+        install(&release, self.source)
+    }
+
+    /// The release to update to.
+    fn target(&self, releases: &[Release], current: Version) -> anyhow::Result<Release> {
+        if let Some(to) = &self.to {
+            let wanted = to.trim_start_matches('v');
+            return releases
+                .iter()
+                .find(|release| release.version == wanted)
+                .cloned()
+                .ok_or_else(|| anyhow::anyhow!("there's no `cargo gpu` release {wanted}"));
+        }
+        Ok(releases
+            .iter()
+            .filter_map(|release| Some((parse_version(&release.version)?, release)))
+            .max_by_key(|(version, _)| *version)
+            .filter(|(version, _)| *version > current)
+            .map_or_else(
+                || Release {
+                    version: CURRENT_VERSION.to_owned(),
+                    tag: None,
+                    notes: None,
+                },
+                |(_, release)| release.clone(),
+            ))
+    }
+}
+
+/// Install `release` with `cargo install`, replacing the running `cargo gpu`.
+fn install(release: &Release, source: ReleaseSource) -> anyhow::Result<()> {
+    let mut cargo = std::process::Command::new("cargo");
+    cargo.args(["install", "--locked", "--force"]);
+    match (source, &release.tag) {
+        (ReleaseSource::Github, Some(tag)) => {
+            cargo
+                .args(["--git", &format!("https://github.com/{REPO}"), "--tag", tag])
+                .arg(CRATE_NAME);
+        }
+        (ReleaseSource::Github | ReleaseSource::CratesIo, _) => {
+            cargo
+                .arg(CRATE_NAME)
+                .args(["--version", &format!("={}", release.version)]);
+        }
+    }
+    crate::user_output!("Installing `cargo gpu` {}...\n", release.version);
+    log::debug!("running {cargo:?}");
+    let status = cargo.status()?;
+    anyhow::ensure!(
+        status.success(),
+        "`cargo install` couldn't install `cargo gpu` {}",
+        release.version
+    );
+    crate::user_output!("Updated `cargo gpu` to {}\n", release.version);
+    Ok(())
+}
+
+/// The releases of `cargo gpu` on crates.io.
+fn crates_io_releases() -> anyhow::Result<Vec<Release>> {
+    let url = format!("{}/{CRATE_NAME}", crate::crates_io::API_URL);
+    let response = String::from_utf8(crate::crates_io::download(&url)?)?;
+    releases_from_crates_io(&response)
+}
+
+/// The releases of `cargo gpu` on GitHub.
+fn github_releases() -> anyhow::Result<Vec<Release>> {
+    let url = format!("https://api.github.com/repos/{REPO}/releases");
+    let response = String::from_utf8(crate::crates_io::download(&url)?)?;
+    releases_from_github(&response)
+}
+
+/// The versions that haven't been yanked, from the crates.io API's response about a crate.
+fn releases_from_crates_io(response: &str) -> anyhow::Result<Vec<Release>> {
+    /// The crates.io API's response about a crate.
+    #[derive(serde::Deserialize)]
+    struct CratesIoCrate {
+        /// Every published version.
+        versions: Vec<CratesIoVersion>,
+    }
+
+    /// A published version in the crates.io API.
+    #[derive(serde::Deserialize)]
+    struct CratesIoVersion {
+        /// The version number.
+        num: String,
+        /// Whether it's been yanked.
+        #[serde(default)]
+        yanked: bool,
+    }
+
+    let krate: CratesIoCrate = serde_json::from_str(response)?;
+    Ok(krate
+        .versions
+        .into_iter()
+        .filter(|version| !version.yanked)
+        .map(|version| Release {
+            version: version.num,
+            tag: None,
+            notes: None,
+        })
+        .collect())
+}
+
+/// The published releases, from the GitHub API's response about a repo's releases.
+fn releases_from_github(response: &str) -> anyhow::Result<Vec<Release>> {
+    /// A release in the GitHub API.
+    #[derive(serde::Deserialize)]
+    struct GithubRelease {
+        /// The release's git tag.
+        tag_name: String,
+        /// The release notes.
+        body: Option<String>,
+        /// Whether it's a draft that hasn't been published.
+        #[serde(default)]
+        draft: bool,
+        /// Whether it's a pre-release.
+        #[serde(default)]
+        prerelease: bool,
+    }
+
+    let releases: Vec<GithubRelease> = serde_json::from_str(response)?;
+    Ok(releases
+        .into_iter()
+        .filter(|release| !release.draft && !release.prerelease)
+        .map(|release| Release {
+            version: release.tag_name.trim_start_matches('v').to_owned(),
+            tag: Some(release.tag_name),
+            notes: release.body,
+        })
+        .collect())
+}
+
+/// The releases after `current`, up to and including `target`, oldest first. When going back to
+/// an older release, it's the releases that are being undone.
+fn changes_between(releases: &[Release], current: Version, target: Version) -> Vec<&Release> {
+    let (after, up_to) = if target > current {
+        (current, target)
+    } else {
+        (target, current)
+    };
+    let mut changes = releases
+        .iter()
+        .filter_map(|release| Some((parse_version(&release.version)?, release)))
+        .filter(|(version, _)| *version > after && *version <= up_to)
+        .collect::<Vec<_>>();
+    changes.sort_by_key(|(version, _)| *version);
+    changes.into_iter().map(|(_, release)| release).collect()
+}
+
+/// Parse a version like `0.2.0` or `v0.2.0`. Pre-releases, like `0.2.0-alpha`, aren't parsed, so
+/// they're never picked as updates.
+fn parse_version(version: &str) -> Option<Version> {
+    let mut parts = version.trim_start_matches('v').split('.');
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next()?.parse().ok()?;
+    let patch = parts.next()?.parse().ok()?;
+    parts.next().is_none().then_some((major, minor, patch))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_log::test]
+    fn parsing_releases() {
+        let crates_io = r#"{"crate":{"name":"cargo-gpu"},"versions":[
+            {"num":"0.3.0","yanked":true},{"num":"0.2.0","yanked":false},{"num":"0.1.0"}]}"#;
+        assert_eq!(
+            releases_from_crates_io(crates_io)
+                .unwrap()
+                .into_iter()
+                .map(|release| release.version)
+                .collect::<Vec<_>>(),
+            ["0.2.0", "0.1.0"]
+        );
+        // A rate limit page, or any other response without versions, isn't "no releases".
+        releases_from_crates_io("<html>Too many requests</html>").unwrap_err();
+        releases_from_crates_io(r#"{"errors":[{"detail":"Not Found"}]}"#).unwrap_err();
+
+        let github = r#"[
+            {"tag_name":"v0.3.0-rc.1","body":"Soon","draft":false,"prerelease":true},
+            {"tag_name":"v0.2.0","body":"Added `cargo gpu diff`","draft":false,"prerelease":false},
+            {"tag_name":"v0.1.0","body":null,"draft":false,"prerelease":false}]"#;
+        let releases = releases_from_github(github).unwrap();
+        assert_eq!(
+            releases.first(),
+            Some(&Release {
+                version: "0.2.0".to_owned(),
+                tag: Some("v0.2.0".to_owned()),
+                notes: Some("Added `cargo gpu diff`".to_owned()),
+            })
+        );
+        assert_eq!(releases.len(), 2);
+    }
+
+    #[test_log::test]
+    fn picking_the_changes_between_versions() {
+        let releases =
+            ["0.1.0", "0.1.1", "0.2.0", "0.10.0", "0.11.0-alpha"].map(|version| Release {
+                version: version.to_owned(),
+                tag: None,
+                notes: None,
+            });
+        let versions = |changes: Vec<&Release>| {
+            changes
+                .into_iter()
+                .map(|release| release.version.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            versions(changes_between(&releases, (0, 1, 0), (0, 10, 0))),
+            ["0.1.1", "0.2.0", "0.10.0"]
+        );
+        assert_eq!(
+            versions(changes_between(&releases, (0, 2, 0), (0, 1, 0))),
+            ["0.1.1", "0.2.0"]
+        );
+        assert_eq!(parse_version("v0.10.0"), Some((0, 10, 0)));
+        assert_eq!(parse_version("0.11.0-alpha"), None);
+    }
+}
@@ -0,0 +1,430 @@
+//! `cargo gpu serve`, a long running server that builds shader crates on request.
+//!
+//! Editor plugins and asset pipelines can ask for a rebuild without paying for starting
+//! `cargo gpu` each time. Each `build` request still checks its `rust-gpu` install, just like
+//! `cargo gpu build` does.
+//!
+//! Anyone who can connect can run builds, and so the shader crate's `build.rs` and hooks, so the
+//! server only ever listens on loopback addresses or a Unix socket.
+//!
+//! The protocol is JSON-RPC 2.0 over a local TCP or Unix socket, with one request or response
+//! per line. Requests take the same arguments as the CLI, and results use the same types as
+//! `--output-format json`, ie [`crate::Linkage`]:
+//!
+//!   `{"jsonrpc":"2.0","id":1,"method":"build","params":{"args":["--shader-crate","shaders"]}}`.
+//!
+//! The methods are `build`, `install`, `version` and `shutdown`.
+
+use anyhow::Context as _;
+use clap::Parser as _;
+
+/// The JSON-RPC error code for a request that isn't valid JSON.
+const PARSE_ERROR: i64 = -32700;
+/// The JSON-RPC error code for an unknown method.
+const METHOD_NOT_FOUND: i64 = -32601;
+/// The JSON-RPC error code for params that don't fit the method.
+const INVALID_PARAMS: i64 = -32602;
+/// The JSON-RPC error code for a request that failed, eg a build with compile errors.
+const SERVER_ERROR: i64 = -32000;
+
+/// `cargo gpu serve`.
+#[derive(clap::Parser, Debug)]
+pub struct Serve {
+    /// Where to listen for requests, either `HOST:PORT` or `unix:PATH`. Only loopback addresses
+    /// are allowed, as anyone who can connect can run builds.
+    #[clap(long, default_value = "127.0.0.1:7117")]
+    pub listen: String,
+}
+
+/// A JSON-RPC request.
+#[derive(Debug, serde::Deserialize)]
+struct Request {
+    /// Identifies the response to the request, `None` for notifications that don't get one.
+    #[serde(default)]
+    id: Option<serde_json::Value>,
+    /// The method to call.
+    method: String,
+    /// The method's arguments.
+    #[serde(default)]
+    params: Params,
+}
+
+/// The arguments of `build` and `install` requests.
+#[derive(Debug, Default, serde::Deserialize)]
+struct Params {
+    /// The same args that would be given to `cargo gpu build` or `cargo gpu install`.
+    #[serde(default)]
+    args: Vec<String>,
+}
+
+/// A failed request.
+#[derive(Debug)]
+struct RpcError {
+    /// The JSON-RPC error code.
+    code: i64,
+    /// What went wrong.
+    message: String,
+}
+
+impl RpcError {
+    /// An error with `code`.
+    fn new(code: i64, message: impl core::fmt::Display) -> Self {
+        Self {
+            code,
+            message: message.to_string(),
+        }
+    }
+}
+
+/// State shared by every connection.
+#[derive(Default)]
+struct Server {
+    /// Only one build runs at a time, as they share the output settings and cargo's locks anyway.
+    build_lock: std::sync::Mutex<()>,
+    /// Set by a `shutdown` request.
+    is_shutting_down: core::sync::atomic::AtomicBool,
+}
+
+impl Serve {
+    /// Entrypoint.
+    pub fn run(&self) -> anyhow::Result<()> {
+        let server = std::sync::Arc::new(Server::default());
+        if let Some(path) = self.listen.strip_prefix("unix:") {
+            serve_unix(std::path::Path::new(path), &server)
+        } else {
+            let listener = std::net::TcpListener::bind(loopback_addrs(&self.listen)?.as_slice())
+                .with_context(|| format!("could not listen on `{}`", self.listen))?;
+            listener.set_nonblocking(true)?;
+            crate::user_output!("Listening for build requests on {}\n", self.listen);
+            accept_loop(&server, || match listener.accept() {
+                Ok((stream, _)) => {
+                    stream.set_nonblocking(false)?;
+                    let reader = stream.try_clone()?;
+                    Ok(Some(connection(reader, stream)))
+                }
+                Err(error) if error.kind() == std::io::ErrorKind::WouldBlock => Ok(None),
+                Err(error) => Err(error.into()),
+            })
+        }
+    }
+}
+
+/// The addresses that `listen` resolves to, which all have to be loopback addresses.
+fn loopback_addrs(listen: &str) -> anyhow::Result<Vec<core::net::SocketAddr>> {
+    let addrs = std::net::ToSocketAddrs::to_socket_addrs(listen)
+        .with_context(|| format!("`{listen}` isn't a `HOST:PORT` address"))?
+        .collect::<Vec<_>>();
+    anyhow::ensure!(
+        !addrs.is_empty() && addrs.iter().all(|addr| addr.ip().is_loopback()),
+        "`cargo gpu serve` only listens on loopback addresses, like `127.0.0.1:7117`, as anyone \
+         who can connect can run builds, not `{listen}`"
+    );
+    Ok(addrs)
+}
+
+/// A new connection's reader and writer.
+type Connection = (
+    Box<dyn std::io::Read + Send>,
+    Box<dyn std::io::Write + Send>,
+);
+
+/// A connection from the two halves of a cloned stream.
+fn connection<S: std::io::Read + std::io::Write + Send + 'static>(
+    reader: S,
+    writer: S,
+) -> Connection {
+    (Box::new(reader), Box::new(writer))
+}
+
+/// Listen on a Unix socket at `path`.
+#[cfg(unix)]
+fn serve_unix(path: &std::path::Path, server: &std::sync::Arc<Server>) -> anyhow::Result<()> {
+    remove_stale_socket(path)?;
+    let listener = std::os::unix::net::UnixListener::bind(path)
+        .with_context(|| format!("could not listen on '{}'", path.display()))?;
+    listener.set_nonblocking(true)?;
+    crate::user_output!("Listening for build requests on {}\n", path.display());
+    let result = accept_loop(server, || match listener.accept() {
+        Ok((stream, _)) => {
+            stream.set_nonblocking(false)?;
+            let reader = stream.try_clone()?;
+            Ok(Some(connection(reader, stream)))
+        }
+        Err(error) if error.kind() == std::io::ErrorKind::WouldBlock => Ok(None),
+        Err(error) => Err(error.into()),
+    });
+    std::fs::remove_file(path)?;
+    result
+}
+
+/// Remove a socket left behind by a previous run at `path`, which would stop us from listening.
+/// Anything else at `path` is an error, rather than something to delete.
+#[cfg(unix)]
+fn remove_stale_socket(path: &std::path::Path) -> anyhow::Result<()> {
+    use std::os::unix::fs::FileTypeExt as _;
+
+    let metadata = match std::fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(error) => {
+            return Err(error).with_context(|| format!("could not check '{}'", path.display()))
+        }
+    };
+    anyhow::ensure!(
+        metadata.file_type().is_socket(),
+        "can't listen on '{}', as something other than a socket is already there",
+        path.display()
+    );
+    std::fs::remove_file(path)
+        .with_context(|| format!("could not remove the old socket at '{}'", path.display()))
+}
+
+/// Unix sockets aren't supported on this platform.
+#[cfg(not(unix))]
+fn serve_unix(path: &std::path::Path, _server: &std::sync::Arc<Server>) -> anyhow::Result<()> {
+    anyhow::bail!(
+        "can't listen on '{}', Unix sockets are only supported on Unix",
+        path.display()
+    )
+}
+
+/// Handle each new connection on its own thread, until a `shutdown` request.
+fn accept_loop(
+    server: &std::sync::Arc<Server>,
+    mut accept: impl FnMut() -> anyhow::Result<Option<Connection>>,
+) -> anyhow::Result<()> {
+    while !server
+        .is_shutting_down
+        .load(core::sync::atomic::Ordering::Relaxed)
+    {
+        let Some((reader, writer)) = accept()? else {
+            std::thread::sleep(core::time::Duration::from_millis(50));
+            continue;
+        };
+        let shared = std::sync::Arc::clone(server);
+        std::thread::spawn(move || {
+            if let Err(error) = shared.handle_connection(reader, writer) {
+                log::warn!("connection failed: {error:?}");
+            }
+        });
+    }
+    crate::user_output!("Shutting down\n");
+    Ok(())
+}
+
+impl Server {
+    /// Answer every request on a connection until it's closed.
+    fn handle_connection(
+        &self,
+        reader: Box<dyn std::io::Read + Send>,
+        mut writer: Box<dyn std::io::Write + Send>,
+    ) -> anyhow::Result<()> {
+        use std::io::BufRead as _;
+
+        for maybe_line in std::io::BufReader::new(reader).lines() {
+            let line = maybe_line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Some(response) = self.respond(&line) {
+                writeln!(writer, "{response}")?;
+                writer.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The response to a line of JSON, `None` for notifications.
+    fn respond(&self, line: &str) -> Option<serde_json::Value> {
+        let request = match serde_json::from_str::<Request>(line) {
+            Ok(request) => request,
+            Err(error) => {
+                return Some(response(
+                    &serde_json::Value::Null,
+                    Err(RpcError::new(PARSE_ERROR, error)),
+                ));
+            }
+        };
+        log::debug!("handling `{}` request", request.method);
+        let result = self.call(&request);
+        request.id.as_ref().map(|id| response(id, result))
+    }
+
+    /// Call a request's method.
+    fn call(&self, request: &Request) -> Result<serde_json::Value, RpcError> {
+        match request.method.as_str() {
+            "build" => self.build(&request.params.args),
+            "install" => self.install(&request.params.args),
+            "version" => Ok(serde_json::json!({
+                "version": env!("CARGO_PKG_VERSION"),
+                "git_hash": env!("GIT_HASH"),
+            })),
+            "shutdown" => {
+                self.is_shutting_down
+                    .store(true, core::sync::atomic::Ordering::Relaxed);
+                Ok(serde_json::Value::Null)
+            }
+            unknown => Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("unknown method `{unknown}`"),
+            )),
+        }
+    }
+
+    /// Build a shader crate, like `cargo gpu build`.
+    fn build(&self, args: &[String]) -> Result<serde_json::Value, RpcError> {
+        let timer = std::time::Instant::now();
+        let mut command = merged_args(args)?;
+        let _lock = self
+            .build_lock
+            .lock()
+            .map_err(|error| RpcError::new(SERVER_ERROR, error))?;
+        let linkage = command
+            .run()
+            .map_err(|error| RpcError::new(SERVER_ERROR, format!("{error:?}")))?;
+        Ok(serde_json::json!({
+            "shader_crate": command.install.spirv_install.shader_crate,
+            "output_dir": command.build_args.output_dir,
+            "modules": linkage,
+            "elapsed_secs": timer.elapsed().as_secs_f64(),
+        }))
+    }
+
+    /// Install `rust-gpu`, like `cargo gpu install`.
+    fn install(&self, args: &[String]) -> Result<serde_json::Value, RpcError> {
+        let mut command = merged_args(args)?;
+        let _lock = self
+            .build_lock
+            .lock()
+            .map_err(|error| RpcError::new(SERVER_ERROR, error))?;
+        let cli_path = command
+            .install
+            .run()
+            .map_err(|error| RpcError::new(SERVER_ERROR, format!("{error:?}")))?;
+        Ok(serde_json::json!({ "spirv_builder_cli": cli_path }))
+    }
+}
+
+/// The args of a request, merged with the shader crate's config like the CLI does.
+fn merged_args(args: &[String]) -> Result<crate::build::Build, RpcError> {
+    let env_args = core::iter::once("cargo-gpu".to_owned())
+        .chain(args.iter().cloned())
+        .collect::<Vec<_>>();
+    // Check the args first, as merging them would exit the whole server if they're invalid.
+    let parsed = crate::build::Build::try_parse_from(&env_args)
+        .map_err(|error| RpcError::new(INVALID_PARAMS, error))?;
+    if parsed.build_args.watch || parsed.workspace {
+        return Err(RpcError::new(
+            INVALID_PARAMS,
+            "`--watch` and `--workspace` aren't supported by `cargo gpu serve`",
+        ));
+    }
+    let shader_crate_path = parsed
+        .selected_shader_crate()
+        .map_err(|error| RpcError::new(INVALID_PARAMS, format!("{error:?}")))?;
+    let mut command =
+        crate::config::Config::clap_command_with_cargo_config(&shader_crate_path, env_args)
+            .map_err(|error| RpcError::new(INVALID_PARAMS, format!("{error:?}")))?;
+    command.install.spirv_install.shader_crate = shader_crate_path;
+    Ok(command)
+}
+
+/// A JSON-RPC response.
+fn response(
+    id: &serde_json::Value,
+    result: Result<serde_json::Value, RpcError>,
+) -> serde_json::Value {
+    match result {
+        Ok(value) => serde_json::json!({ "jsonrpc": "2.0", "id": id, "result": value }),
+        Err(error) => serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": error.code, "message": error.message },
+        }),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_log::test]
+    fn only_listening_on_loopback_addresses() {
+        assert_eq!(
+            loopback_addrs("127.0.0.1:7117").unwrap(),
+            ["127.0.0.1:7117".parse().unwrap()]
+        );
+        assert_eq!(
+            loopback_addrs("[::1]:7117").unwrap(),
+            ["[::1]:7117".parse().unwrap()]
+        );
+        for remote in ["0.0.0.0:7117", "[::]:7117", "192.168.1.2:7117"] {
+            assert!(loopback_addrs(remote)
+                .unwrap_err()
+                .to_string()
+                .contains("only listens on loopback addresses"));
+        }
+    }
+
+    #[cfg(unix)]
+    #[test_log::test]
+    fn only_removing_stale_sockets() {
+        let dir = crate::cache_dir().unwrap();
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let socket = dir.join("cargo-gpu.sock");
+        drop(std::os::unix::net::UnixListener::bind(&socket).unwrap());
+        remove_stale_socket(&socket).unwrap();
+        assert!(!socket.exists());
+        remove_stale_socket(&socket).unwrap();
+
+        let file = dir.join("not-a-socket");
+        std::fs::write(&file, "precious").unwrap();
+        assert!(remove_stale_socket(&file)
+            .unwrap_err()
+            .to_string()
+            .contains("something other than a socket"));
+        assert!(file.is_file());
+        crate::test::tests_teardown();
+    }
+
+    #[test_log::test]
+    fn responding_to_requests() {
+        let server = Server::default();
+        let error_code = |line: &str| {
+            server
+                .respond(line)
+                .unwrap()
+                .pointer("/error/code")
+                .and_then(serde_json::Value::as_i64)
+        };
+
+        let version = server
+            .respond(r#"{"jsonrpc":"2.0","id":1,"method":"version"}"#)
+            .unwrap();
+        assert_eq!(
+            version.pointer("/id").and_then(serde_json::Value::as_i64),
+            Some(1)
+        );
+        assert_eq!(
+            version.pointer("/result/version"),
+            Some(&serde_json::json!(env!("CARGO_PKG_VERSION")))
+        );
+        assert_eq!(
+            error_code(r#"{"jsonrpc":"2.0","id":"a","method":"explode"}"#),
+            Some(METHOD_NOT_FOUND)
+        );
+        assert_eq!(
+            error_code(r#"{"jsonrpc":"2.0","id":2,"method":"build","params":{"args":["--nope"]}}"#),
+            Some(INVALID_PARAMS)
+        );
+        assert_eq!(error_code("{"), Some(PARSE_ERROR));
+
+        assert!(server
+            .respond(r#"{"jsonrpc":"2.0","method":"shutdown"}"#)
+            .is_none());
+        assert!(server
+            .is_shutting_down
+            .load(core::sync::atomic::Ordering::Relaxed));
+    }
+}
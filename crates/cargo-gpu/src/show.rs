@@ -1,6 +1,113 @@
 //! Display various information about `cargo gpu`, eg its cache directory.
 
-use crate::cache_dir;
+use crate::{cache_dir, target_spec_dir};
+
+/// Every extension in the SPIR-V grammar, as of SPIR-V 1.6 revision 1. `rspirv`'s instruction
+/// table only has those that add instructions, so this also has the ones that only add
+/// capabilities or operands, eg `SPV_KHR_storage_buffer_storage_class`.
+const EXTENSIONS: &[&str] = &[
+    "SPV_AMD_gpu_shader_half_float_fetch",
+    "SPV_AMD_shader_ballot",
+    "SPV_AMD_shader_early_and_late_fragment_tests",
+    "SPV_AMD_shader_explicit_vertex_parameter",
+    "SPV_AMD_shader_fragment_mask",
+    "SPV_AMD_shader_image_load_store_lod",
+    "SPV_AMD_texture_gather_bias_lod",
+    "SPV_ARM_core_builtins",
+    "SPV_EXT_demote_to_helper_invocation",
+    "SPV_EXT_descriptor_indexing",
+    "SPV_EXT_fragment_fully_covered",
+    "SPV_EXT_fragment_invocation_density",
+    "SPV_EXT_fragment_shader_interlock",
+    "SPV_EXT_mesh_shader",
+    "SPV_EXT_opacity_micromap",
+    "SPV_EXT_physical_storage_buffer",
+    "SPV_EXT_shader_atomic_float16_add",
+    "SPV_EXT_shader_atomic_float_add",
+    "SPV_EXT_shader_atomic_float_min_max",
+    "SPV_EXT_shader_image_int64",
+    "SPV_EXT_shader_stencil_export",
+    "SPV_EXT_shader_viewport_index_layer",
+    "SPV_GOOGLE_decorate_string",
+    "SPV_GOOGLE_hlsl_functionality1",
+    "SPV_GOOGLE_user_type",
+    "SPV_INTEL_arbitrary_precision_fixed_point",
+    "SPV_INTEL_arbitrary_precision_floating_point",
+    "SPV_INTEL_arbitrary_precision_integers",
+    "SPV_INTEL_blocking_pipes",
+    "SPV_INTEL_debug_module",
+    "SPV_INTEL_device_side_avc_motion_estimation",
+    "SPV_INTEL_float_controls2",
+    "SPV_INTEL_fp_fast_math_mode",
+    "SPV_INTEL_fpga_buffer_location",
+    "SPV_INTEL_fpga_cluster_attributes",
+    "SPV_INTEL_fpga_loop_controls",
+    "SPV_INTEL_fpga_memory_accesses",
+    "SPV_INTEL_fpga_memory_attributes",
+    "SPV_INTEL_fpga_reg",
+    "SPV_INTEL_function_pointers",
+    "SPV_INTEL_inline_assembly",
+    "SPV_INTEL_io_pipes",
+    "SPV_INTEL_kernel_attributes",
+    "SPV_INTEL_long_constant_composite",
+    "SPV_INTEL_loop_fuse",
+    "SPV_INTEL_media_block_io",
+    "SPV_INTEL_memory_access_aliasing",
+    "SPV_INTEL_optnone",
+    "SPV_INTEL_shader_integer_functions2",
+    "SPV_INTEL_split_barrier",
+    "SPV_INTEL_subgroups",
+    "SPV_INTEL_unstructured_loop_controls",
+    "SPV_INTEL_usm_storage_classes",
+    "SPV_INTEL_variable_length_array",
+    "SPV_INTEL_vector_compute",
+    "SPV_KHR_16bit_storage",
+    "SPV_KHR_8bit_storage",
+    "SPV_KHR_bit_instructions",
+    "SPV_KHR_device_group",
+    "SPV_KHR_expect_assume",
+    "SPV_KHR_float_controls",
+    "SPV_KHR_fragment_shader_barycentric",
+    "SPV_KHR_fragment_shading_rate",
+    "SPV_KHR_integer_dot_product",
+    "SPV_KHR_linkonce_odr",
+    "SPV_KHR_multiview",
+    "SPV_KHR_no_integer_wrap_decoration",
+    "SPV_KHR_physical_storage_buffer",
+    "SPV_KHR_post_depth_coverage",
+    "SPV_KHR_ray_cull_mask",
+    "SPV_KHR_ray_query",
+    "SPV_KHR_ray_tracing",
+    "SPV_KHR_shader_atomic_counter_ops",
+    "SPV_KHR_shader_ballot",
+    "SPV_KHR_shader_clock",
+    "SPV_KHR_shader_draw_parameters",
+    "SPV_KHR_storage_buffer_storage_class",
+    "SPV_KHR_subgroup_rotate",
+    "SPV_KHR_subgroup_uniform_control_flow",
+    "SPV_KHR_subgroup_vote",
+    "SPV_KHR_terminate_invocation",
+    "SPV_KHR_uniform_group_instructions",
+    "SPV_KHR_variable_pointers",
+    "SPV_KHR_vulkan_memory_model",
+    "SPV_KHR_workgroup_memory_explicit_layout",
+    "SPV_NVX_multiview_per_view_attributes",
+    "SPV_NV_bindless_texture",
+    "SPV_NV_compute_shader_derivatives",
+    "SPV_NV_cooperative_matrix",
+    "SPV_NV_fragment_shader_barycentric",
+    "SPV_NV_geometry_shader_passthrough",
+    "SPV_NV_mesh_shader",
+    "SPV_NV_ray_tracing",
+    "SPV_NV_ray_tracing_motion_blur",
+    "SPV_NV_sample_mask_override_coverage",
+    "SPV_NV_shader_image_footprint",
+    "SPV_NV_shader_sm_builtins",
+    "SPV_NV_shader_subgroup_partitioned",
+    "SPV_NV_shading_rate",
+    "SPV_NV_stereo_view_rendering",
+    "SPV_NV_viewport_array2",
+];
 
 /// Show the computed source of the spirv-std dependency.
 #[derive(Clone, Debug, clap::Parser)]
@@ -10,44 +117,68 @@ pub struct SpirvSourceDep {
     pub shader_crate: std::path::PathBuf,
 }
 
+/// Find all the shader crates in a workspace.
+#[derive(Clone, Debug, clap::Parser)]
+pub struct ShaderCrates {
+    /// Any directory in the workspace to search, eg the workspace root.
+    #[clap(long, default_value = "./")]
+    pub path: std::path::PathBuf,
+}
+
 /// Different tidbits of information that can be queried at the command line.
 #[derive(Clone, Debug, clap::Subcommand)]
 pub enum Info {
-    /// Displays the location of the cache directory
+    /// Displays the location of the cache directory.
     CacheDirectory,
-    /// The source location of spirv-std
+    /// The source location of spirv-std.
     SpirvSource(SpirvSourceDep),
     /// The git commitsh of this cli tool.
     Commitsh,
-    /// All the available SPIR-V capabilities that can be set with `--capability`
+    /// All the available SPIR-V capabilities that can be set with `--capability`.
     Capabilities,
+    /// All the known SPIR-V extensions that can be set with `--extension`.
+    Extensions,
+    /// Every installed `spirv-builder-cli` and `rustc_codegen_spirv` binary pair in the cache.
+    Installs,
+    /// All the available shader targets that can be set with `--shader-target`.
+    Targets,
+    /// The name and path of every crate in the workspace that depends on `spirv-std`.
+    ShaderCrates(ShaderCrates),
 }
 
-/// `cargo gpu show`
+/// `cargo gpu show`.
 #[derive(clap::Parser)]
 pub struct Show {
-    /// Display information about rust-gpu
+    /// Display information about rust-gpu.
     #[clap(subcommand)]
     command: Info,
+
+    /// Format of the information written to stdout.
+    #[clap(long, value_enum, default_value_t, global = true)]
+    pub output_format: crate::output::Format,
 }
 
 impl Show {
-    /// Entrypoint
-    pub fn run(self) -> anyhow::Result<()> {
+    /// Entrypoint.
+    pub fn run(&self) -> anyhow::Result<()> {
         log::info!("{:?}: ", self.command);
 
+        if crate::output::is_json() {
+            return crate::output::print_json(&self.as_json()?);
+        }
+
         #[expect(
             clippy::print_stdout,
             reason = "The output of this command could potentially be used in a script, \
                       so we _don't_ want to use `crate::user_output`, as that prefixes a crab."
         )]
-        match self.command {
+        match &self.command {
             Info::CacheDirectory => {
                 println!("{}\n", cache_dir()?.display());
             }
             Info::SpirvSource(SpirvSourceDep { shader_crate }) => {
                 let rust_gpu_source =
-                    crate::spirv_source::SpirvSource::get_spirv_std_dep_definition(&shader_crate)?;
+                    crate::spirv_source::SpirvSource::get_spirv_std_dep_definition(shader_crate)?;
                 {
                     println!("{rust_gpu_source}\n");
                 }
@@ -65,11 +196,166 @@ impl Show {
                     println!("  {capability:?}");
                 }
             }
+            Info::Extensions => {
+                println!("All known options to the `cargo gpu build --extension` argument:");
+                for extension in Self::extension_names() {
+                    println!("  {extension}");
+                }
+            }
+            Info::Installs => {
+                println!("NAME\tSOURCE\tVERSION\tCHANNEL\tSIZE\tLAST USED");
+                for pair in crate::install::installed_pairs()? {
+                    let (source, version, channel) = pair.metadata.map_or_else(
+                        || {
+                            (
+                                "unknown".to_owned(),
+                                "unknown".to_owned(),
+                                "unknown".to_owned(),
+                            )
+                        },
+                        |metadata| (metadata.source, metadata.version, metadata.channel),
+                    );
+                    let last_used = pair.last_used.map_or_else(
+                        || "unknown".to_owned(),
+                        |time| {
+                            chrono::DateTime::<chrono::Utc>::from(time)
+                                .format("%Y-%m-%d %H:%M UTC")
+                                .to_string()
+                        },
+                    );
+                    println!(
+                        "{}\t{source}\t{version}\t{channel}\t{}\t{last_used}",
+                        pair.name,
+                        crate::format_size(pair.size)
+                    );
+                }
+            }
+            Info::Targets => {
+                println!(
+                    "All available options to the `cargo gpu build --shader-target` argument:"
+                );
+                let installed = Self::installed_target_names()?;
+                for target in Self::target_names()? {
+                    if installed.contains(&target)
+                        || !crate::install::NEWER_TARGET_SPECS
+                            .contains(&format!("{target}.json").as_str())
+                    {
+                        println!("  {target}");
+                    } else {
+                        println!(
+                            "  {target} (needs a `rust-gpu` from {} or later)",
+                            crate::install::NEWER_TARGETS_DATE
+                        );
+                    }
+                }
+            }
+            Info::ShaderCrates(ShaderCrates { path }) => {
+                for shader_crate in crate::metadata::Metadata::shader_crates(path)? {
+                    println!("{}\t{}", shader_crate.name, shader_crate.path.display());
+                }
+            }
         }
 
         Ok(())
     }
 
+    /// The same information as the text output, but structured for scripts.
+    fn as_json(&self) -> anyhow::Result<serde_json::Value> {
+        Ok(match &self.command {
+            Info::CacheDirectory => serde_json::json!({ "cache_directory": cache_dir()? }),
+            Info::SpirvSource(SpirvSourceDep { shader_crate }) => {
+                let source =
+                    crate::spirv_source::SpirvSource::get_spirv_std_dep_definition(shader_crate)?;
+                serde_json::json!({
+                    "source": source.to_repo(),
+                    "version": source.to_version(),
+                })
+            }
+            Info::Commitsh => serde_json::json!({ "commitsh": std::env!("GIT_HASH") }),
+            Info::Capabilities => {
+                let capabilities = Self::capability_variants_iter()
+                    .map(|capability| format!("{capability:?}"))
+                    .collect::<Vec<_>>();
+                serde_json::json!(capabilities)
+            }
+            Info::Extensions => serde_json::json!(Self::extension_names()),
+            Info::Installs => {
+                let pairs = crate::install::installed_pairs()?
+                    .into_iter()
+                    .map(|pair| {
+                        serde_json::json!({
+                            "name": pair.name,
+                            "path": pair.path,
+                            "metadata": pair.metadata,
+                            "size": pair.size,
+                            "last_used": pair.last_used.map(|time| {
+                                chrono::DateTime::<chrono::Utc>::from(time).to_rfc3339()
+                            }),
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                serde_json::json!(pairs)
+            }
+            Info::Targets => serde_json::json!(Self::target_names()?),
+            Info::ShaderCrates(ShaderCrates { path }) => {
+                let shader_crates = crate::metadata::Metadata::shader_crates(path)?
+                    .into_iter()
+                    .map(|shader_crate| {
+                        serde_json::json!({
+                            "name": shader_crate.name,
+                            "path": shader_crate.path,
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                serde_json::json!(shader_crates)
+            }
+        })
+    }
+
+    /// The names of all the known SPIR-V extensions, from [`EXTENSIONS`] and `rspirv`'s grammar.
+    fn extension_names() -> std::collections::BTreeSet<&'static str> {
+        rspirv::grammar::CoreInstructionTable::iter()
+            .flat_map(|instruction| instruction.extensions.iter().copied())
+            .chain(EXTENSIONS.iter().copied())
+            .collect()
+    }
+
+    /// The names of all the shader targets, both bundled and those copied from a `rust-gpu`
+    /// checkout into the target spec dir of an install.
+    fn target_names() -> anyhow::Result<std::collections::BTreeSet<String>> {
+        let mut names: std::collections::BTreeSet<String> = crate::install::TARGET_SPECS
+            .iter()
+            .filter_map(|(filename, _)| filename.strip_suffix(".json"))
+            .map(ToOwned::to_owned)
+            .collect();
+        names.extend(Self::installed_target_names()?);
+        Ok(names)
+    }
+
+    /// The names of the shader targets in the target spec dirs of the installs, which at least one
+    /// installed `rust-gpu` can compile for.
+    fn installed_target_names() -> anyhow::Result<std::collections::BTreeSet<String>> {
+        let mut names = std::collections::BTreeSet::new();
+        for pair in crate::install::installed_pairs()? {
+            let spec_dir = target_spec_dir(&pair.path);
+            if !spec_dir.is_dir() {
+                continue;
+            }
+            for maybe_entry in std::fs::read_dir(spec_dir)? {
+                let path = maybe_entry?.path();
+                if path
+                    .extension()
+                    .is_some_and(|extension| extension == "json")
+                {
+                    if let Some(name) = path.file_stem() {
+                        names.insert(name.to_string_lossy().into_owned());
+                    }
+                }
+            }
+        }
+        Ok(names)
+    }
+
     /// Iterator over all `Capability` variants.
     fn capability_variants_iter() -> impl Iterator<Item = spirv_builder_cli::spirv::Capability> {
         // Since spirv::Capability is repr(u32) we can iterate over
@@ -79,3 +365,20 @@ impl Show {
         (0..=last_capability).filter_map(spirv_builder_cli::spirv::Capability::from_u32)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_log::test]
+    fn listing_extensions_that_only_add_capabilities_or_operands() {
+        let names = Show::extension_names();
+        for name in [
+            "SPV_KHR_storage_buffer_storage_class",
+            "SPV_EXT_descriptor_indexing",
+            "SPV_KHR_8bit_storage",
+        ] {
+            assert!(names.contains(name), "`{name}` isn't listed");
+        }
+    }
+}
@@ -0,0 +1,186 @@
+//! Bake `--spec-constant` values into the defaults of compiled modules' specialization constants,
+//! so that they don't have to be patched into the binaries by hand.
+//!
+//! Only the defaults change, the constants stay specialization constants, so pipelines can still
+//! set them when they're created.
+
+use anyhow::Context as _;
+use rspirv::binary::Assemble as _;
+use rspirv::dr::{Module, Operand};
+use rspirv::spirv::Op;
+
+/// Set the defaults of the module at `path`'s specialization constants that are in `overrides`.
+///
+/// The constants are keyed by their name or `SpecId`. Returns the keys in `overrides` that matched
+/// a constant in the module.
+pub fn apply(
+    path: &std::path::Path,
+    overrides: &[(String, String)],
+) -> anyhow::Result<Vec<String>> {
+    let bytes =
+        std::fs::read(path).with_context(|| format!("could not read '{}'", path.display()))?;
+    let mut module = rspirv::dr::load_bytes(bytes)
+        .map_err(|error| anyhow::anyhow!("could not parse '{}': {error}", path.display()))?;
+    let matched = override_module(&mut module, overrides)
+        .with_context(|| format!("could not set the spec constants of '{}'", path.display()))?;
+    if !matched.is_empty() {
+        let overridden = module
+            .assemble()
+            .iter()
+            .flat_map(|word| word.to_le_bytes())
+            .collect::<Vec<_>>();
+        crate::atomic::write(path, overridden)?;
+    }
+    Ok(matched)
+}
+
+/// Set the defaults of the module's specialization constants that are in `overrides`. Returns
+/// the names in `overrides` that matched.
+fn override_module(
+    module: &mut Module,
+    overrides: &[(String, String)],
+) -> anyhow::Result<Vec<String>> {
+    let mut changes = Vec::new();
+    let mut matched = Vec::new();
+    for (index, instruction) in module.types_global_values.iter().enumerate() {
+        let Some(spec_constant) = crate::reflect::spec_constant(module, instruction) else {
+            continue;
+        };
+        let id = spec_constant.id.to_string();
+        for (name, value) in overrides {
+            if *name != id && spec_constant.name.as_ref() != Some(name) {
+                continue;
+            }
+            let (opcode, operands) = literal(&spec_constant.ty, value).with_context(|| {
+                format!(
+                    "`{value}` isn't a valid `{}` for `{name}`",
+                    spec_constant.ty
+                )
+            })?;
+            log::debug!("setting the spec constant `{name}` to {value}");
+            changes.push((index, opcode, operands));
+            matched.push(name.clone());
+        }
+    }
+
+    for (index, opcode, operands) in changes {
+        if let Some(instruction) = module.types_global_values.get_mut(index) {
+            instruction.class = rspirv::grammar::CoreInstructionTable::get(opcode);
+            instruction.operands = operands;
+        }
+    }
+    Ok(matched)
+}
+
+/// The opcode and operands of a specialization constant of the type `type_name` with `value`.
+fn literal(type_name: &str, value: &str) -> anyhow::Result<(Op, Vec<Operand>)> {
+    /// A 32 bit word holding a signed integer's bits.
+    const fn signed_word(signed: i32) -> Operand {
+        Operand::LiteralInt32(signed.cast_unsigned())
+    }
+
+    Ok(match type_name {
+        "bool" => match value {
+            "true" => (Op::SpecConstantTrue, Vec::new()),
+            "false" => (Op::SpecConstantFalse, Vec::new()),
+            _ => anyhow::bail!("expected `true` or `false`"),
+        },
+        "i8" => (
+            Op::SpecConstant,
+            vec![signed_word(value.parse::<i8>()?.into())],
+        ),
+        "i16" => (
+            Op::SpecConstant,
+            vec![signed_word(value.parse::<i16>()?.into())],
+        ),
+        "i32" => (Op::SpecConstant, vec![signed_word(value.parse()?)]),
+        "i64" => (
+            Op::SpecConstant,
+            vec![Operand::LiteralInt64(value.parse::<i64>()?.cast_unsigned())],
+        ),
+        "u8" => (
+            Op::SpecConstant,
+            vec![Operand::LiteralInt32(value.parse::<u8>()?.into())],
+        ),
+        "u16" => (
+            Op::SpecConstant,
+            vec![Operand::LiteralInt32(value.parse::<u16>()?.into())],
+        ),
+        "u32" => (
+            Op::SpecConstant,
+            vec![Operand::LiteralInt32(value.parse()?)],
+        ),
+        "u64" => (
+            Op::SpecConstant,
+            vec![Operand::LiteralInt64(value.parse()?)],
+        ),
+        "f32" => (
+            Op::SpecConstant,
+            vec![Operand::LiteralFloat32(value.parse()?)],
+        ),
+        "f64" => (
+            Op::SpecConstant,
+            vec![Operand::LiteralFloat64(value.parse()?)],
+        ),
+        _ => anyhow::bail!("spec constants of type `{type_name}` can't be set"),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_log::test]
+    fn overriding_spec_constant_defaults() {
+        let mut builder = rspirv::dr::Builder::new();
+        let uint = builder.type_int(32, 0);
+        let int = builder.type_int(32, 1);
+        let boolean = builder.type_bool();
+        let workgroup_size = builder.spec_constant_u32(uint, 64);
+        builder.decorate(
+            workgroup_size,
+            rspirv::spirv::Decoration::SpecId,
+            vec![Operand::LiteralInt32(0)],
+        );
+        builder.name(workgroup_size, "WORKGROUP_SIZE");
+        let offset = builder.spec_constant_u32(int, 0);
+        builder.decorate(
+            offset,
+            rspirv::spirv::Decoration::SpecId,
+            vec![Operand::LiteralInt32(1)],
+        );
+        let use_fog = builder.spec_constant_false(boolean);
+        builder.decorate(
+            use_fog,
+            rspirv::spirv::Decoration::SpecId,
+            vec![Operand::LiteralInt32(2)],
+        );
+        builder.name(use_fog, "USE_FOG");
+        let mut module = builder.module();
+
+        let matched = override_module(
+            &mut module,
+            &[
+                ("WORKGROUP_SIZE".to_owned(), "128".to_owned()),
+                ("1".to_owned(), "-2".to_owned()),
+                ("USE_FOG".to_owned(), "true".to_owned()),
+                ("MISSING".to_owned(), "1".to_owned()),
+            ],
+        )
+        .unwrap();
+        assert_eq!(matched, ["WORKGROUP_SIZE", "1", "USE_FOG"]);
+        let defaults = module
+            .types_global_values
+            .iter()
+            .filter_map(|instruction| crate::reflect::spec_constant(&module, instruction))
+            .map(|spec_constant| spec_constant.default)
+            .collect::<Vec<_>>();
+        assert_eq!(defaults, ["128", "-2", "true"]);
+
+        override_module(
+            &mut module,
+            &[("WORKGROUP_SIZE".to_owned(), "-1".to_owned())],
+        )
+        .unwrap_err();
+    }
+}
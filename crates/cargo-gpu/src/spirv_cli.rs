@@ -5,6 +5,9 @@ use anyhow::Context as _;
 
 use crate::spirv_source::SpirvSource;
 
+/// The components of the toolchain that `rust-gpu` needs.
+const REQUIRED_COMPONENTS: [&str; 3] = ["rust-src", "rustc-dev", "llvm-tools"];
+
 /// Cargo dependency for `spirv-builder` and the rust toolchain channel.
 #[derive(Debug, Clone)]
 pub struct SpirvCli {
@@ -19,61 +22,50 @@ pub struct SpirvCli {
     ///     - a repo of "https://github.com/Rust-GPU/rust-gpu.git"
     ///     - a revision of "abc213"
     pub source: SpirvSource,
-    /// The toolchain channel that `rust-gpu` uses, eg "nightly-2024-04-24"
+    /// The toolchain channel that `rust-gpu` uses, eg "nightly-2024-04-24".
     pub channel: String,
-    /// The date of the pinned version of `rust-gpu`
+    /// The date of the pinned version of `rust-gpu`.
     pub date: chrono::NaiveDate,
-    /// Has the user overridden the toolchain consent prompt
-    is_toolchain_install_consent: bool,
 }
 
 impl core::fmt::Display for SpirvCli {
-    #[expect(
-        clippy::min_ident_chars,
-        reason = "It's a core library trait implementation"
-    )]
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         format!("{}+{}", self.source, self.channel).fmt(f)
     }
 }
 
 impl SpirvCli {
-    /// Create instance
+    /// Create instance.
     pub fn new(
         shader_crate_path: &std::path::PathBuf,
         maybe_rust_gpu_source: Option<String>,
         maybe_rust_gpu_version: Option<String>,
         maybe_rust_gpu_channel: Option<String>,
-        is_toolchain_install_consent: bool,
     ) -> anyhow::Result<Self> {
         let (default_rust_gpu_source, rust_gpu_date, default_rust_gpu_channel) =
             SpirvSource::get_rust_gpu_deps_from_shader(shader_crate_path)?;
 
-        let mut maybe_spirv_source: Option<SpirvSource> = None;
-        if let Some(rust_gpu_version) = maybe_rust_gpu_version {
-            let mut source = SpirvSource::CratesIO(rust_gpu_version.clone());
-            if let Some(rust_gpu_source) = maybe_rust_gpu_source {
-                source = SpirvSource::Git {
+        let maybe_spirv_source = maybe_rust_gpu_version.map(|rust_gpu_version| {
+            maybe_rust_gpu_source.map_or_else(
+                || SpirvSource::CratesIO(rust_gpu_version.clone()),
+                |rust_gpu_source| SpirvSource::Git {
                     url: rust_gpu_source,
-                    rev: rust_gpu_version,
-                };
-            }
-            maybe_spirv_source = Some(source);
-        }
+                    rev: rust_gpu_version.clone(),
+                },
+            )
+        });
 
         Ok(Self {
             source: maybe_spirv_source.unwrap_or(default_rust_gpu_source),
             channel: maybe_rust_gpu_channel.unwrap_or(default_rust_gpu_channel),
             date: rust_gpu_date,
-            is_toolchain_install_consent,
         })
     }
 
-    /// Create and/or return the cache directory
+    /// Create and/or return the cache directory.
     pub fn cached_checkout_path(&self) -> anyhow::Result<std::path::PathBuf> {
-        let checkout_dir = crate::cache_dir()?
-            .join("spirv-builder-cli")
-            .join(crate::to_dirname(self.to_string().as_ref()));
+        let checkout_dir =
+            crate::installs_dir()?.join(crate::to_dirname(self.to_string().as_ref()));
         std::fs::create_dir_all(&checkout_dir).with_context(|| {
             format!("could not create checkout dir '{}'", checkout_dir.display())
         })?;
@@ -81,91 +73,160 @@ impl SpirvCli {
         Ok(checkout_dir)
     }
 
-    /// Use `rustup` to install the toolchain and components, if not already installed.
+    /// Use `rustup` to install the toolchain for `channel` and its components, if not already
+    /// installed. Asks first, unless `is_toolchain_install_consent`.
     ///
     /// Pretty much runs:
     ///
     /// * rustup toolchain add nightly-2024-04-24
     /// * rustup component add --toolchain nightly-2024-04-24 rust-src rustc-dev llvm-tools
-    pub fn ensure_toolchain_and_components_exist(&self) -> anyhow::Result<()> {
+    pub fn ensure_toolchain(
+        channel: &str,
+        is_toolchain_install_consent: bool,
+    ) -> anyhow::Result<()> {
         // Check for the required toolchain
-        let output_toolchain_list = std::process::Command::new("rustup")
-            .args(["toolchain", "list"])
-            .output()?;
-        anyhow::ensure!(
-            output_toolchain_list.status.success(),
-            "could not list installed toolchains"
-        );
-        let string_toolchain_list = String::from_utf8_lossy(&output_toolchain_list.stdout);
-        if string_toolchain_list
-            .split_whitespace()
-            .any(|toolchain| toolchain.starts_with(&self.channel))
-        {
-            log::debug!("toolchain {} is already installed", self.channel);
+        if Self::is_toolchain_installed(channel)? {
+            log::debug!("toolchain {channel} is already installed");
         } else {
-            let message = format!("Rust {} with `rustup`", self.channel);
-            self.get_consent_for_toolchain_install(format!("Install {message}").as_ref())?;
+            crate::offline::ensure_nothing_missing(&[
+                format!("Rust toolchain {channel}"),
+                format!("the rust-src, rustc-dev and llvm-tools components of {channel}"),
+            ])?;
+            let message = format!("Rust {channel} with `rustup`");
+            Self::get_consent_for_toolchain_install(
+                is_toolchain_install_consent,
+                format!("Install {message}").as_ref(),
+            )?;
             crate::user_output!("Installing {message}\n");
 
-            let output_toolchain_add = std::process::Command::new("rustup")
+            let mut toolchain_add = std::process::Command::new("rustup");
+            toolchain_add
                 .args(["toolchain", "add"])
-                .arg(&self.channel)
-                .stdout(std::process::Stdio::inherit())
-                .stderr(std::process::Stdio::inherit())
-                .output()?;
+                .arg(channel)
+                .stdout(crate::output::child_stdout())
+                .stderr(std::process::Stdio::inherit());
+            let output_toolchain_add = crate::timings::time("install toolchain", channel, || {
+                crate::log_file::output(&mut toolchain_add, false)
+            })?;
             anyhow::ensure!(
                 output_toolchain_add.status.success(),
                 "could not install required toolchain"
             );
+            crate::output::emit(&crate::output::Event::ToolchainAdded {
+                channel: channel.to_owned(),
+            });
         }
 
         // Check for the required components
-        let output_component_list = std::process::Command::new("rustup")
-            .args(["component", "list", "--toolchain"])
-            .arg(&self.channel)
-            .output()?;
-        anyhow::ensure!(
-            output_component_list.status.success(),
-            "could not list installed components"
-        );
-        let string_component_list = String::from_utf8_lossy(&output_component_list.stdout);
-        let required_components = ["rust-src", "rustc-dev", "llvm-tools"];
-        let installed_components = string_component_list.lines().collect::<Vec<_>>();
-        let all_components_installed = required_components.iter().all(|component| {
-            installed_components.iter().any(|installed_component| {
-                let is_component = installed_component.starts_with(component);
-                let is_installed = installed_component.ends_with("(installed)");
-                is_component && is_installed
-            })
-        });
-        if all_components_installed {
+        if Self::missing_components(channel)?.is_empty() {
             log::debug!("all required components are installed");
         } else {
+            crate::offline::ensure_nothing_missing(&[format!(
+                "the rust-src, rustc-dev and llvm-tools components of {channel}"
+            )])?;
             let message = "toolchain components (rust-src, rustc-dev, llvm-tools) with `rustup`";
-            self.get_consent_for_toolchain_install(format!("Install {message}").as_ref())?;
+            Self::get_consent_for_toolchain_install(
+                is_toolchain_install_consent,
+                format!("Install {message}").as_ref(),
+            )?;
             crate::user_output!("Installing {message}\n");
 
-            let output_component_add = std::process::Command::new("rustup")
+            let mut component_add = std::process::Command::new("rustup");
+            component_add
                 .args(["component", "add", "--toolchain"])
-                .arg(&self.channel)
-                .args(["rust-src", "rustc-dev", "llvm-tools"])
-                .stdout(std::process::Stdio::inherit())
-                .stderr(std::process::Stdio::inherit())
-                .output()?;
+                .arg(channel)
+                .args(REQUIRED_COMPONENTS)
+                .stdout(crate::output::child_stdout())
+                .stderr(std::process::Stdio::inherit());
+            let output_component_add =
+                crate::timings::time("install toolchain components", channel, || {
+                    crate::log_file::output(&mut component_add, false)
+                })?;
             anyhow::ensure!(
                 output_component_add.status.success(),
                 "could not install required components"
             );
+            crate::output::emit(&crate::output::Event::ComponentsAdded {
+                channel: channel.to_owned(),
+                components: REQUIRED_COMPONENTS.map(ToOwned::to_owned).to_vec(),
+            });
         }
 
         Ok(())
     }
 
+    /// Whether `rustup` has the toolchain for `channel` installed.
+    ///
+    /// # Errors
+    /// If `rustup` can't list its toolchains.
+    pub fn is_toolchain_installed(channel: &str) -> anyhow::Result<bool> {
+        let output_toolchain_list = std::process::Command::new("rustup")
+            .args(["toolchain", "list"])
+            .output()?;
+        anyhow::ensure!(
+            output_toolchain_list.status.success(),
+            "could not list installed toolchains"
+        );
+        let string_toolchain_list = String::from_utf8_lossy(&output_toolchain_list.stdout);
+        Ok(string_toolchain_list
+            .split_whitespace()
+            .any(|toolchain| toolchain.starts_with(channel)))
+    }
+
+    /// The components that `rust-gpu` needs which aren't installed for the toolchain of
+    /// `channel`.
+    ///
+    /// # Errors
+    /// If `rustup` can't list the toolchain's components.
+    pub fn missing_components(channel: &str) -> anyhow::Result<Vec<&'static str>> {
+        let output_component_list = std::process::Command::new("rustup")
+            .args(["component", "list", "--toolchain"])
+            .arg(channel)
+            .output()?;
+        anyhow::ensure!(
+            output_component_list.status.success(),
+            "could not list installed components"
+        );
+        let string_component_list = String::from_utf8_lossy(&output_component_list.stdout);
+        let installed_components = string_component_list.lines().collect::<Vec<_>>();
+        Ok(REQUIRED_COMPONENTS
+            .into_iter()
+            .filter(|component| {
+                !installed_components.iter().any(|installed_component| {
+                    let is_component = installed_component.starts_with(component);
+                    let is_installed = installed_component.ends_with("(installed)");
+                    is_component && is_installed
+                })
+            })
+            .collect())
+    }
+
     /// Prompt user if they want to install a new Rust toolchain.
-    fn get_consent_for_toolchain_install(&self, prompt: &str) -> anyhow::Result<()> {
-        if self.is_toolchain_install_consent {
+    fn get_consent_for_toolchain_install(
+        is_toolchain_install_consent: bool,
+        prompt: &str,
+    ) -> anyhow::Result<()> {
+        use std::io::IsTerminal as _;
+
+        if is_toolchain_install_consent {
             return Ok(());
         }
+        let consent = unattended_consent(
+            std::env::var_os("CARGO_GPU_YES").as_deref(),
+            std::env::var_os("CI").as_deref(),
+            std::io::stdin().is_terminal(),
+        );
+        match consent {
+            Ok(true) => {
+                log::debug!("`CARGO_GPU_YES` is set, so not asking for consent");
+                return Ok(());
+            }
+            Ok(false) => (),
+            Err(reason) => anyhow::bail!(
+                "{prompt}? Can't ask, as {reason}. Pass `--auto-install-rust-toolchain`, or set \
+                 `CARGO_GPU_YES=1`, to install it without asking"
+            ),
+        }
         log::debug!("asking for consent to install the required toolchain");
         crossterm::terminal::enable_raw_mode()?;
         crate::user_output!("{prompt} [y/n]: ");
@@ -180,32 +241,216 @@ impl SpirvCli {
         {
             Ok(())
         } else {
-            crate::user_output!("Exiting...\n");
-            std::process::exit(0);
+            anyhow::bail!("declined to install the toolchain that `rust-gpu` needs")
+        }
+    }
+}
+
+/// Whether an environment variable is set to something other than empty, `0` or `false`.
+fn is_truthy(value: Option<&std::ffi::OsStr>) -> bool {
+    value.is_some_and(|set| !(set.is_empty() || set == "0" || set.eq_ignore_ascii_case("false")))
+}
+
+/// Whether consent to install a toolchain has already been given with `CARGO_GPU_YES`, or
+/// otherwise why the user can't be asked for it, so that CI jobs fail rather than wait forever
+/// for an answer.
+fn unattended_consent(
+    yes: Option<&std::ffi::OsStr>,
+    ci: Option<&std::ffi::OsStr>,
+    is_stdin_terminal: bool,
+) -> Result<bool, &'static str> {
+    if is_truthy(yes) {
+        Ok(true)
+    } else if is_truthy(ci) {
+        Err("`CI` is set")
+    } else if !is_stdin_terminal {
+        Err("stdin isn't a terminal")
+    } else {
+        Ok(false)
+    }
+}
+
+/// Check that a toolchain that wasn't installed by `rustup` can build `rust-gpu`.
+///
+/// That's one from Nix or a distro package, say. It has to be a nightly with the components that
+/// `rust-gpu` needs. `path` is the toolchain's root, which has `bin/rustc` in it.
+///
+/// # Errors
+/// If the toolchain's `rustc` can't be run, or it isn't a nightly or is missing components.
+pub fn validate_toolchain_path(path: &std::path::Path) -> anyhow::Result<()> {
+    let rustc = toolchain_bin(path, "rustc");
+    let cargo = toolchain_bin(path, "cargo");
+    anyhow::ensure!(
+        rustc.is_file() && cargo.is_file(),
+        "'{}' isn't a Rust toolchain, it needs `bin/rustc` and `bin/cargo`",
+        path.display()
+    );
+    let output = std::process::Command::new(&rustc)
+        .arg("-vV")
+        .output()
+        .with_context(|| format!("could not run '{}'", rustc.display()))?;
+    anyhow::ensure!(
+        output.status.success(),
+        "'{} -vV' failed:\n{}",
+        rustc.display(),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let version = String::from_utf8_lossy(&output.stdout);
+    let missing = missing_toolchain_parts(path, &version);
+    anyhow::ensure!(
+        missing.is_empty(),
+        "the toolchain at '{}' can't build `rust-gpu`, it's missing {}",
+        path.display(),
+        missing.join(", ")
+    );
+    log::debug!("using the toolchain at '{}'", path.display());
+    Ok(())
+}
+
+/// What `rust-gpu` needs that the toolchain at `path` doesn't have, given the output of its
+/// `rustc -vV`.
+fn missing_toolchain_parts(path: &std::path::Path, version: &str) -> Vec<&'static str> {
+    let mut missing = Vec::new();
+    let is_nightly = version
+        .lines()
+        .next()
+        .is_some_and(|line| line.contains("nightly") || line.contains("-dev"));
+    if !is_nightly {
+        missing.push("a nightly `rustc`");
+    }
+    let host = version
+        .lines()
+        .find_map(|line| line.strip_prefix("host: "))
+        .unwrap_or_default();
+    let rustlib = path.join("lib").join("rustlib");
+    if !rustlib.join("src").join("rust").join("library").is_dir() {
+        missing.push("rust-src");
+    }
+    let has_file_starting_with = |dir: std::path::PathBuf, prefix: &str| {
+        std::fs::read_dir(dir).is_ok_and(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .any(|entry| entry.file_name().to_string_lossy().starts_with(prefix))
+        })
+    };
+    if !has_file_starting_with(rustlib.join(host).join("lib"), "librustc_middle-") {
+        missing.push("rustc-dev");
+    }
+    if !has_file_starting_with(rustlib.join(host).join("bin"), "llvm-") {
+        missing.push("llvm-tools");
+    }
+    missing
+}
+
+/// The path of one of the programs in a toolchain, eg `cargo`.
+fn toolchain_bin(path: &std::path::Path, name: &str) -> std::path::PathBuf {
+    path.join("bin")
+        .join(format!("{name}{}", std::env::consts::EXE_SUFFIX))
+}
+
+/// A `cargo` command for a toolchain: `cargo +channel` with `rustup`, or else the `cargo` of the
+/// toolchain at `toolchain_path`.
+#[must_use]
+pub fn cargo_command(
+    channel: &str,
+    toolchain_path: Option<&std::path::Path>,
+) -> std::process::Command {
+    toolchain_path.map_or_else(
+        || {
+            let mut command = std::process::Command::new("cargo");
+            command.arg(format!("+{channel}"));
+            command
+        },
+        |path| {
+            let mut command = std::process::Command::new(toolchain_bin(path, "cargo"));
+            configure_toolchain_env(&mut command, Some(path));
+            command
+        },
+    )
+}
+
+/// Make a command, and anything it runs, use the toolchain at `toolchain_path`, if there is one,
+/// rather than `rustup`'s.
+pub fn configure_toolchain_env(
+    command: &mut std::process::Command,
+    toolchain_path: Option<&std::path::Path>,
+) {
+    let Some(path) = toolchain_path else {
+        return;
+    };
+    let bin = path.join("bin");
+    let paths = std::env::var_os("PATH").unwrap_or_default();
+    match std::env::join_paths(core::iter::once(bin).chain(std::env::split_paths(&paths))) {
+        Ok(joined) => {
+            command.env("PATH", joined);
         }
+        Err(error) => log::warn!("couldn't add the toolchain to `PATH`: {error}"),
     }
+    command.env("RUSTC", toolchain_bin(path, "rustc"));
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
 
+    #[test_log::test]
+    fn validating_a_toolchain_without_rustup() {
+        let toolchain = crate::cache_dir().unwrap().join("validating_a_toolchain");
+        if toolchain.exists() {
+            std::fs::remove_dir_all(&toolchain).unwrap();
+        }
+        let rustlib = toolchain.join("lib").join("rustlib");
+        let host = rustlib.join("x86_64-unknown-linux-gnu");
+        std::fs::create_dir_all(rustlib.join("src").join("rust").join("library")).unwrap();
+        std::fs::create_dir_all(host.join("lib")).unwrap();
+        std::fs::create_dir_all(host.join("bin")).unwrap();
+        let version = "rustc 1.80.0-nightly (ab14f944a 2024-04-23)\n\
+                       host: x86_64-unknown-linux-gnu\n";
+        assert_eq!(
+            missing_toolchain_parts(&toolchain, version),
+            ["rustc-dev", "llvm-tools"]
+        );
+
+        std::fs::write(host.join("lib").join("librustc_middle-1234.rlib"), "").unwrap();
+        std::fs::write(host.join("bin").join("llvm-objcopy"), "").unwrap();
+        assert!(missing_toolchain_parts(&toolchain, version).is_empty());
+        assert_eq!(
+            missing_toolchain_parts(&toolchain, "rustc 1.80.0 (051478957 2024-07-21)\n"),
+            ["a nightly `rustc`", "rustc-dev", "llvm-tools"]
+        );
+
+        validate_toolchain_path(&toolchain).unwrap_err();
+    }
+
     #[test_log::test]
     fn cached_checkout_dir_sanity() {
         let shader_template_path = crate::test::shader_crate_template_path();
         // TODO: This downloads the `rust-gpu` repo which slows the test down. Can we avoid that
         // just to get the sanity check?
-        let spirv = SpirvCli::new(&shader_template_path, None, None, None, true).unwrap();
+        let spirv = SpirvCli::new(&shader_template_path, None, None, None).unwrap();
         let dir = spirv.cached_checkout_path().unwrap();
         let name = dir
             .file_name()
             .unwrap()
             .to_str()
-            .map(std::string::ToString::to_string)
+            .map(str::to_owned)
             .unwrap();
         assert_eq!(
             "https___github_com_Rust-GPU_rust-gpu+82a0f69+nightly-2024-04-24",
             &name
         );
     }
+
+    #[test_log::test]
+    fn consenting_without_a_prompt() {
+        let set = Some(std::ffi::OsStr::new("1"));
+        let unset = Some(std::ffi::OsStr::new("false"));
+        assert_eq!(unattended_consent(set, set, false), Ok(true));
+        assert_eq!(unattended_consent(unset, set, true), Err("`CI` is set"));
+        assert_eq!(
+            unattended_consent(None, unset, false),
+            Err("stdin isn't a terminal")
+        );
+        assert_eq!(unattended_consent(None, None, true), Ok(false));
+    }
 }
@@ -1,13 +1,22 @@
 //! Use the shader that we're compiling as the default source for which version of `rust-gpu` to use.
 //!
-//! We do this by calling `cargo tree` inside the shader's crate to get the defined `spirv-std`
-//! version. Then with that we `git checkout` the `rust-gpu` repo that corresponds to that version.
+//! We do this by reading the dependency graph from `cargo metadata` to find the `spirv-std` that the
+//! shader crate uses. Then with that we `git checkout` the `rust-gpu` repo that corresponds to that
+//! version, or for releases on crates.io, just fetch what we need from crates.io, see
+//! [`crate::crates_io`].
+//!
 //! From there we can look at the source code to get the required Rust toolchain.
 
 use anyhow::Context as _;
 
-/// The canonical `rust-gpu` URI
-const RUST_GPU_REPO: &str = "https://github.com/Rust-GPU/rust-gpu";
+/// The canonical `rust-gpu` URI.
+pub const RUST_GPU_REPO: &str = "https://github.com/Rust-GPU/rust-gpu";
+
+/// Where the target specs live in the `rust-gpu` repo, newest layout first.
+const TARGET_SPECS_PATHS: &[&str] = &[
+    "crates/rustc_codegen_spirv-target-specs/target-specs",
+    "crates/spirv-builder/target-specs",
+];
 
 /// The various sources that the `rust-gpu` repo can have.
 /// Most commonly it will simply be the canonical version on crates.io. But it could also be the
@@ -17,15 +26,15 @@ pub enum SpirvSource {
     /// If the shader specifies a simple version like `spirv-std = "0.9.0"` then the source of
     /// `rust-gpu` is the conventional crates.io version.
     ///
-    /// `String` is the simple version like, "0.9.0"
+    /// `String` is the simple version like, "0.9.0".
     CratesIO(String),
     /// If the shader specifies a version like:
     ///   `spirv-std = { git = "https://github.com..." ... }`
     /// then the source of `rust-gpu` is `Git`.
     Git {
-        /// URL of the repository
+        /// URL of the repository.
         url: String,
-        /// Revision or "commitsh"
+        /// Revision or "commitsh".
         rev: String,
     },
     /// If the shader specifies a version like:
@@ -55,10 +64,16 @@ impl SpirvSource {
     ) -> anyhow::Result<(Self, chrono::NaiveDate, String)> {
         let rust_gpu_source = Self::get_spirv_std_dep_definition(shader_crate_path)?;
 
-        rust_gpu_source.ensure_repo_is_installed()?;
-        rust_gpu_source.checkout()?;
-
-        let date = rust_gpu_source.get_version_date()?;
+        // Held while cloning, checking out and reading from the repo, so that concurrent
+        // invocations don't change the checkout from under each other.
+        let _lock = crate::lock(&rust_gpu_source.to_dirname()?)?;
+        let date = if let Self::CratesIO(version) = &rust_gpu_source {
+            crate::crates_io::fetch_release(version, &rust_gpu_source.to_dirname()?)?
+        } else {
+            rust_gpu_source.ensure_repo_is_installed()?;
+            rust_gpu_source.checkout()?;
+            rust_gpu_source.get_version_date()?
+        };
         let channel = Self::get_channel_from_toolchain_toml(&rust_gpu_source.to_dirname()?)?;
 
         log::debug!("Parsed version, date and toolchain channel from shader-defined `rust-gpu`: {rust_gpu_source:?}, {date}, {channel}");
@@ -67,15 +82,30 @@ impl SpirvSource {
     }
 
     /// Convert the source to just its version.
+    #[must_use]
     pub fn to_version(&self) -> String {
         match self {
-            Self::CratesIO(version) | Self::Path((_, version)) => version.to_string(),
-            Self::Git { rev, .. } => rev.to_string(),
+            Self::CratesIO(version) | Self::Path((_, version)) => version.clone(),
+            Self::Git { rev, .. } => rev.clone(),
+        }
+    }
+
+    /// The exact revision of `rust-gpu`: the full commit hash for Git sources, which may have
+    /// been a branch or a short hash, and otherwise just the version.
+    pub fn resolved_revision(&self) -> anyhow::Result<String> {
+        match self {
+            Self::Git { .. } => {
+                let repo = self.open_repo()?;
+                let id = self.find_commit(&repo)?.id();
+                Ok(id.to_string())
+            }
+            Self::CratesIO(_) | Self::Path(_) => Ok(self.to_version()),
         }
     }
 
     /// Convert the source to just its repo or path.
-    fn to_repo(&self) -> String {
+    #[must_use]
+    pub fn to_repo(&self) -> String {
         match self {
             Self::CratesIO(_) => RUST_GPU_REPO.to_owned(),
             Self::Git { url, .. } => url.to_owned(),
@@ -86,9 +116,19 @@ impl SpirvSource {
     /// Convert the `rust-gpu` source into a string that can be used as a directory.
     /// It needs to be dynamically created because an end-user might want to swap out the source,
     /// maybe using their own fork for example.
-    fn to_dirname(&self) -> anyhow::Result<std::path::PathBuf> {
+    pub fn to_dirname(&self) -> anyhow::Result<std::path::PathBuf> {
         let dir = crate::to_dirname(self.to_string().as_ref());
-        Ok(crate::cache_dir()?.join("rust-gpu-repo").join(dir))
+        Ok(crate::checkouts_dir()?.join(dir))
+    }
+
+    /// The directory of target specs in the checked-out `rust-gpu` repo. Returns `None` if the repo
+    /// hasn't been checked out, or if this version of `rust-gpu` doesn't ship its own specs.
+    pub fn target_specs_dir(&self) -> anyhow::Result<Option<std::path::PathBuf>> {
+        let checkout = self.to_dirname()?;
+        Ok(TARGET_SPECS_PATHS
+            .iter()
+            .map(|path| checkout.join(path))
+            .find(|path| path.is_dir()))
     }
 
     /// Checkout the `rust-gpu` repo to the requested version.
@@ -98,64 +138,80 @@ impl SpirvSource {
             self.to_dirname()?.display(),
             self.to_version()
         );
-        let output_checkout = std::process::Command::new("git")
-            .current_dir(self.to_dirname()?)
-            .args(["checkout", self.to_version().as_ref()])
-            .output()?;
-        anyhow::ensure!(
-            output_checkout.status.success(),
-            "couldn't checkout revision '{}' of `rust-gpu` at {}",
-            self.to_version(),
-            self.to_dirname()?.to_string_lossy()
-        );
+        crate::timings::time("checkout rust-gpu", self.to_version(), || {
+            let repo = self.open_repo()?;
+            let commit = self.find_commit(&repo)?;
+            repo.checkout_tree(
+                commit.as_object(),
+                Some(git2::build::CheckoutBuilder::new().force()),
+            )?;
+            repo.set_head_detached(commit.id())?;
+            anyhow::Ok(())
+        })
+        .with_context(|| {
+            format!(
+                "couldn't checkout revision '{}' of `rust-gpu` at {}",
+                self.to_version(),
+                self.to_dirname().unwrap_or_default().to_string_lossy()
+            )
+        })
+    }
 
-        Ok(())
+    /// Open the cloned `rust-gpu` repo.
+    fn open_repo(&self) -> anyhow::Result<git2::Repository> {
+        let dir = self.to_dirname()?;
+        git2::Repository::open(&dir)
+            .with_context(|| format!("couldn't open `rust-gpu` repo at {}", dir.display()))
+    }
+
+    /// Find the commit of the requested version in the cloned repo. Like `git checkout`, a
+    /// version that's the name of a branch on the remote is also found.
+    fn find_commit<'repo>(
+        &self,
+        repo: &'repo git2::Repository,
+    ) -> anyhow::Result<git2::Commit<'repo>> {
+        let version = self.to_version();
+        let object = repo
+            .revparse_single(&version)
+            .or_else(|_| repo.revparse_single(&format!("origin/{version}")))
+            .with_context(|| format!("couldn't find revision '{version}' of `rust-gpu`"))?;
+        Ok(object.peel_to_commit()?)
     }
 
     /// Get the date of the version of `rust-gpu` used by the shader. This allows us to know what
     /// features we can use in the `spirv-builder` crate.
     fn get_version_date(&self) -> anyhow::Result<chrono::NaiveDate> {
-        let date_format = "%Y-%m-%d";
-
         log::debug!(
             "Getting `rust-gpu` version date from {}",
             self.to_dirname()?.display(),
         );
-        let output_date = std::process::Command::new("git")
-            .current_dir(self.to_dirname()?)
-            .args([
-                "show",
-                "--no-patch",
-                "--format=%cd",
-                format!("--date=format:'{date_format}'").as_ref(),
-                self.to_version().as_ref(),
-            ])
-            .output()?;
-        anyhow::ensure!(
-            output_date.status.success(),
-            "couldn't get `rust-gpu` version date at for {} at {}",
-            self.to_version(),
-            self.to_dirname()?.to_string_lossy()
-        );
-        let date_string = String::from_utf8_lossy(&output_date.stdout)
-            .to_string()
-            .trim()
-            .replace('\'', "");
-
-        log::debug!(
-            "Parsed date for version {}: {date_string}",
-            self.to_version()
-        );
-
-        Ok(chrono::NaiveDate::parse_from_str(
-            &date_string,
-            date_format,
-        )?)
+        let repo = self.open_repo()?;
+        let commit = self.find_commit(&repo)?;
+        // The date in the committer's own timezone, like `git show` gives.
+        let time = commit.committer().when();
+        let local_seconds = time
+            .seconds()
+            .saturating_add(i64::from(time.offset_minutes()).saturating_mul(60));
+        let date = chrono::DateTime::from_timestamp(local_seconds, 0)
+            .with_context(|| {
+                format!(
+                    "`rust-gpu` version {} has an invalid date",
+                    self.to_version()
+                )
+            })?
+            .date_naive();
+
+        log::debug!("Parsed date for version {}: {date}", self.to_version());
+
+        Ok(date)
     }
 
     /// Parse the `rust-toolchain.toml` in the working tree of the checked-out version of the `rust-gpu` repo.
     fn get_channel_from_toolchain_toml(path: &std::path::PathBuf) -> anyhow::Result<String> {
-        log::debug!("Parsing `rust-toolchain.toml` at {path:?} for the used toolchain");
+        log::debug!(
+            "Parsing `rust-toolchain.toml` at {} for the used toolchain",
+            path.display()
+        );
 
         let contents = std::fs::read_to_string(path.join("rust-toolchain.toml"))?;
         let toml: toml::Table = toml::from_str(&contents)?;
@@ -171,7 +227,7 @@ impl SpirvSource {
         Ok(channel.to_string().replace('"', ""))
     }
 
-    /// Get the shader crate's `spirv_std = ...` definition in its `Cargo.toml`
+    /// Get the shader crate's `spirv_std = ...` definition in its `Cargo.toml`.
     pub fn get_spirv_std_dep_definition(
         shader_crate_path: &std::path::PathBuf,
     ) -> anyhow::Result<Self> {
@@ -182,81 +238,165 @@ impl SpirvSource {
             cwd.join(shader_crate_path)
         }
         .canonicalize()
-        .context("could not get absolute path to shader crate")?;
+        .context("could not get absolute path to shader crate")
+        .map_err(crate::exit::Failure::ShaderCrateNotFound)?;
         if !exec_path.is_dir() {
-            log::error!("{exec_path:?} is not a directory, aborting");
-            anyhow::bail!("{exec_path:?} is not a directory");
+            log::error!("{} is not a directory, aborting", exec_path.display());
+            return Err(crate::exit::Failure::ShaderCrateNotFound(anyhow::anyhow!(
+                "{exec_path:?} is not a directory"
+            ))
+            .into());
         }
 
-        log::debug!("Running `cargo tree` on {}", exec_path.display());
-        let output_cargo_tree = std::process::Command::new("cargo")
-            .current_dir(&exec_path)
-            .args(["tree", "--workspace", "--prefix", "none"])
+        let cargo_toml_path = exec_path.join("Cargo.toml");
+        log::debug!("Running `cargo metadata` on {}", cargo_toml_path.display());
+        let output_cargo_metadata = std::process::Command::new("cargo")
+            .args(["metadata", "--format-version", "1", "--manifest-path"])
+            .arg(&cargo_toml_path)
+            .args(crate::offline::cargo_args())
             .output()?;
-        anyhow::ensure!(
-            output_cargo_tree.status.success(),
-            "could not query shader's `Cargo.toml` for `spirv-std` dependency"
-        );
-        let cargo_tree_string = String::from_utf8_lossy(&output_cargo_tree.stdout);
+        if !output_cargo_metadata.status.success() {
+            let stderr = String::from_utf8_lossy(&output_cargo_metadata.stderr);
+            let error = anyhow::anyhow!(
+                "could not query shader's `Cargo.toml` for `spirv-std` dependency:\n{stderr}"
+            );
+            return Err(if crate::exit::is_lockfile_conflict(&stderr) {
+                crate::exit::Failure::LockfileConflict(error).into()
+            } else {
+                error
+            });
+        }
+        let metadata: serde_json::Value = serde_json::from_slice(&output_cargo_metadata.stdout)?;
 
-        let maybe_spirv_std_def = cargo_tree_string
-            .lines()
-            .find(|line| line.contains("spirv-std"));
-        log::trace!("  found {maybe_spirv_std_def:?}");
+        let spirv_std = Self::find_spirv_std_package(&metadata, &cargo_toml_path)?;
+        Self::from_spirv_std_package(spirv_std)
+    }
 
-        let Some(spirv_std_def) = maybe_spirv_std_def else {
-            anyhow::bail!("`spirv-std` not found in shader's `Cargo.toml` at {exec_path:?}:\n{cargo_tree_string}");
+    /// Find the `spirv-std` package that the shader crate uses in the output of `cargo metadata`.
+    ///
+    /// The dependency graph is searched breadth first from the shader crate, so the `spirv-std`
+    /// closest to it is found even if there are many versions in the workspace, and even if the
+    /// dependency has been renamed.
+    fn find_spirv_std_package<'json>(
+        metadata: &'json serde_json::Value,
+        cargo_toml_path: &std::path::Path,
+    ) -> anyhow::Result<&'json serde_json::Value> {
+        let packages = metadata
+            .pointer("/packages")
+            .and_then(serde_json::Value::as_array)
+            .context("`cargo metadata` has no packages")?;
+        let package_by_id = |id: &str| {
+            packages.iter().find(|package| {
+                package.pointer("/id").and_then(serde_json::Value::as_str) == Some(id)
+            })
         };
+        let shader_id = packages
+            .iter()
+            .find(|package| {
+                package
+                    .pointer("/manifest_path")
+                    .and_then(serde_json::Value::as_str)
+                    .is_some_and(|manifest_path| {
+                        std::path::Path::new(manifest_path) == cargo_toml_path
+                    })
+            })
+            .and_then(|package| package.pointer("/id")?.as_str())
+            .with_context(|| {
+                format!(
+                    "shader crate not found in `cargo metadata` of '{}'",
+                    cargo_toml_path.display()
+                )
+            })?;
+        let nodes = metadata
+            .pointer("/resolve/nodes")
+            .and_then(serde_json::Value::as_array)
+            .context("`cargo metadata` has no dependency graph")?;
+
+        let mut queue = std::collections::VecDeque::from([shader_id]);
+        let mut seen = std::collections::HashSet::from([shader_id]);
+        while let Some(id) = queue.pop_front() {
+            let Some(package) = package_by_id(id) else {
+                continue;
+            };
+            if id != shader_id
+                && package.pointer("/name").and_then(serde_json::Value::as_str) == Some("spirv-std")
+            {
+                log::trace!("  found {id}");
+                return Ok(package);
+            }
+            let dependencies = nodes
+                .iter()
+                .find(|node| node.pointer("/id").and_then(serde_json::Value::as_str) == Some(id))
+                .and_then(|node| node.pointer("/dependencies")?.as_array());
+            for dependency in dependencies.into_iter().flatten() {
+                if let Some(dependency_id) = dependency.as_str() {
+                    if seen.insert(dependency_id) {
+                        queue.push_back(dependency_id);
+                    }
+                }
+            }
+        }
 
-        Self::parse_spirv_std_source_and_version(spirv_std_def)
+        anyhow::bail!(
+            "`spirv-std` not found in the dependencies of the shader crate at '{}'",
+            cargo_toml_path.display()
+        )
     }
 
-    /// Parse a string like:
-    ///   `spirv-std v0.9.0 (https://github.com/Rust-GPU/rust-gpu?rev=54f6978c#54f6978c) (*)`
+    /// The source of a `spirv-std` package from `cargo metadata`, whose `source` is like:
+    ///   `git+https://github.com/Rust-GPU/rust-gpu?rev=54f6978c#54f6978c...`
     /// Which would return:
-    ///   `SpirvSource::Git("https://github.com/Rust-GPU/rust-gpu", "54f6978c")`
-    fn parse_spirv_std_source_and_version(spirv_std_def: &str) -> anyhow::Result<Self> {
-        log::trace!("parsing spirv-std source and version from def: '{spirv_std_def}'");
-        let parts: Vec<String> = spirv_std_def.split_whitespace().map(String::from).collect();
-        let version = parts
-            .get(1)
+    ///   `SpirvSource::Git("https://github.com/Rust-GPU/rust-gpu", "54f6978c")`.
+    fn from_spirv_std_package(package: &serde_json::Value) -> anyhow::Result<Self> {
+        log::trace!("parsing spirv-std source and version from package: {package}");
+        let version = package
+            .pointer("/version")
+            .and_then(serde_json::Value::as_str)
             .context("Couldn't find `spirv_std` version in shader crate")?
             .to_owned();
-        let mut source = Self::CratesIO(version.clone());
-
-        if parts.len() > 2 {
-            let mut source_string = parts
-                .get(2)
-                .context("Couldn't get Uri from dependency string")?
-                .to_owned();
-            source_string = source_string.replace(['(', ')'], "");
-
-            // Unfortunately Uri ignores the fragment/hash portion of the Uri.
-            //
-            // There's been a ticket open for years:
-            // <https://github.com/hyperium/http/issues/127>
-            //
-            // So here we'll parse the fragment out of the source string by hand
-            let uri = source_string.parse::<http::Uri>()?;
-            let maybe_hash = if source_string.contains('#') {
-                let splits = source_string.split('#');
-                splits.last().map(std::borrow::ToOwned::to_owned)
-            } else {
-                None
-            };
-            if uri.scheme().is_some() {
-                source = Self::parse_git_source(version, &uri, maybe_hash)?;
-            } else {
-                source = Self::Path((source_string, version));
+        let maybe_source = package
+            .pointer("/source")
+            .and_then(serde_json::Value::as_str);
+
+        let source = match maybe_source {
+            Some(registry)
+                if registry.starts_with("registry+") || registry.starts_with("sparse+") =>
+            {
+                Self::CratesIO(version)
             }
-        }
+            Some(git) => {
+                let source_string = git.strip_prefix("git+").unwrap_or(git);
+                // Unfortunately Uri ignores the fragment/hash portion of the Uri.
+                //
+                // There's been a ticket open for years:
+                // <https://github.com/hyperium/http/issues/127>
+                //
+                // So here we'll parse the fragment out of the source string by hand
+                let uri = source_string.parse::<http::Uri>()?;
+                let maybe_hash = source_string
+                    .split_once('#')
+                    .map(|(_, hash)| hash.to_owned());
+                Self::parse_git_source(version, &uri, maybe_hash)?
+            }
+            // Path dependencies don't have a source.
+            None => {
+                let manifest_path = package
+                    .pointer("/manifest_path")
+                    .and_then(serde_json::Value::as_str)
+                    .context("Couldn't find the path of `spirv-std`")?;
+                let path = std::path::Path::new(manifest_path)
+                    .parent()
+                    .context("`spirv-std`'s `Cargo.toml` has no directory")?;
+                Self::Path((path.display().to_string(), version))
+            }
+        };
 
         log::debug!("Parsed `rust-gpu` source and version: {source:?}");
 
         Ok(source)
     }
 
-    /// Parse a Git source like: `https://github.com/Rust-GPU/rust-gpu?rev=54f6978c#54f6978c`
+    /// Parse a Git source like: `https://github.com/Rust-GPU/rust-gpu?rev=54f6978c#54f6978c`.
     fn parse_git_source(
         version: String,
         uri: &http::Uri,
@@ -302,44 +442,200 @@ impl SpirvSource {
         version
     }
 
-    /// `git clone` the `rust-gpu` repo. We use it to get the required Rust toolchain to compile
+    /// Check out the `rust-gpu` repo. We use it to get the required Rust toolchain to compile
     /// the shader.
+    ///
+    /// Every checkout of the same repo is a worktree of one bare clone of it, so that testing
+    /// several versions doesn't keep several near-identical clones. Only the requested version
+    /// is fetched if possible, see [`SpirvSource::shallow_fetch`].
     fn ensure_repo_is_installed(&self) -> anyhow::Result<()> {
-        if self.to_dirname()?.exists() {
+        let dir = self.to_dirname()?;
+        if dir.exists() {
+            if git2::Repository::open(&dir).is_ok() {
+                log::debug!(
+                    "Not checking out `rust-gpu` repo ({}) as it already exists at {}",
+                    self.to_repo(),
+                    dir.display(),
+                );
+                return Ok(());
+            }
+            // Eg a worktree unpacked from an archive made on another machine, whose link to its
+            // clone is for that machine's cache dir.
             log::debug!(
-                "Not cloning `rust-gpu` repo ({}) as it already exists at {}",
-                self.to_repo(),
-                self.to_dirname()?.to_string_lossy().as_ref(),
+                "re-adding the broken `rust-gpu` checkout at {}",
+                dir.display()
+            );
+            std::fs::remove_dir_all(&dir)?;
+        }
+
+        let bare_dir = self.bare_repo_dir()?;
+        // Held while fetching into the clone and adding the worktree, as checkouts of other
+        // versions share the clone.
+        let _lock = crate::lock(&bare_dir)?;
+        let repo = Self::open_or_init_bare_repo(&bare_dir)?;
+        if self.find_commit(&repo).is_ok()
+            && (Self::is_commit_hash(&self.to_version()) || crate::offline::is_enabled())
+        {
+            log::debug!(
+                "`rust-gpu` {} has already been fetched into {}",
+                self.to_version(),
+                bare_dir.display()
             );
-            return Ok(());
+        } else {
+            self.fetch(&repo)?;
         }
 
         log::debug!(
-            "Cloning `rust-gpu` repo {} to {}",
-            self.to_repo(),
-            self.to_dirname()?.to_string_lossy().as_ref(),
+            "Adding a worktree of `rust-gpu` repo {} at {}",
+            bare_dir.display(),
+            dir.display(),
         );
+        self.add_worktree(&repo, &dir).or_else(|error| {
+            if dir.exists() {
+                std::fs::remove_dir_all(&dir)?;
+            }
+            Err(error)
+        })
+    }
 
-        crate::user_output!("Cloning `rust-gpu` repo...\n");
+    /// The bare clone that the checkout at `checkout` is a worktree of, if it is one.
+    #[must_use]
+    pub fn shared_clone_of(checkout: &std::path::Path) -> Option<std::path::PathBuf> {
+        let repo = git2::Repository::open(checkout).ok()?;
+        repo.is_worktree().then(|| repo.commondir().to_path_buf())
+    }
 
-        let output_clone = std::process::Command::new("git")
-            .args([
-                "clone",
-                self.to_repo().as_ref(),
-                self.to_dirname()?.to_string_lossy().as_ref(),
-            ])
-            .output()?;
+    /// The bare clone of the source's repo, that every checkout of it is a worktree of.
+    fn bare_repo_dir(&self) -> anyhow::Result<std::path::PathBuf> {
+        Ok(crate::repos_dir()?.join(crate::to_dirname(&self.to_repo())))
+    }
 
-        anyhow::ensure!(
-            output_clone.status.success(),
-            "couldn't clone `rust-gpu` {} to {}\n{}",
-            self.to_repo(),
-            self.to_dirname()?.to_string_lossy(),
-            String::from_utf8_lossy(&output_clone.stderr)
-        );
+    /// Open the bare clone at `dir`, or start a new one.
+    fn open_or_init_bare_repo(dir: &std::path::Path) -> anyhow::Result<git2::Repository> {
+        git2::Repository::open_bare(dir)
+            .or_else(|_| git2::Repository::init_bare(dir))
+            .with_context(|| format!("couldn't open `rust-gpu` clone at {}", dir.display()))
+    }
+
+    /// Whether `version` is a full commit hash, which always refers to the same commit.
+    fn is_commit_hash(version: &str) -> bool {
+        version.len() == 40 && version.chars().all(|char| char.is_ascii_hexdigit())
+    }
 
+    /// Fetch the requested version into the bare clone, only the version's commit if possible.
+    fn fetch(&self, repo: &git2::Repository) -> anyhow::Result<()> {
+        // A path source is fetched from the local filesystem, so it's fine offline.
+        if !matches!(self, Self::Path(_)) {
+            crate::offline::ensure_nothing_missing(&[format!(
+                "the `rust-gpu` repo {} at {}",
+                self.to_repo(),
+                self.to_version()
+            )])?;
+        }
+
+        crate::user_output!("Cloning `rust-gpu` repo...\n");
+        crate::timings::time("clone rust-gpu", self.to_repo(), || {
+            self.shallow_fetch(repo).or_else(|error| {
+                log::debug!("shallow fetch of `rust-gpu` failed, fetching everything: {error:?}");
+                let mut fetch_options = Self::fetch_options();
+                if repo.is_shallow() {
+                    // Earlier shallow fetches would otherwise leave gaps in the history.
+                    fetch_options.depth(i32::MAX);
+                }
+                repo.remote_anonymous(&self.to_repo())?.fetch(
+                    &[
+                        "+refs/heads/*:refs/remotes/origin/*",
+                        "+refs/tags/*:refs/tags/*",
+                    ],
+                    Some(&mut fetch_options),
+                    None,
+                )?;
+                anyhow::Ok(())
+            })
+        })
+        .with_context(|| {
+            format!(
+                "couldn't clone `rust-gpu` {} to {}",
+                self.to_repo(),
+                repo.path().display()
+            )
+        })
+    }
+
+    /// Fetch only the commit of the requested version, without any of its history, which is
+    /// much quicker than a full clone. It only works when the version is a full commit hash, a
+    /// branch or a tag, and only for remotes that support shallow fetches.
+    fn shallow_fetch(&self, repo: &git2::Repository) -> anyhow::Result<()> {
+        let mut remote = repo.remote_anonymous(&self.to_repo())?;
+        let version = self.to_version();
+        // Stored as if it were a remote branch, so that `find_commit()` finds branches and tags.
+        let refspec = format!("+{version}:refs/remotes/origin/{version}");
+        remote.fetch(
+            &[refspec.as_str()],
+            Some(Self::fetch_options().depth(1)),
+            None,
+        )?;
+        Ok(())
+    }
+
+    /// Add a worktree of the bare clone `repo` at `dir`, on a branch of the same name as `dir`
+    /// that points at the requested version.
+    fn add_worktree(&self, repo: &git2::Repository, dir: &std::path::Path) -> anyhow::Result<()> {
+        let name = dir
+            .file_name()
+            .context("checkout directory has no name")?
+            .to_string_lossy()
+            .into_owned();
+        // The worktree's directory may have been removed, eg by `cargo gpu clean`.
+        if let Ok(stale) = repo.find_worktree(&name) {
+            if stale.validate().is_err() {
+                log::debug!("pruning the stale `rust-gpu` worktree `{name}`");
+                stale.prune(None)?;
+            }
+        }
+        if let Some(parent) = dir.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let commit = self.find_commit(repo)?;
+        let branch = repo.reference(
+            &format!("refs/heads/{name}"),
+            commit.id(),
+            true,
+            "cargo gpu checkout",
+        )?;
+        repo.worktree(
+            &name,
+            dir,
+            Some(git2::WorktreeAddOptions::new().reference(Some(&branch))),
+        )
+        .with_context(|| format!("couldn't add a `rust-gpu` worktree at {}", dir.display()))?;
         Ok(())
     }
+
+    /// Options for fetching `rust-gpu`, which log the progress of the transfer.
+    fn fetch_options() -> git2::FetchOptions<'static> {
+        let mut callbacks = git2::RemoteCallbacks::new();
+        let mut reported_percent: usize = 0;
+        callbacks.transfer_progress(move |progress| {
+            let percent = progress
+                .received_objects()
+                .saturating_mul(100)
+                .checked_div(progress.total_objects())
+                .unwrap_or(0);
+            if percent >= reported_percent.saturating_add(10) {
+                reported_percent = percent;
+                log::info!(
+                    "received {}/{} `rust-gpu` objects",
+                    progress.received_objects(),
+                    progress.total_objects()
+                );
+            }
+            true
+        });
+        let mut fetch_options = git2::FetchOptions::new();
+        fetch_options.remote_callbacks(callbacks);
+        fetch_options
+    }
 }
 
 #[cfg(test)]
@@ -361,9 +657,12 @@ mod test {
 
     #[test_log::test]
     fn parsing_spirv_std_dep_for_git_source() {
-        let definition =
-            "spirv-std v9.9.9 (https://github.com/Rust-GPU/rust-gpu?rev=82a0f69#82a0f69) (*)";
-        let source = SpirvSource::parse_spirv_std_source_and_version(definition).unwrap();
+        let package = serde_json::json!({
+            "name": "spirv-std",
+            "version": "9.9.9",
+            "source": "git+https://github.com/Rust-GPU/rust-gpu?rev=82a0f69#82a0f69b7c5d8e9f0a1b2c3d4e5f6a7b8c9d0e1f",
+        });
+        let source = SpirvSource::from_spirv_std_package(&package).unwrap();
         assert_eq!(
             source,
             SpirvSource::Git {
@@ -375,8 +674,12 @@ mod test {
 
     #[test_log::test]
     fn parsing_spirv_std_dep_for_git_source_hash() {
-        let definition = "spirv-std v9.9.9 (https://github.com/Rust-GPU/rust-gpu#82a0f69) (*)";
-        let source = SpirvSource::parse_spirv_std_source_and_version(definition).unwrap();
+        let package = serde_json::json!({
+            "name": "spirv-std",
+            "version": "9.9.9",
+            "source": "git+https://github.com/Rust-GPU/rust-gpu#82a0f69",
+        });
+        let source = SpirvSource::from_spirv_std_package(&package).unwrap();
         assert_eq!(
             source,
             SpirvSource::Git {
@@ -386,6 +689,135 @@ mod test {
         );
     }
 
+    #[test_log::test]
+    fn finding_the_closest_renamed_spirv_std() {
+        let metadata = serde_json::json!({
+            "packages": [
+                {
+                    "id": "shader",
+                    "name": "shader",
+                    "version": "0.1.0",
+                    "manifest_path": "/shader/Cargo.toml",
+                    "source": null,
+                },
+                {
+                    "id": "helpers",
+                    "name": "helpers",
+                    "version": "0.1.0",
+                    "manifest_path": "/helpers/Cargo.toml",
+                    "source": null,
+                },
+                {
+                    "id": "old-spirv-std",
+                    "name": "spirv-std",
+                    "version": "0.8.0",
+                    "manifest_path": "/registry/spirv-std-0.8.0/Cargo.toml",
+                    "source": "registry+https://github.com/rust-lang/crates.io-index",
+                },
+                {
+                    "id": "new-spirv-std",
+                    "name": "spirv-std",
+                    "version": "0.9.0",
+                    "manifest_path": "/registry/spirv-std-0.9.0/Cargo.toml",
+                    "source": "registry+https://github.com/rust-lang/crates.io-index",
+                },
+            ],
+            "resolve": {
+                "nodes": [
+                    // `std = { package = "spirv-std", version = "0.9.0" }`
+                    { "id": "shader", "dependencies": ["helpers", "new-spirv-std"] },
+                    { "id": "helpers", "dependencies": ["old-spirv-std"] },
+                    { "id": "old-spirv-std", "dependencies": [] },
+                    { "id": "new-spirv-std", "dependencies": [] },
+                ],
+            },
+        });
+        let package = SpirvSource::find_spirv_std_package(
+            &metadata,
+            std::path::Path::new("/shader/Cargo.toml"),
+        )
+        .unwrap();
+        assert_eq!(
+            SpirvSource::from_spirv_std_package(package).unwrap(),
+            SpirvSource::CratesIO("0.9.0".to_owned())
+        );
+    }
+
+    #[test_log::test]
+    fn sharing_one_clone_between_checkouts() {
+        let upstream = crate::cache_dir().unwrap().join("upstream-rust-gpu");
+        if upstream.exists() {
+            std::fs::remove_dir_all(&upstream).unwrap();
+        }
+        let repo = git2::Repository::init(&upstream).unwrap();
+        let signature = git2::Signature::now("test", "test@example.com").unwrap();
+        let mut commits = Vec::new();
+        for channel in ["nightly-2024-04-24", "nightly-2024-11-22"] {
+            std::fs::write(
+                upstream.join("rust-toolchain.toml"),
+                format!("[toolchain]\nchannel = \"{channel}\"\n"),
+            )
+            .unwrap();
+            let mut index = repo.index().unwrap();
+            index
+                .add_path(std::path::Path::new("rust-toolchain.toml"))
+                .unwrap();
+            let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+            let parents = repo
+                .head()
+                .ok()
+                .and_then(|head| head.peel_to_commit().ok())
+                .into_iter()
+                .collect::<Vec<_>>();
+            let parent_refs = parents.iter().collect::<Vec<_>>();
+            let commit = repo
+                .commit(
+                    Some("HEAD"),
+                    &signature,
+                    &signature,
+                    channel,
+                    &tree,
+                    &parent_refs,
+                )
+                .unwrap();
+            commits.push(commit.to_string());
+        }
+
+        for (commit, channel) in commits
+            .iter()
+            .zip(["nightly-2024-04-24", "nightly-2024-11-22"])
+        {
+            let source = SpirvSource::Git {
+                url: upstream.display().to_string(),
+                rev: commit.clone(),
+            };
+            let checkout = source.to_dirname().unwrap();
+            if checkout.exists() {
+                std::fs::remove_dir_all(&checkout).unwrap();
+            }
+            source.ensure_repo_is_installed().unwrap();
+            source.checkout().unwrap();
+            assert!(SpirvSource::shared_clone_of(&checkout)
+                .is_some_and(|clone| clone.starts_with(crate::repos_dir().unwrap())));
+            assert_eq!(
+                SpirvSource::get_channel_from_toolchain_toml(&checkout).unwrap(),
+                channel
+            );
+
+            // A checkout that was removed is added again.
+            std::fs::remove_dir_all(&checkout).unwrap();
+            source.ensure_repo_is_installed().unwrap();
+            assert!(checkout.join("rust-toolchain.toml").is_file());
+        }
+
+        let bare_repos = std::fs::read_dir(crate::repos_dir().unwrap())
+            .unwrap()
+            .filter_map(Result::ok)
+            .filter(|entry| entry.path().is_dir())
+            .count();
+        assert_eq!(bare_repos, 1);
+    }
+
     #[test_log::test]
     fn path_sanity() {
         let path = std::path::PathBuf::from("./");
@@ -0,0 +1,135 @@
+//! `--stats`, statistics about each compiled module, eg for tracking shader bloat over time.
+
+use anyhow::Context as _;
+use spirv_builder_cli::{Linkage, ModuleStats};
+
+/// Gather the statistics of the SPIR-V module at `spv_path`.
+pub fn of_module(
+    spv_path: &std::path::Path,
+    compile_time: core::time::Duration,
+) -> anyhow::Result<ModuleStats> {
+    let bytes = std::fs::read(spv_path)
+        .with_context(|| format!("could not read SPIR-V file '{}'", spv_path.display()))?;
+    let size = u64::try_from(bytes.len())?;
+    let module = rspirv::dr::load_bytes(bytes)
+        .map_err(|error| anyhow::anyhow!("could not parse '{}': {error}", spv_path.display()))?;
+    Ok(of_parsed_module(&module, size, compile_time))
+}
+
+/// The statistics of an already parsed module.
+fn of_parsed_module(
+    module: &rspirv::dr::Module,
+    size: u64,
+    compile_time: core::time::Duration,
+) -> ModuleStats {
+    let capabilities = module
+        .capabilities
+        .iter()
+        .filter_map(|instruction| match instruction.operands.first() {
+            Some(rspirv::dr::Operand::Capability(capability)) => Some(format!("{capability:?}")),
+            _ => None,
+        })
+        .collect();
+    ModuleStats {
+        size,
+        instruction_count: u64::try_from(module.all_inst_iter().count()).unwrap_or(u64::MAX),
+        capabilities,
+        compile_ms: u64::try_from(compile_time.as_millis()).unwrap_or(u64::MAX),
+    }
+}
+
+/// Add the statistics of each entry point's module to its linkage. Modules are only read once,
+/// even when many entry points share them.
+pub fn add_to_linkage(
+    linkage: Vec<Linkage>,
+    shader_crate: &std::path::Path,
+    compile_time: core::time::Duration,
+) -> anyhow::Result<Vec<Linkage>> {
+    let mut by_module = std::collections::HashMap::new();
+    linkage
+        .into_iter()
+        .map(|entry| {
+            if !by_module.contains_key(&entry.source_path) {
+                let stats = of_module(&shader_crate.join(&entry.source_path), compile_time)?;
+                by_module.insert(entry.source_path.clone(), stats);
+            }
+            let stats = by_module
+                .get(&entry.source_path)
+                .context("module stats should have been gathered already")?
+                .clone();
+            Ok(entry.with_stats(stats))
+        })
+        .collect()
+}
+
+/// Print a table of the statistics of each module.
+pub fn print_table(linkage: &[Linkage]) {
+    let mut modules = std::collections::BTreeMap::new();
+    for entry in linkage {
+        if let Some(stats) = &entry.stats {
+            modules.insert(entry.source_path.as_str(), stats);
+        }
+    }
+    crate::user_output!("MODULE\tSIZE\tINSTRUCTIONS\tCOMPILE TIME\tCAPABILITIES\n");
+    for (module, stats) in modules {
+        crate::user_output!(
+            "{module}\t{}\t{}\t{:.3}s\t{}\n",
+            crate::format_size(stats.size),
+            stats.instruction_count,
+            core::time::Duration::from_millis(stats.compile_ms).as_secs_f64(),
+            stats.capabilities.join(", ")
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_log::test]
+    fn counting_instructions_and_capabilities() {
+        let mut builder = rspirv::dr::Builder::new();
+        builder.capability(rspirv::spirv::Capability::Shader);
+        builder.capability(rspirv::spirv::Capability::Int8);
+        builder.memory_model(
+            rspirv::spirv::AddressingModel::Logical,
+            rspirv::spirv::MemoryModel::GLSL450,
+        );
+        let void = builder.type_void();
+        let function_type = builder.type_function(void, vec![]);
+        let main = builder
+            .begin_function(
+                void,
+                None,
+                rspirv::spirv::FunctionControl::NONE,
+                function_type,
+            )
+            .unwrap();
+        builder.begin_block(None).unwrap();
+        builder.ret().unwrap();
+        builder.end_function().unwrap();
+        builder.entry_point(
+            rspirv::spirv::ExecutionModel::GLCompute,
+            main,
+            "main",
+            vec![],
+        );
+
+        let stats = of_parsed_module(
+            &builder.module(),
+            1234,
+            core::time::Duration::from_millis(42),
+        );
+        assert_eq!(
+            stats,
+            ModuleStats {
+                size: 1234,
+                // 2 capabilities, the memory model, the entry point, 2 types and the function's 4
+                // instructions.
+                instruction_count: 10,
+                capabilities: vec!["Shader".to_owned(), "Int8".to_owned()],
+                compile_ms: 42,
+            }
+        );
+    }
+}
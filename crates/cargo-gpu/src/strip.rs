@@ -0,0 +1,78 @@
+//! `--strip`, remove the names, decoration strings and debug instructions from the compiled
+//! modules with `spirv-opt`'s strip passes, to make the shipped modules as small as they can be.
+//!
+//! Modules are stripped after they've been reflected on, so the manifest still has the names of
+//! their bindings and spec constants.
+
+use anyhow::Context as _;
+use spirv_builder_cli::args::BuildArgs;
+use spirv_tools::opt::{Optimizer as _, Passes};
+
+/// The `spirv-opt` passes that strip a module, `--strip-debug` and `--strip-nonsemantic`.
+const PASSES: [Passes; 2] = [Passes::StripDebugInfo, Passes::StripNonSemanticInfo];
+
+/// Strip the module at `path` in place, and report how much smaller it got.
+pub fn strip(path: &std::path::Path, build_args: &BuildArgs) -> anyhow::Result<()> {
+    let bytes =
+        std::fs::read(path).with_context(|| format!("could not read '{}'", path.display()))?;
+    let before = u64::try_from(bytes.len())?;
+    let binary = spirv_tools::binary::Binary::try_from(bytes)
+        .with_context(|| format!("'{}' is not a SPIR-V module", path.display()))?;
+
+    let mut spirv_opt = spirv_tools::opt::create(Some(crate::optimize::target_env(
+        build_args.single_shader_target(),
+    )?));
+    for pass in PASSES {
+        spirv_opt.register_pass(pass);
+    }
+    log::debug!("stripping {}", path.display());
+    let stripped = spirv_opt
+        .optimize(
+            &binary,
+            &mut |message: spirv_tools::error::Message| {
+                log::warn!("spirv-opt: {}", message.message);
+            },
+            None,
+        )
+        .with_context(|| {
+            format!(
+                "`spirv-opt` could not strip '{}', is it installed and on your PATH?",
+                path.display()
+            )
+        })?;
+    let after = u64::try_from(stripped.as_bytes().len())?;
+    crate::atomic::write(path, stripped.as_bytes())?;
+
+    crate::user_output!("{}\n", savings(path, before, after));
+    Ok(())
+}
+
+/// A line describing how much stripping the module at `path` saved.
+fn savings(path: &std::path::Path, before: u64, after: u64) -> String {
+    let saved = before.saturating_sub(after);
+    let percent = saved.saturating_mul(100).checked_div(before).unwrap_or(0);
+    format!(
+        "Stripped '{}' from {} to {}, saving {} ({percent}%)",
+        path.display(),
+        crate::format_size(before),
+        crate::format_size(after),
+        crate::format_size(saved)
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_log::test]
+    fn describing_the_savings() {
+        assert_eq!(
+            savings(std::path::Path::new("shaders/main.spv"), 4096, 3072),
+            "Stripped 'shaders/main.spv' from 4.0 KiB to 3.0 KiB, saving 1.0 KiB (25%)"
+        );
+        assert_eq!(
+            savings(std::path::Path::new("empty.spv"), 0, 0),
+            "Stripped 'empty.spv' from 0.0 B to 0.0 B, saving 0.0 B (0%)"
+        );
+    }
+}
@@ -0,0 +1,181 @@
+//! `--timings`, a report of how long each phase of an install or build took, similar to
+//! `cargo build --timings`. Useful for finding out why a cold build is slow.
+//!
+//! Phases are only recorded once [`enable`] has been called. The report is written as both JSON
+//! and HTML to `cargo-gpu-timings/` in the current directory.
+
+/// A phase of an install or a build that took some time.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct Phase {
+    /// What happened, eg "compile shaders".
+    pub name: String,
+    /// What it happened to, eg the shader crate.
+    pub detail: String,
+    /// When the phase started, in milliseconds since timings were enabled.
+    pub start_ms: u64,
+    /// How long the phase took, in milliseconds.
+    pub duration_ms: u64,
+}
+
+/// When timings were enabled, phases are timed relative to this.
+static STARTED: std::sync::OnceLock<std::time::Instant> = std::sync::OnceLock::new();
+
+/// Every phase recorded so far.
+static PHASES: std::sync::Mutex<Vec<Phase>> = std::sync::Mutex::new(Vec::new());
+
+/// Start recording phases.
+pub fn enable() {
+    let _: &std::time::Instant = STARTED.get_or_init(std::time::Instant::now);
+}
+
+/// Whether phases are being recorded.
+#[must_use]
+pub fn is_enabled() -> bool {
+    STARTED.get().is_some()
+}
+
+/// Run `phase`, recording how long it took if timings are enabled.
+pub fn time<T, D: core::fmt::Display, P: FnOnce() -> T>(name: &str, detail: D, phase: P) -> T {
+    let Some(started) = STARTED.get() else {
+        return phase();
+    };
+    let phase_started = std::time::Instant::now();
+    let result = phase();
+    let recorded = Phase {
+        name: name.to_owned(),
+        detail: detail.to_string(),
+        start_ms: millis(phase_started.duration_since(*started)),
+        duration_ms: millis(phase_started.elapsed()),
+    };
+    if let Ok(mut phases) = PHASES.lock() {
+        phases.push(recorded);
+    }
+    result
+}
+
+/// Every phase recorded so far, in the order that they finished.
+#[must_use]
+pub fn phases() -> Vec<Phase> {
+    PHASES
+        .lock()
+        .map(|phases| phases.clone())
+        .unwrap_or_default()
+}
+
+/// Write the JSON and HTML reports of every recorded phase, returning their paths.
+///
+/// # Errors
+/// If the reports couldn't be written.
+pub fn write_report() -> anyhow::Result<[std::path::PathBuf; 2]> {
+    let dir = std::path::absolute("cargo-gpu-timings")?;
+    std::fs::create_dir_all(&dir)?;
+    let phases = phases();
+    let json_path = dir.join("cargo-gpu-timing.json");
+    std::fs::write(&json_path, serde_json::to_string_pretty(&phases)?)?;
+    let html_path = dir.join("cargo-gpu-timing.html");
+    std::fs::write(&html_path, render_html(&phases))?;
+    Ok([json_path, html_path])
+}
+
+/// A standalone HTML page with a table of phases and a bar chart of when each one ran.
+fn render_html(phases: &[Phase]) -> String {
+    let total_ms = phases
+        .iter()
+        .map(|phase| phase.start_ms + phase.duration_ms)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    let percent = |ms: u64| (ms * 100).checked_div(total_ms).unwrap_or(0);
+
+    let rows = phases
+        .iter()
+        .map(|phase| {
+            format!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td>\
+             <td class=\"chart\"><div class=\"bar\" style=\"margin-left: {}%; width: {}%\"></div></td></tr>\n",
+            escape_html(&phase.name),
+            escape_html(&phase.detail),
+            format_ms(phase.start_ms),
+            format_ms(phase.duration_ms),
+            percent(phase.start_ms),
+            percent(phase.duration_ms).max(1),
+            )
+        })
+        .collect::<Vec<_>>()
+        .concat();
+
+    format!(
+        "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>cargo gpu timings</title>
+<style>
+body {{ font-family: sans-serif; }}
+table {{ border-collapse: collapse; width: 100%; }}
+td, th {{ padding: 4px 8px; text-align: left; border-bottom: 1px solid #ddd; }}
+.chart {{ width: 50%; }}
+.bar {{ height: 1em; background: #4a90d9; }}
+</style>
+</head>
+<body>
+<h1>cargo gpu timings</h1>
+<p>Total time: {}</p>
+<table>
+<tr><th>Phase</th><th>Detail</th><th>Start</th><th>Duration</th><th></th></tr>
+{rows}</table>
+</body>
+</html>
+",
+        format_ms(total_ms)
+    )
+}
+
+/// Milliseconds as seconds, eg "1.234s".
+fn format_ms(ms: u64) -> String {
+    format!(
+        "{:.3}s",
+        core::time::Duration::from_millis(ms).as_secs_f64()
+    )
+}
+
+/// Escape text for including in HTML.
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// A duration in whole milliseconds.
+fn millis(duration: core::time::Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_log::test]
+    fn rendering_the_html_report() {
+        let phases = vec![
+            Phase {
+                name: "clone rust-gpu".to_owned(),
+                detail: "https://github.com/Rust-GPU/rust-gpu".to_owned(),
+                start_ms: 0,
+                duration_ms: 1500,
+            },
+            Phase {
+                name: "compile shaders".to_owned(),
+                detail: "<shader-crate>".to_owned(),
+                start_ms: 1500,
+                duration_ms: 500,
+            },
+        ];
+        let html = render_html(&phases);
+        assert!(html.contains("<p>Total time: 2.000s</p>"));
+        assert!(html.contains("<td>clone rust-gpu</td>"));
+        assert!(html.contains("<td>&lt;shader-crate&gt;</td>"));
+        assert!(html.contains("margin-left: 75%; width: 25%"));
+    }
+}
@@ -0,0 +1,190 @@
+//! Transpile compiled SPIR-V to other shader languages, using `naga`. This lets engines that
+//! don't use Vulkan consume `rust-gpu` shaders directly.
+
+use anyhow::Context as _;
+use spirv_builder_cli::args::TranspileTarget;
+
+/// Parse and validate a SPIR-V module with `naga`.
+fn parse_spirv(
+    spv_path: &std::path::Path,
+) -> anyhow::Result<(naga::Module, naga::valid::ModuleInfo)> {
+    let bytes = std::fs::read(spv_path)
+        .with_context(|| format!("could not read SPIR-V file '{}'", spv_path.display()))?;
+    let module = naga::front::spv::parse_u8_slice(&bytes, &naga::front::spv::Options::default())
+        .with_context(|| format!("could not parse SPIR-V file '{}'", spv_path.display()))?;
+    let info = naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::all(),
+    )
+    .validate(&module)
+    .with_context(|| format!("naga could not validate '{}'", spv_path.display()))?;
+    Ok((module, info))
+}
+
+/// The path that `spv_path` gets transpiled to for the given target.
+///
+/// GLSL only supports one entry point per file, so its output also gets named after the entry
+/// point. All the other targets write every entry point of the module to the same file.
+#[must_use]
+pub fn output_path(
+    spv_path: &std::path::Path,
+    target: TranspileTarget,
+    entry_point: &str,
+) -> std::path::PathBuf {
+    match target {
+        TranspileTarget::Wgsl => spv_path.with_extension("wgsl"),
+        TranspileTarget::Hlsl => spv_path.with_extension("hlsl"),
+        TranspileTarget::Msl => spv_path.with_extension("metal"),
+        TranspileTarget::Glsl => {
+            spv_path.with_extension(format!("{}.glsl", entry_point.replace("::", "-")))
+        }
+    }
+}
+
+/// Write a module as GLSL, which can only contain the given entry point.
+fn write_glsl(
+    module: &naga::Module,
+    info: &naga::valid::ModuleInfo,
+    entry_point: &str,
+) -> anyhow::Result<String> {
+    let stage = module
+        .entry_points
+        .iter()
+        .find(|candidate| candidate.name == entry_point)
+        .with_context(|| format!("entry point `{entry_point}` not found in module"))?
+        .stage;
+    let options = naga::back::glsl::Options {
+        version: naga::back::glsl::Version::Desktop(450),
+        ..naga::back::glsl::Options::default()
+    };
+    let pipeline_options = naga::back::glsl::PipelineOptions {
+        shader_stage: stage,
+        entry_point: entry_point.to_owned(),
+        multiview: None,
+    };
+
+    let mut source = String::new();
+    let mut writer = naga::back::glsl::Writer::new(
+        &mut source,
+        module,
+        info,
+        &options,
+        &pipeline_options,
+        naga::proc::BoundsCheckPolicies::default(),
+    )?;
+    writer.write()?;
+    Ok(source)
+}
+
+/// Transpile a `.spv` file to the given target, writing the result alongside it. Returns the path
+/// of the written file, see [`output_path`].
+pub fn transpile(
+    spv_path: &std::path::Path,
+    target: TranspileTarget,
+    entry_point: &str,
+) -> anyhow::Result<std::path::PathBuf> {
+    let (module, info) = parse_spirv(spv_path)?;
+    let source = match target {
+        TranspileTarget::Wgsl => {
+            naga::back::wgsl::write_string(&module, &info, naga::back::wgsl::WriterFlags::empty())?
+        }
+        TranspileTarget::Hlsl => {
+            let mut source = String::new();
+            naga::back::hlsl::Writer::new(
+                &mut source,
+                &naga::back::hlsl::Options::default(),
+                &naga::back::hlsl::PipelineOptions::default(),
+            )
+            .write(&module, &info, None)?;
+            source
+        }
+        TranspileTarget::Msl => {
+            naga::back::msl::write_string(
+                &module,
+                &info,
+                &naga::back::msl::Options::default(),
+                &naga::back::msl::PipelineOptions::default(),
+            )?
+            .0
+        }
+        TranspileTarget::Glsl => write_glsl(&module, &info, entry_point)?,
+    };
+
+    let output_path = output_path(spv_path, target, entry_point);
+    crate::atomic::write(&output_path, source)?;
+    log::debug!(
+        "transpiled {} to {}",
+        spv_path.display(),
+        output_path.display()
+    );
+    Ok(output_path)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A minimal compute shader, as assembled by `spirv-as`:
+    ///
+    /// ```text
+    /// OpCapability Shader
+    /// OpMemoryModel Logical GLSL450
+    /// OpEntryPoint GLCompute %main "main"
+    /// OpExecutionMode %main LocalSize 1 1 1
+    /// %void = OpTypeVoid
+    /// %fn = OpTypeFunction %void
+    /// %main = OpFunction %void None %fn
+    /// %entry = OpLabel
+    /// OpReturn
+    /// OpFunctionEnd
+    /// ```
+    #[rustfmt::skip]
+    const COMPUTE_SHADER: &[u32] = &[
+        0x0723_0203, 0x0001_0000, 0, 6, 0, // Header
+        0x0002_0011, 1, // OpCapability Shader
+        0x0003_000e, 0, 1, // OpMemoryModel Logical GLSL450
+        0x0005_000f, 5, 1, 0x6e69_616d, 0, // OpEntryPoint GLCompute %1 "main"
+        0x0006_0010, 1, 17, 1, 1, 1, // OpExecutionMode %1 LocalSize 1 1 1
+        0x0002_0013, 2, // %2 = OpTypeVoid
+        0x0003_0021, 3, 2, // %3 = OpTypeFunction %2
+        0x0005_0036, 2, 1, 0, 3, // %1 = OpFunction %2 None %3
+        0x0002_00f8, 4, // %4 = OpLabel
+        0x0001_00fd, // OpReturn
+        0x0001_0038, // OpFunctionEnd
+    ];
+
+    #[test_log::test]
+    fn spirv_to_other_languages() {
+        let dir = crate::cache_dir().unwrap().join("transpile");
+        std::fs::create_dir_all(&dir).unwrap();
+        let spv_path = dir.join("shader.spv");
+        let bytes = COMPUTE_SHADER
+            .iter()
+            .flat_map(|word| word.to_le_bytes())
+            .collect::<Vec<_>>();
+        std::fs::write(&spv_path, bytes).unwrap();
+
+        let wgsl_path = transpile(&spv_path, TranspileTarget::Wgsl, "main").unwrap();
+        assert_eq!(wgsl_path, dir.join("shader.wgsl"));
+        let wgsl = std::fs::read_to_string(wgsl_path).unwrap();
+        assert!(wgsl.contains("@compute @workgroup_size(1, 1, 1)"), "{wgsl}");
+        assert!(wgsl.contains("fn main()"), "{wgsl}");
+
+        let glsl_path = transpile(&spv_path, TranspileTarget::Glsl, "main").unwrap();
+        assert_eq!(glsl_path, dir.join("shader.main.glsl"));
+        let glsl = std::fs::read_to_string(glsl_path).unwrap();
+        assert!(glsl.starts_with("#version 450"), "{glsl}");
+
+        let hlsl_path = transpile(&spv_path, TranspileTarget::Hlsl, "main").unwrap();
+        assert!(std::fs::read_to_string(hlsl_path)
+            .unwrap()
+            .contains("[numthreads(1, 1, 1)]"));
+
+        let msl_path = transpile(&spv_path, TranspileTarget::Msl, "main").unwrap();
+        assert!(std::fs::read_to_string(msl_path)
+            .unwrap()
+            .contains("kernel void"));
+
+        crate::test::tests_teardown();
+    }
+}
@@ -0,0 +1,191 @@
+//! `cargo gpu uninstall`, remove specific installed binary pairs from the cache directory.
+
+use crate::{disk_size, format_size, install::InstalledPair};
+
+/// `cargo gpu uninstall`.
+///
+/// Installs are selected with the same identifiers that were used to install them. Only installs
+/// that match every given identifier are removed.
+#[derive(clap::Parser, Debug)]
+pub struct Uninstall {
+    /// Source of the `spirv-builder` dependency, eg: <https://github.com/Rust-GPU/rust-gpu>.
+    #[clap(long)]
+    pub spirv_builder_source: Option<String>,
+
+    /// Version of the `spirv-builder` dependency, either a crates.io version or a Git "commitsh".
+    #[clap(long)]
+    pub spirv_builder_version: Option<String>,
+
+    /// Rust toolchain channel that `spirv-builder` was built with.
+    #[clap(long)]
+    pub rust_toolchain: Option<String>,
+
+    /// Also remove the cached checkout of the `rust-gpu` repo that each install was made from,
+    /// unless another install still uses it.
+    #[clap(long)]
+    pub checkout: bool,
+
+    /// Only list what would be removed and how much space would be reclaimed.
+    #[clap(long)]
+    pub dry_run: bool,
+}
+
+impl Uninstall {
+    /// Entrypoint.
+    pub fn run(&self) -> anyhow::Result<()> {
+        let mut total = 0;
+        for path in self.paths_to_remove()? {
+            let size = disk_size(&path)?;
+            total += size;
+            if self.dry_run {
+                crate::user_output!("Would remove {} ({})\n", path.display(), format_size(size));
+            } else {
+                log::debug!("removing '{}'", path.display());
+                std::fs::remove_dir_all(&path)?;
+                crate::user_output!("Removed {} ({})\n", path.display(), format_size(size));
+            }
+        }
+
+        if self.dry_run {
+            crate::user_output!("Would reclaim {}\n", format_size(total));
+        } else {
+            crate::user_output!("Reclaimed {}\n", format_size(total));
+        }
+
+        Ok(())
+    }
+
+    /// Whether the install was made with all the given identifiers.
+    fn is_match(&self, pair: &InstalledPair) -> bool {
+        let Some(metadata) = &pair.metadata else {
+            return false;
+        };
+        let is_same = |maybe_wanted: &Option<String>, actual: &str| {
+            maybe_wanted.as_ref().is_none_or(|wanted| wanted == actual)
+        };
+        is_same(&self.spirv_builder_source, &metadata.source)
+            && is_same(&self.spirv_builder_version, &metadata.version)
+            && is_same(&self.rust_toolchain, &metadata.channel)
+    }
+
+    /// All the existing paths that should be removed.
+    fn paths_to_remove(&self) -> anyhow::Result<Vec<std::path::PathBuf>> {
+        anyhow::ensure!(
+            self.spirv_builder_source.is_some()
+                || self.spirv_builder_version.is_some()
+                || self.rust_toolchain.is_some(),
+            "give at least one of `--spirv-builder-source`, `--spirv-builder-version` or \
+             `--rust-toolchain` to select the installs to remove. \
+             Use `cargo gpu clean --installs` to remove all of them."
+        );
+
+        let (matching, remaining): (Vec<_>, Vec<_>) = crate::install::installed_pairs()?
+            .into_iter()
+            .partition(|pair| self.is_match(pair));
+        anyhow::ensure!(
+            !matching.is_empty(),
+            "no installs match. Run `cargo gpu show installs` to see them all, installs without a \
+             source can be removed with `cargo gpu clean --installs <NAME>`"
+        );
+
+        let mut paths: Vec<std::path::PathBuf> =
+            matching.iter().map(|pair| pair.path.clone()).collect();
+        if self.checkout {
+            for checkout in matching
+                .iter()
+                .filter_map(|pair| pair.metadata.as_ref()?.checkout.clone())
+            {
+                let is_still_used = remaining.iter().any(|pair| {
+                    pair.metadata
+                        .as_ref()
+                        .is_some_and(|metadata| metadata.checkout.as_ref() == Some(&checkout))
+                });
+                if is_still_used {
+                    log::info!(
+                        "not removing '{}' as another install uses it",
+                        checkout.display()
+                    );
+                    continue;
+                }
+                if checkout.exists() && !paths.contains(&checkout) {
+                    paths.push(checkout);
+                }
+            }
+        }
+
+        Ok(paths)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{checkouts_dir, install::PairMetadata, installs_dir};
+    use clap::Parser as _;
+
+    /// Make a cache directory with two installs of the same `rust-gpu` checkout, built with
+    /// different toolchains.
+    fn setup_cache() -> std::path::PathBuf {
+        crate::test::tests_teardown();
+        let checkout = checkouts_dir().unwrap().join("0_10_0");
+        std::fs::create_dir_all(&checkout).unwrap();
+        let installs = installs_dir().unwrap();
+        for channel in ["nightly-2024-04-24", "nightly-2024-11-22"] {
+            let path = installs.join(format!("0_10_0+{channel}"));
+            std::fs::create_dir_all(&path).unwrap();
+            let metadata = PairMetadata {
+                source: "https://github.com/Rust-GPU/rust-gpu".to_owned(),
+                version: "0.10.0".to_owned(),
+                channel: channel.to_owned(),
+                checkout: Some(checkout.clone()),
+                checksums: std::collections::BTreeMap::new(),
+                builder_support: None,
+            };
+            std::fs::write(
+                path.join("cargo-gpu-install.json"),
+                serde_json::to_string(&metadata).unwrap(),
+            )
+            .unwrap();
+        }
+        installs
+    }
+
+    #[test_log::test]
+    fn removes_only_matching_installs() {
+        let installs = setup_cache();
+        Uninstall::parse_from([
+            "uninstall",
+            "--rust-toolchain",
+            "nightly-2024-04-24",
+            "--checkout",
+        ])
+        .run()
+        .unwrap();
+        assert!(!installs.join("0_10_0+nightly-2024-04-24").exists());
+        assert!(installs.join("0_10_0+nightly-2024-11-22").is_dir());
+        // The other install still uses the checkout.
+        assert!(checkouts_dir().unwrap().join("0_10_0").is_dir());
+    }
+
+    #[test_log::test]
+    fn removes_unused_checkouts() {
+        setup_cache();
+        let uninstall = Uninstall::parse_from([
+            "uninstall",
+            "--spirv-builder-version",
+            "0.10.0",
+            "--checkout",
+        ]);
+        assert_eq!(uninstall.paths_to_remove().unwrap().len(), 3);
+    }
+
+    #[test_log::test]
+    fn no_matches_is_an_error() {
+        setup_cache();
+        let uninstall = Uninstall::parse_from(["uninstall", "--spirv-builder-version", "0.9.0"]);
+        uninstall.paths_to_remove().unwrap_err();
+        Uninstall::parse_from(["uninstall"])
+            .paths_to_remove()
+            .unwrap_err();
+    }
+}
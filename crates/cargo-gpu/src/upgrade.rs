@@ -0,0 +1,258 @@
+//! `cargo gpu upgrade`, move a shader crate to another version of `rust-gpu`.
+//!
+//! The `spirv-std` dependency in the shader crate's `Cargo.toml` is rewritten, keeping its
+//! features and formatting, and `Cargo.lock` is updated to match. Then the binary pair for the new
+//! version is installed, so that the next build doesn't have to wait for it.
+
+use anyhow::Context as _;
+
+/// `cargo gpu upgrade`.
+#[derive(clap::Parser, Debug)]
+pub struct Upgrade {
+    /// The same arguments as `cargo gpu install`.
+    #[clap(flatten)]
+    pub install: crate::install::Install,
+
+    /// The `rust-gpu` version to upgrade to, either a released version from crates.io, eg
+    /// "0.10.0", or a commit of the `rust-gpu` repo, or of the fork that the shader crate already
+    /// uses.
+    #[clap(long)]
+    pub to: String,
+}
+
+impl Upgrade {
+    /// Entrypoint.
+    pub fn run(&self) -> anyhow::Result<()> {
+        let shader_crate = &self.install.spirv_install.shader_crate;
+        let manifest_path = shader_crate.join("Cargo.toml");
+        let manifest = std::fs::read_to_string(&manifest_path)
+            .with_context(|| format!("could not read '{}'", manifest_path.display()))?;
+        let mut document: toml_edit::DocumentMut = manifest
+            .parse()
+            .with_context(|| format!("could not parse '{}'", manifest_path.display()))?;
+        let previous = upgrade_manifest(&mut document, &self.to)?;
+        write_upgraded_manifest(&manifest_path, &manifest, &document.to_string())?;
+        crate::user_output!(
+            "Upgraded `spirv-std` in {} from {previous} to {}\n",
+            manifest_path.display(),
+            self.to
+        );
+        // The pinned `rust-gpu` is the one being upgraded from, so pin the new one instead.
+        crate::pin::remove(shader_crate)?;
+
+        let mut command =
+            crate::config::Config::install_with_cargo_config(shader_crate, &self.install)?;
+        if let Some(version) = &command.install.spirv_install.spirv_builder_version {
+            log::warn!(
+                "`spirv-builder-version` is set to {version}, so builds won't use the `rust-gpu` \
+                 version that `spirv-std` was upgraded to"
+            );
+        }
+        let cli_path = command.install.run()?;
+        let install_dir = cli_path.parent().context("install has no directory")?;
+        if let Some(metadata) = crate::install::PairMetadata::read(install_dir) {
+            crate::user_output!(
+                "Installed `rust-gpu` {} with toolchain {}\n",
+                metadata.version,
+                metadata.channel
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Point every `spirv-std` dependency in a shader crate's `Cargo.toml` at `rust_gpu_version`,
+/// including target-specific ones. Returns how the dependency was previously defined.
+fn upgrade_manifest(
+    document: &mut toml_edit::DocumentMut,
+    rust_gpu_version: &str,
+) -> anyhow::Result<String> {
+    let mut previous = None;
+    let mut tables = Vec::new();
+    for (key, item) in document.as_table_mut().iter_mut() {
+        match key.get() {
+            "dependencies" => tables.extend(item.as_table_like_mut()),
+            "target" => {
+                let targets = item.as_table_like_mut().into_iter().flat_map(|targets| {
+                    targets
+                        .iter_mut()
+                        .filter_map(|(_, target)| target.get_mut("dependencies"))
+                        .filter_map(toml_edit::Item::as_table_like_mut)
+                });
+                tables.extend(targets);
+            }
+            _ => (),
+        }
+    }
+
+    for table in tables {
+        for (name, dependency) in table.iter_mut() {
+            let is_spirv_std = name == "spirv-std"
+                || dependency.get("package").and_then(toml_edit::Item::as_str) == Some("spirv-std");
+            if is_spirv_std {
+                previous.get_or_insert_with(|| dependency.to_string().trim().to_owned());
+                upgrade_dependency(dependency, rust_gpu_version)?;
+            }
+        }
+    }
+    previous.context("the shader crate doesn't depend on `spirv-std`")
+}
+
+/// Point a `spirv-std` dependency at `rust_gpu_version`, keeping its other keys, eg `features`.
+fn upgrade_dependency(
+    dependency: &mut toml_edit::Item,
+    rust_gpu_version: &str,
+) -> anyhow::Result<()> {
+    if dependency.is_str() {
+        *dependency = toml_edit::value(toml_edit::InlineTable::new());
+    }
+    let table = dependency
+        .as_table_like_mut()
+        .context("`spirv-std` dependency isn't a version or a table")?;
+    anyhow::ensure!(
+        !table.contains_key("workspace"),
+        "`spirv-std` is inherited from the workspace, upgrade it in the workspace's `Cargo.toml`"
+    );
+    let url = table
+        .get("git")
+        .and_then(toml_edit::Item::as_str)
+        .unwrap_or(crate::spirv_source::RUST_GPU_REPO)
+        .to_owned();
+
+    // Keys that are kept are updated in place, so that they stay where they were.
+    let is_plain_version = if crate::new::is_release(rust_gpu_version) {
+        for key in ["git", "rev", "branch", "tag", "path"] {
+            table.remove(key);
+        }
+        table.insert("version", toml_edit::value(rust_gpu_version));
+        table.len() == 1
+    } else {
+        for key in ["version", "branch", "tag", "path"] {
+            table.remove(key);
+        }
+        table.insert("git", toml_edit::value(url));
+        table.insert("rev", toml_edit::value(rust_gpu_version));
+        false
+    };
+    if is_plain_version {
+        *dependency = toml_edit::value(rust_gpu_version);
+    }
+    Ok(())
+}
+
+/// Write the `upgraded` manifest over the `original` at `manifest_path`, and update `Cargo.lock`
+/// to match. If the update fails, the original is put back, so that the shader crate is never
+/// left with a `Cargo.toml` that its `Cargo.lock` doesn't match.
+fn write_upgraded_manifest(
+    manifest_path: &std::path::Path,
+    original: &str,
+    upgraded: &str,
+) -> anyhow::Result<()> {
+    crate::atomic::write(manifest_path, upgraded)?;
+    if let Err(error) = update_lockfile(manifest_path) {
+        crate::atomic::write(manifest_path, original)
+            .with_context(|| format!("could not restore '{}'", manifest_path.display()))?;
+        return Err(error.context(format!(
+            "could not upgrade '{}', so it was left as it was",
+            manifest_path.display()
+        )));
+    }
+    Ok(())
+}
+
+/// Update the shader crate's `Cargo.lock` for its new `spirv-std`, without updating any of its
+/// other dependencies.
+fn update_lockfile(manifest_path: &std::path::Path) -> anyhow::Result<()> {
+    log::debug!("updating `Cargo.lock` for {}", manifest_path.display());
+    let output = std::process::Command::new("cargo")
+        .args(["update", "--workspace", "--manifest-path"])
+        .arg(manifest_path)
+        .args(crate::offline::cargo_args())
+        .output()?;
+    anyhow::ensure!(
+        output.status.success(),
+        "could not update `Cargo.lock` for the new `spirv-std`:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_log::test]
+    fn upgrading_spirv_std() {
+        let mut document: toml_edit::DocumentMut = "[dependencies]\nspirv-std = \"0.9.0\"\n\n\
+            [target.'cfg(target_arch = \"spirv\")'.dependencies]\n\
+            gpu = { package = \"spirv-std\", version = \"0.9.0\", features = [\"bytemuck\"] }\n"
+            .parse()
+            .unwrap();
+        let previous = upgrade_manifest(&mut document, "0.10.0").unwrap();
+        assert_eq!(previous, "\"0.9.0\"");
+        let upgraded = document.to_string();
+        assert!(upgraded.contains("spirv-std = \"0.10.0\"\n"));
+        assert!(upgraded.contains(
+            "gpu = { package = \"spirv-std\", version = \"0.10.0\", features = [\"bytemuck\"] }"
+        ));
+
+        let mut fork: toml_edit::DocumentMut =
+            "[dependencies]\nspirv-std = { git = \"https://example.com/fork\", branch = \"main\" }\n"
+                .parse()
+                .unwrap();
+        upgrade_manifest(&mut fork, "abc1234").unwrap();
+        assert!(fork
+            .to_string()
+            .contains("spirv-std = { git = \"https://example.com/fork\", rev = \"abc1234\" }"));
+
+        let mut inherited: toml_edit::DocumentMut = "[dependencies]\nspirv-std.workspace = true\n"
+            .parse()
+            .unwrap();
+        upgrade_manifest(&mut inherited, "0.10.0").unwrap_err();
+    }
+
+    #[test_log::test]
+    fn restoring_the_manifest_when_the_update_fails() {
+        let dir = crate::cache_dir().unwrap().join("upgrade");
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::write(dir.join("src").join("lib.rs"), "").unwrap();
+        let manifest_path = dir.join("Cargo.toml");
+        let original = "[package]\nname = \"shaders\"\nversion = \"0.1.0\"\nedition = \"2021\"\n";
+        std::fs::write(&manifest_path, original).unwrap();
+
+        let upgraded =
+            format!("{original}\n[dependencies]\nspirv-std = {{ path = \"missing\" }}\n");
+        write_upgraded_manifest(&manifest_path, original, &upgraded).unwrap_err();
+        assert_eq!(std::fs::read_to_string(&manifest_path).unwrap(), original);
+        crate::test::tests_teardown();
+    }
+
+    #[test_log::test]
+    fn installing_with_the_parsed_args() {
+        use clap::Parser as _;
+
+        let shader_crate = crate::test::shader_crate_test_path();
+        let upgrade = Upgrade::parse_from([
+            "cargo-gpu",
+            "--to",
+            "0.10.0",
+            "--shader-crate",
+            &shader_crate.display().to_string(),
+            "--spirv-builder-source",
+            "upgrade",
+            "--offline",
+        ]);
+        let command =
+            crate::config::Config::install_with_cargo_config(&shader_crate, &upgrade.install)
+                .unwrap();
+        assert_eq!(
+            command
+                .install
+                .spirv_install
+                .spirv_builder_source
+                .as_deref(),
+            Some("upgrade")
+        );
+        assert!(command.install.spirv_install.offline);
+    }
+}
@@ -0,0 +1,102 @@
+//! Validate compiled SPIR-V modules with `spirv-val`.
+//!
+//! Like `spirv-opt`, this uses the `spirv-tools` bindings in their "installed tools" mode. As
+//! validation is on by default, a missing `spirv-val` binary only warns rather than failing.
+
+use anyhow::Context as _;
+use spirv_builder_cli::args::BuildArgs;
+use spirv_tools::val::Validator as _;
+
+/// The `spirv-val` options matching the ones the shader crate was compiled with.
+fn validator_options(build_args: &BuildArgs) -> spirv_tools::val::ValidatorOptions {
+    spirv_tools::val::ValidatorOptions {
+        relax_struct_store: build_args.relax_struct_store,
+        relax_logical_pointer: build_args.relax_logical_pointer,
+        relax_block_layout: Some(build_args.relax_block_layout),
+        uniform_buffer_standard_layout: build_args.uniform_buffer_standard_layout,
+        scalar_block_layout: build_args.scalar_block_layout,
+        skip_block_layout: build_args.skip_block_layout,
+        ..Default::default()
+    }
+}
+
+/// Run `spirv-val` on the SPIR-V module at `spv_path`. `entry_points` are the entry points that
+/// the module contains, they're used to say which shaders are affected by an invalid module.
+///
+/// Invalid modules are an error, unless `--no-validate` is set, in which case they only warn.
+pub fn validate(
+    spv_path: &std::path::Path,
+    entry_points: &[String],
+    build_args: &BuildArgs,
+) -> anyhow::Result<()> {
+    let contents = std::fs::read(spv_path)
+        .with_context(|| format!("could not read SPIR-V file '{}'", spv_path.display()))?;
+    bytes(contents, spv_path, entry_points, build_args)
+}
+
+/// Run `spirv-val` on a module that's already been read, eg after decompressing it. `spv_path`
+/// is only used in messages.
+pub fn bytes(
+    bytes: Vec<u8>,
+    spv_path: &std::path::Path,
+    entry_points: &[String],
+    build_args: &BuildArgs,
+) -> anyhow::Result<()> {
+    let binary = spirv_tools::binary::Binary::try_from(bytes)
+        .with_context(|| format!("'{}' is not a SPIR-V module", spv_path.display()))?;
+
+    let target_env = match crate::optimize::target_env(build_args.single_shader_target()) {
+        Ok(target_env) => target_env,
+        Err(error) => {
+            log::warn!("skipping SPIR-V validation: {error}");
+            return Ok(());
+        }
+    };
+    log::debug!("validating {} for {target_env}", spv_path.display());
+    let Err(error) = spirv_tools::val::create(Some(target_env))
+        .validate(&binary, Some(validator_options(build_args)))
+    else {
+        return Ok(());
+    };
+
+    if error.inner == spirv_tools::error::SpirvResult::Unsupported {
+        log::warn!("skipping SPIR-V validation, couldn't run `spirv-val`: {error}");
+        return Ok(());
+    }
+
+    let message = error
+        .diagnostic
+        .map_or_else(|| error.inner.to_string(), |diagnostic| diagnostic.message);
+    let report = format!(
+        "invalid SPIR-V module '{}' for entry point(s) {}:\n{message}",
+        spv_path.display(),
+        entry_points
+            .iter()
+            .map(|entry_point| format!("`{entry_point}`"))
+            .collect::<Vec<_>>()
+            .join(", "),
+    );
+    if build_args.no_validate {
+        log::warn!("{report}");
+        crate::user_output!("Warning: {report}\n");
+        Ok(())
+    } else {
+        Err(crate::exit::Failure::Validation(anyhow::anyhow!(report)).into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use clap::Parser as _;
+
+    #[test_log::test]
+    fn validator_options_match_build_args() {
+        let build_args =
+            BuildArgs::parse_from(["", "--relax-block-layout", "--scalar-block-layout"]);
+        let options = validator_options(&build_args);
+        assert_eq!(options.relax_block_layout, Some(true));
+        assert!(options.scalar_block_layout);
+        assert!(!options.skip_block_layout);
+    }
+}
@@ -0,0 +1,222 @@
+//! `cargo gpu verify`, check that a manifest matches the files that it lists, eg as a cheap CI
+//! gate on compiled shaders that are committed to a repo.
+
+use anyhow::Context as _;
+use spirv_builder_cli::{args::BuildArgs, Linkage, Manifest};
+
+/// `cargo gpu verify`.
+#[derive(clap::Parser, Debug)]
+pub struct Verify {
+    /// A manifest written by `cargo gpu build`, or the output dir that it's in.
+    manifest: std::path::PathBuf,
+
+    /// Directory containing the shader crate. Paths in the manifest are relative to it.
+    #[clap(long, default_value = "./")]
+    shader_crate: std::path::PathBuf,
+
+    /// The options that the modules were built with, so that `spirv-val` checks them the same
+    /// way, eg `--shader-target` and `--scalar-block-layout`.
+    #[clap(flatten)]
+    build_args: BuildArgs,
+}
+
+impl Verify {
+    /// Entrypoint.
+    pub fn run(&self) -> anyhow::Result<()> {
+        let manifest_path = crate::diff::manifest_path(&self.manifest);
+        let manifest = crate::diff::read_manifest(&manifest_path)?;
+        let problems = self.problems(&manifest, &manifest_path)?;
+        anyhow::ensure!(
+            problems.is_empty(),
+            "'{}' doesn't match its files:\n{}",
+            manifest_path.display(),
+            problems
+                .iter()
+                .map(|problem| format!("  - {problem}"))
+                .collect::<Vec<_>>()
+                .join("\n")
+        );
+        crate::user_output!(
+            "Verified the {} entry points in '{}'\n",
+            manifest.entry_points.len(),
+            manifest_path.display()
+        );
+        Ok(())
+    }
+
+    /// Everything about the manifest that doesn't match its files.
+    fn problems(
+        &self,
+        manifest: &Manifest,
+        manifest_path: &std::path::Path,
+    ) -> anyhow::Result<Vec<String>> {
+        let manifest_dir = manifest_path
+            .parent()
+            .unwrap_or_else(|| std::path::Path::new("./"));
+        let resolve = |path: &str| manifest.resolve_path(path, &self.shader_crate, manifest_dir);
+
+        let mut problems = missing_files(&manifest.entry_points, resolve);
+        problems.extend(duplicate_wgsl_entry_points(&manifest.entry_points));
+
+        // Each module only needs hashing and validating once, even when many entry points share
+        // it.
+        let mut modules: std::collections::BTreeMap<&str, Vec<&Linkage>> =
+            std::collections::BTreeMap::new();
+        for entry in &manifest.entry_points {
+            modules
+                .entry(entry.source_path.as_str())
+                .or_default()
+                .push(entry);
+        }
+        let mut packs = std::collections::HashMap::new();
+        for (source_path, entries) in modules {
+            let path = resolve(source_path);
+            if !path.is_file() {
+                continue;
+            }
+            let bytes = std::fs::read(&path)
+                .with_context(|| format!("could not read '{}'", path.display()))?;
+            let Some(entry) = entries.first() else {
+                continue;
+            };
+            if let Some(sha256) = &entry.sha256 {
+                if crate::prebuilt::sha256_hex(&bytes) != *sha256 {
+                    problems.push(format!("the hash of '{source_path}' doesn't match"));
+                }
+            }
+            if let Some(packed) = &entry.packed {
+                let pack_path = resolve(&packed.path);
+                if pack_path.is_file() && !packs.contains_key(&pack_path) {
+                    let pack = std::fs::read(&pack_path)
+                        .with_context(|| format!("could not read '{}'", pack_path.display()))?;
+                    packs.insert(pack_path.clone(), pack);
+                }
+                if let Some(pack) = packs.get(&pack_path) {
+                    let start = usize::try_from(packed.offset)?;
+                    let end = start.saturating_add(usize::try_from(packed.length)?);
+                    if pack.get(start..end).map(crate::prebuilt::sha256_hex)
+                        != Some(packed.sha256.clone())
+                    {
+                        problems.push(format!(
+                            "the hash of '{source_path}' in '{}' doesn't match",
+                            packed.path
+                        ));
+                    }
+                }
+            }
+
+            let module = match &entry.compression {
+                Some(compression) => crate::compress::decompress(&bytes, compression)
+                    .with_context(|| format!("could not decompress '{}'", path.display()))?,
+                None => bytes,
+            };
+            let entry_points = entries
+                .iter()
+                .map(|module_entry| module_entry.entry_point.clone())
+                .collect::<Vec<_>>();
+            if let Err(error) =
+                crate::validate::bytes(module, &path, &entry_points, &self.build_args)
+            {
+                problems.push(format!("{error:#}"));
+            }
+        }
+        Ok(problems)
+    }
+}
+
+/// The files listed in the manifest that don't exist.
+fn missing_files(
+    entry_points: &[Linkage],
+    resolve: impl Fn(&str) -> std::path::PathBuf,
+) -> Vec<String> {
+    let mut checked = std::collections::HashSet::new();
+    let mut problems = Vec::new();
+    for entry in entry_points {
+        for path in entry.paths() {
+            if checked.insert(path) && !resolve(path).is_file() {
+                problems.push(format!(
+                    "'{path}', of the entry point `{}`, doesn't exist",
+                    entry.entry_point
+                ));
+            }
+        }
+    }
+    problems
+}
+
+/// The WGSL entry point names that more than one entry point of the same variant has.
+fn duplicate_wgsl_entry_points(entry_points: &[Linkage]) -> Vec<String> {
+    let mut names = std::collections::HashMap::new();
+    let mut problems = Vec::new();
+    for entry in entry_points {
+        let key = (entry.variant.as_deref(), entry.wgsl_entry_point.as_str());
+        if let Some(other) = names.insert(key, entry.entry_point.as_str()) {
+            problems.push(format!(
+                "`{}` is the WGSL entry point name of both `{other}` and `{}`",
+                entry.wgsl_entry_point, entry.entry_point
+            ));
+        }
+    }
+    problems
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use clap::Parser as _;
+
+    #[test_log::test]
+    fn finding_problems_with_a_manifest() {
+        let dir = crate::cache_dir().unwrap().join("verify");
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut builder = rspirv::dr::Builder::new();
+        builder.capability(rspirv::spirv::Capability::Shader);
+        builder.memory_model(
+            rspirv::spirv::AddressingModel::Logical,
+            rspirv::spirv::MemoryModel::GLSL450,
+        );
+        let module = builder.module();
+        let bytes = rspirv::binary::Assemble::assemble(&module)
+            .iter()
+            .flat_map(|word| word.to_le_bytes())
+            .collect::<Vec<_>>();
+        std::fs::write(dir.join("shader.spv"), &bytes).unwrap();
+
+        let entry = |entry_point: &str, wgsl_entry_point: &str| Linkage {
+            wgsl_entry_point: wgsl_entry_point.to_owned(),
+            sha256: Some(crate::prebuilt::sha256_hex(&bytes)),
+            ..Linkage::new(entry_point, "shader.spv")
+        };
+        let manifest = Manifest::new(vec![
+            entry("main_fs", "main_fs"),
+            entry("main_vs", "main_vs"),
+        ]);
+        let verify = Verify::parse_from([
+            "verify",
+            dir.to_str().unwrap(),
+            "--shader-crate",
+            dir.to_str().unwrap(),
+        ]);
+        let manifest_path = dir.join("manifest.json");
+        assert!(verify
+            .problems(&manifest, &manifest_path)
+            .unwrap()
+            .is_empty());
+
+        let mut broken = manifest;
+        if let [first, second] = broken.entry_points.as_mut_slice() {
+            first.wgsl_source_path = Some("main_fs.wgsl".to_owned());
+            first.sha256 = Some("0".repeat(64));
+            "main_fs".clone_into(&mut second.wgsl_entry_point);
+        }
+        assert_eq!(
+            verify.problems(&broken, &manifest_path).unwrap(),
+            [
+                "'main_fs.wgsl', of the entry point `main_fs`, doesn't exist",
+                "`main_fs` is the WGSL entry point name of both `main_fs` and `main_vs`",
+                "the hash of 'shader.spv' doesn't match",
+            ]
+        );
+        crate::test::tests_teardown();
+    }
+}
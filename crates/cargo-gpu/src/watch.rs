@@ -0,0 +1,320 @@
+//! `cargo gpu build --watch`, rebuild the shaders whenever any of their inputs change.
+//!
+//! Every file in the shader crate, its path dependencies and the workspace's `Cargo.lock` is
+//! polled for changes, except for editor temporary files, `target/`, anything matching
+//! `--watch-ignore` and, by default, anything ignored by `.gitignore`. A change only triggers a
+//! rebuild once the files have stopped changing for `--watch-debounce-ms`. Every rebuild is a
+//! normal build, so the manifest and everything else in the output dir is kept up to date, and
+//! running programs can be told about it with `--on-success` or [`crate::notify`].
+
+use crate::build::Build;
+
+/// How often the inputs are checked for changes.
+const POLL_INTERVAL: core::time::Duration = core::time::Duration::from_millis(500);
+
+/// Files that are always ignored, mostly the temporary files that editors write when saving.
+const DEFAULT_IGNORES: &[&str] = &[
+    "target/", ".git/", "*~", "*.swp", "*.swx", "*.tmp", ".#*", "#*#", "4913",
+];
+
+/// The modification time of every input file, `None` for files that have been removed.
+type Snapshot = std::collections::BTreeMap<std::path::PathBuf, Option<std::time::SystemTime>>;
+
+/// A `.gitignore`-style pattern.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct IgnoreRule {
+    /// The directory that the pattern is relative to.
+    base: std::path::PathBuf,
+    /// The glob, without any leading or trailing `/`.
+    pattern: String,
+    /// Whether the pattern has to match the whole path relative to `base`, rather than just a
+    /// file name, because it contains a `/`.
+    is_anchored: bool,
+    /// Whether the pattern only matches directories, because it ends with a `/`.
+    is_dir_only: bool,
+}
+
+impl IgnoreRule {
+    /// Parse a line of a `.gitignore`, or a `--watch-ignore` glob. Blank lines, comments and
+    /// negated patterns, which aren't supported, give `None`.
+    fn parse(base: &std::path::Path, line: &str) -> Option<Self> {
+        let trimmed = line.trim_end();
+        if trimmed.is_empty() || trimmed.starts_with('#') || trimmed.starts_with('!') {
+            return None;
+        }
+        let is_dir_only = trimmed.ends_with('/');
+        let without_trailing = trimmed.trim_end_matches('/');
+        let is_anchored = without_trailing.contains('/');
+        Some(Self {
+            base: base.to_path_buf(),
+            pattern: without_trailing.trim_start_matches('/').to_owned(),
+            is_anchored,
+            is_dir_only,
+        })
+    }
+
+    /// Whether this rule ignores `path`.
+    fn is_match(&self, path: &std::path::Path, is_dir: bool) -> bool {
+        if self.is_dir_only && !is_dir {
+            return false;
+        }
+        let Ok(relative) = path.strip_prefix(&self.base) else {
+            return false;
+        };
+        if self.is_anchored {
+            let relative_path = relative
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            crate::build::glob_match(&self.pattern, &relative_path)
+        } else {
+            path.file_name().is_some_and(|name| {
+                crate::build::glob_match(&self.pattern, &name.to_string_lossy())
+            })
+        }
+    }
+}
+
+/// What's watched, and how.
+struct Watcher {
+    /// The shader crate's build, which is cloned for each rebuild.
+    build: Build,
+    /// The files and directories that are watched.
+    roots: Vec<std::path::PathBuf>,
+    /// Rules for ignoring files, other than those in `.gitignore`s.
+    ignores: Vec<IgnoreRule>,
+    /// Whether files ignored by `.gitignore` are also ignored by the watcher.
+    is_respecting_gitignore: bool,
+    /// Where to send the manifest after each successful rebuild, from `--notify`.
+    notifier: Option<crate::notify::Notifier>,
+}
+
+/// Watch the inputs of `build`, which has already been built once, and rebuild on changes.
+///
+/// Failed rebuilds are reported, but watching carries on. This never returns unless the inputs
+/// can't be found.
+///
+/// # Errors
+/// If the shader crate or its inputs can't be found.
+pub fn watch(mut build: Build) -> anyhow::Result<()> {
+    build.build_args.watch = false;
+    build.install.spirv_install.shader_crate =
+        build.install.spirv_install.shader_crate.canonicalize()?;
+    build.build_args.output_dir = build.build_args.output_dir.canonicalize()?;
+
+    let shader_crate = build.install.spirv_install.shader_crate.clone();
+    // The defaults apply to path dependencies too, so they aren't relative to the shader crate.
+    let ignores = DEFAULT_IGNORES
+        .iter()
+        .filter_map(|glob| IgnoreRule::parse(std::path::Path::new(""), glob))
+        .chain(
+            build
+                .build_args
+                .watch_ignore
+                .iter()
+                .filter_map(|glob| IgnoreRule::parse(&shader_crate, glob)),
+        )
+        .collect();
+    let debounce = core::time::Duration::from_millis(build.build_args.watch_debounce_ms);
+    let notifier = build
+        .build_args
+        .notify
+        .as_deref()
+        .map(crate::notify::Notifier::bind)
+        .transpose()?;
+    let mut watcher = Watcher {
+        notifier,
+        is_respecting_gitignore: !build.build_args.watch_no_gitignore,
+        roots: crate::metadata::Metadata::local_inputs(&shader_crate)?,
+        ignores,
+        build,
+    };
+
+    crate::user_output!("Watching shader crate and recompiling on changes...\n");
+    let mut last = watcher.snapshot()?;
+    loop {
+        std::thread::sleep(POLL_INTERVAL);
+        let mut current = watcher.snapshot()?;
+        if current == last {
+            continue;
+        }
+        // Wait for the files to settle, eg for an editor to finish saving or `git checkout` to
+        // finish switching branches.
+        loop {
+            std::thread::sleep(debounce);
+            let settled = watcher.snapshot()?;
+            if settled == current {
+                break;
+            }
+            current = settled;
+        }
+
+        rebuild(&watcher.build, watcher.notifier.as_ref());
+        // A rebuild may have been caused by a change to the dependencies, so look for the inputs
+        // again.
+        match crate::metadata::Metadata::local_inputs(&shader_crate) {
+            Ok(roots) => watcher.roots = roots,
+            Err(error) => log::warn!("couldn't find the shader crate's inputs: {error:?}"),
+        }
+        last = watcher.snapshot()?;
+    }
+}
+
+impl Watcher {
+    /// The current state of every watched file.
+    fn snapshot(&self) -> anyhow::Result<Snapshot> {
+        let mut files = Vec::new();
+        for root in &self.roots {
+            if root.is_dir() {
+                let mut ignores = self.ignores.clone();
+                if self.is_respecting_gitignore {
+                    for ancestor in root.ancestors().skip(1) {
+                        ignores.extend(gitignore_rules(ancestor));
+                        if ancestor.join(".git").exists() {
+                            break;
+                        }
+                    }
+                }
+                self.files_in_dir(&mut files, root, ignores)?;
+            } else {
+                files.push(root.clone());
+            }
+        }
+        Ok(files
+            .into_iter()
+            .map(|file| {
+                let modified = std::fs::metadata(&file)
+                    .and_then(|metadata| metadata.modified())
+                    .ok();
+                (file, modified)
+            })
+            .collect())
+    }
+
+    /// Every file in `dir` that isn't ignored, other than the output dir.
+    fn files_in_dir(
+        &self,
+        files: &mut Vec<std::path::PathBuf>,
+        dir: &std::path::Path,
+        mut ignores: Vec<IgnoreRule>,
+    ) -> anyhow::Result<()> {
+        if self.is_respecting_gitignore {
+            ignores.extend(gitignore_rules(dir));
+        }
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            // It may have been removed since it was found.
+            return Ok(());
+        };
+        for maybe_entry in entries {
+            let path = maybe_entry?.path();
+            let is_dir = path.is_dir();
+            if path == self.build.build_args.output_dir
+                || ignores.iter().any(|rule| rule.is_match(&path, is_dir))
+            {
+                continue;
+            }
+            if is_dir {
+                self.files_in_dir(files, &path, ignores.clone())?;
+            } else {
+                files.push(path);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The rules in `dir`'s `.gitignore`, if it has one.
+fn gitignore_rules(dir: &std::path::Path) -> Vec<IgnoreRule> {
+    std::fs::read_to_string(dir.join(".gitignore"))
+        .map(|contents| {
+            contents
+                .lines()
+                .filter_map(|line| IgnoreRule::parse(dir, line))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Build again, and if it worked, run the `--on-success` command and notify any `--notify`
+/// clients.
+fn rebuild(build: &Build, notifier: Option<&crate::notify::Notifier>) {
+    crate::user_output!("Shader inputs changed, recompiling...\n");
+    // `Build::run` changes some of its args, so always start from the originals.
+    match build.clone().run() {
+        Ok(_) => {
+            let manifest_path = build
+                .build_args
+                .output_dir
+                .join(&build.build_args.manifest_file);
+            crate::user_output!("Rebuilt {}\n", manifest_path.display());
+            if let Some(command) = &build.build_args.on_success {
+                run_on_success(command, &manifest_path);
+            }
+            if let Some(clients) = notifier {
+                // Compacted, so that it fits on one line for Unix socket clients.
+                let compacted = std::fs::read(&manifest_path)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|bytes| Ok(serde_json::from_slice::<serde_json::Value>(&bytes)?));
+                match compacted {
+                    Ok(manifest) => clients.send(&manifest.to_string()),
+                    Err(error) => log::warn!("couldn't read the manifest to send it: {error}"),
+                }
+            }
+        }
+        Err(error) => {
+            crate::user_output!("Rebuild failed: {error:?}\n");
+        }
+    }
+}
+
+/// Run the `--on-success` shell command, with the manifest's path in the `CARGO_GPU_MANIFEST`
+/// environment variable.
+fn run_on_success(command: &str, manifest_path: &std::path::Path) {
+    log::debug!("running on-success command `{command}`");
+    let status = crate::hooks::shell(command)
+        .env("CARGO_GPU_MANIFEST", manifest_path)
+        .stdout(crate::output::child_stdout())
+        .status();
+    match status {
+        Ok(exit) if exit.success() => {}
+        Ok(exit) => {
+            crate::user_output!("`{command}` failed with {exit}\n");
+        }
+        Err(error) => {
+            crate::user_output!("Couldn't run `{command}`: {error}\n");
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_log::test]
+    fn matching_ignore_rules() {
+        let base = std::path::Path::new("/shaders");
+        let is_ignored = |line: &str, path: &str, is_dir: bool| {
+            IgnoreRule::parse(base, line)
+                .unwrap()
+                .is_match(std::path::Path::new(path), is_dir)
+        };
+
+        assert!(is_ignored("*.swp", "/shaders/src/.lib.rs.swp", false));
+        assert!(!is_ignored("*.swp", "/shaders/src/lib.rs", false));
+        assert!(is_ignored("target/", "/shaders/target", true));
+        assert!(!is_ignored("target/", "/shaders/target", false));
+        assert!(is_ignored("/assets/*.png", "/shaders/assets/a.png", false));
+        assert!(!is_ignored(
+            "/assets/*.png",
+            "/shaders/src/assets/a.png",
+            false
+        ));
+        assert!(!is_ignored("*.swp", "/elsewhere/.lib.rs.swp", false));
+        assert!(IgnoreRule::parse(std::path::Path::new(""), "*~")
+            .unwrap()
+            .is_match(std::path::Path::new("/anywhere/lib.rs~"), false));
+        assert!(IgnoreRule::parse(base, "# comment").is_none());
+        assert!(IgnoreRule::parse(base, "!keep.rs").is_none());
+    }
+}
@@ -0,0 +1,112 @@
+//! `workspace-manifest.json`, the manifests of every shader crate built into the same output dir,
+//! namespaced by crate.
+//!
+//! Each build adds its crate to the one in its output dir, replacing what was there for that
+//! crate, so it collects the crates of a `--workspace` build and of separate builds alike.
+
+use anyhow::Context as _;
+use spirv_builder_cli::{args::ManifestPaths, Linkage, WorkspaceManifest};
+
+/// The name of the workspace manifest, in the output dir.
+pub const FILE: &str = "workspace-manifest.json";
+
+/// Add the entry points of the shader crate `name` to the workspace manifest in `output_dir`.
+pub fn update(
+    output_dir: &std::path::Path,
+    name: &str,
+    shader_crate: &std::path::Path,
+    linkage: &[Linkage],
+    paths: ManifestPaths,
+) -> anyhow::Result<std::path::PathBuf> {
+    let path = std::path::absolute(output_dir.join(FILE))?;
+    // Several shader crates can be built into the same output dir at once.
+    let _lock = crate::lock(&path)?;
+    let mut manifest = read(&path)?;
+    manifest.schema_version = spirv_builder_cli::MANIFEST_SCHEMA_VERSION;
+    let workspace_paths = match paths {
+        ManifestPaths::RelativeToCrate | ManifestPaths::RelativeToManifest => {
+            ManifestPaths::RelativeToManifest
+        }
+        ManifestPaths::Absolute => ManifestPaths::Absolute,
+    };
+    manifest.crates.insert(
+        name.to_owned(),
+        crate::build::manifest_with_paths(
+            linkage,
+            workspace_paths,
+            shader_crate,
+            path.parent().unwrap_or(output_dir),
+        ),
+    );
+    crate::atomic::write(&path, serde_json::to_string_pretty(&manifest)?)
+        .with_context(|| format!("could not write '{}'", path.display()))?;
+    log::info!(
+        "added `{name}` to the workspace manifest '{}'",
+        path.display()
+    );
+    crate::output::emit(&crate::output::Event::ManifestWritten { path: path.clone() });
+    Ok(path)
+}
+
+/// Read the workspace manifest at `path`, or an empty one if there isn't one yet.
+fn read(path: &std::path::Path) -> anyhow::Result<WorkspaceManifest> {
+    if !path.is_file() {
+        return Ok(WorkspaceManifest::default());
+    }
+    let json = std::fs::read_to_string(path)
+        .with_context(|| format!("could not read '{}'", path.display()))?;
+    WorkspaceManifest::from_json(&json).with_context(|| {
+        format!(
+            "could not parse '{}', delete it to start a new one",
+            path.display()
+        )
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test_log::test]
+    fn namespacing_entry_points_by_crate() {
+        let dir = crate::cache_dir().unwrap().join("workspace-manifest");
+        let output_dir = dir.join("shaders");
+        std::fs::create_dir_all(&output_dir).unwrap();
+        let linkage = |entry_point: &str| vec![Linkage::new(entry_point, "shaders/module.spv")];
+
+        update(
+            &output_dir,
+            "lights",
+            &dir,
+            &linkage("main_fs"),
+            ManifestPaths::RelativeToCrate,
+        )
+        .unwrap();
+        update(
+            &output_dir,
+            "sky",
+            &dir,
+            &linkage("main_vs"),
+            ManifestPaths::RelativeToCrate,
+        )
+        .unwrap();
+        let path = update(
+            &output_dir,
+            "lights",
+            &dir,
+            &linkage("main_cs"),
+            ManifestPaths::RelativeToCrate,
+        )
+        .unwrap();
+
+        let manifest = read(&path).unwrap();
+        assert_eq!(
+            manifest
+                .entry_points()
+                .map(|(name, entry)| format!("{name} {} {}", entry.entry_point, entry.source_path))
+                .collect::<Vec<_>>(),
+            ["lights main_cs module.spv", "sky main_vs module.spv"]
+        );
+        crate::test::tests_teardown();
+    }
+}
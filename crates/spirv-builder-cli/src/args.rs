@@ -1,7 +1,11 @@
 #[cfg(feature = "spirv-builder-pre-cli")]
 use spirv_0_2 as spirv;
 
-#[cfg(any(feature = "spirv-builder-0_10", feature = "rspirv-latest"))]
+#[cfg(any(
+    feature = "spirv-builder-0_10",
+    feature = "spirv-builder-0_11",
+    feature = "rspirv-latest"
+))]
 use spirv_0_3 as spirv;
 
 use std::str::FromStr as _;
@@ -26,12 +30,257 @@ pub enum SpirvMetadata {
     Full,
 }
 
+/// Shader languages that the compiled SPIR-V can be transpiled to.
+#[derive(
+    clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Hash, serde::Deserialize, serde::Serialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum TranspileTarget {
+    /// WebGPU Shading Language.
+    Wgsl,
+    /// HLSL, for Direct3D.
+    Hlsl,
+    /// GLSL 4.50, one file per entry point.
+    Glsl,
+    /// Metal Shading Language.
+    Msl,
+}
+
+/// Host code that can be generated from the reflected shaders.
+#[derive(
+    clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum CodegenTarget {
+    /// A Rust module with `wgpu::BindGroupLayoutDescriptor` constants.
+    Wgpu,
+    /// A Rust module with `ash::vk::DescriptorSetLayoutBinding` tables and push constant ranges.
+    Ash,
+    /// A C header with entry point names, module paths and binding indices.
+    #[serde(rename = "c-header")]
+    CHeader,
+}
+
+/// How modules are put in the output dir.
+#[derive(
+    clap::ValueEnum,
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    serde::Deserialize,
+    serde::Serialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputLink {
+    /// Copy them (the default).
+    #[default]
+    Copy,
+    /// Hard link them, falling back to copying across filesystems.
+    Hardlink,
+    /// Symlink them, so that the output dir always has the freshest modules.
+    Symlink,
+}
+
+/// What the paths in the manifest are relative to.
+#[derive(
+    clap::ValueEnum,
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    serde::Deserialize,
+    serde::Serialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum ManifestPaths {
+    /// Relative to the shader crate (the default).
+    #[default]
+    RelativeToCrate,
+    /// Relative to the directory that the manifest is in.
+    RelativeToManifest,
+    /// Absolute paths.
+    Absolute,
+}
+
+/// How `--embed` puts the modules' bytes in the generated Rust.
+#[derive(
+    clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum EmbedMode {
+    /// `include_bytes!` the module files, which have to be there when the host crate is compiled
+    /// (the default).
+    IncludeBytes,
+    /// Literal byte arrays, so that the generated file stands on its own.
+    Literal,
+}
+
+/// Formats that the manifest of built shaders can be written in.
+#[derive(
+    clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum ManifestFormat {
+    /// The `manifest.json` file (the default).
+    Json,
+    /// A TOML file, with an `[[entry_points]]` table for each entry point.
+    Toml,
+    /// A RON file, for RON based tooling like Bevy's.
+    Ron,
+    /// A Rust module with a constant for each entry point's name and module, for `include!`ing.
+    Rust,
+    /// A `.bevy.ron` file for Bevy's asset system, with each module's path relative to the
+    /// `assets/` directory and a label for each entry point.
+    Bevy,
+}
+
+/// The steps that the compiled modules go through on their way to the output dir and the
+/// manifest, see `--output-processors`.
+#[derive(
+    clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Hash, serde::Deserialize, serde::Serialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum Processor {
+    /// Copy, or link, each module into the output dir. It always comes first.
+    Copy,
+    /// Optimize the modules with `spirv-opt`, for `--opt-level` and `--opt-pass`.
+    Optimize,
+    /// Bake the `--spec-constant` defaults into the modules.
+    SpecConstants,
+    /// Rename the entry points in the modules, for `--rename-entry-point`.
+    RenameEntryPoints,
+    /// Strip the debug info that `--reproducible` builds can't have.
+    Reproducible,
+    /// Validate the modules with `spirv-val`, unless `--no-validate` is given.
+    Validate,
+    /// Reflect on the entry points' bindings, capabilities and spec constants, for the manifest.
+    Reflect,
+    /// Strip the modules, for `--strip`.
+    Strip,
+    /// Transpile the modules, for `--transpile`.
+    Transpile,
+    /// Gather the modules' statistics, for `--stats`.
+    Stats,
+    /// Compress the modules, for `--compress`.
+    Compress,
+    /// Pack the modules into one file, for `--pack`.
+    Pack,
+    /// Generate code from the manifest, for `--codegen`.
+    Codegen,
+}
+
+impl Processor {
+    /// Every processor, in the order that they run in by default.
+    pub const DEFAULT_ORDER: [Self; 13] = [
+        Self::Copy,
+        Self::Optimize,
+        Self::SpecConstants,
+        Self::RenameEntryPoints,
+        Self::Reproducible,
+        Self::Validate,
+        Self::Reflect,
+        Self::Strip,
+        Self::Transpile,
+        Self::Stats,
+        Self::Compress,
+        Self::Pack,
+        Self::Codegen,
+    ];
+}
+
+/// Schemes that the emitted modules can be compressed with.
+#[derive(
+    clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    /// Zstandard, written as `.spv.zst`.
+    Zstd,
+}
+
+impl Compression {
+    /// The scheme's name, as recorded in the manifest.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Zstd => "zstd",
+        }
+    }
+
+    /// The extension that's added to compressed modules, after `.spv`.
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::Zstd => "zst",
+        }
+    }
+}
+
+/// What a shader does when it panics. Only `spirv-builder` 0.11 and newer can change it.
+#[derive(
+    clap::ValueEnum,
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    serde::Deserialize,
+    serde::Serialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum ShaderPanicStrategy {
+    /// Return from the entry point straight away (the default).
+    #[default]
+    SilentExit,
+    /// Print the panic message, the entry point's inputs and a backtrace with `debugPrintf`, then
+    /// exit. This needs the `SPV_KHR_non_semantic_info` extension and a validation layer that
+    /// shows `debugPrintf` output.
+    DebugPrintf,
+}
+
+/// A build of the shader crate with its own set of Cargo features, see `--variant`.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct Variant {
+    /// The variant's name, which its output goes under.
+    pub name: String,
+    /// Cargo features to enable as well as those from `--features`.
+    #[serde(default)]
+    pub features: Vec<String>,
+    /// Don't enable the shader crate's default features.
+    #[serde(default)]
+    pub no_default_features: bool,
+}
+
+/// How much to optimize the compiled SPIR-V with `spirv-opt`.
+#[derive(
+    clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum OptLevel {
+    /// Don't run `spirv-opt` (the default).
+    None,
+    /// Optimize for performance, `spirv-opt -O`.
+    Performance,
+    /// Optimize for size, `spirv-opt -Os`.
+    Size,
+}
+
 #[derive(clap::Parser, Debug, Clone, serde::Deserialize, serde::Serialize)]
 pub struct BuildArgs {
     /// Path to the output directory for the compiled shaders.
     #[clap(long, short, default_value = "./")]
     pub output_dir: std::path::PathBuf,
 
+    /// How to put the compiled modules in the output dir. Linking saves copying large
+    /// multimodule builds, but modules that `--opt-level` or `--reproducible` change are always
+    /// written as new files.
+    #[clap(long, value_enum, default_value = "copy")]
+    pub output_link: OutputLink,
+
     /// Watch the shader crate directory and automatically recompile on changes.
     #[clap(long, short, action)]
     pub watch: bool,
@@ -49,11 +298,16 @@ pub struct BuildArgs {
     #[arg(hide(true), default_value = "spirv-unknown-vulkan1.2")]
     pub target: String,
 
-    /// Shader target.
-    // TODO: how to list the available options? Would be nice to have a command like:
-    //   `cargo gpu show targets`
+    /// Shader target. Can be given multiple times to build for each target, in which case each
+    /// target's output goes in its own subdirectory of the output dir, named after the target. See
+    /// `cargo gpu show targets` for the available targets, or use `auto` to pick the newest
+    /// Vulkan target that the local GPU supports.
     #[clap(long, default_value = "spirv-unknown-vulkan1.2")]
-    pub shader_target: String,
+    #[serde(
+        deserialize_with = "deserialize_shader_targets",
+        serialize_with = "serialize_shader_targets"
+    )]
+    pub shader_target: Vec<String>,
 
     /// Treat warnings as errors during compilation.
     #[arg(long, default_value = "false")]
@@ -69,7 +323,8 @@ pub struct BuildArgs {
     pub capability: Vec<spirv::Capability>,
 
     /// Enables the provided SPIR-V extensions.
-    /// See <https://github.com/KhronosGroup/SPIRV-Registry> for all extensions
+    /// See `cargo gpu show extensions` or <https://github.com/KhronosGroup/SPIRV-Registry> for all
+    /// extensions
     #[arg(long)]
     pub extension: Vec<String>,
 
@@ -81,6 +336,12 @@ pub struct BuildArgs {
     #[arg(long, value_parser=Self::spirv_metadata, default_value = "none")]
     pub spirv_metadata: SpirvMetadata,
 
+    /// Keep the Rust source files and lines in the modules, so that tools like RenderDoc and
+    /// Nsight can show shaders' Rust source. Implies `--spirv-metadata full`.
+    #[arg(long, default_value = "false")]
+    #[serde(default)]
+    pub debug_info: bool,
+
     /// Allow store from one struct type to a different type with compatible layout and members.
     #[arg(long, default_value = "false")]
     pub relax_struct_store: bool,
@@ -119,9 +380,343 @@ pub struct BuildArgs {
     ///Renames the manifest.json file to the given name
     #[clap(long, short, default_value = "manifest.json")]
     pub manifest_file: String,
+
+    /// The formats to write the manifest in. Formats other than JSON are written next to the
+    /// JSON manifest, with their own extension, eg `.toml`.
+    #[clap(long, value_enum, value_delimiter = ',', default_value = "json")]
+    pub manifest_format: Vec<ManifestFormat>,
+
+    /// What the paths in the JSON, TOML and RON manifests are relative to, so that consumers
+    /// running from other directories can find the modules.
+    #[clap(long, value_enum, default_value = "relative-to-crate")]
+    pub manifest_paths: ManifestPaths,
+
+    /// Also transpile the compiled SPIR-V to the given shader languages. The transpiled files are
+    /// written alongside the `.spv` files and listed in the manifest.
+    #[clap(long, value_enum, value_delimiter = ',', visible_alias = "emit")]
+    pub transpile: Vec<TranspileTarget>,
+
+    /// How to name the modules copied to the output dir. Placeholders are `{name}`, the module's
+    /// name from `rust-gpu`, `{entry}`, the entry point's name for modules with a single entry
+    /// point, `{target}`, the shader target, and `{hash}`, a hash of the module's contents.
+    #[clap(long, default_value = "{name}.spv")]
+    pub output_file_template: String,
+
+    /// Only copy, process and list the given entry points in the manifest, by their full path,
+    /// eg "lighting::main_fs", or just their function name. Can be given multiple times.
+    #[clap(long)]
+    pub entry_point: Vec<String>,
+
+    /// Leave out entry points matching these patterns, eg "debug_*" or "tests::*". Patterns are
+    /// matched against both the full path of the entry point and its function name.
+    #[clap(long, value_delimiter = ',')]
+    pub exclude_entry_points: Vec<String>,
+
+    /// Rename an entry point, in both its module and the manifest, eg
+    /// `--rename-entry-point shaders::main_fs=fragment_main`. The old name is matched like
+    /// `--entry-point`. Can be given multiple times.
+    #[clap(long = "rename-entry-point", value_parser = Self::key_value, value_name = "OLD=NEW")]
+    #[serde(
+        default,
+        deserialize_with = "deserialize_table",
+        serialize_with = "serialize_table"
+    )]
+    pub entry_point_renames: Vec<(String, String)>,
+
+    /// Generate host code describing the built shaders' entry points and bindings. The generated
+    /// files are written next to the manifest, eg `manifest.wgpu.rs`.
+    #[clap(long, value_enum, value_delimiter = ',')]
+    pub codegen: Vec<CodegenTarget>,
+
+    /// Generate a Rust module that embeds every entry point's module, for host crates that want
+    /// no shader files to read or ship at runtime. It's written next to the manifest, eg
+    /// `manifest.embed.rs`.
+    #[clap(
+        long,
+        value_enum,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "include-bytes"
+    )]
+    pub embed: Option<EmbedMode>,
+
+    /// Optimize the compiled SPIR-V with `spirv-opt`, which must be installed.
+    #[clap(long, value_enum, default_value = "none")]
+    pub opt_level: OptLevel,
+
+    /// Extra `spirv-opt` passes to run, by their `spirv-opt` flag name, eg "merge-blocks".
+    /// These run after any passes from `--opt-level`.
+    #[clap(long)]
+    pub opt_pass: Vec<String>,
+
+    /// Strip the names, decoration strings and debug instructions from the modules with
+    /// `spirv-opt`, which must be installed, to make them as small as they can be. The manifest
+    /// still has the reflected names.
+    #[clap(long)]
+    #[serde(default)]
+    pub strip: bool,
+
+    /// Only warn about modules that fail `spirv-val` validation, instead of failing the build.
+    #[clap(long)]
+    pub no_validate: bool,
+
+    /// Rebuild the shaders even if none of their inputs have changed since the last build.
+    #[clap(long)]
+    pub force_rebuild: bool,
+
+    /// A shared cache of compiled shaders, either an HTTP(S) URL or a directory. Builds whose
+    /// inputs have been built before are downloaded from it instead of being compiled.
+    #[clap(long)]
+    pub remote_cache: Option<String>,
+
+    /// Upload compiled shaders to `--remote-cache`, eg from CI.
+    #[clap(long)]
+    pub remote_cache_upload: bool,
+
+    /// Print statistics about each compiled module, its size, instruction count, capabilities and
+    /// compile time, and add them to the manifest.
+    #[clap(long)]
+    pub stats: bool,
+
+    /// Make the build byte-for-byte reproducible on other machines: require a pinned `rust-gpu`,
+    /// ignore environment variables that change compilation, strip file paths and other debug
+    /// info from the modules, and write the hashes of every input and output to
+    /// `build-info.json` in the output dir. The paths that non-semantic debug info keeps are
+    /// remapped from the workspace and `cargo`'s and `rustup`'s homes, but not from elsewhere.
+    #[clap(long)]
+    pub reproducible: bool,
+
+    /// Compress the emitted modules, eg to shrink the shaders shipped to web and mobile. The
+    /// modules are written as `.spv.zst`, and the manifest records the scheme along with their
+    /// compressed and uncompressed sizes.
+    #[clap(long, value_enum)]
+    pub compress: Option<Compression>,
+
+    /// Also pack every module into a single file with this name in the output dir, so that they
+    /// can all be loaded at once. The offset, length and SHA-256 of each entry point's module in
+    /// the pack are recorded in the manifest.
+    #[clap(long)]
+    pub pack: Option<String>,
+
+    /// Write a Makefile-style depfile to this path, listing every file that the shaders were
+    /// built from according to `rustc`'s dep-info, so that `build.rs` scripts, Ninja and Bazel
+    /// can declare accurate rerun dependencies. It's only written by a compile, so it turns off
+    /// `--remote-cache` downloads.
+    #[clap(long)]
+    pub emit_depfile: Option<std::path::PathBuf>,
+
+    /// For running from a `build.rs`: print `cargo:rerun-if-changed` directives for every file
+    /// that the shaders are built from and `cargo:warning` directives for compiler warnings, and
+    /// only log the usual progress messages.
+    #[clap(long)]
+    pub build_script_mode: bool,
+
+    /// With `--watch`, a shell command to run after every successful rebuild, eg to tell an engine
+    /// to reload its shaders. The manifest's path is in the `CARGO_GPU_MANIFEST` environment
+    /// variable.
+    #[clap(long)]
+    pub on_success: Option<String>,
+
+    /// With `--watch`, how many milliseconds the shader crate's files have to stay unchanged
+    /// before rebuilding, so that a burst of saves only causes one rebuild.
+    #[clap(long, default_value = "300")]
+    pub watch_debounce_ms: u64,
+
+    /// With `--watch`, ignore changes to files matching this `.gitignore`-style glob, relative
+    /// to the shader crate, eg `assets/*.png`. Can be given multiple times. Editor temporary
+    /// files and `target/` are always ignored.
+    #[clap(long)]
+    pub watch_ignore: Vec<String>,
+
+    /// With `--watch`, don't ignore the files that are ignored by `.gitignore`.
+    #[clap(long)]
+    pub watch_no_gitignore: bool,
+
+    /// With `--watch`, send the manifest's JSON to clients of this address after every
+    /// successful rebuild, so that running programs can hot-reload their shaders. Either a
+    /// WebSocket, `ws://127.0.0.1:PORT`, or a Unix socket, `unix:PATH`, which is sent one
+    /// manifest per line.
+    #[clap(long)]
+    pub notify: Option<String>,
+
+    /// The `target` dir for compiling the shader crate, eg on a fast scratch disk. Defaults to
+    /// `CARGO_TARGET_DIR`, or else the shader crate's own `target` dir. `rust-gpu` builds in its
+    /// `spirv-builder` subdirectory.
+    #[clap(long)]
+    pub target_dir: Option<std::path::PathBuf>,
+
+    /// Extra `rustc` flags for compiling the shader crate, separated by spaces, eg
+    /// "-Cdebug-assertions=on". They're added to `rust-gpu`'s own, through `RUSTGPU_RUSTFLAGS`.
+    #[clap(long, allow_hyphen_values = true)]
+    pub rustflags: Option<String>,
+
+    /// Extra options for `rust-gpu`'s codegen backend, separated by spaces, eg
+    /// "--no-early-report-zombies". They're passed on through `RUSTGPU_CODEGEN_ARGS`.
+    #[clap(long, allow_hyphen_values = true)]
+    pub codegen_args: Option<String>,
+
+    /// Extra flags for the `cargo build` of the shader crate, separated by spaces, eg
+    /// "--locked -Zbuild-std=core". For flags that `cargo gpu` doesn't have an option for.
+    /// They're passed on through `RUSTGPU_CARGOFLAGS`, so a flag can't contain spaces.
+    #[clap(long, allow_hyphen_values = true)]
+    pub cargo_flags: Option<String>,
+
+    /// Extra flags for the `cargo build` of the shader crate, like `--cargo-flags`, given after
+    /// `--`, eg `cargo gpu build -- --locked`.
+    #[clap(last = true)]
+    pub cargo_args: Vec<String>,
+
+    /// What shaders do when they panic, only supported by `spirv-builder` 0.11 and newer.
+    #[clap(long, value_enum, default_value = "silent-exit")]
+    #[serde(default)]
+    pub shader_panic_strategy: ShaderPanicStrategy,
+
+    /// Build with the `[package.metadata.rust-gpu.profile.<PROFILE>]` config, which overrides
+    /// the rest of the shader crate's config.
+    #[clap(long)]
+    #[serde(default)]
+    pub profile: Option<String>,
+
+    /// Also build the shader crate with a different set of Cargo features, eg
+    /// `--variant shadows:features=pcf,soft` or `--variant minimal:no-default-features`. Each
+    /// variant's output goes in a subdirectory of the output dir named after it. Can be given
+    /// multiple times.
+    #[clap(long = "variant", value_parser = Self::variant)]
+    #[serde(default)]
+    pub variants: Vec<Variant>,
+
+    /// How many shader crates, targets or variants to build at once, defaults to the number of
+    /// CPUs. Installing is still done one at a time.
+    #[clap(long, short)]
+    #[serde(default)]
+    pub jobs: Option<usize>,
+
+    /// Bake a value into the default of a specialization constant, eg `--spec-constant FOO=4`,
+    /// where `FOO` is the constant's name or its `SpecId`. Can be given multiple times. Pipelines
+    /// can still override the new defaults.
+    #[clap(long = "spec-constant", value_parser = Self::key_value, value_name = "NAME=VALUE")]
+    #[serde(
+        default,
+        deserialize_with = "deserialize_table",
+        serialize_with = "serialize_table"
+    )]
+    pub spec_constants: Vec<(String, String)>,
+
+    /// A shell command to run from the shader crate before every build, eg to generate code from
+    /// game data. `{manifest}` and `{output_dir}` in it are replaced with the quoted paths of the
+    /// manifest and the output dir. Can be given multiple times, they're run in order.
+    #[clap(long)]
+    #[serde(default)]
+    pub pre_build: Vec<String>,
+
+    /// A shell command to run from the shader crate after every successful build, eg to compress
+    /// or upload the shaders. `{manifest}` and `{output_dir}` in it are replaced with the quoted
+    /// paths of the manifest and the output dir. Can be given multiple times, they're run in
+    /// order.
+    #[clap(long)]
+    #[serde(default)]
+    pub post_build: Vec<String>,
+
+    /// Set an environment variable for compiling the shader crate and for its hooks, eg
+    /// `--env LEVEL_COUNT=12`. Can be given multiple times.
+    #[clap(long, value_parser = Self::key_value, value_name = "KEY=VALUE")]
+    #[serde(
+        default,
+        deserialize_with = "deserialize_table",
+        serialize_with = "serialize_table"
+    )]
+    pub env: Vec<(String, String)>,
+
+    /// The steps that the compiled modules go through, in order, eg to validate them only after
+    /// stripping them, or to skip reflecting on them. Defaults to all of them, in the order
+    /// `copy,optimize,spec-constants,rename-entry-points,reproducible,validate,reflect,strip,
+    /// transpile,stats,compress,pack,codegen`. Each step still only does anything when its own
+    /// option is given, eg `strip` with `--strip`. Only `reflect`, and steps whose options aren't
+    /// given, can be left out.
+    #[clap(long, value_enum, value_delimiter = ',')]
+    #[serde(default)]
+    pub output_processors: Vec<Processor>,
+}
+
+/// Shader targets can be configured as either a single target or a list of them.
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+fn deserialize_shader_targets<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<String>, D::Error> {
+    Ok(
+        match <OneOrMany as serde::Deserialize>::deserialize(deserializer)? {
+            OneOrMany::One(target) => vec![target],
+            OneOrMany::Many(targets) => targets,
+        },
+    )
+}
+
+/// A single target is serialized as a plain string, as that's what previously installed
+/// `spirv-builder-cli`s expect.
+fn serialize_shader_targets<S: serde::Serializer>(
+    targets: &Vec<String>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match targets.as_slice() {
+        [target] => serializer.serialize_str(target),
+        _ => serde::Serialize::serialize(targets, serializer),
+    }
+}
+
+/// Options like `spec-constants` and `entry-point-renames` are configured as tables, eg
+/// `spec-constants = { FOO = 4, USE_FOG = true }`, whose keys are the user's own. Their values
+/// are kept as strings.
+fn deserialize_table<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<(String, String)>, D::Error> {
+    let table: std::collections::BTreeMap<String, serde_json::Value> =
+        serde::Deserialize::deserialize(deserializer)?;
+    table
+        .into_iter()
+        .map(|(key, value)| match value {
+            serde_json::Value::String(string) => Ok((key, string)),
+            serde_json::Value::Bool(_) | serde_json::Value::Number(_) => {
+                Ok((key, value.to_string()))
+            }
+            serde_json::Value::Null
+            | serde_json::Value::Array(_)
+            | serde_json::Value::Object(_) => Err(serde::de::Error::custom(format!(
+                "`{key}` should be a string, a number or a bool"
+            ))),
+        })
+        .collect()
+}
+
+/// Tables are serialized back as tables, with every value as a string.
+fn serialize_table<S: serde::Serializer>(
+    table: &[(String, String)],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_map(table.iter().map(|(key, value)| (key, value)))
 }
 
 impl BuildArgs {
+    /// The shader target, when building for only one of them.
+    pub fn single_shader_target(&self) -> &str {
+        self.shader_target.first().map_or("", String::as_str)
+    }
+
+    /// Every extra flag for the shader crate's `cargo build`, from `--cargo-flags` then `--`.
+    pub fn all_cargo_flags(&self) -> Vec<String> {
+        self.cargo_flags
+            .iter()
+            .flat_map(|flags| flags.split_whitespace())
+            .map(str::to_owned)
+            .chain(self.cargo_args.iter().cloned())
+            .collect()
+    }
+
     /// Clap value parser for `SpirvMetadata`.
     fn spirv_metadata(metadata: &str) -> Result<SpirvMetadata, clap::Error> {
         match metadata {
@@ -132,6 +727,48 @@ impl BuildArgs {
         }
     }
 
+    /// Clap value parser for `Variant`, from `NAME[:features=A,B][:no-default-features]`.
+    fn variant(variant: &str) -> Result<Variant, String> {
+        let mut parts = variant.split(':');
+        let name = parts.next().unwrap_or_default();
+        if name.is_empty() || name.contains(['/', '\\']) || name == "." || name == ".." {
+            return Err(format!("`{name}` isn't a valid variant name"));
+        }
+        let mut parsed = Variant {
+            name: name.to_owned(),
+            features: Vec::new(),
+            no_default_features: false,
+        };
+        for part in parts {
+            if part == "no-default-features" {
+                parsed.no_default_features = true;
+            } else if let Some(features) = part.strip_prefix("features=") {
+                parsed.features.extend(
+                    features
+                        .split(',')
+                        .filter(|feature| !feature.is_empty())
+                        .map(str::to_owned),
+                );
+            } else {
+                return Err(format!(
+                    "unknown variant option `{part}`, expected `features=...` or \
+                     `no-default-features`"
+                ));
+            }
+        }
+        Ok(parsed)
+    }
+
+    /// Clap value parser for the entries of table options, from `KEY=VALUE`.
+    fn key_value(key_value: &str) -> Result<(String, String), String> {
+        match key_value.split_once('=') {
+            Some((key, value)) if !key.trim().is_empty() && !value.trim().is_empty() => {
+                Ok((key.trim().to_owned(), value.trim().to_owned()))
+            }
+            _ => Err(format!("expected `KEY=VALUE`, got `{key_value}`")),
+        }
+    }
+
     /// Clap value parser for `Capability`.
     fn spirv_capability(capability: &str) -> Result<spirv::Capability, clap::Error> {
         spirv::Capability::from_str(capability).map_or_else(
@@ -174,6 +811,60 @@ pub struct InstallArgs {
     pub force_spirv_cli_rebuild: bool,
 
     /// Assume "yes" to "Install Rust toolchain: [y/n]" prompt.
+    ///
+    /// Setting `CARGO_GPU_YES=1` does the same. Without either, installing fails rather than
+    /// prompting when stdin isn't a terminal or `CI` is set.
     #[clap(long, action)]
     pub auto_install_rust_toolchain: bool,
+
+    /// Try to download a prebuilt `spirv-builder-cli` and `rustc_codegen_spirv` pair, instead of
+    /// compiling them from source. Compiling from source is still the fallback if there is no
+    /// prebuilt pair for the `rust-gpu` version, toolchain and OS.
+    #[clap(long, action)]
+    pub download_prebuilt: bool,
+
+    /// Server to download prebuilt `spirv-builder-cli` and `rustc_codegen_spirv` pairs from. Pairs
+    /// are looked up as the assets of the `v<cargo gpu version>` release on it. They're checked
+    /// against the release's `SHA256SUMS`, which only guards against corrupted downloads, as it
+    /// comes from the same server.
+    #[clap(
+        long,
+        default_value = "https://github.com/Rust-GPU/cargo-gpu/releases/download"
+    )]
+    pub prebuilt_server: String,
+
+    /// Don't access the network. Only already cloned `rust-gpu` repos, and already installed
+    /// toolchains and binary pairs are used, and `--offline` is passed on to `cargo`.
+    #[clap(long, action)]
+    pub offline: bool,
+
+    /// Also write every log message, and the output of the `cargo` and `rustup` commands that are
+    /// run, to this file, with timestamps. Useful for looking into failures on CI.
+    #[clap(long)]
+    pub log_file: Option<std::path::PathBuf>,
+
+    /// Where to compile `spirv-builder-cli` and `rustc_codegen_spirv`, eg on a fast scratch disk.
+    /// Each `rust-gpu` version gets its own directory in it. Defaults to a `target` dir next to
+    /// the install in the cache dir, whatever `CARGO_TARGET_DIR` is.
+    #[clap(long)]
+    pub install_target_dir: Option<std::path::PathBuf>,
+
+    /// A `RUSTC_WRAPPER`, eg `sccache`, for compiling `spirv-builder-cli` and
+    /// `rustc_codegen_spirv`, so that the backend's build can come from a compile cache shared
+    /// between machines. Without it, any `RUSTC_WRAPPER` in the environment is used as usual.
+    #[clap(long)]
+    pub install_rustc_wrapper: Option<std::path::PathBuf>,
+
+    /// An already built `rustc_codegen_spirv` to compile shaders with, eg from a local `rust-gpu`
+    /// checkout, instead of installing one. `--rust-toolchain` has to be the toolchain that it was
+    /// built with. The build is run by a `spirv-builder-cli` next to it, or else an installed one
+    /// for the same toolchain.
+    #[clap(long)]
+    pub codegen_backend_path: Option<std::path::PathBuf>,
+
+    /// A toolchain to use instead of installing one with `rustup`, eg from Nix or a distro
+    /// package. It's the toolchain's root directory, with `bin/rustc` in it, and it has to be the
+    /// nightly that `rust-gpu` needs, with the rust-src, rustc-dev and llvm-tools components.
+    #[clap(long)]
+    pub toolchain_path: Option<std::path::PathBuf>,
 }
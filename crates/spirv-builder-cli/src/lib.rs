@@ -3,30 +3,396 @@ pub mod args;
 #[cfg(feature = "spirv-builder-pre-cli")]
 pub use spirv_0_2 as spirv;
 
-#[cfg(any(feature = "spirv-builder-0_10", feature = "rspirv-latest"))]
+#[cfg(any(
+    feature = "spirv-builder-0_10",
+    feature = "spirv-builder-0_11",
+    feature = "rspirv-latest"
+))]
 pub use spirv_0_3 as spirv;
 
+/// The version of [`Manifest`]'s schema. It's bumped whenever a change would stop older readers
+/// from understanding a manifest, but not for new optional fields, which readers should ignore.
+pub const MANIFEST_SCHEMA_VERSION: u32 = 1;
+
+/// The manifest written by `cargo gpu build`, listing every compiled shader entry point.
+///
+/// Build scripts and engines can parse it with [`Manifest::from_json`] rather than describing
+/// it with their own structs.
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq)]
+pub struct Manifest {
+    /// The schema version that the manifest was written with, see [`MANIFEST_SCHEMA_VERSION`].
+    pub schema_version: u32,
+    /// What the manifest's paths are relative to.
+    #[serde(default)]
+    pub paths: args::ManifestPaths,
+    /// The shader target that `--shader-target auto` picked, if it was used.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auto_shader_target: Option<AutoShaderTarget>,
+    /// Every compiled entry point.
+    pub entry_points: Vec<Linkage>,
+}
+
+impl Manifest {
+    /// A manifest of the given entry points, with the current schema version.
+    pub fn new(entry_points: Vec<Linkage>) -> Self {
+        Self {
+            schema_version: MANIFEST_SCHEMA_VERSION,
+            paths: args::ManifestPaths::default(),
+            auto_shader_target: None,
+            entry_points,
+        }
+    }
+
+    /// Parse a JSON manifest. Manifests from before the schema was versioned, which are just an
+    /// array of entry points, are parsed as schema version 0.
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        let value: serde_json::Value = serde_json::from_str(json)?;
+        if value.is_array() {
+            return Ok(Self {
+                schema_version: 0,
+                paths: args::ManifestPaths::default(),
+                auto_shader_target: None,
+                entry_points: serde_json::from_value(value)?,
+            });
+        }
+        serde_json::from_value(value)
+    }
+
+    /// Where the file at `path` in the manifest is, given where the shader crate and the dir
+    /// that the manifest is in are.
+    pub fn resolve_path(
+        &self,
+        path: &str,
+        shader_crate: &std::path::Path,
+        manifest_dir: &std::path::Path,
+    ) -> std::path::PathBuf {
+        match self.paths {
+            args::ManifestPaths::RelativeToCrate => shader_crate.join(path),
+            args::ManifestPaths::RelativeToManifest => manifest_dir.join(path),
+            args::ManifestPaths::Absolute => std::path::PathBuf::from(path),
+        }
+    }
+}
+
+/// The manifest of every shader crate built into the same output dir, `workspace-manifest.json`,
+/// so that a host with several shader crates only has to read one file.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Default, PartialEq, Eq)]
+pub struct WorkspaceManifest {
+    /// The schema version that the manifest was written with, see [`MANIFEST_SCHEMA_VERSION`].
+    pub schema_version: u32,
+    /// The manifest of each shader crate, by its package name. Their paths are never relative to
+    /// the crate, as there's more than one.
+    pub crates: std::collections::BTreeMap<String, Manifest>,
+}
+
+impl WorkspaceManifest {
+    /// Parse a JSON workspace manifest.
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    /// Every entry point of every shader crate, with the name of its crate.
+    pub fn entry_points(&self) -> impl Iterator<Item = (&str, &Linkage)> {
+        self.crates.iter().flat_map(|(name, manifest)| {
+            manifest
+                .entry_points
+                .iter()
+                .map(move |entry| (name.as_str(), entry))
+        })
+    }
+}
+
+/// The shader target that `--shader-target auto` picked for the local GPU.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AutoShaderTarget {
+    /// The picked target, eg `spirv-unknown-vulkan1.3`.
+    pub target: String,
+    /// The GPU that it was picked for, or `None` if no GPU was found and the default target was
+    /// used.
+    pub device: Option<String>,
+    /// The newest Vulkan version that the GPU supports, eg `1.3`.
+    pub vulkan_version: Option<String>,
+}
+
 /// Shader source and entry point that can be used to create shader linkage.
-#[derive(serde::Serialize, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Linkage {
     pub source_path: String,
     pub entry_point: String,
     pub wgsl_entry_point: String,
+    /// Path to the entry point's WGSL source, when transpiling to WGSL.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wgsl_source_path: Option<String>,
+    /// Path to the entry point's HLSL source, when transpiling to HLSL.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hlsl_source_path: Option<String>,
+    /// Path to the entry point's GLSL source, when transpiling to GLSL.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub glsl_source_path: Option<String>,
+    /// Path to the entry point's MSL source, when transpiling to MSL.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub msl_source_path: Option<String>,
+    /// The entry point's shader stage, eg "vertex", as found by reflecting on the module.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stage: Option<String>,
+    /// The descriptor bindings used by the entry point, as found by reflecting on the module.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bindings: Vec<DescriptorBinding>,
+    /// The size in bytes of the entry point's push constants, if it uses any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub push_constant_size: Option<u32>,
+    /// The capabilities declared by the entry point's module that it actually uses, as found by
+    /// reflecting on the module.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub capabilities: Vec<String>,
+    /// The specialization constants that the entry point uses, as found by reflecting on the
+    /// module.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub spec_constants: Vec<SpecConstant>,
+    /// The `--variant` that the entry point was built for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub variant: Option<String>,
+    /// The extra Cargo features that the entry point's variant was built with.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub features: Vec<String>,
+    /// Statistics about the entry point's module, when building with `--stats`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stats: Option<ModuleStats>,
+    /// The lowercase hex SHA-256 of the entry point's module file, as written, for integrity
+    /// checks and spotting changed modules without hashing them again.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// How the entry point's module file is compressed, when building with `--compress`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compression: Option<ModuleCompression>,
+    /// Where the entry point's module is in the pack file, when building with `--pack`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub packed: Option<PackedModule>,
+}
+
+/// How a module file is compressed.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ModuleCompression {
+    /// The compression scheme, eg "zstd".
+    pub scheme: String,
+    /// Size of the module's binary in bytes, before compression.
+    pub size: u64,
+    /// Size of the compressed module file in bytes.
+    pub compressed_size: u64,
+}
+
+/// Statistics about a compiled SPIR-V module, useful for tracking shader bloat over time.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ModuleStats {
+    /// Size of the module's binary in bytes.
+    pub size: u64,
+    /// Number of SPIR-V instructions in the module.
+    pub instruction_count: u64,
+    /// The capabilities that the module declares, eg "Shader".
+    pub capabilities: Vec<String>,
+    /// How long the shader crate took to compile, in milliseconds. All of a crate's modules are
+    /// compiled together, so they all share the same compile time.
+    pub compile_ms: u64,
+}
+
+/// Where a module is in a pack file, which is every module of a build one after another.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PackedModule {
+    /// Path to the pack file.
+    pub path: String,
+    /// Where the module starts in the pack, in bytes.
+    pub offset: u64,
+    /// The size of the module in bytes.
+    pub length: u64,
+    /// The lowercase hex SHA-256 of the module's bytes in the pack.
+    pub sha256: String,
+}
+
+/// A specialization constant, which pipelines can set the value of when they're created.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SpecConstant {
+    /// The constant's `SpecId`, which pipelines set its value by.
+    pub id: u32,
+    /// The constant's name, if the module has debug names.
+    pub name: Option<String>,
+    /// The constant's type, eg "bool", "u32", "i32" or "f32".
+    #[serde(rename = "type")]
+    pub ty: String,
+    /// The constant's value when pipelines don't set it, eg "4" or "true".
+    pub default: String,
+}
+
+/// A resource in a descriptor set that is used by a shader entry point.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DescriptorBinding {
+    pub set: u32,
+    pub binding: u32,
+    /// The kind of descriptor, eg "uniform_buffer", "storage_buffer" or "sampled_image".
+    pub descriptor_type: String,
+    /// The number of descriptors in an array of resources, `None` for runtime-sized arrays.
+    pub count: Option<u32>,
+    /// The resource's name, if the module has debug names.
+    pub name: Option<String>,
+    /// Whether the resource is decorated as only ever being read from.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub read_only: bool,
+    /// Whether the resource is decorated as only ever being written to.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub write_only: bool,
+    /// The details of image resources, including the image in a combined image sampler.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image: Option<ImageInfo>,
+}
+
+/// The details of an image resource.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ImageInfo {
+    /// Eg "1d", "2d", "3d", "cube" or "buffer".
+    pub dimension: String,
+    /// Whether it is an array of images, eg a "2d" array.
+    pub arrayed: bool,
+    /// Whether it is a depth image.
+    pub depth: bool,
+    pub multisampled: bool,
+    /// The type of the image's texels, one of "float", "sint" or "uint".
+    pub sampled_type: String,
+    /// The SPIR-V image format, eg "Rgba8", or "Unknown" for sampled images.
+    pub format: String,
 }
 
 impl Linkage {
     pub fn new(entry_point: impl AsRef<str>, source_path: impl AsRef<std::path::Path>) -> Self {
         Self {
             // Force a forward slash convention here so it works on all OSs
-            source_path: source_path
-                .as_ref()
-                .components()
-                .map(|c| c.as_os_str().to_string_lossy())
-                .collect::<Vec<_>>()
-                .join("/"),
+            source_path: to_forward_slashes(source_path),
             wgsl_entry_point: entry_point.as_ref().replace("::", ""),
             entry_point: entry_point.as_ref().to_string(),
+            wgsl_source_path: None,
+            hlsl_source_path: None,
+            glsl_source_path: None,
+            msl_source_path: None,
+            stage: None,
+            bindings: Vec::new(),
+            push_constant_size: None,
+            capabilities: Vec::new(),
+            spec_constants: Vec::new(),
+            variant: None,
+            features: Vec::new(),
+            stats: None,
+            sha256: None,
+            compression: None,
+            packed: None,
+        }
+    }
+
+    /// Set the reflected shader stage and descriptor bindings of the entry point.
+    pub fn with_reflection(mut self, stage: String, bindings: Vec<DescriptorBinding>) -> Self {
+        self.stage = Some(stage);
+        self.bindings = bindings;
+        self
+    }
+
+    /// Set the reflected size of the entry point's push constants.
+    pub fn with_push_constant_size(mut self, push_constant_size: Option<u32>) -> Self {
+        self.push_constant_size = push_constant_size;
+        self
+    }
+
+    /// Set the capabilities that the entry point uses.
+    pub fn with_capabilities(mut self, capabilities: Vec<String>) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Set the specialization constants that the entry point uses.
+    pub fn with_spec_constants(mut self, spec_constants: Vec<SpecConstant>) -> Self {
+        self.spec_constants = spec_constants;
+        self
+    }
+
+    /// Set the variant that the entry point was built for, and its Cargo features.
+    pub fn with_variant(mut self, variant: String, features: Vec<String>) -> Self {
+        self.variant = Some(variant);
+        self.features = features;
+        self
+    }
+
+    /// Set the SHA-256 of the entry point's module.
+    pub fn with_sha256(mut self, sha256: String) -> Self {
+        self.sha256 = Some(sha256);
+        self
+    }
+
+    /// Set how the entry point's module is compressed.
+    pub fn with_compression(mut self, compression: ModuleCompression) -> Self {
+        self.compression = Some(compression);
+        self
+    }
+
+    /// Set where the entry point's module is in the pack file.
+    pub fn with_packed(mut self, packed: PackedModule) -> Self {
+        self.packed = Some(packed);
+        self
+    }
+
+    /// Set the statistics of the entry point's module.
+    pub fn with_stats(mut self, stats: ModuleStats) -> Self {
+        self.stats = Some(stats);
+        self
+    }
+
+    /// Set the path to the entry point's source, transpiled to the given target.
+    pub fn with_transpiled_source_path(
+        mut self,
+        target: args::TranspileTarget,
+        path: impl AsRef<std::path::Path>,
+    ) -> Self {
+        let path = Some(to_forward_slashes(path));
+        match target {
+            args::TranspileTarget::Wgsl => self.wgsl_source_path = path,
+            args::TranspileTarget::Hlsl => self.hlsl_source_path = path,
+            args::TranspileTarget::Glsl => self.glsl_source_path = path,
+            args::TranspileTarget::Msl => self.msl_source_path = path,
         }
+        self
+    }
+
+    /// Every path in the linkage, the module's first.
+    pub fn paths(&self) -> Vec<&str> {
+        core::iter::once(self.source_path.as_str())
+            .chain(
+                [
+                    &self.wgsl_source_path,
+                    &self.hlsl_source_path,
+                    &self.glsl_source_path,
+                    &self.msl_source_path,
+                ]
+                .into_iter()
+                .flatten()
+                .map(String::as_str),
+            )
+            .chain(self.packed.as_ref().map(|packed| packed.path.as_str()))
+            .collect()
+    }
+
+    /// Change every path in the linkage, eg to make them absolute.
+    pub fn with_paths_mapped(mut self, map: impl Fn(&str) -> String) -> Self {
+        self.source_path = map(&self.source_path);
+        for path in [
+            &mut self.wgsl_source_path,
+            &mut self.hlsl_source_path,
+            &mut self.glsl_source_path,
+            &mut self.msl_source_path,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            *path = map(path);
+        }
+        if let Some(packed) = &mut self.packed {
+            packed.path = map(&packed.path);
+        }
+        self
     }
 
     pub fn fn_name(&self) -> &str {
@@ -34,6 +400,15 @@ impl Linkage {
     }
 }
 
+/// Force a forward slash convention for paths so that they work on all OSs.
+fn to_forward_slashes(path: impl AsRef<std::path::Path>) -> String {
+    path.as_ref()
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
 /// A built shader entry-point, used in `spirv-builder-cli` to generate
 /// a `build-manifest.json` used by `cargo-gpu`.
 #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord)]
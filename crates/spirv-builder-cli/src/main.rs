@@ -11,6 +11,9 @@ use spirv_builder_pre_cli as spirv_builder;
 #[cfg(feature = "spirv-builder-0_10")]
 use spirv_builder_0_10 as spirv_builder;
 
+#[cfg(feature = "spirv-builder-0_11")]
+use spirv_builder_0_11 as spirv_builder;
+
 use spirv_builder::{CompileResult, MetadataPrintout, ModuleResult, SpirvBuilder};
 use spirv_builder_cli::ShaderModule;
 
@@ -116,12 +119,12 @@ pub fn main() {
         set_codegen_spirv_location(args.install.dylib_path);
     }
 
-    #[cfg(feature = "spirv-builder-0_10")]
+    #[cfg(any(feature = "spirv-builder-0_10", feature = "spirv-builder-0_11"))]
     {
-        log::debug!("using spirv-builder-0_10");
+        log::debug!("using spirv-builder-0_10 or newer");
         builder = builder
             .rustc_codegen_spirv_location(args.install.dylib_path)
-            .target_spec(args.build.shader_target);
+            .target_spec(args.build.single_shader_target());
 
         if args.build.no_default_features {
             log::info!("setting cargo --no-default-features");
@@ -133,16 +136,27 @@ pub fn main() {
         }
     }
 
-    log::debug!("Calling `rust-gpu`'s `spirv-builder` library");
-
-    if args.build.watch {
-        println!("🦀 Watching and recompiling shader on changes...");
-        builder.watch(move |compile_result| {
-            handle_compile_result(&compile_result, &args_for_result);
+    #[cfg(feature = "spirv-builder-0_11")]
+    {
+        log::debug!("using spirv-builder-0_11");
+        builder = builder.shader_panic_strategy(match args.build.shader_panic_strategy {
+            args::ShaderPanicStrategy::SilentExit => spirv_builder::ShaderPanicStrategy::SilentExit,
+            args::ShaderPanicStrategy::DebugPrintf => {
+                spirv_builder::ShaderPanicStrategy::DebugPrintfThenExit {
+                    print_inputs: true,
+                    print_backtrace: true,
+                }
+            }
         });
-        std::thread::park();
-    } else {
-        let result = builder.build().unwrap();
-        handle_compile_result(&result, &args_for_result);
     }
+
+    #[cfg(not(feature = "spirv-builder-0_11"))]
+    if args.build.shader_panic_strategy != args::ShaderPanicStrategy::SilentExit {
+        log::warn!("`--shader-panic-strategy` needs a newer `spirv-builder`, ignoring it");
+    }
+
+    log::debug!("Calling `rust-gpu`'s `spirv-builder` library");
+
+    let result = builder.build().unwrap();
+    handle_compile_result(&result, &args_for_result);
 }
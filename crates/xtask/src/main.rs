@@ -13,6 +13,7 @@ enum Cli {
     TestBuild,
 }
 
+/// Run a command, failing if it doesn't exit successfully.
 fn cmd(args: impl IntoIterator<Item = impl AsRef<str>>) -> anyhow::Result<()> {
     let mut args = args.into_iter();
     let mut cmd = std::process::Command::new(args.next().context("no args")?.as_ref());
@@ -32,9 +33,9 @@ fn cmd(args: impl IntoIterator<Item = impl AsRef<str>>) -> anyhow::Result<()> {
 
 /// Overwrites a toml file's output-dir field, and reverts that on drop.
 struct ShaderCrateTemplateCargoTomlWriter {
-    /// Original string
+    /// Original string.
     original_shader_crate_template_str: String,
-    /// Parsed toml table
+    /// Parsed toml table.
     table: toml::Table,
 }
 
@@ -46,10 +47,10 @@ impl Drop for ShaderCrateTemplateCargoTomlWriter {
 }
 
 impl ShaderCrateTemplateCargoTomlWriter {
-    /// Path to the Cargo.toml
+    /// Path to the Cargo.toml.
     const PATH: &str = "crates/shader-crate-template/Cargo.toml";
 
-    /// Create a new one
+    /// Create a new one.
     fn new() -> Self {
         let original_shader_crate_template_str = std::fs::read_to_string(Self::PATH).unwrap();
         let table = toml::from_str::<toml::Table>(&original_shader_crate_template_str).unwrap();
@@ -59,7 +60,7 @@ impl ShaderCrateTemplateCargoTomlWriter {
         }
     }
 
-    /// Replace the output-dir
+    /// Replace the output-dir.
     fn replace_output_dir(&mut self, path: impl AsRef<std::path::Path>) -> anyhow::Result<()> {
         let package = self
             .table